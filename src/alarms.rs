@@ -0,0 +1,40 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::Path;
+
+use crate::drivers::rust_sources;
+
+const NEW_VIRTUAL_ALARM: &str = "VirtualMuxAlarm::new(";
+// matches the construction of a component in a board's setup
+static COMPONENT_NEW: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\w+Component)::new\(").unwrap());
+
+/// Number of virtual alarms the board creates, which bounds the iteration of
+/// the virtual alarm mux over its clients. Counts the alarms created directly
+/// in the board's sources, and those created by each component it constructs
+/// (per construction, so a component used twice counts twice).
+pub fn virtual_alarm_count(board_path: &Path, tockpath: &Path) -> usize {
+    let mut board_files = vec![];
+    rust_sources(&board_path.join("src"), &mut board_files);
+    let mut component_files = vec![];
+    rust_sources(
+        &tockpath.join("boards/components/src"),
+        &mut component_files,
+    );
+    let components: Vec<String> = component_files
+        .iter()
+        .filter_map(|f| std::fs::read_to_string(f).ok())
+        .collect();
+
+    let mut count = 0;
+    for file in board_files {
+        let source = std::fs::read_to_string(&file).unwrap_or_default();
+        count += source.matches(NEW_VIRTUAL_ALARM).count();
+        for component in COMPONENT_NEW.captures_iter(&source) {
+            let definition = Regex::new(&format!(r"pub struct {}\b", &component[1])).unwrap();
+            if let Some(component_source) = components.iter().find(|s| definition.is_match(s)) {
+                count += component_source.matches(NEW_VIRTUAL_ALARM).count();
+            }
+        }
+    }
+    count
+}
@@ -0,0 +1,117 @@
+use haybale::{BBInstrIndex, PathEntry};
+use llvm_ir::{Constant, Instruction, Operand, Terminator};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::call_graph::called_function_name;
+use crate::instruction_counter::entry_range;
+
+/// Demangled names (`{:#}`) of the functions allocating memory, with the
+/// position of their size argument in the LLVM signature: grants of Tock 2.x
+/// processes, the `alloc` of Tock 1.x processes, and Rust's global allocator.
+static ALLOCATORS: Lazy<Vec<(&str, Regex, usize)>> = Lazy::new(|| {
+    vec![
+        (
+            "grant",
+            r" as kernel::process::Process>::allocate_grant$",
+            3,
+        ),
+        (
+            "custom grant",
+            r" as kernel::process::Process>::allocate_custom_grant$",
+            1,
+        ),
+        (
+            "process memory",
+            r"^<kernel::process::.* as kernel::process::ProcessType>::alloc$",
+            1,
+        ),
+        ("heap", r"^__rust_alloc(_zeroed)?$", 0),
+    ]
+    .into_iter()
+    .map(|(kind, pattern, size_arg)| (kind, Regex::new(pattern).unwrap(), size_arg))
+    .collect()
+});
+
+/// A call allocating memory on a path.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Allocation {
+    /// What is allocated, e.g. `grant` or `heap`
+    pub kind: String,
+    /// Demangled name of the allocating function
+    pub function: String,
+    /// Bytes allocated, if the size is a constant at the call
+    pub size: Option<u64>,
+}
+
+fn constant(operand: &Operand) -> Option<u64> {
+    match operand {
+        Operand::ConstantOperand(c) => match &**c {
+            Constant::Int { value, .. } => Some(*value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the `i`th entry of a path is where a function is entered, at the
+/// first instruction of its entry block.
+fn enters_function(path: &[PathEntry], i: usize) -> bool {
+    let location = &path[i].0;
+    location.instr == BBInstrIndex::Instr(0)
+        && location.func.basic_blocks.first().map(|bb| &bb.name) == Some(&location.bb.name)
+}
+
+/// The calls allocating memory along a path, in path order. Calls through
+/// trait objects (as the kernel calls into processes) are recognized by the
+/// function the path enters.
+pub fn on_path(path: &[PathEntry]) -> Vec<Allocation> {
+    let mut allocations = vec![];
+    for i in 0..path.len() {
+        let bb = &path[i].0.bb;
+        let range = entry_range(path, i);
+        let last = range.end.saturating_sub(1);
+        for index in range {
+            let (callee, arguments) = match (bb.instrs.get(index), &bb.term) {
+                (Some(Instruction::Call(call)), _) => (&call.function, &call.arguments),
+                (None, Terminator::Invoke(invoke)) => (&invoke.function, &invoke.arguments),
+                _ => continue,
+            };
+            let name = match called_function_name(callee) {
+                Some(name) => name,
+                None if index == last && i + 1 < path.len() && enters_function(path, i + 1) => {
+                    path[i + 1].0.func.name.as_str()
+                }
+                None => continue,
+            };
+            let demangled = format!("{:#}", rustc_demangle::demangle(name));
+            let (kind, size_arg) =
+                match ALLOCATORS.iter().find(|(_, re, _)| re.is_match(&demangled)) {
+                    Some((kind, _, size_arg)) => (kind, *size_arg),
+                    None => continue,
+                };
+            allocations.push(Allocation {
+                kind: kind.to_string(),
+                function: demangled,
+                size: arguments
+                    .get(size_arg)
+                    .and_then(|(operand, _)| constant(operand)),
+            });
+        }
+    }
+    allocations
+}
+
+/// A line describing the allocations of a path, e.g. `2 (grant: 24 bytes,
+/// heap: unknown size)`.
+pub fn describe(allocations: &[Allocation]) -> String {
+    let each: Vec<String> = allocations
+        .iter()
+        .map(|a| match a.size {
+            Some(size) => format!("{}: {} bytes", a.kind, size),
+            None => format!("{}: unknown size", a.kind),
+        })
+        .collect();
+    format!("{} ({})", allocations.len(), each.join(", "))
+}
@@ -0,0 +1,87 @@
+use haybale::Project;
+use llvm_ir::{Function, Type};
+use regex::Regex;
+
+use crate::config_file::{AllowConfig, ArgumentConfig, ArgumentConstraint, ParametricConfig};
+use crate::syscall_abi::SyscallAbi;
+use crate::{matches_work_type, KernelWorkType};
+
+/// The argument of an allow handler holding the length of the shared buffer,
+/// as compiled: a buffer passed as a (pointer, length) pair, like Tock 1.x's
+/// `AppSlice`, is split into the last two arguments. Tock 2.x process
+/// buffers are passed by reference, with no length argument.
+fn length_arg(function: &Function) -> Option<usize> {
+    let params = &function.parameters;
+    let last = params.len().checked_sub(1)?;
+    match (&*params.get(last.checked_sub(1)?)?.ty, &*params[last].ty) {
+        (Type::PointerType { .. }, Type::IntegerType { .. }) => Some(last),
+        _ => None,
+    }
+}
+
+/// Constraints and parametric points for the allow handlers of `project`:
+/// the length of the shared buffer is at most the documented maximum, and
+/// each handler is analyzed at the minimum, typical and maximum lengths.
+/// Handlers without a length argument are listed and left unconstrained.
+pub fn entry_configs(
+    project: &Project,
+    config: &AllowConfig,
+    abi: SyscallAbi,
+) -> Result<(Vec<ArgumentConfig>, Vec<ParametricConfig>), String> {
+    let drivers = config
+        .drivers
+        .iter()
+        .map(|d| {
+            Regex::new(&d.function)
+                .map(|re| (re, d))
+                .map_err(|e| format!("invalid allow driver pattern: {}", e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut arguments = vec![];
+    let mut parametric = vec![];
+    for (f, _m) in project.all_functions() {
+        if f.basic_blocks.is_empty() || !matches_work_type(&f.name, &KernelWorkType::Allows, abi) {
+            continue;
+        }
+        let demangled = rustc_demangle::demangle(&f.name).to_string();
+        let driver = drivers.iter().find(|(re, _)| re.is_match(&demangled));
+        let (typical, max) = match driver {
+            Some((_, d)) => (d.typical_len, d.max_len),
+            None => (config.typical_len, config.max_len),
+        };
+        let arg = match driver.and_then(|(_, d)| d.arg).or_else(|| length_arg(f)) {
+            Some(arg) => arg,
+            None => {
+                println!(
+                    "No buffer length argument found for allow handler {}",
+                    demangled
+                );
+                continue;
+            }
+        };
+        let mut values: Vec<u64> = vec![config.min_len.min(max), max];
+        values.extend(typical.filter(|&t| t < max));
+        values.sort_unstable();
+        values.dedup();
+        let function = format!("^{}$", regex::escape(&demangled));
+        arguments.push(ArgumentConfig {
+            function: function.clone(),
+            constraints: vec![ArgumentConstraint {
+                arg,
+                min: None,
+                max: Some(max),
+                values: None,
+                offset: None,
+                bits: None,
+            }],
+        });
+        parametric.push(ParametricConfig {
+            function,
+            arg,
+            values,
+            offset: None,
+            bits: None,
+        });
+    }
+    Ok((arguments, parametric))
+}
@@ -0,0 +1,179 @@
+use haybale::Project;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::call_graph::direct_callees;
+use crate::config_file::{AsmStubCost, HookConfig};
+use crate::host;
+
+/// Functions called in `project` without a definition in it, such as the
+/// context switch and startup code written in assembly. LLVM intrinsics,
+/// which haybale executes itself, are left out.
+pub fn without_ir(project: &Project) -> BTreeSet<&str> {
+    let defined: HashSet<&str> = project
+        .all_functions()
+        .map(|(f, _m)| f.name.as_str())
+        .collect();
+    project
+        .all_functions()
+        .flat_map(|(f, _m)| direct_callees(f))
+        .filter(|callee| !defined.contains(callee) && !callee.starts_with("llvm."))
+        .collect()
+}
+
+/// The static libraries build scripts link into the crates built into
+/// `target_dir`, e.g. a vendor's crypto library written in C, from the
+/// `rustc-link-lib` and `rustc-link-search` instructions they printed.
+fn linked_libraries(target_dir: &str) -> Vec<PathBuf> {
+    let pattern = format!(
+        "{}/build/*/output",
+        glob::Pattern::escape(target_dir.trim_end_matches('/'))
+    );
+    let mut libraries = vec![];
+    for output in glob::glob(&pattern).into_iter().flatten().flatten() {
+        let output = std::fs::read_to_string(&output).unwrap_or_default();
+        let mut names = vec![];
+        let mut search = vec![];
+        for line in output.lines() {
+            // `cargo::` is the newer syntax of build script instructions
+            let line = line
+                .trim_start_matches("cargo::")
+                .trim_start_matches("cargo:");
+            if let Some(lib) = line.strip_prefix("rustc-link-lib=") {
+                match lib.split_once('=') {
+                    Some(("static", name)) => names.push(name.to_owned()),
+                    Some(_) => {}
+                    None => names.push(lib.to_owned()),
+                }
+            } else if let Some(dir) = line.strip_prefix("rustc-link-search=") {
+                search.push(PathBuf::from(
+                    dir.split_once('=').map_or(dir, |(_kind, dir)| dir),
+                ));
+            }
+        }
+        for name in names {
+            if let Some(path) = search
+                .iter()
+                .map(|dir| dir.join(format!("lib{}.a", name)))
+                .find(|path| path.is_file())
+            {
+                libraries.push(path);
+            }
+        }
+    }
+    libraries.sort();
+    libraries.dedup();
+    libraries
+}
+
+/// The functions each of `libraries` defines, by symbol, with the file name
+/// of the library, listed with `llvm-nm`.
+fn library_symbols(libraries: &[PathBuf]) -> HashMap<String, String> {
+    let mut symbols = HashMap::new();
+    let nm = match host::llvm_tool("llvm-nm") {
+        Ok(nm) => nm,
+        Err(e) => {
+            if !libraries.is_empty() {
+                println!(
+                    "Warning: not listing the symbols of linked libraries: {}",
+                    e
+                );
+            }
+            return symbols;
+        }
+    };
+    for library in libraries {
+        let output = match Command::new(&nm)
+            .arg("--defined-only")
+            .arg(library)
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            _ => {
+                println!(
+                    "Warning: failed to list the symbols of {}",
+                    library.display()
+                );
+                continue;
+            }
+        };
+        let file_name = library
+            .file_name()
+            .map(Path::new)
+            .unwrap_or(library)
+            .display()
+            .to_string();
+        // lines are `<address> <type> <symbol>`, `T` for functions
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let [_, "T" | "t" | "W", symbol] = line.split_whitespace().collect::<Vec<_>>()[..] {
+                symbols.insert(symbol.to_owned(), file_name.clone());
+            }
+        }
+    }
+    symbols
+}
+
+/// Hooks stubbing the functions of `project` without IR which have a cost in
+/// `table`: their calls return an unconstrained value and add their cost to
+/// the path. The others are reported, as paths reaching them fail. Functions
+/// of C libraries linked into the board (which have no bitcode either) are
+/// reported with the library defining them, found in `target_dir`.
+pub fn hooks(
+    project: &Project,
+    table: &HashMap<String, AsmStubCost>,
+    target_dir: &str,
+) -> HashMap<String, HookConfig> {
+    let libraries = library_symbols(&linked_libraries(target_dir));
+    let mut hooks = HashMap::new();
+    let mut missing = vec![];
+    for name in without_ir(project) {
+        match table.get(name) {
+            Some(cost) => {
+                hooks.insert(
+                    name.to_owned(),
+                    HookConfig {
+                        function: name.to_owned(),
+                        return_values: None,
+                        ir: 0,
+                        machine: cost.instructions,
+                        cycles: cost.cycles,
+                    },
+                );
+            }
+            None => missing.push(name),
+        }
+    }
+    if !hooks.is_empty() {
+        let from_libraries = hooks
+            .keys()
+            .filter(|name| libraries.contains_key(*name))
+            .count();
+        println!(
+            "Stubbing {} functions without IR, {} of them from C libraries",
+            hooks.len(),
+            from_libraries
+        );
+    }
+    if !missing.is_empty() {
+        println!(
+            "Warning: {} functions without IR have no cost under [asm_stubs], paths calling them fail:",
+            missing.len()
+        );
+        for name in missing {
+            match libraries.get(name) {
+                Some(library) => println!("  {} (in {})", name, library),
+                None => println!("  {}", name),
+            }
+        }
+    }
+    for name in table.keys() {
+        if !hooks.contains_key(name) {
+            println!(
+                "Warning: [asm_stubs] function {} is not called without IR",
+                name
+            );
+        }
+    }
+    hooks
+}
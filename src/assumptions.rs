@@ -0,0 +1,165 @@
+use haybale::Project;
+use serde::{Deserialize, Serialize};
+
+use crate::panics::{self, PanicPaths};
+use crate::results::LongestPath;
+use crate::{is_syscall_handler, loop_bound, work_type_of, AnalysisSettings, KernelWorkType};
+
+/// Something a result relies on, which its consumers have to accept for the
+/// number to be a bound.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Assumption {
+    /// What the assumption is about, e.g. `loop bound`
+    pub kind: String,
+    pub detail: String,
+}
+
+fn assumption(kind: &str, detail: String) -> Assumption {
+    Assumption {
+        kind: kind.to_owned(),
+        detail,
+    }
+}
+
+fn panic_treatment(kind: &str, treatment: PanicPaths) -> String {
+    match treatment {
+        PanicPaths::Prune => format!("paths raising {} panics are pruned", kind),
+        PanicPaths::Count => format!("paths raising {} panics are counted", kind),
+        PanicPaths::Separate => format!(
+            "paths raising {} panics are pruned, and reported separately",
+            kind
+        ),
+    }
+}
+
+/// The assumptions under which `func_name` was analyzed: those of the
+/// effective configuration of its analysis, and those the longest path
+/// found, if any, relied on.
+pub fn collect(
+    project: &Project,
+    func_name: &str,
+    settings: &AnalysisSettings,
+    path: Option<&LongestPath>,
+) -> Vec<Assumption> {
+    if settings.fixed_costs.contains_key(func_name) {
+        return vec![assumption(
+            "symbol costs",
+            "the cost given in the config file is trusted, the function is not analyzed".to_owned(),
+        )];
+    }
+    let mut assumptions = vec![assumption(
+        "null pointers",
+        "null pointer dereferences are not explored, Rust's safety is trusted".to_owned(),
+    )];
+    let (bound, source) = loop_bound(project, func_name, settings);
+    assumptions.push(assumption(
+        "loop bound",
+        match source {
+            Some(source) => format!("loops are unrolled at most {} times, {}", bound, source),
+            None => format!("loops are unrolled at most {} times", bound),
+        },
+    ));
+    assumptions.push(assumption(
+        "solver",
+        match settings.time_budget_s {
+            Some(budget) => format!(
+                "solver queries time out after {}s, the analysis once they took {}s in all",
+                settings.timeout_s, budget
+            ),
+            None => format!("solver queries time out after {}s", settings.timeout_s),
+        },
+    ));
+    let panics = &settings.panics;
+    for (kind, treatment) in &[
+        ("unreachable", panics.unreachable),
+        ("assertion", panics.assertion),
+        ("overflow", panics.overflow),
+    ] {
+        assumptions.push(assumption("panics", panic_treatment(kind, *treatment)));
+    }
+    if let Ok(hooked) = panics::hooked_functions(project, panics, false) {
+        assumptions.push(assumption(
+            "panics",
+            format!(
+                "{} panic functions end the paths calling them",
+                hooked.len()
+            ),
+        ));
+    }
+    if settings.process_state.grants_allocated {
+        assumptions.push(assumption(
+            "process state",
+            "the grants of every process are already allocated".to_owned(),
+        ));
+    }
+    if let Some(constraints) = settings.argument_constraints.get(func_name) {
+        for c in constraints {
+            let range = match (&c.values, c.min, c.max) {
+                (Some(values), _, _) => format!("one of {:?}", values),
+                (None, min, max) => format!(
+                    "between {} and {}",
+                    min.unwrap_or(0),
+                    max.map_or("the maximum".to_owned(), |m| m.to_string())
+                ),
+            };
+            assumptions.push(assumption(
+                "arguments",
+                format!("argument {} is {}", c.arg, range),
+            ));
+        }
+    }
+    if let Some(KernelWorkType::SyscallDispatch) = work_type_of(func_name, settings.syscall_abi) {
+        assumptions.push(assumption(
+            "syscall dispatch",
+            "capsule syscall handlers are stubbed, only the kernel's dispatch is counted"
+                .to_owned(),
+        ));
+    } else if let Some(KernelWorkType::Startup) = work_type_of(func_name, settings.syscall_abi) {
+        assumptions.push(assumption(
+            "startup",
+            "the path ends on entering the kernel loop".to_owned(),
+        ));
+    } else if is_syscall_handler(func_name, settings.syscall_abi) {
+        assumptions.push(assumption(
+            "syscall dispatch",
+            "the kernel's dispatch to the handler is not counted".to_owned(),
+        ));
+    }
+    let cost_model = &settings.cost_model;
+    if cost_model.mnemonics.is_empty() {
+        assumptions.push(assumption(
+            "cost model",
+            format!(
+                "every machine instruction takes {} cycles",
+                cost_model.default
+            ),
+        ));
+    }
+    if let Some(path) = path {
+        for hooked in &path.hooked_functions {
+            assumptions.push(assumption(
+                "hooks",
+                format!("calls to {} cost what their hook says", hooked),
+            ));
+        }
+        if path.estimated_machine > 0 {
+            assumptions.push(assumption(
+                "disassembly",
+                format!(
+                    "{} machine instructions are estimated from the IR of blocks not found in the disassembly",
+                    path.estimated_machine
+                ),
+            ));
+        }
+    }
+    assumptions
+}
+
+/// The assumptions as an appendix to a result.
+pub fn render(assumptions: &[Assumption]) -> String {
+    let mut out = "Assumptions:\n".to_owned();
+    for a in assumptions {
+        out += &format!("  {}: {}\n", a.kind, a.detail);
+    }
+    out
+}
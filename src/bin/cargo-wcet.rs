@@ -0,0 +1,95 @@
+//! Entry point for running this tool as `cargo wcet` from inside a Tock
+//! checkout (or, with `--no-tock`, any embedded crate).
+//!
+//! Cargo invokes external subcommands as `cargo-wcet wcet <args>`, so this
+//! drops the subcommand name, fills in the tock path, board and target
+//! directory from the surrounding cargo workspace when they were not passed
+//! explicitly, and runs the `wcet-rs` binary installed alongside it.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+/// Whether any of the given flags was passed, as `--flag value` or `--flag=value`.
+fn has_flag(args: &[String], flags: &[&str]) -> bool {
+    args.iter().any(|arg| {
+        flags
+            .iter()
+            .any(|flag| arg == flag || arg.starts_with(&format!("{}=", flag)))
+    })
+}
+
+fn cargo_metadata() -> serde_json::Value {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let output = Command::new(cargo)
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .expect("failed to execute cargo metadata");
+    if !output.status.success() {
+        panic!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    serde_json::from_slice(&output.stdout).expect("failed to parse cargo metadata")
+}
+
+/// Name of the package whose manifest is in the current directory, as it
+/// appears in bitcode file names.
+fn current_crate_name(metadata: &serde_json::Value, cwd: &Path) -> Option<String> {
+    metadata["packages"]
+        .as_array()?
+        .iter()
+        .find(|p| p["manifest_path"].as_str().map(Path::new) == Some(&cwd.join("Cargo.toml")))
+        .and_then(|p| p["name"].as_str())
+        .map(|name| name.replace('-', "_"))
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|s| s.as_str()) == Some("wcet") {
+        args.remove(0);
+    }
+
+    let metadata = cargo_metadata();
+    let cwd = env::current_dir().unwrap();
+    let workspace_root = PathBuf::from(metadata["workspace_root"].as_str().unwrap());
+
+    let mut extra = vec![];
+    if !has_flag(&args, &["--target-dir"]) {
+        extra.push("--target-dir".to_owned());
+        extra.push(metadata["target_directory"].as_str().unwrap().to_owned());
+    }
+    if has_flag(&args, &["--no-tock"]) {
+        if !has_flag(&args, &["-b", "--board"]) {
+            let name = current_crate_name(&metadata, &cwd)
+                .expect("run from the crate to analyze, or pass --board");
+            extra.push("--board".to_owned());
+            extra.push(name);
+        }
+    } else {
+        if !has_flag(&args, &["-p", "--tockpath"]) {
+            extra.push("--tockpath".to_owned());
+            extra.push(workspace_root.display().to_string());
+        }
+        if !has_flag(&args, &["-b", "--board"]) {
+            // Inside boards/<board>, analyze that board
+            if let Ok(board) = cwd.strip_prefix(workspace_root.join("boards")) {
+                if board.components().next().is_some() {
+                    extra.push("--board".to_owned());
+                    extra.push(board.display().to_string());
+                }
+            }
+        }
+    }
+
+    let wcet = env::current_exe()
+        .unwrap()
+        .with_file_name(format!("wcet-rs{}", env::consts::EXE_SUFFIX));
+    let status = Command::new(&wcet)
+        .args(extra)
+        .args(args)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to execute {}: {}", wcet.display(), e));
+    exit(status.code().unwrap_or(1));
+}
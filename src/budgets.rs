@@ -0,0 +1,239 @@
+use haybale::Project;
+use llvm_ir::DebugLoc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::drivers::rust_sources;
+use crate::PathLength;
+
+// matches a budget annotation, e.g. `// wcet: max 2000 instructions`
+static BUDGET_COMMENT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*//+\s*wcet:\s*max\s+([0-9_]+)\s+(ir instructions|machine instructions|instructions|cycles)\s*$")
+        .unwrap()
+});
+// matches the first line of a function item
+static FN_ITEM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"^\s*(pub(\([^)]*\))?\s+)?(const\s+)?(unsafe\s+)?(extern\s+("[^"]*"\s+)?)?fn\s+\w+"#,
+    )
+    .unwrap()
+});
+
+/// What a budget bounds. Plain `instructions` are LLVM IR instructions, like
+/// `--budget`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetUnit {
+    IrInstructions,
+    MachineInstructions,
+    Cycles,
+}
+
+impl BudgetUnit {
+    fn of(self, length: &PathLength) -> u64 {
+        match self {
+            BudgetUnit::IrInstructions => length.ir as u64,
+            BudgetUnit::MachineInstructions => length.machine as u64,
+            BudgetUnit::Cycles => length.cycles,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BudgetUnit::IrInstructions => "IR instructions",
+            BudgetUnit::MachineInstructions => "machine instructions",
+            BudgetUnit::Cycles => "cycles",
+        }
+    }
+}
+
+/// A budget annotated in a comment above a function in the sources.
+#[derive(Debug, Clone)]
+pub struct SourceBudget {
+    pub file: PathBuf,
+    /// Line of the function the budget applies to, from 1
+    pub line: u32,
+    pub max: u64,
+    pub unit: BudgetUnit,
+}
+
+/// The budget annotations of the Rust source `source` of `file`. An
+/// annotation applies to the function item following it, possibly after
+/// further comments and attributes; the line of an annotation that is not
+/// above a function is returned as an error.
+fn annotations(file: &Path, source: &str) -> Vec<Result<SourceBudget, usize>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut annotations = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        let captures = match BUDGET_COMMENT.captures(line) {
+            Some(captures) => captures,
+            None => continue,
+        };
+        let max = match captures[1].replace('_', "").parse() {
+            Ok(max) => max,
+            Err(_) => continue,
+        };
+        let unit = match &captures[2] {
+            "machine instructions" => BudgetUnit::MachineInstructions,
+            "cycles" => BudgetUnit::Cycles,
+            _ => BudgetUnit::IrInstructions,
+        };
+        let item = lines[i + 1..].iter().position(|l| {
+            let l = l.trim_start();
+            !(l.starts_with("//") || l.starts_with("#["))
+        });
+        annotations.push(match item.map(|offset| i + 1 + offset) {
+            Some(fn_line) if FN_ITEM.is_match(lines[fn_line]) => Ok(SourceBudget {
+                file: file.to_path_buf(),
+                line: fn_line as u32 + 1,
+                max,
+                unit,
+            }),
+            _ => Err(i + 1),
+        });
+    }
+    annotations
+}
+
+/// Find the budget annotations in the Rust sources under `dirs`, warning
+/// about those that are not above a function.
+pub fn scan(dirs: &[&Path]) -> Vec<SourceBudget> {
+    let mut files = vec![];
+    for dir in dirs {
+        rust_sources(dir, &mut files);
+    }
+    let mut files: Vec<PathBuf> = files
+        .into_iter()
+        .filter_map(|f| std::fs::canonicalize(f).ok())
+        .collect();
+    files.sort();
+    files.dedup();
+
+    let mut budgets = vec![];
+    for file in files {
+        let source = match std::fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+        for annotation in annotations(&file, &source) {
+            match annotation {
+                Ok(budget) => budgets.push(budget),
+                Err(line) => println!(
+                    "Warning: wcet budget at {}:{} is not above a function",
+                    file.display(),
+                    line
+                ),
+            }
+        }
+    }
+    budgets
+}
+
+/// The budget annotated on the function defined at `loc`, if any. rustc
+/// usually emits paths relative to the workspace root it ran in, given as
+/// the directory, so the file is compared to the budgets' (canonical) files
+/// once joined to it: its name alone would match any crate's `src/lib.rs`.
+fn budget_at<'b>(budgets: &'b [SourceBudget], loc: &DebugLoc) -> Option<&'b SourceBudget> {
+    let filename = Path::new(&loc.filename);
+    let full: PathBuf = match &loc.directory {
+        Some(dir) if !filename.is_absolute() => Path::new(dir).join(filename),
+        _ => filename.to_path_buf(),
+    };
+    let full = std::fs::canonicalize(full).ok()?;
+    budgets
+        .iter()
+        .find(|b| b.line == loc.line && b.file == full)
+}
+
+/// Check the analyzed functions against the budgets annotated on their
+/// definitions (every monomorphization of a function shares its budget).
+/// Returns the report and the number of functions exceeding their budget.
+pub fn check(
+    project: &Project,
+    budgets: &[SourceBudget],
+    results: &HashMap<String, Result<PathLength, String>>,
+) -> (String, usize) {
+    let mut names: Vec<&String> = results.keys().collect();
+    names.sort();
+
+    let mut report = String::new();
+    let mut exceeded = 0;
+    for name in names {
+        let budget = match project
+            .get_func_by_name(name)
+            .and_then(|(f, _m)| f.debugloc.as_ref())
+            .and_then(|loc| budget_at(budgets, loc))
+        {
+            Some(budget) => budget,
+            None => continue,
+        };
+        let status = match &results[name] {
+            Ok(length) => {
+                let actual = budget.unit.of(length);
+                if actual > budget.max {
+                    exceeded += 1;
+                    format!("EXCEEDED ({})", actual)
+                } else {
+                    format!("ok ({})", actual)
+                }
+            }
+            Err(e) => format!("not analyzed: {}", e),
+        };
+        report += &format!(
+            "{}: max {} {} ({}:{}): {}\n",
+            rustc_demangle::demangle(name),
+            budget.max,
+            budget.unit.name(),
+            budget.file.display(),
+            budget.line,
+            status
+        );
+    }
+    (report, exceeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Result<(u32, u64, BudgetUnit), usize>> {
+        annotations(Path::new("src/lib.rs"), source)
+            .into_iter()
+            .map(|a| a.map(|b| (b.line, b.max, b.unit)))
+            .collect()
+    }
+
+    #[test]
+    fn restricted_visibility() {
+        let source = "// wcet: max 2_000 cycles\npub(crate) fn handle_interrupt(&self) {}\n";
+        assert_eq!(parse(source), vec![Ok((2, 2000, BudgetUnit::Cycles))]);
+    }
+
+    #[test]
+    fn attributes_and_comments_before_the_item() {
+        let source = "\
+// wcet: max 500 machine instructions
+/// Handles the interrupt.
+#[inline(never)]
+#[allow(dead_code)]
+pub unsafe extern \"C\" fn handler() {}
+";
+        assert_eq!(
+            parse(source),
+            vec![Ok((5, 500, BudgetUnit::MachineInstructions))]
+        );
+    }
+
+    #[test]
+    fn not_above_a_function() {
+        let source = "\
+// wcet: max 100 instructions
+struct Console;
+
+fn command() {}
+// wcet: max 100 instructions
+";
+        assert_eq!(parse(source), vec![Err(1), Err(5)]);
+    }
+}
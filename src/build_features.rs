@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The cargo features and `--cfg` flags the board was built with. Tock's
+/// kernel options (e.g. `trace_syscalls`, `debug_panics`) are features of
+/// the kernel crate, and change its code paths, so results of builds with
+/// different features are not comparable.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BuildFeatures {
+    /// Enabled features of each crate, by crate name
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Flags passed to rustc with `--cfg`
+    pub cfgs: Vec<String>,
+}
+
+/// The parts of a cargo fingerprint of a compiled crate that are needed.
+#[derive(Deserialize)]
+struct Fingerprint {
+    /// The features, as a JSON list in a string
+    #[serde(default)]
+    features: String,
+    #[serde(default)]
+    rustflags: Vec<String>,
+}
+
+/// The newest fingerprint of each crate (libraries and the board's binary)
+/// in the fingerprint directory of `target_dir`: a previous build with other
+/// features leaves its fingerprints next to those of the last build.
+fn fingerprints(target_dir: &str) -> BTreeMap<String, PathBuf> {
+    let pattern = format!(
+        "{}/.fingerprint/*/*.json",
+        glob::Pattern::escape(target_dir.trim_end_matches('/'))
+    );
+    let mut newest: BTreeMap<String, (std::time::SystemTime, PathBuf)> = BTreeMap::new();
+    for path in glob::glob(&pattern).into_iter().flatten().flatten() {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        let name = match stem
+            .strip_prefix("lib-")
+            .or_else(|| stem.strip_prefix("bin-"))
+        {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let modified = match path.metadata().and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        match newest.get(&name) {
+            Some((newer, _)) if *newer >= modified => {}
+            _ => {
+                newest.insert(name, (modified, path));
+            }
+        }
+    }
+    newest
+        .into_iter()
+        .map(|(name, (_, path))| (name, path))
+        .collect()
+}
+
+fn read(path: &Path) -> Option<Fingerprint> {
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+/// The features and `--cfg` flags of the last build into `target_dir`
+/// (e.g. `target/thumbv7em-none-eabihf/release/`), from cargo's fingerprints.
+pub fn detect(target_dir: &str) -> BuildFeatures {
+    let mut detected = BuildFeatures::default();
+    for (name, path) in fingerprints(target_dir) {
+        let fingerprint = match read(&path) {
+            Some(fingerprint) => fingerprint,
+            None => continue,
+        };
+        let features = serde_json::from_str(&fingerprint.features).unwrap_or_default();
+        detected.features.insert(name, features);
+        let mut flags = fingerprint.rustflags.iter();
+        while let Some(flag) = flags.next() {
+            let cfg = match flag.strip_prefix("--cfg") {
+                Some("") => flags.next().cloned(),
+                Some(cfg) => Some(cfg.trim_start_matches('=').to_owned()),
+                None => None,
+            };
+            if let Some(cfg) = cfg.filter(|cfg| !detected.cfgs.contains(cfg)) {
+                detected.cfgs.push(cfg);
+            }
+        }
+    }
+    detected.cfgs.sort();
+    detected
+}
+
+/// How the configurations of two builds differ, one line per difference.
+/// Crates only one of them compiled (e.g. the boards, or a capsule crate
+/// only one uses) are not compared.
+pub fn differences(before: &BuildFeatures, after: &BuildFeatures) -> Vec<String> {
+    let mut differences = vec![];
+    for (name, b) in &before.features {
+        match after.features.get(name) {
+            Some(a) if a != b => {
+                differences.push(format!("features of {}: {:?} vs {:?}", name, b, a))
+            }
+            _ => {}
+        }
+    }
+    if before.cfgs != after.cfgs {
+        differences.push(format!(
+            "--cfg flags: {:?} vs {:?}",
+            before.cfgs, after.cfgs
+        ));
+    }
+    differences
+}
@@ -0,0 +1,140 @@
+use haybale::Project;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config_file::CalibrationConfig;
+use crate::host;
+use crate::PathLength;
+
+/// A calibration function, with the cycles counted by the analysis and those
+/// measured on hardware or in an emulator.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Sample {
+    pub function: String,
+    pub counted_cycles: u64,
+    pub measured_cycles: u64,
+}
+
+/// Correction of a board's cycle counts, saved as `calibration.json` in the
+/// board's results and applied to the results of later runs.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Calibration {
+    /// Factor the counted cycles are multiplied by: the largest ratio of
+    /// measured to counted cycles, so that no calibration function is
+    /// underestimated
+    pub factor: f64,
+    pub samples: Vec<Sample>,
+}
+
+/// The functions to calibrate with, matching the configured regexes on
+/// demangled names.
+pub fn suite<'p>(project: &'p Project, config: &CalibrationConfig) -> Result<Vec<&'p str>, String> {
+    if config.functions.is_empty() || config.command.is_none() {
+        return Err("calibration needs functions and a command under [calibration]".to_owned());
+    }
+    let patterns = config
+        .functions
+        .iter()
+        .map(|f| Regex::new(f))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid calibration function pattern: {}", e))?;
+    let mut functions: Vec<&str> = project
+        .all_functions()
+        .map(|(f, _m)| f.name.as_str())
+        .filter(|name| {
+            let demangled = rustc_demangle::demangle(name).to_string();
+            patterns.iter().any(|p| p.is_match(&demangled))
+        })
+        .collect();
+    functions.sort_unstable();
+    functions.dedup();
+    Ok(functions)
+}
+
+/// Measure the cycles a function takes by running the configured command from
+/// the board directory, with the function's mangled and demangled names in
+/// `WCET_FUNCTION` and `WCET_FUNCTION_DEMANGLED`. The command prints the
+/// measured cycles as the last line of its output.
+fn measure(command: &str, function: &str, board_path: &str) -> Result<u64, String> {
+    let output = host::shell(command)
+        .current_dir(board_path)
+        .env("WCET_FUNCTION", function)
+        .env(
+            "WCET_FUNCTION_DEMANGLED",
+            rustc_demangle::demangle(function).to_string(),
+        )
+        .output()
+        .map_err(|e| format!("failed to execute {:?}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!("measuring {} failed", function));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .last()
+        .and_then(|line| line.trim().parse().ok())
+        .ok_or_else(|| format!("measuring {} printed no cycle count", function))
+}
+
+/// Measure each analyzed calibration function and derive the board's
+/// correction factor. Functions whose analysis failed are skipped.
+pub fn calibrate(
+    config: &CalibrationConfig,
+    results: &HashMap<String, Result<PathLength, String>>,
+    board_path: &str,
+) -> Result<Calibration, String> {
+    let command = config
+        .command
+        .as_deref()
+        .ok_or("no calibration command under [calibration]")?;
+    let mut functions: Vec<(&String, &PathLength)> = results
+        .iter()
+        .filter_map(|(name, result)| Some((name, result.as_ref().ok()?)))
+        .collect();
+    functions.sort_by_key(|(name, _)| *name);
+
+    let mut samples = vec![];
+    for (function, length) in functions {
+        let measured_cycles = measure(command, function, board_path)?;
+        println!(
+            "{}: {} cycles counted, {} measured",
+            rustc_demangle::demangle(function),
+            length.cycles,
+            measured_cycles
+        );
+        samples.push(Sample {
+            function: function.clone(),
+            counted_cycles: length.cycles,
+            measured_cycles,
+        });
+    }
+    let factor = samples
+        .iter()
+        .filter(|s| s.counted_cycles > 0)
+        .map(|s| s.measured_cycles as f64 / s.counted_cycles as f64)
+        .fold(None, |max: Option<f64>, ratio| {
+            Some(max.map_or(ratio, |m| m.max(ratio)))
+        })
+        .ok_or("no calibration function was analyzed successfully")?;
+    Ok(Calibration { factor, samples })
+}
+
+impl Calibration {
+    pub fn save(&self, board_results: &str) -> Result<(), String> {
+        let path = format!("{}/calibration.json", board_results);
+        std::fs::write(&path, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|e| format!("failed to write {}: {}", path, e))
+    }
+
+    /// The calibration saved in `board_results`, if any.
+    pub fn load(board_results: &str) -> Result<Option<Self>, String> {
+        let path = format!("{}/calibration.json", board_results);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Some)
+                .map_err(|e| format!("failed to parse {}: {}", path, e)),
+            Err(_) => Ok(None),
+        }
+    }
+}
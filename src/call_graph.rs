@@ -0,0 +1,167 @@
+use haybale::Project;
+use llvm_ir::{Constant, Either, Function, Instruction, Name, Operand, Terminator};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::PathLength;
+
+/// The function a call operand refers to, if it is a direct call (possibly
+/// through a bitcast of the function pointer).
+pub fn called_function_name(operand: &Either<llvm_ir::InlineAssembly, Operand>) -> Option<&str> {
+    let mut constant = match operand {
+        Either::Right(Operand::ConstantOperand(c)) => c,
+        _ => return None,
+    };
+    loop {
+        match &**constant {
+            Constant::GlobalReference {
+                name: Name::Name(name),
+                ..
+            } => return Some(name),
+            Constant::BitCast(bitcast) => constant = &bitcast.operand,
+            _ => return None,
+        }
+    }
+}
+
+/// Names of all functions directly called by `f`, in order of first call.
+pub fn direct_callees(f: &Function) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut callees = vec![];
+    for bb in f.basic_blocks.iter() {
+        let calls = bb
+            .instrs
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Call(call) => Some(&call.function),
+                _ => None,
+            })
+            .chain(match &bb.term {
+                Terminator::Invoke(invoke) => Some(&invoke.function),
+                _ => None,
+            });
+        for callee in calls.filter_map(called_function_name) {
+            if seen.insert(callee) {
+                callees.push(callee);
+            }
+        }
+    }
+    callees
+}
+
+/// The static call graph of a project, restricted to functions with a
+/// definition in the project. Calls through function pointers and trait
+/// objects cannot be resolved statically, and are not included.
+pub struct CallGraph<'p> {
+    edges: HashMap<&'p str, Vec<&'p str>>,
+}
+
+impl<'p> CallGraph<'p> {
+    pub fn new(project: &'p Project) -> Self {
+        let defined: HashSet<&str> = project
+            .all_functions()
+            .map(|(f, _m)| f.name.as_str())
+            .collect();
+        let edges = project
+            .all_functions()
+            .map(|(f, _m)| {
+                let callees = direct_callees(f)
+                    .into_iter()
+                    .filter(|callee| defined.contains(callee))
+                    .collect();
+                (f.name.as_str(), callees)
+            })
+            .collect();
+        CallGraph { edges }
+    }
+
+    pub fn callees(&self, name: &str) -> &[&'p str] {
+        self.edges.get(name).map_or(&[], |c| c.as_slice())
+    }
+
+    /// All functions reachable from any of `roots` (including the roots
+    /// themselves), in breadth-first order.
+    pub fn reachable_from(&self, roots: &[&'p str]) -> Vec<&'p str> {
+        let mut seen = HashSet::new();
+        let mut order = vec![];
+        let mut queue = VecDeque::new();
+        for &root in roots {
+            if seen.insert(root) {
+                queue.push_back(root);
+            }
+        }
+        while let Some(name) = queue.pop_front() {
+            order.push(name);
+            for &callee in self.callees(name) {
+                if seen.insert(callee) {
+                    queue.push_back(callee);
+                }
+            }
+        }
+        order
+    }
+
+    /// Render the subgraph over `nodes` in Graphviz DOT format, labeling each
+    /// function with its longest path where one was found.
+    pub fn to_dot(
+        &self,
+        nodes: &[&str],
+        results: &HashMap<String, Result<PathLength, String>>,
+    ) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let included: HashSet<&str> = nodes.iter().copied().collect();
+        let mut dot = String::from("digraph call_graph {\n    node [shape=box];\n");
+        for &name in nodes {
+            let mut label = escape(&format!("{:#}", rustc_demangle::demangle(name)));
+            match results.get(name) {
+                Some(Ok(length)) => label += &format!("\\n{} instructions", length.ir),
+                Some(Err(_)) => label += "\\nanalysis failed",
+                None => {}
+            }
+            dot += &format!("    \"{}\" [label=\"{}\"];\n", escape(name), label);
+        }
+        for &name in nodes {
+            for &callee in self.callees(name) {
+                if included.contains(callee) {
+                    dot += &format!("    \"{}\" -> \"{}\";\n", escape(name), escape(callee));
+                }
+            }
+        }
+        dot + "}\n"
+    }
+
+    /// The subgraph over `nodes` as JSON, with each function's longest path
+    /// (or analysis error) where available.
+    pub fn to_json(
+        &self,
+        nodes: &[&str],
+        results: &HashMap<String, Result<PathLength, String>>,
+    ) -> serde_json::Value {
+        let included: HashSet<&str> = nodes.iter().copied().collect();
+        let json_nodes: Vec<_> = nodes
+            .iter()
+            .map(|&name| {
+                let (longest_path, error) = match results.get(name) {
+                    Some(Ok(length)) => (Some(length.ir), None),
+                    Some(Err(e)) => (None, Some(e)),
+                    None => (None, None),
+                };
+                serde_json::json!({
+                    "name": name,
+                    "demangled": format!("{:#}", rustc_demangle::demangle(name)),
+                    "longest_path": longest_path,
+                    "error": error,
+                })
+            })
+            .collect();
+        let edges: Vec<_> = nodes
+            .iter()
+            .flat_map(|&name| {
+                self.callees(name)
+                    .iter()
+                    .filter(|callee| included.contains(*callee))
+                    .map(move |callee| serde_json::json!({ "caller": name, "callee": callee }))
+            })
+            .collect();
+        serde_json::json!({ "nodes": json_nodes, "edges": edges })
+    }
+}
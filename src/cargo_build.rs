@@ -0,0 +1,156 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The parts of a message of cargo's JSON output that are needed to find bitcode.
+#[derive(Deserialize)]
+struct Message {
+    reason: String,
+    target: Option<Target>,
+    #[serde(default)]
+    filenames: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+    kind: Vec<String>,
+}
+
+/// The bitcode rustc emitted for a compiled crate. Cargo only reports the
+/// rlib or binary it produced, but rustc writes the bitcode next to it in
+/// `deps`, named after the crate and its metadata hash.
+fn bitcode_for(target: &Target, filenames: &[PathBuf]) -> Option<PathBuf> {
+    let artifact = filenames.first()?;
+    let parent = artifact.parent()?;
+    // binaries are reported at their uplifted location outside of `deps`
+    let deps = if parent.ends_with("deps") {
+        parent.to_path_buf()
+    } else {
+        parent.join("deps")
+    };
+    let pattern = format!(
+        "{}/{}-*.bc",
+        glob::Pattern::escape(&deps.to_string_lossy()),
+        target.name.replace('-', "_")
+    );
+    glob::glob(&pattern)
+        .ok()?
+        .filter_map(|p| p.ok())
+        // a previous build with different flags may have left older bitcode
+        .max_by_key(|p| p.metadata().and_then(|m| m.modified()).ok())
+}
+
+/// Build the crate in `crate_dir` for `target` by invoking cargo directly,
+/// having rustc emit LLVM bitcode (and, if `emit_asm`, assembly) for every
+/// crate it compiles, and return the paths of the produced bitcode files.
+/// Cargo's output is appended to `log`.
+///
+/// The emit flag is appended to `RUSTFLAGS` (from `env`, or else the
+/// environment), which takes precedence over rustflags set in cargo config
+/// files, so boards relying on those must set the equivalent `RUSTFLAGS`.
+pub fn build(
+    crate_dir: &str,
+    target: &str,
+    target_dir: &str,
+    clean: bool,
+    emit_asm: bool,
+    env: &HashMap<String, String>,
+    log: &mut String,
+) -> Result<Vec<PathBuf>, String> {
+    println!("Compiling {:?} with cargo, please wait...", crate_dir);
+    let target_dir =
+        std::fs::canonicalize(target_dir).unwrap_or_else(|_| PathBuf::from(target_dir));
+
+    if clean {
+        let output = Command::new("cargo")
+            .current_dir(crate_dir)
+            .envs(env)
+            .arg("clean")
+            .arg("--release")
+            .arg("--target")
+            .arg(target)
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .output()
+            .map_err(|e| format!("failed to execute cargo clean: {}", e))?;
+        *log += "$ cargo clean\n";
+        *log += &String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err("cargo clean failed".to_owned());
+        }
+    }
+
+    let emit = match emit_asm {
+        true => "--emit=llvm-bc,asm,link",
+        false => "--emit=llvm-bc,link",
+    };
+    let rustflags = match env
+        .get("RUSTFLAGS")
+        .cloned()
+        .or_else(|| std::env::var("RUSTFLAGS").ok())
+    {
+        Some(flags) if !flags.trim().is_empty() => flags + " " + emit,
+        _ => emit.to_owned(),
+    };
+    let output = Command::new("cargo")
+        .current_dir(crate_dir)
+        .envs(env)
+        .env("RUSTFLAGS", &rustflags)
+        .arg("build")
+        .arg("--release")
+        .arg("--target")
+        .arg(target)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .arg("--message-format=json-render-diagnostics")
+        .output()
+        .map_err(|e| format!("failed to execute cargo build: {}", e))?;
+    // stdout only holds the JSON messages, diagnostics are rendered to stderr
+    *log += &format!("$ RUSTFLAGS={:?} cargo build\n", rustflags);
+    *log += &String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err("cargo build failed".to_owned());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut bitcode = vec![];
+    for line in stdout.lines() {
+        let message: Message = match serde_json::from_str(line) {
+            Ok(message) => message,
+            // cargo may interleave output of build scripts
+            Err(_) => continue,
+        };
+        let target = match message.target {
+            Some(target) if message.reason == "compiler-artifact" => target,
+            _ => continue,
+        };
+        // build scripts and proc macros are compiled for the host
+        if target
+            .kind
+            .iter()
+            .any(|k| k == "custom-build" || k == "proc-macro")
+        {
+            continue;
+        }
+        match bitcode_for(&target, &message.filenames) {
+            Some(path) => bitcode.push(path),
+            None => println!("No bitcode found for crate {}", target.name),
+        }
+    }
+    println!("Finished building, found {} bitcode files", bitcode.len());
+    Ok(bitcode)
+}
+
+/// The directory containing the bitcode of the crate `crate_name`, if it was built.
+pub fn bitcode_dir(bitcode: &[PathBuf], crate_name: &str) -> Option<PathBuf> {
+    let prefix = format!("{}-", crate_name);
+    bitcode
+        .iter()
+        .find(
+            |p| matches!(p.file_name().and_then(|n| n.to_str()), Some(n) if n.starts_with(&prefix)),
+        )
+        .and_then(|p| p.parent())
+        .map(Path::to_path_buf)
+}
@@ -0,0 +1,435 @@
+use haybale::{PathEntry, Project};
+use llvm_ir::Name;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::{AnalysisSettings, PathLength};
+
+/// How often the exploration of a function is saved.
+const FRONTIER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Outcomes of the functions a run has finished analyzing, saved as
+/// `checkpoint.json` in the board's results after each function, so that an
+/// interrupted run can be resumed without analyzing them again. A function
+/// interrupted in the middle of its analysis by the bounded engine continues
+/// from its `Frontier`, by the exhaustive engine from its `RoundFrontier`;
+/// with the longest path search, it starts over.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Checkpoint {
+    /// Fingerprint of the sources the bitcode was built from
+    pub fingerprint: String,
+    /// Settings of the run, which a resumed run must share
+    pub settings: serde_json::Value,
+    pub outcomes: HashMap<String, Result<PathLength, String>>,
+    /// The accumulated results database and the id of the run in it, which
+    /// a resumed run keeps writing to
+    #[serde(default)]
+    pub accumulated_run: Option<(String, i64)>,
+}
+
+impl Checkpoint {
+    pub fn new(fingerprint: &str, settings: &AnalysisSettings) -> Self {
+        Checkpoint {
+            fingerprint: fingerprint.to_owned(),
+            settings: serde_json::to_value(settings).unwrap(),
+            outcomes: HashMap::new(),
+            accumulated_run: None,
+        }
+    }
+
+    /// The checkpoint of an interrupted run with the same sources and
+    /// settings, or a new one if there is none.
+    pub fn resume(
+        board_results: &str,
+        fingerprint: &str,
+        settings: &AnalysisSettings,
+    ) -> Result<Self, String> {
+        let path = format!("{}/checkpoint.json", board_results);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Ok(Self::new(fingerprint, settings)),
+        };
+        let checkpoint: Checkpoint = serde_json::from_str(&contents)
+            .map_err(|e| format!("failed to parse {}: {}", path, e))?;
+        let new = Self::new(fingerprint, settings);
+        if checkpoint.fingerprint != new.fingerprint {
+            return Err(format!(
+                "{} was saved for other sources, remove it or run without --resume",
+                path
+            ));
+        }
+        if checkpoint.settings != new.settings {
+            return Err(format!(
+                "{} was saved with other settings, remove it or run without --resume",
+                path
+            ));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Save the checkpoint, replacing the previous one only once it is
+    /// completely written.
+    pub fn save(&self, board_results: &str) -> Result<(), String> {
+        let path = format!("{}/checkpoint.json", board_results);
+        let partial = format!("{}.partial", path);
+        std::fs::write(&partial, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|e| format!("failed to write {}: {}", partial, e))?;
+        std::fs::rename(&partial, &path).map_err(|e| format!("failed to write {}: {}", path, e))
+    }
+}
+
+/// A block entered by a path, by function and block name.
+pub type Block<'p> = (&'p str, &'p Name);
+
+/// The block `bb` of function `func` in `project`, if it still has it.
+fn find_block<'p>(project: &'p Project, func: &str, bb: &str) -> Option<Block<'p>> {
+    let (function, _) = project.get_func_by_name(func)?;
+    let block = function
+        .basic_blocks
+        .iter()
+        .find(|block| block.name.to_string() == bb)?;
+    Some((&function.name, &block.name))
+}
+
+/// Write `contents` to `file`, replacing it only once it is completely
+/// written.
+fn write_save(file: &str, contents: String) -> Result<(), String> {
+    if let Some(dir) = std::path::Path::new(file).parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    }
+    let partial = format!("{}.partial", file);
+    std::fs::write(&partial, contents)
+        .map_err(|e| format!("failed to write {}: {}", partial, e))?;
+    std::fs::rename(&partial, file).map_err(|e| format!("failed to write {}: {}", file, e))
+}
+
+/// The exploration frontier of a function, as saved: the blocks of the
+/// paths, and paths as indices into them.
+#[derive(Serialize, Deserialize, Default)]
+struct SavedFrontier {
+    /// Function and block names
+    blocks: Vec<(String, String)>,
+    /// Prefixes of paths whose every continuation was explored
+    explored: Vec<Vec<u32>>,
+    /// The longest path found
+    longest: Vec<u32>,
+}
+
+/// What the bounded engine explored of a function so far, saved in the
+/// run's `frontiers` directory while it runs so that a resumed run continues
+/// the exploration instead of starting it over. haybale's states hold live
+/// solver instances and cannot be saved, so the frontier is kept as branch
+/// decisions: the prefixes of paths (the blocks they entered) whose every
+/// continuation was explored, which the resumed exploration abandons as
+/// soon as it enters them, and the longest path found, which it replays to
+/// find it again. haybale backtracks depth-first, so when a path leaves the
+/// previous one after its `d` first blocks, every continuation of the
+/// previous one's `d + 1` first blocks was explored.
+pub struct Frontier<'p> {
+    file: String,
+    blocks: Vec<Block<'p>>,
+    ids: HashMap<Block<'p>, u32>,
+    /// Trie of the explored prefixes: each node's parent and last block, the
+    /// child of each node for a block, and the nodes explored completely
+    nodes: Vec<(u32, u32)>,
+    children: HashMap<(u32, u32), u32>,
+    explored: HashSet<u32>,
+    /// The longest path found before the exploration was resumed, replayed
+    replay: Vec<u32>,
+    longest: Vec<u32>,
+    previous: Vec<u32>,
+    saved_at: Instant,
+}
+
+/// The file the exploration of `entry` is saved to in `dir`. Analyses of
+/// the same entry with the panic paths counted or not are saved apart.
+pub fn frontier_file(dir: &str, entry: &str, count_separate: bool) -> String {
+    let mut hasher = DefaultHasher::new();
+    (entry, count_separate).hash(&mut hasher);
+    format!("{}/{:016x}.json", dir, hasher.finish())
+}
+
+impl<'p> Frontier<'p> {
+    /// The exploration saved in `file`, or a new one if there is none.
+    /// Blocks no longer in `project` end the saved paths there.
+    pub fn load(file: &str, project: &'p Project) -> Result<Self, String> {
+        let mut frontier = Frontier {
+            file: file.to_owned(),
+            blocks: vec![],
+            ids: HashMap::new(),
+            nodes: vec![(0, 0)],
+            children: HashMap::new(),
+            explored: HashSet::new(),
+            replay: vec![],
+            longest: vec![],
+            previous: vec![],
+            saved_at: Instant::now(),
+        };
+        let saved: SavedFrontier = match std::fs::read_to_string(file) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("failed to parse {}: {}", file, e))?,
+            Err(_) => return Ok(frontier),
+        };
+        let blocks: Vec<Option<u32>> = saved
+            .blocks
+            .iter()
+            .map(|(func, bb)| Some(frontier.id(find_block(project, func, bb)?)))
+            .collect();
+        let resolve = |path: &[u32]| -> Option<Vec<u32>> {
+            path.iter().map(|&i| *blocks.get(i as usize)?).collect()
+        };
+        for prefix in saved.explored.iter().filter_map(|p| resolve(p)) {
+            let node = frontier.insert(&prefix);
+            frontier.explored.insert(node);
+        }
+        frontier.replay = resolve(&saved.longest).unwrap_or_default();
+        Ok(frontier)
+    }
+
+    fn id(&mut self, block: Block<'p>) -> u32 {
+        let next = self.blocks.len() as u32;
+        let id = *self.ids.entry(block).or_insert(next);
+        if id == next {
+            self.blocks.push(block);
+        }
+        id
+    }
+
+    fn path_ids(&mut self, path: &[PathEntry<'p>]) -> Vec<u32> {
+        path.iter()
+            .map(|entry| self.id((&entry.0.func.name, &entry.0.bb.name)))
+            .collect()
+    }
+
+    /// The node of the trie for `prefix`, inserted if needed.
+    fn insert(&mut self, prefix: &[u32]) -> u32 {
+        let mut node = 0;
+        for &block in prefix {
+            let (parent, next) = (node, self.nodes.len() as u32);
+            node = *self.children.entry((parent, block)).or_insert(next);
+            if node == next {
+                self.nodes.push((parent, block));
+            }
+        }
+        node
+    }
+
+    /// Whether every continuation of `path` was explored before the
+    /// exploration was resumed, unless it leads to the longest path.
+    pub fn is_explored(&self, path: &[PathEntry<'p>]) -> bool {
+        let mut node = 0;
+        let mut explored = false;
+        for entry in path {
+            if self.explored.contains(&node) {
+                explored = true;
+                break;
+            }
+            let child = self
+                .ids
+                .get(&(entry.0.func.name.as_str(), &entry.0.bb.name))
+                .and_then(|&id| self.children.get(&(node, id)));
+            match child {
+                Some(&child) => node = child,
+                None => return false,
+            }
+        }
+        let replayed = path.len() <= self.replay.len()
+            && path.iter().zip(&self.replay).all(|(entry, &id)| {
+                self.blocks[id as usize] == (&entry.0.func.name, &entry.0.bb.name)
+            });
+        (explored || self.explored.contains(&node)) && !replayed
+    }
+
+    /// Record a path ended (returned, or abandoned), and save the
+    /// exploration if it was not saved for a while.
+    pub fn path_done(&mut self, path: &[PathEntry<'p>]) -> Result<(), String> {
+        let ids = self.path_ids(path);
+        let shared = ids
+            .iter()
+            .zip(&self.previous)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if shared < self.previous.len() {
+            let previous = std::mem::take(&mut self.previous);
+            let node = self.insert(&previous[..=shared]);
+            self.explored.insert(node);
+        }
+        self.previous = ids;
+        if self.saved_at.elapsed() >= FRONTIER_INTERVAL {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Record the longest path found so far.
+    pub fn longest_found(&mut self, path: &[PathEntry<'p>]) {
+        self.longest = self.path_ids(path);
+    }
+
+    /// The prefix of node `node` in the trie.
+    fn prefix(&self, mut node: u32) -> Vec<u32> {
+        let mut prefix = vec![];
+        while node != 0 {
+            let (parent, block) = self.nodes[node as usize];
+            prefix.push(block);
+            node = parent;
+        }
+        prefix.reverse();
+        prefix
+    }
+
+    /// Save the explored prefixes not within another, and the last path,
+    /// replacing the previous save only once it is completely written.
+    fn save(&mut self) -> Result<(), String> {
+        let mut explored: Vec<Vec<u32>> = self
+            .explored
+            .iter()
+            .map(|&node| self.prefix(node))
+            .collect();
+        explored.push(self.previous.clone());
+        explored.sort();
+        explored.dedup_by(|later, earlier| later.starts_with(earlier));
+        let saved = SavedFrontier {
+            blocks: self
+                .blocks
+                .iter()
+                .map(|(func, bb)| (func.to_string(), bb.to_string()))
+                .collect(),
+            explored,
+            longest: self.longest.clone(),
+        };
+        write_save(&self.file, serde_json::to_string(&saved).unwrap())?;
+        self.saved_at = Instant::now();
+        Ok(())
+    }
+
+    /// The exploration is complete: remove its save.
+    pub fn finish(&self) {
+        let _ = std::fs::remove_file(&self.file);
+    }
+}
+
+/// A prefix of a path the exhaustive engine is to continue: the blocks it
+/// entered, and the longest the paths continuing it can be, if known.
+pub struct Prefix<'p> {
+    pub blocks: Vec<Block<'p>>,
+    pub bound: Option<usize>,
+}
+
+/// The prefixes a resumed breadth-first search continues, and the longest
+/// path it had found.
+pub type Resumed<'p> = (Vec<Prefix<'p>>, Vec<Block<'p>>);
+
+/// The frontier of the exhaustive engine's breadth-first search, as saved:
+/// the blocks of the paths, and paths as indices into them.
+#[derive(Serialize, Deserialize, Default)]
+struct SavedRound {
+    /// Function and block names
+    blocks: Vec<(String, String)>,
+    /// Prefixes of the paths the next round continues, with their bounds
+    prefixes: Vec<(Vec<u32>, Option<usize>)>,
+    /// The longest path found
+    longest: Vec<u32>,
+}
+
+/// Where the exhaustive engine saves the frontier of its breadth-first
+/// search, in the run's `frontiers` directory: after a round, if it was not
+/// saved for a while, the prefixes the next round continues and the longest
+/// path found, which a resumed search runs again to find it again. A round
+/// interrupted before its end is run again from its start.
+pub struct RoundFrontier {
+    file: String,
+    saved_at: Instant,
+}
+
+impl RoundFrontier {
+    /// The frontier saved in `file`, with its prefixes and longest path if
+    /// there is one. A save with blocks no longer in `project` is not
+    /// resumed.
+    pub fn load<'p>(
+        file: &str,
+        project: &'p Project,
+    ) -> Result<(Self, Option<Resumed<'p>>), String> {
+        let frontier = RoundFrontier {
+            file: file.to_owned(),
+            saved_at: Instant::now(),
+        };
+        let saved: SavedRound = match std::fs::read_to_string(file) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("failed to parse {}: {}", file, e))?,
+            Err(_) => return Ok((frontier, None)),
+        };
+        let blocks: Option<Vec<Block<'p>>> = saved
+            .blocks
+            .iter()
+            .map(|(func, bb)| find_block(project, func, bb))
+            .collect();
+        let blocks = match blocks {
+            Some(blocks) => blocks,
+            None => return Ok((frontier, None)),
+        };
+        let resolve = |path: &[u32]| -> Option<Vec<Block<'p>>> {
+            path.iter()
+                .map(|&i| blocks.get(i as usize).copied())
+                .collect()
+        };
+        let prefixes: Option<Vec<Prefix<'p>>> = saved
+            .prefixes
+            .iter()
+            .map(|(path, bound)| {
+                Some(Prefix {
+                    blocks: resolve(path)?,
+                    bound: *bound,
+                })
+            })
+            .collect();
+        let resumed = prefixes.zip(resolve(&saved.longest));
+        Ok((frontier, resumed))
+    }
+
+    /// A round ended, leaving `prefixes` to continue: save them with the
+    /// longest path found if the frontier was not saved for a while.
+    pub fn round_done<'p>(
+        &mut self,
+        prefixes: &[Prefix<'p>],
+        longest: &[Block<'p>],
+    ) -> Result<(), String> {
+        if self.saved_at.elapsed() < FRONTIER_INTERVAL {
+            return Ok(());
+        }
+        let mut blocks = vec![];
+        let mut ids = HashMap::new();
+        let mut path_ids = |path: &[Block<'p>]| -> Vec<u32> {
+            path.iter()
+                .map(|&(func, bb)| {
+                    *ids.entry((func, bb)).or_insert_with(|| {
+                        blocks.push((func.to_owned(), bb.to_string()));
+                        blocks.len() as u32 - 1
+                    })
+                })
+                .collect()
+        };
+        let prefixes = prefixes
+            .iter()
+            .map(|prefix| (path_ids(&prefix.blocks), prefix.bound))
+            .collect();
+        let longest = path_ids(longest);
+        let saved = SavedRound {
+            blocks,
+            prefixes,
+            longest,
+        };
+        write_save(&self.file, serde_json::to_string(&saved).unwrap())?;
+        self.saved_at = Instant::now();
+        Ok(())
+    }
+
+    /// The search is complete: remove its save.
+    pub fn finish(&self) {
+        let _ = std::fs::remove_file(&self.file);
+    }
+}
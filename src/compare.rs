@@ -0,0 +1,284 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+use crate::build_features::{self, BuildFeatures};
+use crate::host;
+use crate::report::{parse_summary, ComparisonRow, Noise};
+use crate::run_dir;
+
+/// A board and its results, by mangled function name.
+pub type BoardSummary = (String, HashMap<String, usize>);
+
+/// Options selecting boards, which are not forwarded to the analysis of each
+/// compared board.
+const BOARD_OPTIONS: &[&str] = &[
+    "--compare-boards",
+    "--diff-boards",
+    "--board",
+    "-b",
+    "--board-path",
+];
+
+/// Command line arguments of this run, without the ones selecting boards,
+/// so they can be forwarded to the analysis of each compared board.
+fn forwarded_args() -> Vec<String> {
+    let mut forwarded = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if BOARD_OPTIONS.contains(&arg.as_str()) {
+            args.next();
+        } else if !BOARD_OPTIONS
+            .iter()
+            .any(|option| arg.starts_with(&format!("{}=", option)))
+        {
+            forwarded.push(arg);
+        }
+    }
+    forwarded
+}
+
+/// Load the summary of each board from `resultspath`. Boards without results
+/// are analyzed first, in parallel, by running this tool once per board with
+/// the remaining arguments of this run.
+pub fn load_or_run(boards: &[String], resultspath: &str) -> Result<Vec<BoardSummary>, String> {
+    let selected: Vec<(String, Vec<String>)> = boards
+        .iter()
+        .map(|board| (board.clone(), vec!["--board".to_owned(), board.clone()]))
+        .collect();
+    load_or_run_selected(&selected, resultspath)
+}
+
+/// Like `load_or_run`, for boards given by the path to their crate. Their
+/// results are named after the crate's directory, which must differ.
+pub fn load_or_run_paths(
+    board_paths: &[String],
+    resultspath: &str,
+) -> Result<Vec<BoardSummary>, String> {
+    let selected: Vec<(String, Vec<String>)> = board_paths
+        .iter()
+        .map(|path| {
+            let path = host::trim_path(path);
+            let name = host::last_component(path);
+            (name, vec!["--board-path".to_owned(), path.to_owned()])
+        })
+        .collect();
+    for (i, (name, _)) in selected.iter().enumerate() {
+        if selected[..i].iter().any(|(other, _)| other == name) {
+            return Err(format!(
+                "boards compared by path must be in directories with different names, {} is not",
+                name
+            ));
+        }
+    }
+    load_or_run_selected(&selected, resultspath)
+}
+
+/// Load or analyze each board, given by its name and the arguments selecting it.
+fn load_or_run_selected(
+    boards: &[(String, Vec<String>)],
+    resultspath: &str,
+) -> Result<Vec<BoardSummary>, String> {
+    let summary_path = |board: &str| format!("{}/summary.txt", run_dir::latest(resultspath, board));
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let args = forwarded_args();
+    let children: Vec<_> = boards
+        .iter()
+        .filter(|(board, _)| !Path::new(&summary_path(board)).exists())
+        .map(|(board, select)| {
+            println!("No results for {}, analyzing it", board);
+            let (exe, args, board, select) =
+                (exe.clone(), args.clone(), board.clone(), select.clone());
+            thread::spawn(move || {
+                let status = Command::new(&exe)
+                    .args(&args)
+                    .args(&select)
+                    .status()
+                    .map_err(|e| format!("failed to analyze {}: {}", board, e))?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("analysis of {} failed", board))
+                }
+            })
+        })
+        .collect();
+    for child in children {
+        child.join().map_err(|_| "analysis thread panicked")??;
+    }
+
+    boards
+        .iter()
+        .map(|(board, _)| {
+            let path = summary_path(board);
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path, e))?;
+            Ok((board.clone(), parse_summary(&contents)))
+        })
+        .collect()
+}
+
+/// The part of a run's manifest recording how the board was built.
+#[derive(Deserialize)]
+struct ManifestBuild {
+    build_features: Option<BuildFeatures>,
+}
+
+/// Refuse to compare boards whose results were obtained from builds with
+/// different features or `--cfg` flags, which change the kernel's code
+/// paths, unless `allow`ed. Boards whose manifest does not record them
+/// (from older versions of this tool) are compared with a warning.
+pub fn check_build_features(
+    boards: &[BoardSummary],
+    resultspath: &str,
+    allow: bool,
+) -> Result<(), String> {
+    let mut recorded = vec![];
+    for (board, _) in boards {
+        let path = format!("{}/manifest.json", run_dir::latest(resultspath, board));
+        let manifest: Option<ManifestBuild> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok());
+        match manifest.and_then(|m| m.build_features) {
+            Some(features) => recorded.push((board, features)),
+            None => println!(
+                "Warning: the build features of {} are not recorded, comparing anyway",
+                board
+            ),
+        }
+    }
+    for pair in recorded.windows(2) {
+        let ((before, b), (after, a)) = (&pair[0], &pair[1]);
+        let differences = build_features::differences(b, a);
+        if differences.is_empty() {
+            continue;
+        }
+        let mut message = format!("{} and {} were built differently:", before, after);
+        for difference in differences {
+            message += &format!("\n  {}", difference);
+        }
+        if !allow {
+            return Err(message + "\npass --allow-feature-mismatch to compare them anyway");
+        }
+        println!("Warning: {}", message);
+    }
+    Ok(())
+}
+
+/// Results of each board by demangled name without the crate hash, keeping
+/// the worst result if several instances share a name.
+pub fn by_demangled_name(summary: &HashMap<String, usize>) -> BTreeMap<String, usize> {
+    let mut results = BTreeMap::new();
+    for (name, &len) in summary {
+        let worst = results
+            .entry(format!("{:#}", rustc_demangle::demangle(name)))
+            .or_insert(len);
+        *worst = (*worst).max(len);
+    }
+    results
+}
+
+/// Tab-separated difference of each function's longest path between two board
+/// configurations, for the functions both have, with a summary of how the
+/// worst result and the functions only one of them has changed. Changes within
+/// `noise` are listed with their numbers, but not counted as changes.
+pub fn diff(before: &BoardSummary, after: &BoardSummary, noise: &Noise) -> String {
+    let (before_name, before) = (&before.0, by_demangled_name(&before.1));
+    let (after_name, after) = (&after.0, by_demangled_name(&after.1));
+
+    let mut out = format!(
+        "function\t{}\t{}\tdelta\tdelta %\tstatus\n",
+        before_name, after_name
+    );
+    let mut changed = 0;
+    let mut within_noise = 0;
+    for (name, &old) in &before {
+        if let Some(&new) = after.get(name) {
+            let delta = new as i64 - old as i64;
+            let status = if delta == 0 {
+                "unchanged"
+            } else if noise.is_noise(old, new) {
+                within_noise += 1;
+                "noise"
+            } else {
+                changed += 1;
+                "changed"
+            };
+            out += &format!(
+                "{}\t{}\t{}\t{:+}\t{:+.1}\t{}\n",
+                name,
+                old,
+                new,
+                delta,
+                delta as f64 / old.max(1) as f64 * 100.0,
+                status
+            );
+        }
+    }
+    let only_before = before.keys().filter(|n| !after.contains_key(*n)).count();
+    let only_after = after.keys().filter(|n| !before.contains_key(*n)).count();
+    out += &format!(
+        "\n{} shared functions changed ({} more within noise), {} only in {}, {} only in {}\n",
+        changed, within_noise, only_before, before_name, only_after, after_name
+    );
+    out += &format!(
+        "Worst longest path: {} in {}, {} in {}\n",
+        before.values().max().copied().unwrap_or(0),
+        before_name,
+        after.values().max().copied().unwrap_or(0),
+        after_name
+    );
+    out
+}
+
+/// Rows comparing two board configurations, for every function either has.
+pub fn diff_rows(before: &BoardSummary, after: &BoardSummary) -> Vec<ComparisonRow> {
+    let (before, after) = (by_demangled_name(&before.1), by_demangled_name(&after.1));
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| ComparisonRow {
+            function: name.clone(),
+            old: before.get(name).copied(),
+            new: after.get(name).copied(),
+            failed: false,
+        })
+        .collect()
+}
+
+/// Tab-separated matrix with a row per function and a column per board.
+/// Functions are matched across boards by their demangled name without the
+/// crate hash, keeping the worst result if several instances share a name.
+pub fn matrix(summaries: &[BoardSummary]) -> String {
+    let mut rows: BTreeMap<String, Vec<Option<usize>>> = BTreeMap::new();
+    for (i, (_, summary)) in summaries.iter().enumerate() {
+        for (name, &len) in summary {
+            let row = rows
+                .entry(format!("{:#}", rustc_demangle::demangle(name)))
+                .or_insert_with(|| vec![None; summaries.len()]);
+            row[i] = row[i].max(Some(len));
+        }
+    }
+
+    let mut out = String::from("function");
+    for (board, _) in summaries {
+        out += &format!("\t{}", board);
+    }
+    out += "\n";
+    for (name, row) in rows {
+        out += &name;
+        for len in row {
+            match len {
+                Some(len) => out += &format!("\t{}", len),
+                None => out += "\t-",
+            }
+        }
+        out += "\n";
+    }
+    out
+}
@@ -0,0 +1,450 @@
+use clap::arg_enum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::instruction_counter::CostModel;
+use crate::process_state::ProcessState;
+
+/// Settings read from the TOML file passed with `--config`, for configuration
+/// that is too board- or project-specific to hard-code.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Regexes on demangled names of functions which are never analyzed
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Regexes on demangled names of further functions which only run after a
+    /// panic, e.g. a board's own panic helpers, hooked to abort like the built-in ones
+    #[serde(default)]
+    pub panic_functions: Vec<String>,
+    /// Number of virtual alarms on the board, instead of counting them in its sources
+    pub alarm_clients: Option<usize>,
+    /// Constraints on the arguments of functions, excluding inputs they never receive
+    #[serde(default)]
+    pub arguments: Vec<ArgumentConfig>,
+    /// Functions also analyzed at several values of one argument
+    #[serde(default)]
+    pub parametric: Vec<ParametricConfig>,
+    /// Lengths of the buffers shared with `allow`, which allow handlers are
+    /// constrained to and analyzed at
+    #[serde(default)]
+    pub allow: Option<AllowConfig>,
+    /// Where the kernel's syscall dispatch receives the driver number, to
+    /// analyze the dispatch to each of the board's drivers
+    #[serde(default)]
+    pub syscall_dispatch: Option<SyscallDispatchConfig>,
+    /// Assumptions about the state of processes
+    #[serde(default)]
+    pub process_state: ProcessState,
+    /// Per-category settings, keyed by category name (e.g. "commands")
+    #[serde(default)]
+    pub categories: HashMap<String, CategoryConfig>,
+    /// Periodic or sporadic work for schedulability analysis
+    #[serde(default)]
+    pub tasks: Vec<TaskConfig>,
+    /// Interrupt sources whose handlers and client callbacks are combined,
+    /// instead of pairing handlers with clients by name
+    #[serde(default)]
+    pub interrupt_sources: Vec<InterruptSourceConfig>,
+    /// Functions replaced by a hook during the analysis
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    /// Trusted costs of functions, replacing their symbolic execution
+    #[serde(default)]
+    pub symbol_costs: Vec<SymbolCost>,
+    /// Costs of functions implemented in assembly or C libraries, by symbol
+    #[serde(default)]
+    pub asm_stubs: HashMap<String, AsmStubCost>,
+    /// DMA channel and descriptor counts, by chip crate, overriding the
+    /// built-in ones
+    #[serde(default)]
+    pub dma: HashMap<String, DmaLimits>,
+    /// Commands receiving the results as they complete
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+    /// Functions whose failure stops the run, or is tolerated
+    #[serde(default)]
+    pub failures: FailureConfig,
+    /// Subsystems of crates, by crate name, overriding the ones guessed from
+    /// their names
+    #[serde(default)]
+    pub subsystems: HashMap<String, String>,
+    /// Cycles taken by machine instructions, used to report results in time
+    #[serde(default)]
+    pub cost_model: CostModel,
+    /// How to build the board
+    #[serde(default)]
+    pub build: BuildConfig,
+    /// How to measure functions to calibrate cycle counts against
+    #[serde(default)]
+    pub calibration: CalibrationConfig,
+}
+
+/// Functions measured on hardware or in an emulator with `--calibrate`.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CalibrationConfig {
+    /// Regexes on demangled names of the functions to calibrate with
+    #[serde(default)]
+    pub functions: Vec<String>,
+    /// Shell command run from the board directory to measure the function
+    /// named by `WCET_FUNCTION`, printing its cycles as the last line
+    pub command: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Shell command building the board from its directory, instead of `make`
+    pub command: Option<String>,
+    /// Shell command run before clean builds, instead of `make clean`
+    pub clean_command: Option<String>,
+    /// Additional environment variables for the build, e.g. `RUSTFLAGS`
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryConfig {
+    /// Regexes on demangled names of functions in this category which are not analyzed
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Constraints on the arguments of the functions matching a pattern.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ArgumentConfig {
+    /// Regex on demangled names of the functions whose arguments are constrained
+    pub function: String,
+    pub constraints: Vec<ArgumentConstraint>,
+}
+
+/// An integer argument the WCET of the functions matching a pattern depends
+/// on (e.g. a buffer length), and the values to analyze them at.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ParametricConfig {
+    /// Regex on demangled names of the functions analyzed parametrically
+    pub function: String,
+    /// Position of the argument in the function's LLVM signature, from 0
+    pub arg: usize,
+    pub values: Vec<u64>,
+    /// Byte offset of the integer in what the argument points to, if it is
+    /// passed inside a struct or enum
+    pub offset: Option<u64>,
+    /// Width in bits of the integer at `offset`, by default 32
+    pub bits: Option<u32>,
+}
+
+/// Where `handle_syscall` finds the driver number of a syscall, so that the
+/// dispatch is also analyzed at each driver number of the board.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SyscallDispatchConfig {
+    /// Position of the syscall argument in `handle_syscall`'s LLVM signature, from 0
+    pub arg: usize,
+    /// Byte offset of the driver number in the syscall the argument points
+    /// to, if the argument is not the driver number itself
+    pub offset: Option<u64>,
+    /// Width in bits of the driver number at `offset`, by default 32
+    pub bits: Option<u32>,
+}
+
+/// Lengths, in bytes, of the buffers processes share with the board's
+/// drivers through `allow`, on which the cost of allow handlers often depends.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AllowConfig {
+    /// Shortest length analyzed
+    #[serde(default)]
+    pub min_len: u64,
+    /// A typical length, also analyzed
+    pub typical_len: Option<u64>,
+    /// Longest length documented by the drivers
+    pub max_len: u64,
+    /// Lengths of the drivers whose documented maximum differs
+    #[serde(default)]
+    pub drivers: Vec<AllowDriverConfig>,
+}
+
+/// Buffer lengths of the allow handlers matching a pattern, overriding the
+/// board-wide ones.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AllowDriverConfig {
+    /// Regex on demangled names of the allow handlers
+    pub function: String,
+    pub typical_len: Option<u64>,
+    pub max_len: u64,
+    /// Position of the length argument in the handlers' LLVM signature, from
+    /// 0, when it is not the last one following a pointer
+    pub arg: Option<usize>,
+}
+
+/// Functions which are not executed symbolically: calls to them return
+/// immediately, with a value constrained to `return_values` if given (e.g. the
+/// encodings of `Ok(())` and `Err(BUSY)` of a HIL call), and add a fixed cost
+/// to the longest path.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    /// Regex on demangled names of the hooked functions
+    pub function: String,
+    /// Values the call may return, as unsigned integers of the return type's width
+    pub return_values: Option<Vec<u64>>,
+    /// Cost of each call, in LLVM IR instructions
+    #[serde(default)]
+    pub ir: usize,
+    /// Cost of each call, in machine instructions
+    #[serde(default)]
+    pub machine: usize,
+    /// Cost of each call, in cycles
+    #[serde(default)]
+    pub cycles: u64,
+}
+
+/// A trusted bound on a function, e.g. from measurements or a manual proof,
+/// used instead of executing it symbolically: both when it is analyzed and
+/// when it is called.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolCost {
+    /// Regex on demangled names of the functions
+    pub function: String,
+    /// Cost in LLVM IR instructions
+    #[serde(default)]
+    pub ir: usize,
+    /// Cost in machine instructions
+    pub machine: usize,
+    /// Cost in cycles
+    pub cycles: u64,
+}
+
+impl SymbolCost {
+    /// The hook replacing calls to the functions.
+    pub fn hook(&self) -> HookConfig {
+        HookConfig {
+            function: self.function.clone(),
+            return_values: None,
+            ir: self.ir,
+            machine: self.machine,
+            cycles: self.cycles,
+        }
+    }
+}
+
+/// Cost of a call to a function implemented in assembly, or in a C library
+/// linked into the board, which has no IR to execute symbolically.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AsmStubCost {
+    /// Machine instructions executed by the function, which vendor
+    /// datasheets of C libraries may not give
+    #[serde(default)]
+    pub instructions: usize,
+    /// Cycles taken by the function
+    pub cycles: u64,
+}
+
+/// The DMA hardware of a chip, whose counts bound the loops of its DMA
+/// interrupt handlers over channels and descriptors.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DmaLimits {
+    /// DMA channels (or streams) the handlers iterate over
+    pub channels: usize,
+    /// Descriptors (e.g. of a linked list) processed per channel, if any
+    pub descriptors: Option<usize>,
+}
+
+/// How failures of functions (errors, or exceeding `--budget`) are handled,
+/// by regexes on their demangled names.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FailureConfig {
+    /// Functions whose failure stops the run, even without `--fail-fast`
+    #[serde(default)]
+    pub abort: Vec<String>,
+    /// Functions whose failure neither stops the run nor fails it
+    #[serde(default)]
+    pub tolerate: Vec<String>,
+}
+
+/// A command receiving each function's result as JSON, and the outcomes of
+/// all of them at the end of the run.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SinkConfig {
+    /// Shell command, run with `sh -c` (`cmd /C` on Windows)
+    pub command: String,
+}
+
+/// Values an integer argument may take: either one of `values`, or between
+/// `min` and `max` (inclusive, unsigned), but not both.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ArgumentConstraint {
+    /// Position of the argument in the function's LLVM signature, from 0
+    pub arg: usize,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub values: Option<Vec<u64>>,
+    /// Byte offset of the constrained integer in what the argument points
+    /// to, if it is passed inside a struct or enum
+    pub offset: Option<u64>,
+    /// Width in bits of the integer at `offset`, by default 32
+    pub bits: Option<u32>,
+}
+
+/// A task of the system, whose WCET is taken from the results of the
+/// functions it runs. Times are in the same unit as the results.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TaskConfig {
+    pub name: String,
+    /// Regex on demangled names; the task's WCET is the worst of the matching results
+    pub function: String,
+    /// Period, or minimum inter-arrival time
+    pub period: u64,
+    /// Relative deadline, defaults to the period
+    pub deadline: Option<u64>,
+    /// Fixed priority, lower is more urgent. Rate-monotonic if omitted
+    pub priority: Option<u32>,
+}
+
+/// A hardware interrupt, served by a chip-level handler and the client
+/// callbacks it dispatches to.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InterruptSourceConfig {
+    pub name: String,
+    /// Regex on demangled names of the chip-level handlers; the worst applies
+    pub handler: String,
+    /// Regexes on demangled names of the client callbacks; the worst applies
+    #[serde(default)]
+    pub clients: Vec<String>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config {}: {}", path, e))?;
+        toml::from_str(&contents).map_err(|e| format!("failed to parse config {}: {}", path, e))
+    }
+}
+
+arg_enum! {
+    /// Whether machinery that is only used for debugging is analyzed
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ExclusionProfile {
+        Production,
+        Debug,
+    }
+}
+
+/// Demangled names of debugging machinery which production builds compile
+/// out, or never run in deployment: the process console and debug writer.
+const DEBUG_ONLY: &[&str] = &[
+    r"^capsules(_\w+)?::process_console::",
+    r"^capsules(_\w+)?::low_level_debug::",
+    r"^kernel::debug::",
+    r"^kernel::process_printer::",
+];
+
+impl ExclusionProfile {
+    fn patterns(self) -> Vec<String> {
+        match self {
+            ExclusionProfile::Production => DEBUG_ONLY.iter().map(|p| p.to_string()).collect(),
+            ExclusionProfile::Debug => vec![],
+        }
+    }
+}
+
+fn compile_kind(patterns: &[String], kind: &str) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| format!("invalid {} pattern {:?}: {}", kind, p, e)))
+        .collect()
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    compile_kind(patterns, "exclude")
+}
+
+/// Compiled exclusion patterns, from the config file, `--exclude`, and the
+/// exclusion profile.
+pub struct Exclusions {
+    global: Vec<Regex>,
+    by_category: HashMap<String, Vec<Regex>>,
+}
+
+impl Exclusions {
+    pub fn new(
+        config: &ConfigFile,
+        cli_patterns: &[String],
+        profile: ExclusionProfile,
+    ) -> Result<Self, String> {
+        let mut global = compile(&config.exclude)?;
+        global.extend(compile(cli_patterns)?);
+        global.extend(compile(&profile.patterns())?);
+        let by_category = config
+            .categories
+            .iter()
+            .map(|(name, c)| Ok((name.to_lowercase(), compile(&c.exclude)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Exclusions {
+            global,
+            by_category,
+        })
+    }
+
+    /// Whether the function with the given mangled name, selected as part of
+    /// `category`, should be skipped.
+    pub fn is_excluded(&self, name: &str, category: &str) -> bool {
+        let demangled = rustc_demangle::demangle(name).to_string();
+        self.global
+            .iter()
+            .chain(
+                self.by_category
+                    .get(&category.to_lowercase())
+                    .into_iter()
+                    .flatten(),
+            )
+            .any(|re| re.is_match(&demangled))
+    }
+}
+
+/// Compiled failure patterns from the config file.
+pub struct Failures {
+    abort: Vec<Regex>,
+    tolerate: Vec<Regex>,
+}
+
+impl Failures {
+    pub fn new(config: &FailureConfig) -> Result<Self, String> {
+        Ok(Failures {
+            abort: compile_kind(&config.abort, "abort")?,
+            tolerate: compile_kind(&config.tolerate, "tolerate")?,
+        })
+    }
+
+    fn matches(patterns: &[Regex], name: &str) -> bool {
+        let demangled = rustc_demangle::demangle(name).to_string();
+        patterns.iter().any(|re| re.is_match(&demangled))
+    }
+
+    /// Whether a failure of the function with the given mangled name stops
+    /// the run.
+    pub fn aborts(&self, name: &str) -> bool {
+        Failures::matches(&self.abort, name)
+    }
+
+    /// Whether a failure of the function with the given mangled name is
+    /// ignored.
+    pub fn tolerates(&self, name: &str) -> bool {
+        Failures::matches(&self.tolerate, name)
+    }
+}
@@ -0,0 +1,451 @@
+use llvm_ir::{Function, Type};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::allow;
+use crate::config_file::{AllowConfig, ArgumentConfig, ArgumentConstraint, ParametricConfig};
+use crate::host;
+use crate::load_project;
+use crate::syscall_abi;
+
+/// Bitcode module holding the constrained entry points, loaded with the rest
+/// of the project's bitcode.
+const WRAPPER_MODULE: &str = "wcet-rs-constraints";
+
+/// Function the entry points call with arguments outside their constraints,
+/// declared in the entry point module and hooked to abort the path.
+pub const EXCLUDED: &str = "wcet_rs.excluded";
+
+/// Name of the entry point analyzed in place of a function with constrained arguments.
+pub fn wrapper_name(func_name: &str) -> String {
+    format!("wcet_rs.constrained.{}", func_name)
+}
+
+/// Name of the entry point analyzing a function parametric in one argument
+/// at `value` of that argument.
+pub fn parametric_name(func_name: &str, value: u64) -> String {
+    format!("wcet_rs.parametric.{}.{}", value, func_name)
+}
+
+/// Name of the entry point analyzing the syscall dispatch `func_name` at
+/// driver number `driver`.
+pub fn driver_name(func_name: &str, driver: u64) -> String {
+    format!("wcet_rs.driver.{}.{}", driver, func_name)
+}
+
+/// The functions with constrained entry points, by mangled name.
+pub struct EntryPoints {
+    /// Constraints on the arguments of each constrained function
+    pub arguments: HashMap<String, Vec<ArgumentConstraint>>,
+    /// Argument and values of each function analyzed parametrically
+    pub parametric: HashMap<String, ParametricConfig>,
+    /// Argument and driver numbers of each syscall dispatch function
+    pub drivers: HashMap<String, ParametricConfig>,
+    /// The bitcode file holding the entry points, if any were generated
+    pub module: Option<PathBuf>,
+}
+
+/// Whether `func_name` is a generated entry point, whose instructions are
+/// not part of the analyzed function.
+pub fn is_entry_point(func_name: &str) -> bool {
+    func_name.starts_with("wcet_rs.")
+}
+
+/// Whether `path` is a bitcode file of entry points, generated by this or an
+/// earlier run.
+pub fn is_entry_module(path: &Path) -> bool {
+    path.file_stem().and_then(|s| s.to_str()) == Some(WRAPPER_MODULE)
+}
+
+/// A global or type name in LLVM assembly, which may contain any character
+/// but quotes when quoted.
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name)
+}
+
+/// The type in LLVM assembly syntax. Named structs are collected in `named`,
+/// to be declared as opaque types in the wrapper module.
+fn llvm_type(ty: &Type, named: &mut BTreeSet<String>) -> Result<String, String> {
+    Ok(match ty {
+        Type::VoidType => "void".to_owned(),
+        Type::IntegerType { bits } => format!("i{}", bits),
+        Type::PointerType {
+            pointee_type,
+            addr_space,
+        } => match (addr_space, &**pointee_type) {
+            // opaque types can be pointed to, but not passed by value
+            (0, Type::NamedStructType { name }) => {
+                named.insert(name.clone());
+                format!("%{}*", quoted(name))
+            }
+            (0, pointee) => format!("{}*", llvm_type(pointee, named)?),
+            (space, pointee) => {
+                format!("{} addrspace({})*", llvm_type(pointee, named)?, space)
+            }
+        },
+        Type::ArrayType {
+            element_type,
+            num_elements,
+        } => format!("[{} x {}]", num_elements, llvm_type(element_type, named)?),
+        Type::StructType {
+            element_types,
+            is_packed,
+        } => {
+            let elements = element_types
+                .iter()
+                .map(|t| llvm_type(t, named))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ");
+            match is_packed {
+                true => format!("<{{ {} }}>", elements),
+                false => format!("{{ {} }}", elements),
+            }
+        }
+        Type::FuncType {
+            result_type,
+            param_types,
+            is_var_arg,
+        } => {
+            let mut params = param_types
+                .iter()
+                .map(|t| llvm_type(t, named))
+                .collect::<Result<Vec<_>, _>>()?;
+            if *is_var_arg {
+                params.push("...".to_owned());
+            }
+            format!("{} ({})", llvm_type(result_type, named)?, params.join(", "))
+        }
+        other => return Err(format!("unsupported parameter type {:?}", other)),
+    })
+}
+
+/// Instructions loading the `bits`-wide integer at byte `offset` of what
+/// the pointer `param` points to into `%c{index}.field`.
+fn field_load(index: usize, ty: &str, param: &str, offset: u64, bits: u32) -> String {
+    let c = format!("%c{}", index);
+    let mut ir = String::new();
+    writeln!(ir, "  {}.bytes = bitcast {} {} to i8*", c, ty, param).unwrap();
+    writeln!(
+        ir,
+        "  {}.at = getelementptr i8, i8* {}.bytes, i64 {}",
+        c, c, offset
+    )
+    .unwrap();
+    writeln!(ir, "  {}.ptr = bitcast i8* {}.at to i{}*", c, c, bits).unwrap();
+    writeln!(ir, "  {}.field = load i{}, i{}* {}.ptr", c, bits, bits, c).unwrap();
+    ir
+}
+
+/// The condition a constraint places on an integer parameter, as a sequence
+/// of instructions defining `%c{index}`.
+fn constraint_check(
+    index: usize,
+    constraint: &ArgumentConstraint,
+    ty: &str,
+    param: &str,
+) -> Result<String, String> {
+    let c = format!("%c{}", index);
+    let mut ir = String::new();
+    if let Some(values) = &constraint.values {
+        if constraint.min.is_some() || constraint.max.is_some() {
+            return Err(format!(
+                "argument {} is constrained to both values and a range",
+                constraint.arg
+            ));
+        }
+        if values.is_empty() {
+            return Err(format!("no values allowed for argument {}", constraint.arg));
+        }
+        for (i, value) in values.iter().enumerate() {
+            writeln!(ir, "  {}.eq{} = icmp eq {} {}, {}", c, i, ty, param, value).unwrap();
+        }
+        let mut acc = format!("{}.eq0", c);
+        for i in 1..values.len() {
+            writeln!(ir, "  {}.or{} = or i1 {}, {}.eq{}", c, i, acc, c, i).unwrap();
+            acc = format!("{}.or{}", c, i);
+        }
+        writeln!(ir, "  {} = or i1 {}, false", c, acc).unwrap();
+    } else {
+        let min = constraint.min.unwrap_or(0);
+        writeln!(ir, "  {}.min = icmp uge {} {}, {}", c, ty, param, min).unwrap();
+        match constraint.max {
+            Some(max) => {
+                writeln!(ir, "  {}.max = icmp ule {} {}, {}", c, ty, param, max).unwrap();
+                writeln!(ir, "  {} = and i1 {}.min, {}.max", c, c, c).unwrap();
+            }
+            None => writeln!(ir, "  {} = or i1 {}.min, false", c, c).unwrap(),
+        }
+    }
+    Ok(ir)
+}
+
+/// LLVM assembly declaring `function`, to be called from the entry points.
+fn declaration(function: &Function, named: &mut BTreeSet<String>) -> Result<String, String> {
+    let types = function
+        .parameters
+        .iter()
+        .map(|p| llvm_type(&p.ty, named))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!(
+        "declare {} @{}({})\n",
+        llvm_type(&function.return_type, named)?,
+        quoted(&function.name),
+        types.join(", ")
+    ))
+}
+
+/// LLVM assembly of an entry point `name` calling `function` only with
+/// arguments satisfying `constraints`. Other arguments call `EXCLUDED`, whose
+/// hook aborts the path, so that every engine discards them.
+fn wrapper(
+    function: &Function,
+    name: &str,
+    constraints: &[ArgumentConstraint],
+    named: &mut BTreeSet<String>,
+) -> Result<String, String> {
+    let types = function
+        .parameters
+        .iter()
+        .map(|p| llvm_type(&p.ty, named))
+        .collect::<Result<Vec<_>, _>>()?;
+    let params: Vec<String> = (0..types.len()).map(|i| format!("%a{}", i)).collect();
+    let typed: Vec<String> = types
+        .iter()
+        .zip(&params)
+        .map(|(ty, p)| format!("{} {}", ty, p))
+        .collect();
+
+    let mut checks = String::new();
+    let mut conditions = vec![];
+    for (index, constraint) in constraints.iter().enumerate() {
+        let ty = types
+            .get(constraint.arg)
+            .ok_or_else(|| format!("{} has no argument {}", function.name, constraint.arg))?;
+        let param = &params[constraint.arg];
+        let (ty, value) = match (constraint.offset, &*function.parameters[constraint.arg].ty) {
+            (Some(offset), Type::PointerType { .. }) => {
+                let bits = constraint.bits.unwrap_or(32);
+                checks += &field_load(index, ty, param, offset, bits);
+                (format!("i{}", bits), format!("%c{}.field", index))
+            }
+            (None, Type::IntegerType { .. }) => (ty.clone(), param.clone()),
+            (Some(_), _) => {
+                return Err(format!(
+                    "argument {} of {} is not a pointer",
+                    constraint.arg, function.name
+                ))
+            }
+            (None, _) => {
+                return Err(format!(
+                    "argument {} of {} is not an integer",
+                    constraint.arg, function.name
+                ))
+            }
+        };
+        checks += &constraint_check(index, constraint, &ty, &value)?;
+        conditions.push(format!("%c{}", index));
+    }
+    let mut all = conditions
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "true".to_owned());
+    for (i, condition) in conditions.iter().enumerate().skip(1) {
+        writeln!(checks, "  %all{} = and i1 {}, {}", i, all, condition).unwrap();
+        all = format!("%all{}", i);
+    }
+
+    let return_type = llvm_type(&function.return_type, named)?;
+    let mut ir = String::new();
+    writeln!(ir, "define void @{}({}) {{", quoted(name), typed.join(", ")).unwrap();
+    ir += "entry:\n";
+    ir += &checks;
+    writeln!(ir, "  br i1 {}, label %call, label %excluded", all).unwrap();
+    ir += "excluded:\n";
+    writeln!(ir, "  call void @{}()", quoted(EXCLUDED)).unwrap();
+    ir += "  unreachable\ncall:\n";
+    writeln!(
+        ir,
+        "  call {} @{}({})",
+        return_type,
+        quoted(&function.name),
+        typed.join(", ")
+    )
+    .unwrap();
+    ir += "  ret void\n}\n";
+    Ok(ir)
+}
+
+/// Entry points analyzing `function` at each of the values of `parameter`,
+/// named with `name`, keeping its other `constraints`.
+fn value_entry_points(
+    function: &Function,
+    parameter: &ParametricConfig,
+    constraints: Option<&Vec<ArgumentConstraint>>,
+    name: fn(&str, u64) -> String,
+    named: &mut BTreeSet<String>,
+) -> Result<String, String> {
+    let mut constraints: Vec<ArgumentConstraint> = constraints
+        .map(|c| {
+            c.iter()
+                .filter(|c| c.arg != parameter.arg || c.offset != parameter.offset)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut definitions = String::new();
+    for &value in &parameter.values {
+        constraints.push(ArgumentConstraint {
+            arg: parameter.arg,
+            min: None,
+            max: None,
+            values: Some(vec![value]),
+            offset: parameter.offset,
+            bits: parameter.bits,
+        });
+        definitions += &wrapper(function, &name(&function.name, value), &constraints, named)?;
+        constraints.pop();
+    }
+    Ok(definitions)
+}
+
+/// Generate constrained entry points for the functions in `bitcode` matching
+/// the configured argument constraints, and one for each value of the
+/// functions analyzed parametrically, and assemble them into a bitcode file in
+/// `bc_dir`, to be loaded with the rest of the project. With `allow`, allow
+/// handlers not matching any configured pattern are also constrained to the
+/// buffer lengths and analyzed at them. With `dispatch`, the syscall dispatch
+/// functions it matches are analyzed at each of its driver numbers.
+pub fn generate(
+    bc_dir: &str,
+    bitcode: &[PathBuf],
+    arguments: &[ArgumentConfig],
+    parametric: &[ParametricConfig],
+    allow: Option<&AllowConfig>,
+    dispatch: Option<&ParametricConfig>,
+) -> Result<EntryPoints, String> {
+    let bc_path = format!("{}/{}.bc", bc_dir, WRAPPER_MODULE);
+    // Entry points of a previous run would be loaded with the project
+    let _ = std::fs::remove_file(&bc_path);
+    let mut entry_points = EntryPoints {
+        arguments: HashMap::new(),
+        parametric: HashMap::new(),
+        drivers: HashMap::new(),
+        module: None,
+    };
+    if arguments.is_empty() && parametric.is_empty() && allow.is_none() && dispatch.is_none() {
+        return Ok(entry_points);
+    }
+
+    let project = load_project(bitcode)?;
+    let (allow_arguments, allow_parametric) = match allow {
+        Some(allow) => allow::entry_configs(&project, allow, syscall_abi::detect(&project))?,
+        None => (vec![], vec![]),
+    };
+    let matching = |pattern: &str| -> Result<Vec<&Function>, String> {
+        let re = Regex::new(pattern).map_err(|e| format!("invalid function pattern: {}", e))?;
+        Ok(project
+            .all_functions()
+            .filter(|(f, _m)| {
+                !f.basic_blocks.is_empty()
+                    && re.is_match(&rustc_demangle::demangle(&f.name).to_string())
+            })
+            .map(|(f, _m)| f)
+            .collect())
+    };
+    let mut named = BTreeSet::new();
+    let mut declarations = BTreeMap::new();
+    let mut definitions = String::new();
+    // the first matching pattern applies
+    for argument in arguments.iter().chain(&allow_arguments) {
+        for f in matching(&argument.function)? {
+            if entry_points.arguments.contains_key(&f.name) {
+                continue;
+            }
+            declarations.insert(f.name.clone(), declaration(f, &mut named)?);
+            definitions += &wrapper(f, &wrapper_name(&f.name), &argument.constraints, &mut named)?;
+            entry_points
+                .arguments
+                .insert(f.name.clone(), argument.constraints.clone());
+        }
+    }
+    for parameter in parametric.iter().chain(&allow_parametric) {
+        for f in matching(&parameter.function)? {
+            if entry_points.parametric.contains_key(&f.name) {
+                continue;
+            }
+            declarations.insert(f.name.clone(), declaration(f, &mut named)?);
+            // the other arguments remain constrained
+            definitions += &value_entry_points(
+                f,
+                parameter,
+                entry_points.arguments.get(&f.name),
+                parametric_name,
+                &mut named,
+            )?;
+            entry_points
+                .parametric
+                .insert(f.name.clone(), parameter.clone());
+        }
+    }
+    if let Some(dispatch) = dispatch {
+        for f in matching(&dispatch.function)? {
+            declarations.insert(f.name.clone(), declaration(f, &mut named)?);
+            definitions += &value_entry_points(
+                f,
+                dispatch,
+                entry_points.arguments.get(&f.name),
+                driver_name,
+                &mut named,
+            )?;
+            entry_points
+                .drivers
+                .insert(f.name.clone(), dispatch.clone());
+        }
+    }
+
+    // pointer sizes, and so the offsets of the entry points, are the board's
+    let (_f, board) = project
+        .all_functions()
+        .next()
+        .ok_or("no bitcode module to take the target from")?;
+    let mut module = format!("; ModuleID = '{}'\n", WRAPPER_MODULE);
+    writeln!(
+        module,
+        "target datalayout = \"{}\"",
+        board.data_layout.layout_str
+    )
+    .unwrap();
+    if let Some(triple) = &board.target_triple {
+        writeln!(module, "target triple = \"{}\"", triple).unwrap();
+    }
+    for name in &named {
+        writeln!(module, "%{} = type opaque", quoted(name)).unwrap();
+    }
+    writeln!(module, "declare void @{}()", quoted(EXCLUDED)).unwrap();
+    for declaration in declarations.values() {
+        module += declaration;
+    }
+    module += &definitions;
+    let ll_path = format!("{}/{}.ll", bc_dir, WRAPPER_MODULE);
+    std::fs::write(&ll_path, module).map_err(|e| format!("failed to write {}: {}", ll_path, e))?;
+    let status = Command::new(host::llvm_tool("llvm-as")?)
+        .arg(&ll_path)
+        .arg("-o")
+        .arg(&bc_path)
+        .status()
+        .map_err(|e| format!("failed to run llvm-as: {}", e))?;
+    if !status.success() {
+        return Err(format!("failed to assemble {}", ll_path));
+    }
+    entry_points.module = Some(PathBuf::from(bc_path));
+    println!(
+        "Constrained the arguments of {} functions, {} analyzed parametrically, {} at each driver number",
+        entry_points.arguments.len(),
+        entry_points.parametric.len(),
+        entry_points.drivers.len()
+    );
+    Ok(entry_points)
+}
@@ -0,0 +1,190 @@
+//! Fixtures checking the machine instruction counting against canned
+//! disassembly, without building a board. Each directory under
+//! `tests/fixtures/instruction_counter` holds a `disassembly.s` (as llc or
+//! the build emits it) and any number of cases, `*.toml` files giving a path
+//! through it, an optional cost model and the expected counts:
+//!
+//! ```toml
+//! machine = 6
+//! cycles = 12
+//!
+//! [cost_model]
+//! branch_taken = 3
+//!
+//! [[path]]
+//! func = "_ZN4test3foo17h0123456789abcdefE"
+//! bb = "%start"
+//! ir_len = 4
+//! ```
+//!
+//! The blocks of the path are listed in execution order, with their IR
+//! names; `ir_len` only matters for blocks estimated from the IR.
+
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::instruction_counter::{
+    count_path_blocks, CostModel, Disassem, DisassemblyIndex, PathBlock,
+};
+
+/// A block of a fixture's path.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FixtureBlock {
+    func: String,
+    bb: String,
+    #[serde(default)]
+    ir_len: usize,
+}
+
+/// A case of a fixture, as written in its file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    path: Vec<FixtureBlock>,
+    #[serde(default)]
+    cost_model: CostModel,
+    machine: usize,
+    cycles: u64,
+    #[serde(default)]
+    estimated: usize,
+    #[serde(default)]
+    inline_asm: usize,
+}
+
+/// A path through canned disassembly and the counts expected for it.
+pub struct Fixture {
+    /// The case's file, for reporting
+    pub name: String,
+    pub disassembly: Disassem,
+    pub path: Vec<PathBlock>,
+    pub cost_model: CostModel,
+    pub machine: usize,
+    pub cycles: u64,
+    pub estimated: usize,
+    pub inline_asm: usize,
+}
+
+/// The directory holding the fixtures of the corpus.
+pub fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/instruction_counter")
+}
+
+/// Load every case of the fixture directory `dir`.
+pub fn load(dir: &Path) -> Result<Vec<Fixture>, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))
+    };
+    let disassembly: Disassem = read(&dir.join("disassembly.s"))?
+        .lines()
+        .map(|line| line.to_owned())
+        .collect();
+    let mut cases: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("failed to read {:?}: {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension() == Some(OsStr::new("toml")))
+        .collect();
+    cases.sort();
+    let mut fixtures = vec![];
+    for path in cases {
+        let case: Case = toml::from_str(&read(&path)?)
+            .map_err(|e| format!("failed to parse {:?}: {}", path, e))?;
+        fixtures.push(Fixture {
+            name: path.display().to_string(),
+            disassembly: disassembly.clone(),
+            path: case
+                .path
+                .into_iter()
+                .map(|block| PathBlock {
+                    module: "fixture".to_owned(),
+                    func: block.func,
+                    bb: block.bb,
+                    ir_len: block.ir_len,
+                })
+                .collect(),
+            cost_model: case.cost_model,
+            machine: case.machine,
+            cycles: case.cycles,
+            estimated: case.estimated,
+            inline_asm: case.inline_asm,
+        });
+    }
+    Ok(fixtures)
+}
+
+/// Count the fixture's path, describing any count that differs from the
+/// expected one along with the listing of the path.
+pub fn check(fixture: &Fixture) -> Result<(), String> {
+    let index = DisassemblyIndex::new(&fixture.disassembly);
+    let mut listing = vec![];
+    let count = count_path_blocks(
+        &fixture.disassembly,
+        &index,
+        &fixture.path,
+        &fixture.cost_model,
+        Some(&mut listing),
+    )?;
+    let mut mismatches = vec![];
+    for (what, expected, counted) in [
+        (
+            "machine instructions",
+            fixture.machine as u64,
+            count.machine as u64,
+        ),
+        ("cycles", fixture.cycles, count.cycles),
+        (
+            "estimated instructions",
+            fixture.estimated as u64,
+            count.estimated as u64,
+        ),
+        (
+            "inline asm instructions",
+            fixture.inline_asm as u64,
+            count.inline_asm as u64,
+        ),
+    ] {
+        if expected != counted {
+            mismatches.push(format!(
+                "{}: expected {}, counted {}",
+                what, expected, counted
+            ));
+        }
+    }
+    match mismatches.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "{}\n  {}\n{}",
+            fixture.name,
+            mismatches.join("\n  "),
+            String::from_utf8_lossy(&listing)
+        )),
+    }
+}
+
+#[test]
+fn corpus() {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(corpus_dir())
+        .expect("failed to read the fixture corpus")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    let mut failures = vec![];
+    let mut cases = 0;
+    for dir in dirs {
+        match load(&dir) {
+            Ok(fixtures) => {
+                for fixture in fixtures {
+                    cases += 1;
+                    if let Err(e) = check(&fixture) {
+                        failures.push(e);
+                    }
+                }
+            }
+            Err(e) => failures.push(e),
+        }
+    }
+    assert!(cases > 0, "no fixtures found in {:?}", corpus_dir());
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,157 @@
+use haybale::Project;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{resolve_function, AnalysisSettings, PathLength};
+
+/// Per-request overrides of the settings the daemon was started with.
+#[derive(Deserialize, Default, Debug)]
+pub struct SettingsOverrides {
+    timeout: Option<u64>,
+    loop_bound: Option<usize>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut AnalysisSettings) {
+        if let Some(timeout) = self.timeout {
+            settings.timeout_s = timeout;
+        }
+        if let Some(loop_bound) = self.loop_bound {
+            settings.loop_bound = loop_bound;
+        }
+    }
+}
+
+/// Body of a `POST /analyze` request.
+#[derive(Deserialize)]
+struct AnalysisRequest {
+    /// Mangled or demangled name of the function to analyze
+    function: String,
+    #[serde(flatten)]
+    overrides: SettingsOverrides,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Clone)]
+struct Job {
+    id: usize,
+    function: String,
+    status: JobStatus,
+    result: Option<usize>,
+    error: Option<String>,
+}
+
+type Jobs = Arc<Mutex<Vec<Job>>>;
+
+fn json_response<T: Serialize>(value: &T, status: u16) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    Response::from_string(serde_json::to_string(value).unwrap())
+        .with_status_code(status)
+        .with_header(header)
+}
+
+fn error_response(message: &str, status: u16) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(&serde_json::json!({ "error": message }), status)
+}
+
+fn handle_request(mut request: Request, jobs: &Jobs, queue: &Sender<(usize, SettingsOverrides)>) {
+    let url = request.url().to_owned();
+    let response = match (request.method(), url.as_str()) {
+        (Method::Post, "/analyze") => {
+            let mut body = String::new();
+            let parsed = request
+                .as_reader()
+                .read_to_string(&mut body)
+                .map_err(|e| e.to_string())
+                .and_then(|_| {
+                    serde_json::from_str::<AnalysisRequest>(&body).map_err(|e| e.to_string())
+                });
+            match parsed {
+                Ok(req) => {
+                    let mut jobs = jobs.lock().unwrap();
+                    let id = jobs.len();
+                    jobs.push(Job {
+                        id,
+                        function: req.function,
+                        status: JobStatus::Queued,
+                        result: None,
+                        error: None,
+                    });
+                    queue.send((id, req.overrides)).unwrap();
+                    json_response(&serde_json::json!({ "id": id }), 202)
+                }
+                Err(e) => error_response(&e, 400),
+            }
+        }
+        (Method::Get, "/jobs") => json_response(&*jobs.lock().unwrap(), 200),
+        (Method::Get, path) if path.starts_with("/jobs/") => {
+            let job = path["/jobs/".len()..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|id| jobs.lock().unwrap().get(id).cloned());
+            match job {
+                Some(job) => json_response(&job, 200),
+                None => error_response("no such job", 404),
+            }
+        }
+        _ => error_response("not found", 404),
+    };
+    let _ = request.respond(response);
+}
+
+/// Serve analysis requests over a local HTTP/JSON API until the process is killed.
+/// The project stays loaded between requests, and jobs are analyzed one at a
+/// time by `analyze`, on the calling thread.
+///
+/// - `POST /analyze` with `{"function": ..., "timeout": ..., "loop_bound": ...}`
+///   queues a job and returns its id
+/// - `GET /jobs` lists all jobs, `GET /jobs/<id>` returns a single job,
+///   including its result once it completes
+pub fn serve<F>(addr: &str, project: &Project, mut analyze: F) -> Result<(), String>
+where
+    F: FnMut(&str, &SettingsOverrides) -> Result<PathLength, String>,
+{
+    let server = Server::http(addr).map_err(|e| format!("failed to bind {}: {}", addr, e))?;
+    let jobs: Jobs = Arc::new(Mutex::new(vec![]));
+    let (queue, pending) = channel();
+
+    let listener_jobs = jobs.clone();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle_request(request, &listener_jobs, &queue);
+        }
+    });
+    println!("Serving analysis requests on {}", addr);
+
+    for (id, overrides) in pending {
+        let function = {
+            let mut jobs = jobs.lock().unwrap();
+            jobs[id].status = JobStatus::Running;
+            jobs[id].function.clone()
+        };
+        let result = resolve_function(project, &function).and_then(|f| analyze(f, &overrides));
+        let mut jobs = jobs.lock().unwrap();
+        match result {
+            Ok(length) => {
+                jobs[id].status = JobStatus::Done;
+                jobs[id].result = Some(length.ir);
+            }
+            Err(e) => {
+                jobs[id].status = JobStatus::Failed;
+                jobs[id].error = Some(e);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::results::FunctionResult;
+
+/// `{:#}` demangled name of a function without the type arguments of its
+/// generic types and functions, e.g. `<capsules::Foo<> as kernel::Bar<>>::baz`,
+/// so that instantiations of the same generic code share a name.
+pub fn without_type_arguments(name: &str) -> String {
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    let mut out = String::with_capacity(demangled.len());
+    // for each open `<`, whether its contents are type arguments, which are
+    // dropped; a qualified path's `<Type as Trait>` is kept
+    let mut open: Vec<bool> = vec![];
+    let mut previous = ' ';
+    for c in demangled.chars() {
+        let dropping = open.last() == Some(&true);
+        match c {
+            '<' => {
+                let arguments = dropping || previous.is_alphanumeric() || previous == '_';
+                if !dropping {
+                    out.push(c);
+                }
+                open.push(arguments);
+            }
+            // not the arrow of a function pointer type
+            '>' if previous != '-' => {
+                open.pop();
+                if !open.contains(&true) {
+                    out.push(c);
+                }
+            }
+            _ if !dropping => out.push(c),
+            _ => {}
+        }
+        previous = c;
+    }
+    out
+}
+
+/// Hash of a result's longest path, identifying structurally identical paths:
+/// the same blocks, by function without type arguments, with the same counts.
+fn signature(result: &FunctionResult) -> Option<u64> {
+    let (length, path) = match (&result.length, &result.path, &result.error) {
+        (Some(length), Some(path), None) if !path.blocks.is_empty() => (length, path),
+        _ => return None,
+    };
+    let mut hasher = DefaultHasher::new();
+    (length.ir, length.machine, length.cycles).hash(&mut hasher);
+    for block in &path.blocks {
+        without_type_arguments(&block.func).hash(&mut hasher);
+        (&block.bb, block.ir, block.machine).hash(&mut hasher);
+    }
+    Some(hasher.finish())
+}
+
+/// The longest paths of a run's results so far, to list each distinct path
+/// once: instantiations of a generic function often have near-identical
+/// worst paths.
+#[derive(Default)]
+pub struct PathIndex {
+    /// Demangled name of the first function found with each path
+    canonical: HashMap<u64, String>,
+    /// Number of results referring to another's path
+    pub duplicates: usize,
+}
+
+impl PathIndex {
+    /// The demangled name of the function whose result already lists the
+    /// same longest path as `result`, if any, or record `result`'s as listed.
+    pub fn same_path_as(&mut self, result: &FunctionResult) -> Option<String> {
+        let signature = signature(result)?;
+        match self.canonical.get(&signature) {
+            Some(canonical) if *canonical != result.demangled => {
+                self.duplicates += 1;
+                Some(canonical.clone())
+            }
+            Some(_) => None,
+            None => {
+                self.canonical.insert(signature, result.demangled.clone());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use haybale::Project;
+use llvm_ir::{Either, Function, Instruction, Name, Terminator};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::call_graph::{called_function_name, direct_callees, CallGraph};
+
+/// Weight of a call through a function pointer or trait object in the
+/// score: haybale has to solve for its possible targets.
+const DYNAMIC_CALL_WEIGHT: usize = 50;
+/// Weight of each level of calls in the score.
+const CALL_DEPTH_WEIGHT: usize = 10;
+/// Predicted seconds of analysis per point of score. This is a rough guess;
+/// compare `difficulty.tsv` with the analysis times in the results to tune it.
+const SECONDS_PER_SCORE: f64 = 0.01;
+
+/// Cheap static metrics of a function and everything it calls, estimating
+/// how hard it is to analyze before running symbolic execution.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Difficulty {
+    /// Basic blocks of the function and the functions it reaches
+    pub blocks: usize,
+    /// Loops (back edges) in them
+    pub loops: usize,
+    /// Longest chain of direct calls from the function, not counting recursion
+    pub call_depth: usize,
+    /// Calls through function pointers or trait objects in them
+    pub dynamic_calls: usize,
+}
+
+impl Difficulty {
+    /// Every block is explored about once per loop around it, and each
+    /// dynamic call and level of calls adds to the paths to consider.
+    pub fn score(&self) -> usize {
+        self.blocks * (1 + self.loops)
+            + DYNAMIC_CALL_WEIGHT * self.dynamic_calls
+            + CALL_DEPTH_WEIGHT * self.call_depth
+    }
+
+    /// Predicted time budget for the analysis, in seconds.
+    pub fn predicted_s(&self) -> u64 {
+        (self.score() as f64 * SECONDS_PER_SCORE).ceil() as u64
+    }
+
+    /// Whether the analysis is likely to time out: predicted to exceed the
+    /// time budget, or without one, ten solver query timeouts.
+    pub fn likely_timeout(&self, timeout_s: u64, time_budget_s: Option<u64>) -> bool {
+        self.predicted_s() > time_budget_s.unwrap_or(10 * timeout_s)
+    }
+}
+
+/// Static metrics of a function on its own.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FunctionMetrics {
+    pub blocks: usize,
+    /// LLVM IR instructions, terminators included
+    pub instructions: usize,
+    /// Loops (back edges)
+    pub loops: usize,
+    /// Functions it calls directly
+    pub callees: usize,
+    /// Calls through function pointers or trait objects
+    pub dynamic_calls: usize,
+}
+
+impl FunctionMetrics {
+    pub fn of(f: &Function) -> Self {
+        FunctionMetrics {
+            blocks: f.basic_blocks.len(),
+            instructions: f.basic_blocks.iter().map(|bb| bb.instrs.len() + 1).sum(),
+            loops: back_edges(f),
+            callees: direct_callees(f).len(),
+            dynamic_calls: dynamic_calls(f),
+        }
+    }
+}
+
+fn successors(term: &Terminator) -> Vec<&Name> {
+    match term {
+        Terminator::Br(br) => vec![&br.dest],
+        Terminator::CondBr(br) => vec![&br.true_dest, &br.false_dest],
+        Terminator::Switch(switch) => switch
+            .dests
+            .iter()
+            .map(|(_, dest)| dest)
+            .chain(Some(&switch.default_dest))
+            .collect(),
+        Terminator::Invoke(invoke) => vec![&invoke.return_label, &invoke.exception_label],
+        _ => vec![],
+    }
+}
+
+/// Number of back edges in the control flow graph of `f`, found by a depth
+/// first search from its entry block.
+fn back_edges(f: &Function) -> usize {
+    let mut count = 0;
+    let mut on_stack: HashSet<&Name> = HashSet::new();
+    let mut visited: HashSet<&Name> = HashSet::new();
+    let entry = match f.basic_blocks.first() {
+        Some(bb) => &bb.name,
+        None => return 0,
+    };
+    // each frame is a block and the successors still to visit
+    let mut stack = vec![(entry, 0)];
+    visited.insert(entry);
+    on_stack.insert(entry);
+    while let Some(&(name, next)) = stack.last() {
+        let succs = match f.get_bb_by_name(name) {
+            Some(bb) => successors(&bb.term),
+            None => vec![],
+        };
+        match succs.get(next) {
+            Some(&succ) => {
+                stack.last_mut().unwrap().1 += 1;
+                if on_stack.contains(succ) {
+                    count += 1;
+                } else if visited.insert(succ) {
+                    on_stack.insert(succ);
+                    stack.push((succ, 0));
+                }
+            }
+            None => {
+                on_stack.remove(name);
+                stack.pop();
+            }
+        }
+    }
+    count
+}
+
+fn dynamic_calls(f: &Function) -> usize {
+    let calls = f.basic_blocks.iter().flat_map(|bb| {
+        bb.instrs
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Call(call) => Some(&call.function),
+                _ => None,
+            })
+            .chain(match &bb.term {
+                Terminator::Invoke(invoke) => Some(&invoke.function),
+                _ => None,
+            })
+    });
+    calls
+        .filter(|callee| {
+            matches!(callee, Either::Right(_)) && called_function_name(callee).is_none()
+        })
+        .count()
+}
+
+/// Estimates the difficulty of functions of a project, computing the
+/// metrics of each function only once however many functions call it.
+pub struct Estimator<'p> {
+    project: &'p Project,
+    graph: CallGraph<'p>,
+    metrics: HashMap<&'p str, FunctionMetrics>,
+    depths: HashMap<&'p str, usize>,
+}
+
+impl<'p> Estimator<'p> {
+    pub fn new(project: &'p Project) -> Self {
+        Estimator {
+            project,
+            graph: CallGraph::new(project),
+            metrics: HashMap::new(),
+            depths: HashMap::new(),
+        }
+    }
+
+    /// The metrics of function `name` on its own.
+    pub fn metrics(&mut self, name: &'p str) -> FunctionMetrics {
+        let project = self.project;
+        *self
+            .metrics
+            .entry(name)
+            .or_insert_with(|| match project.get_func_by_name(name) {
+                Some((f, _)) => FunctionMetrics::of(f),
+                None => FunctionMetrics::default(),
+            })
+    }
+
+    fn call_depth(&mut self, name: &'p str, in_progress: &mut HashSet<&'p str>) -> usize {
+        if let Some(&depth) = self.depths.get(name) {
+            return depth;
+        }
+        if !in_progress.insert(name) {
+            return 0;
+        }
+        let callees = self.graph.callees(name).to_vec();
+        let depth = callees
+            .into_iter()
+            .map(|callee| 1 + self.call_depth(callee, in_progress))
+            .max()
+            .unwrap_or(0);
+        in_progress.remove(name);
+        self.depths.insert(name, depth);
+        depth
+    }
+
+    pub fn estimate(&mut self, name: &'p str) -> Difficulty {
+        let mut difficulty = Difficulty {
+            call_depth: self.call_depth(name, &mut HashSet::new()),
+            ..Difficulty::default()
+        };
+        for f in self.graph.reachable_from(&[name]) {
+            let metrics = self.metrics(f);
+            difficulty.blocks += metrics.blocks;
+            difficulty.loops += metrics.loops;
+            difficulty.dynamic_calls += metrics.dynamic_calls;
+        }
+        difficulty
+    }
+}
+
+/// Order `functions` from the easiest to the hardest, so that quick results
+/// come first and the likely timeouts last. Ties keep their order.
+pub fn order_hardest_last(functions: &mut [&str], difficulties: &HashMap<&str, Difficulty>) {
+    functions.sort_by_key(|f| difficulties.get(f).map_or(0, Difficulty::score));
+}
+
+/// A table of the difficulty of each function, with its predicted time.
+pub fn render(functions: &[&str], difficulties: &HashMap<&str, Difficulty>) -> String {
+    let mut out =
+        "function\tblocks\tloops\tcall depth\tdynamic calls\tscore\tpredicted s\n".to_owned();
+    for f in functions {
+        if let Some(d) = difficulties.get(f) {
+            out += &format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                rustc_demangle::demangle(f),
+                d.blocks,
+                d.loops,
+                d.call_depth,
+                d.dynamic_calls,
+                d.score(),
+                d.predicted_s()
+            );
+        }
+    }
+    out
+}
+
+/// A table of the metrics of each function on its own, largest first, to
+/// spot the giant functions that may need configuration before analyzing.
+pub fn render_metrics(metrics: &mut [(&str, FunctionMetrics)]) -> String {
+    metrics.sort_by(|(a, ma), (b, mb)| {
+        mb.instructions
+            .cmp(&ma.instructions)
+            .then(mb.blocks.cmp(&ma.blocks))
+            .then(a.cmp(b))
+    });
+    let mut out = "blocks\tinstructions\tloops\tcallees\tdynamic calls\tfunction\n".to_owned();
+    for (name, m) in metrics.iter() {
+        out += &format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\n",
+            m.blocks,
+            m.instructions,
+            m.loops,
+            m.callees,
+            m.dynamic_calls,
+            rustc_demangle::demangle(name)
+        );
+    }
+    out
+}
@@ -0,0 +1,126 @@
+use haybale::Project;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::instruction_counter::{bb_pattern, is_function_start, Disassem, DisassemblyIndex};
+
+// matches a label at the start of a line, capturing its name
+static LABEL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([^\s@#][^\s]*):").unwrap());
+// matches a call to a function created by the machine outliner, capturing its name
+static OUTLINED_CALL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*bl\s+(OUTLINED_FUNCTION_\w+)").unwrap());
+
+/// How much of a module's code was found in the disassembly, which decides
+/// how reliable the machine instruction counts of paths through it are.
+#[derive(Debug, Default)]
+pub struct ModuleStats {
+    /// Functions with a body in the module
+    pub functions: usize,
+    /// Functions whose label (or that of a symbol they alias) was found
+    pub functions_found: usize,
+    /// Basic blocks of the functions found
+    pub blocks: usize,
+    /// Basic blocks whose label or comment was found in their function
+    pub blocks_found: usize,
+    /// Calls to outlined functions from the functions found
+    pub outlined_calls: usize,
+    /// Calls to outlined functions whose body was found
+    pub outlined_resolved: usize,
+}
+
+/// Index the disassembly like the instruction counting does, per module of
+/// `project`.
+pub fn collect(
+    project: &Project,
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+) -> BTreeMap<String, ModuleStats> {
+    let labels: HashMap<&str, usize> = disassembly
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| Some((LABEL.captures(line)?.get(1)?.as_str(), i)))
+        .collect();
+    let mut stats: BTreeMap<String, ModuleStats> = BTreeMap::new();
+    for (f, m) in project.all_functions() {
+        let module = stats.entry(m.name.clone()).or_default();
+        module.functions += 1;
+        let start = labels.get(f.name.as_str()).or_else(|| {
+            index
+                .resolve_alias(&f.name)
+                .and_then(|target| labels.get(target))
+        });
+        let start = match start {
+            Some(&start) => start,
+            None => continue,
+        };
+        module.functions_found += 1;
+        // the function's lines, up to its end marker or the next function
+        let body: Vec<&str> = disassembly[start + 1..]
+            .iter()
+            .take_while(|line| !line.contains(".Lfunc_end") && !is_function_start(line))
+            .map(String::as_str)
+            .collect();
+        for bb in &f.basic_blocks {
+            module.blocks += 1;
+            if let Some(bb_re) = bb_pattern(&bb.name.to_string()) {
+                if body.iter().any(|line| bb_re.is_match(line)) {
+                    module.blocks_found += 1;
+                }
+            }
+        }
+        for call in body.iter().filter_map(|line| OUTLINED_CALL.captures(line)) {
+            module.outlined_calls += 1;
+            if labels.contains_key(&call[1]) {
+                module.outlined_resolved += 1;
+            }
+        }
+    }
+    stats
+}
+
+/// The statistics of all modules together.
+pub fn total(stats: &BTreeMap<String, ModuleStats>) -> ModuleStats {
+    let mut total = ModuleStats::default();
+    for s in stats.values() {
+        total.functions += s.functions;
+        total.functions_found += s.functions_found;
+        total.blocks += s.blocks;
+        total.blocks_found += s.blocks_found;
+        total.outlined_calls += s.outlined_calls;
+        total.outlined_resolved += s.outlined_resolved;
+    }
+    total
+}
+
+pub fn percent(part: usize, whole: usize) -> String {
+    match whole {
+        0 => "-".to_owned(),
+        _ => format!("{:.1}%", part as f64 / whole as f64 * 100.0),
+    }
+}
+
+fn row(name: &str, s: &ModuleStats) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        name,
+        s.functions,
+        percent(s.functions_found, s.functions),
+        s.blocks,
+        percent(s.blocks_found, s.blocks),
+        s.outlined_calls,
+        percent(s.outlined_resolved, s.outlined_calls)
+    )
+}
+
+/// A tab-separated table of the statistics of each module, followed by their
+/// total.
+pub fn render(stats: &BTreeMap<String, ModuleStats>) -> String {
+    let mut out =
+        "module\tfunctions\tfound\tblocks\tlabeled\toutlined calls\tresolved\n".to_owned();
+    for (name, s) in stats {
+        out += &row(name, s);
+    }
+    out += &row("total", &total(stats));
+    out
+}
@@ -0,0 +1,57 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::config_file::DmaLimits;
+
+/// DMA channels of the chips in the Tock tree, from their reference manuals.
+/// None of their handlers walk descriptor lists.
+const BUILTIN: &[(&str, usize)] = &[
+    // PDCA
+    ("sam4l", 16),
+    // DMA1 and DMA2, 8 streams each, with a handler per controller
+    ("stm32f4xx", 8),
+    // DMA1
+    ("stm32f303xc", 7),
+    // eDMA
+    ("imxrt10xx", 32),
+    ("rp2040", 12),
+];
+
+// matches the DMA interrupt handlers of a chip on `{:#}` demangled names,
+// capturing the chip crate, e.g. `<sam4l::dma::DMAChannel as ...>::handle_interrupt`
+static DMA_HANDLER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^<?(\w+)::(\w+::)*\w*dma\w*::.*handle_interrupt$").unwrap());
+
+/// The DMA limits of every chip: the built-in ones, overridden by those of
+/// the config file.
+pub fn limits(configured: &HashMap<String, DmaLimits>) -> HashMap<String, DmaLimits> {
+    let mut limits: HashMap<String, DmaLimits> = BUILTIN
+        .iter()
+        .map(|&(chip, channels)| {
+            (
+                chip.to_owned(),
+                DmaLimits {
+                    channels,
+                    descriptors: None,
+                },
+            )
+        })
+        .collect();
+    limits.extend(configured.iter().map(|(chip, l)| (chip.clone(), *l)));
+    limits
+}
+
+/// The chip crate of the DMA interrupt handler `name` (mangled), if it is one.
+pub fn handler_chip(name: &str) -> Option<String> {
+    DMA_HANDLER
+        .captures(&format!("{:#}", rustc_demangle::demangle(name)))
+        .map(|captures| captures[1].to_owned())
+}
+
+/// Loop bound of a DMA handler: each channel once, and each of its
+/// descriptors once per channel. haybale bounds the iterations of an inner
+/// loop across all runs of the outer one, so the counts multiply.
+pub fn loop_bound(limits: &DmaLimits) -> usize {
+    (limits.channels * limits.descriptors.unwrap_or(1)).max(1)
+}
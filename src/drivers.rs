@@ -0,0 +1,405 @@
+use haybale::Project;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::config_file::{ParametricConfig, SyscallDispatchConfig};
+use crate::parametric::ParametricPoint;
+use crate::PathLength;
+
+/// Longest path of the syscall dispatch to each driver, by driver number.
+pub type DispatchByDriver = HashMap<usize, Result<PathLength, String>>;
+
+// matches a `with_driver` match arm dispatching on a capsule's driver number
+static DRIVER_ARM: Lazy<Regex> = Lazy::new(|| Regex::new(r"([\w:]+)::DRIVER_NUM\s*=>").unwrap());
+// matches the definition of a capsule's driver number
+static DRIVER_NUM_DEF: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"pub const DRIVER_NUM: usize\s*=\s*([^;]+);").unwrap());
+// matches a variant of the `driver::NUM` enum
+static NUM_VARIANT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*(\w+)\s*=\s*(0x[0-9a-fA-F_]+|[0-9_]+)\s*,").unwrap());
+static NUM_REF: Lazy<Regex> = Lazy::new(|| Regex::new(r"NUM::(\w+)").unwrap());
+static PACKAGE_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?m)^name\s*=\s*"([^"]+)""#).unwrap());
+
+fn parse_int(s: &str) -> Option<usize> {
+    let s = s.trim().replace('_', "");
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// All Rust source files under `dir`, skipping build output.
+pub fn rust_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            if !matches!(
+                path.file_name().and_then(|n| n.to_str()),
+                Some("target" | ".git")
+            ) {
+                rust_sources(&path, files);
+            }
+        } else if path.extension().and_then(|e| e.to_str()) == Some("rs") {
+            files.push(path);
+        }
+    }
+}
+
+/// The module path (e.g. `capsules_core::console`) of a source file, derived
+/// from the crate it belongs to and its location under that crate's `src`.
+fn module_path(file: &Path) -> Option<String> {
+    let mut crate_dir = file.parent()?;
+    while !crate_dir.join("Cargo.toml").exists() {
+        crate_dir = crate_dir.parent()?;
+    }
+    let manifest = std::fs::read_to_string(crate_dir.join("Cargo.toml")).ok()?;
+    let crate_name = PACKAGE_NAME.captures(&manifest)?[1].replace('-', "_");
+
+    let relative = file
+        .strip_prefix(crate_dir.join("src"))
+        .ok()?
+        .with_extension("");
+    let mut segments = vec![crate_name];
+    segments.extend(
+        relative
+            .iter()
+            .map(|s| s.to_string_lossy().into_owned())
+            .filter(|s| s != "lib" && s != "mod" && s != "main"),
+    );
+    Some(segments.join("::"))
+}
+
+/// Map each capsule module dispatched to by the board's `with_driver`
+/// implementation to its driver number.
+pub fn driver_numbers(tockpath: &Path, board_path: &Path) -> HashMap<String, usize> {
+    let board_main = std::fs::read_to_string(board_path.join("src/main.rs")).unwrap_or_default();
+    let dispatched: Vec<&str> = DRIVER_ARM
+        .captures_iter(&board_main)
+        .map(|c| c.get(1).unwrap().as_str())
+        .collect();
+
+    let mut files = vec![];
+    rust_sources(tockpath, &mut files);
+    let sources: Vec<(PathBuf, String)> = files
+        .into_iter()
+        .filter_map(|f| Some((f.clone(), std::fs::read_to_string(f).ok()?)))
+        .collect();
+
+    // Driver numbers are usually assigned through the `driver::NUM` enum
+    let mut enum_values = HashMap::new();
+    for (_, contents) in sources.iter() {
+        if let Some(start) = contents.find("pub enum NUM") {
+            let body = &contents[start..];
+            let body = &body[..body.find('}').unwrap_or(body.len())];
+            for c in NUM_VARIANT.captures_iter(body) {
+                if let Some(num) = parse_int(&c[2]) {
+                    enum_values.insert(c[1].to_owned(), num);
+                }
+            }
+        }
+    }
+
+    let mut defined = HashMap::new();
+    for (file, contents) in sources.iter() {
+        if let Some(c) = DRIVER_NUM_DEF.captures(contents) {
+            let num = parse_int(&c[1]).or_else(|| {
+                let variant = NUM_REF.captures(&c[1])?;
+                enum_values.get(&variant[1]).copied()
+            });
+            if let (Some(module), Some(num)) = (module_path(file), num) {
+                defined.insert(module, num);
+            }
+        }
+    }
+
+    dispatched
+        .into_iter()
+        .filter_map(|module| {
+            // The board may refer to the module through a shorter path
+            // (e.g. via a `use`), so match on the path suffix
+            defined
+                .iter()
+                .find(|(path, _)| *path == module || path.ends_with(&format!("::{}", module)))
+                .map(|(_, &num)| (module.to_owned(), num))
+        })
+        .collect()
+}
+
+/// The kernel's syscall dispatch, entered at each of the board's driver
+/// numbers where `config` says `handle_syscall` finds it.
+pub fn dispatch_entry(
+    config: &SyscallDispatchConfig,
+    drivers: &HashMap<String, usize>,
+) -> ParametricConfig {
+    let mut values: Vec<u64> = drivers.values().map(|&num| num as u64).collect();
+    values.sort_unstable();
+    values.dedup();
+    ParametricConfig {
+        function: "::handle_syscall".to_owned(),
+        arg: config.arg,
+        values,
+        offset: config.offset,
+        bits: config.bits,
+    }
+}
+
+/// Record the dispatch to each driver found in the analysis of one syscall
+/// dispatch function. Of several instantiations, the longest path is kept,
+/// and a failure to bound any of them is kept over a bound.
+pub fn record_dispatch(dispatch: &mut DispatchByDriver, points: &[ParametricPoint]) {
+    for point in points {
+        let driver = point.value as usize;
+        let longer = match (dispatch.get(&driver), &point.length) {
+            (None, _) | (Some(Ok(_)), Err(_)) => true,
+            (Some(Ok(known)), Ok(length)) => length.ir > known.ir,
+            (Some(Err(_)), _) => false,
+        };
+        if longer {
+            dispatch.insert(driver, point.length.clone());
+        }
+    }
+}
+
+/// The driver number and capsule module a syscall handler belongs to.
+pub fn driver_for_function<'a>(
+    drivers: &'a HashMap<String, usize>,
+    name: &str,
+) -> Option<(usize, &'a str)> {
+    let demangled = rustc_demangle::demangle(name).to_string();
+    drivers
+        .iter()
+        .filter(|(module, _)| demangled.contains(&format!("{}::", module)))
+        // prefer the most specific module
+        .max_by_key(|(module, _)| module.len())
+        .map(|(module, &num)| (num, module.as_str()))
+}
+
+/// Render syscall handler results grouped by the driver number they are
+/// reached through, with the kernel's dispatch to the driver, if analyzed,
+/// added to each. Functions that could not be attributed to a driver are
+/// listed last.
+pub fn group_by_driver(
+    drivers: &HashMap<String, usize>,
+    results: &HashMap<String, Result<PathLength, String>>,
+    dispatch: &DispatchByDriver,
+    is_syscall: impl Fn(&str) -> bool,
+) -> String {
+    let mut groups: BTreeMap<Option<(usize, &str)>, Vec<_>> = BTreeMap::new();
+    for (name, result) in results.iter().filter(|(name, _)| is_syscall(name)) {
+        groups
+            .entry(driver_for_function(drivers, name))
+            .or_default()
+            .push((name, result));
+    }
+
+    let mut out = String::new();
+    let (unattributed, attributed): (Vec<_>, Vec<_>) =
+        groups.into_iter().partition(|(driver, _)| driver.is_none());
+    for (driver, mut functions) in attributed.into_iter().chain(unattributed) {
+        let dispatched = driver.and_then(|(num, _)| dispatch.get(&num));
+        match driver {
+            Some((num, module)) => out += &format!("driver {:#x} ({}):\n", num, module),
+            None => out += "unknown driver:\n",
+        }
+        match dispatched {
+            Some(Ok(length)) => out += &format!("    dispatch: {}\n", length.ir),
+            Some(Err(e)) => out += &format!("    dispatch: {}\n", e),
+            None => {}
+        }
+        functions.sort_by_key(|(name, _)| *name);
+        for (name, result) in functions {
+            let result = match (result, dispatched) {
+                (Ok(length), Some(Ok(dispatch))) => format!(
+                    "{} + {} dispatch = {}",
+                    length.ir,
+                    dispatch.ir,
+                    length.ir + dispatch.ir
+                ),
+                (Ok(length), _) => length.ir.to_string(),
+                (Err(e), _) => e.clone(),
+            };
+            out += &format!("    {}: {}\n", rustc_demangle::demangle(name), result);
+        }
+    }
+    out
+}
+
+/// The driver trait method a syscall handler implements, e.g. `command` or
+/// `allow_readwrite`, from the last component of its demangled name.
+pub fn trait_method(name: &str) -> String {
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    demangled.rsplit("::").next().unwrap_or_default().to_owned()
+}
+
+/// Number of arguments a handler takes besides `self`, as compiled: slices
+/// and other aggregates may be passed as several arguments.
+pub fn method_arity(project: &Project, name: &str) -> Option<usize> {
+    project
+        .get_func_by_name(name)
+        .map(|(f, _m)| f.parameters.len().saturating_sub(1))
+}
+
+/// The middle value of a sorted, non-empty list (the lower one of an even count).
+fn median<T: Copy>(sorted: &[T]) -> T {
+    sorted[(sorted.len() - 1) / 2]
+}
+
+/// Render aggregates of the syscall handler results per driver trait method
+/// and arity across every driver of the board: the number of handlers, and
+/// the maximum and median of their longest paths, for documentation to quote.
+pub fn class_aggregates(
+    project: &Project,
+    results: &HashMap<String, Result<PathLength, String>>,
+    is_syscall: impl Fn(&str) -> bool,
+) -> String {
+    // handlers and their longest paths, by method and arity
+    let mut classes: BTreeMap<_, Vec<(&String, Option<&PathLength>)>> = BTreeMap::new();
+    for (name, result) in results.iter().filter(|(name, _)| is_syscall(name)) {
+        classes
+            .entry((trait_method(name), method_arity(project, name)))
+            .or_default()
+            .push((name, result.as_ref().ok()));
+    }
+
+    let mut out = String::from(
+        "method\tarity\thandlers\tfailed\tmax IR\tmedian IR\tmax cycles\tmedian cycles\tworst handler\n",
+    );
+    for ((method, arity), handlers) in classes {
+        let mut lengths: Vec<(&String, &PathLength)> = handlers
+            .iter()
+            .filter_map(|(name, length)| Some((*name, (*length)?)))
+            .collect();
+        let failed = handlers.len() - lengths.len();
+        let arity = arity.map_or_else(|| "-".to_owned(), |a| a.to_string());
+        if lengths.is_empty() {
+            out += &format!(
+                "{}\t{}\t{}\t{}\t-\t-\t-\t-\t-\n",
+                method,
+                arity,
+                handlers.len(),
+                failed
+            );
+            continue;
+        }
+        lengths.sort_by(|(a_name, a), (b_name, b)| a.ir.cmp(&b.ir).then(b_name.cmp(a_name)));
+        let ir: Vec<usize> = lengths.iter().map(|(_, l)| l.ir).collect();
+        let mut cycles: Vec<u64> = lengths.iter().map(|(_, l)| l.cycles).collect();
+        cycles.sort_unstable();
+        let (worst, _) = lengths.last().unwrap();
+        out += &format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:#}\n",
+            method,
+            arity,
+            handlers.len(),
+            failed,
+            ir[ir.len() - 1],
+            median(&ir),
+            cycles[cycles.len() - 1],
+            median(&cycles),
+            rustc_demangle::demangle(worst)
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files` (paths relative to a fresh directory, and contents)
+    /// under the system's temporary directory, returning the directory.
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("wcet-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn driver_numbers_from_sources() {
+        let root = tree(
+            "drivers",
+            &[
+                (
+                    "capsules/Cargo.toml",
+                    "[package]\nname = \"capsules-core\"\n",
+                ),
+                (
+                    "capsules/src/driver.rs",
+                    "pub enum NUM {\n    Alarm = 0x0,\n    Console = 0x0000_0001,\n}\n",
+                ),
+                (
+                    "capsules/src/console.rs",
+                    "pub const DRIVER_NUM: usize = driver::NUM::Console as usize;\n",
+                ),
+                (
+                    "capsules/src/alarm/mod.rs",
+                    "pub const DRIVER_NUM: usize = driver::NUM::Alarm as usize;\n",
+                ),
+                (
+                    "capsules/src/led.rs",
+                    "pub const DRIVER_NUM: usize = 0x0000_0002;\n",
+                ),
+                (
+                    "boards/imix/src/main.rs",
+                    "match driver_num {\n\
+                     capsules_core::console::DRIVER_NUM => f(Some(self.console)),\n\
+                     alarm::DRIVER_NUM => f(Some(self.alarm)),\n\
+                     led::DRIVER_NUM => f(Some(self.led)),\n\
+                     capsules_core::rng::DRIVER_NUM => f(Some(self.rng)),\n\
+                     _ => f(None),\n}\n",
+                ),
+            ],
+        );
+        let drivers = driver_numbers(&root, &root.join("boards/imix"));
+        std::fs::remove_dir_all(&root).unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("capsules_core::console".to_owned(), 1);
+        expected.insert("alarm".to_owned(), 0);
+        expected.insert("led".to_owned(), 2);
+        assert_eq!(drivers, expected);
+    }
+
+    #[test]
+    fn function_attributed_to_the_most_specific_module() {
+        let mut drivers = HashMap::new();
+        drivers.insert("capsules::led".to_owned(), 2);
+        drivers.insert("capsules::led::rgb".to_owned(), 3);
+        assert_eq!(
+            driver_for_function(
+                &drivers,
+                "_ZN8capsules3led3rgb3Rgb7command17h0123456789abcdefE"
+            ),
+            Some((3, "capsules::led::rgb"))
+        );
+        assert_eq!(
+            driver_for_function(&drivers, "_ZN8capsules3led3Led7command17h0123456789abcdefE"),
+            Some((2, "capsules::led"))
+        );
+        assert_eq!(
+            driver_for_function(
+                &drivers,
+                "_ZN8capsules5ledger6Ledger7command17h0123456789abcdefE"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn trait_method_of_handler() {
+        assert_eq!(
+            trait_method("_ZN8capsules3led3Led15allow_readwrite17h0123456789abcdefE"),
+            "allow_readwrite"
+        );
+    }
+}
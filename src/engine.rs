@@ -0,0 +1,774 @@
+use clap::arg_enum;
+use haybale::backend::DefaultBackend;
+use haybale::solver_utils::{get_possible_solutions_for_bv, PossibleSolutions};
+use haybale::{symex_function, Config, ParameterVal, PathEntry, Project, ReturnValue, State};
+use llvm_ir::function::ParameterAttribute;
+use llvm_ir::{Function, Instruction, Name, Operand, Terminator, Type};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::call_graph::called_function_name;
+use crate::checkpoint::{Block, Frontier, Prefix, RoundFrontier};
+use crate::instruction_counter::path_ir_length;
+
+arg_enum! {
+    /// Symbolic execution engine finding the longest paths
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum EngineKind {
+        Longest,
+        Exhaustive,
+        Bounded,
+    }
+}
+
+impl EngineKind {
+    pub fn engine(self) -> &'static dyn Engine {
+        match self {
+            EngineKind::Longest => &Haybale,
+            EngineKind::Exhaustive => &Exhaustive,
+            EngineKind::Bounded => &Bounded,
+        }
+    }
+}
+
+/// The longest path an engine found through a function.
+pub struct FoundPath<'p> {
+    /// Number of LLVM IR instructions along the path, as counted by the engine
+    pub ir: usize,
+    /// Basic blocks the path goes through, in order
+    pub path: Vec<PathEntry<'p>>,
+    /// The LLVM IR instructions of the path, for the results
+    pub llvm_instructions: String,
+}
+
+/// A path an engine explored through a function, until it returned.
+pub struct ExploredPath<'a, 'p> {
+    /// The concrete value returned, directly or through an `sret` pointer,
+    /// "void", or "symbolic" if the path may return more than one value
+    pub return_value: String,
+    pub path: &'a [PathEntry<'p>],
+}
+
+/// A symbolic execution engine exploring the paths of a project's functions.
+/// The rest of the tool only goes through this trait, so that engines (another
+/// haybale version, or a different exploration strategy) can be swapped here.
+/// Engines are configured with haybale's `Config`, for its hooks and bounds.
+pub trait Engine {
+    /// Find the longest path through `entry`. Engines able to save their
+    /// exploration save it to `frontier` while they run, and resume it from
+    /// there.
+    fn longest_path<'p>(
+        &self,
+        entry: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        time_results: bool,
+        frontier: Option<&str>,
+    ) -> Result<FoundPath<'p>, String>;
+
+    /// Explore every path through `func_name` with the given parameter
+    /// values, calling `visit` on each path that returns. Paths ending in a
+    /// panic or unwinding are not visited.
+    fn explore<'p>(
+        &self,
+        func_name: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        params: Option<Vec<ParameterVal>>,
+        visit: &mut dyn FnMut(ExploredPath<'_, 'p>) -> Result<(), String>,
+    ) -> Result<(), String>;
+}
+
+/// The haybale fork the tool is built with, whose longest path search prunes
+/// paths shorter than one already found.
+pub struct Haybale;
+
+impl Engine for Haybale {
+    fn longest_path<'p>(
+        &self,
+        entry: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        time_results: bool,
+        _frontier: Option<&str>,
+    ) -> Result<FoundPath<'p>, String> {
+        let (ir, state) =
+            haybale::dyn_dispatch::find_longest_path(entry, project, config, time_results)?;
+        Ok(FoundPath {
+            ir,
+            path: state.get_path().clone(),
+            llvm_instructions: state.pretty_path_llvm_instructions(),
+        })
+    }
+
+    fn explore<'p>(
+        &self,
+        func_name: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        params: Option<Vec<ParameterVal>>,
+        visit: &mut dyn FnMut(ExploredPath<'_, 'p>) -> Result<(), String>,
+    ) -> Result<(), String> {
+        explore_paths(func_name, project, config, params, visit)
+    }
+}
+
+/// The value of `bv`, or "symbolic" if it may have more than one.
+fn concrete_value(
+    state: &State<'_, DefaultBackend>,
+    bv: &<DefaultBackend as haybale::backend::Backend>::BV,
+) -> Result<String, String> {
+    match get_possible_solutions_for_bv(state.solver.clone(), bv, 1).map_err(|e| e.to_string())? {
+        PossibleSolutions::Exactly(values) if values.len() == 1 => {
+            Ok(values.into_iter().next().unwrap().to_string())
+        }
+        _ => Ok("symbolic".to_owned()),
+    }
+}
+
+/// The value a void function returned through its `sret` parameter, if it has
+/// one: functions returning a large struct, like Tock 2.x's `CommandReturn`,
+/// write it to memory their caller passes a pointer to.
+fn sret_value(
+    state: &State<'_, DefaultBackend>,
+    function: &Function,
+) -> Result<Option<String>, String> {
+    let sret = function.parameters.iter().find(|param| {
+        param
+            .attributes
+            .iter()
+            .any(|attribute| matches!(attribute, ParameterAttribute::SRet { .. }))
+    });
+    let (param, pointee) = match sret.map(|param| (param, &*param.ty)) {
+        Some((param, Type::PointerType { pointee_type, .. })) => (param, pointee_type),
+        _ => return Ok(None),
+    };
+    let bits = match state.size_in_bits(pointee) {
+        Some(bits) if bits > 0 => bits,
+        _ => return Ok(None),
+    };
+    let pointer = state
+        .operand_to_bv(&Operand::LocalOperand {
+            name: param.name.clone(),
+            ty: param.ty.clone(),
+        })
+        .map_err(|e| e.to_string())?;
+    let value = state.read(&pointer, bits).map_err(|e| e.to_string())?;
+    concrete_value(state, &value).map(Some)
+}
+
+/// Explore every returning path through `func_name` with haybale's execution
+/// manager, which backtracks through the paths depth-first.
+fn explore_paths<'p>(
+    func_name: &str,
+    project: &'p Project,
+    config: Config<'p, DefaultBackend>,
+    params: Option<Vec<ParameterVal>>,
+    visit: &mut dyn FnMut(ExploredPath<'_, 'p>) -> Result<(), String>,
+) -> Result<(), String> {
+    let (function, _module) = project
+        .get_func_by_name(func_name)
+        .ok_or_else(|| format!("function {} not found", func_name))?;
+    let mut em = symex_function(func_name, project, config, params).map_err(|e| e.to_string())?;
+    while let Some(return_value) = em.next() {
+        let state = em.state();
+        let return_value = match return_value.map_err(|e| e.to_string())? {
+            ReturnValue::Return(bv) => concrete_value(state, &bv)?,
+            ReturnValue::ReturnVoid => {
+                sret_value(state, function)?.unwrap_or_else(|| "void".to_owned())
+            }
+            // paths ending in a panic or unwinding do not return
+            ReturnValue::Throw(_) | ReturnValue::Abort => continue,
+        };
+        visit(ExploredPath {
+            return_value,
+            path: state.get_path(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Explores every path without haybale's longest path optimizations,
+/// breadth-first, keeping the longest. The paths are extended in rounds: each
+/// round continues the prefixes (the blocks entered) of the paths the
+/// previous one stopped, by `ROUND_BLOCKS` blocks, and those that cannot beat
+/// the longest path found yet (what they have run so far plus the longest
+/// static path they could still take, from the block each function on their
+/// call stack is in) are dropped from the frontier, or abandoned as soon as
+/// they cannot within a round. The estimate is only known for paths whose
+/// remaining functions have no loops, recursion or indirect calls, so other
+/// paths are kept. Once a path reaches the static bound of the function no
+/// other path can be longer, and the search stops. This suits driver code
+/// that branches widely (e.g. over command numbers) but not deeply, where
+/// short paths finishing early bound the many others.
+///
+/// haybale's states hold live solver instances and cannot be kept between
+/// rounds, so each round runs the execution manager again, abandoning paths
+/// as soon as they leave the prefixes of the frontier: only their branch
+/// decisions are kept, and the prefixes run again. A path only reaches an
+/// `unreachable` instruction after a hook returns from a function that never
+/// does (e.g. the kernel loop ending the startup path), and ends there.
+pub struct Exhaustive;
+
+impl Engine for Exhaustive {
+    fn longest_path<'p>(
+        &self,
+        entry: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        _time_results: bool,
+        frontier: Option<&str>,
+    ) -> Result<FoundPath<'p>, String> {
+        search_breadth_first(entry, project, config, frontier)
+    }
+
+    fn explore<'p>(
+        &self,
+        func_name: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        params: Option<Vec<ParameterVal>>,
+        visit: &mut dyn FnMut(ExploredPath<'_, 'p>) -> Result<(), String>,
+    ) -> Result<(), String> {
+        explore_paths(func_name, project, config, params, visit)
+    }
+}
+
+/// Explores every path without haybale's longest path optimizations,
+/// depth-first, keeping the longest, and abandons a path as soon as what it
+/// has run so far plus the longest static path it could still take (from the
+/// block each function on its call stack is in) cannot beat the longest path
+/// found yet, sparing the solver queries of the rest of the path. The
+/// estimate is only known for paths whose remaining functions have no loops,
+/// recursion or indirect calls, so other paths run to the end. Once a path
+/// reaches the static bound of the function, the search stops.
+///
+/// haybale's execution manager holds a single state and backtracks it to the
+/// last unexplored branch, so this search needs no frontier of states, and
+/// its exploration is saved as the prefixes it explored completely.
+pub struct Bounded;
+
+impl Engine for Bounded {
+    fn longest_path<'p>(
+        &self,
+        entry: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        _time_results: bool,
+        frontier: Option<&str>,
+    ) -> Result<FoundPath<'p>, String> {
+        search_all_paths(entry, project, config, frontier)
+    }
+
+    fn explore<'p>(
+        &self,
+        func_name: &str,
+        project: &'p Project,
+        config: Config<'p, DefaultBackend>,
+        params: Option<Vec<ParameterVal>>,
+        visit: &mut dyn FnMut(ExploredPath<'_, 'p>) -> Result<(), String>,
+    ) -> Result<(), String> {
+        explore_paths(func_name, project, config, params, visit)
+    }
+}
+
+/// The error a path abandoned by `Bounded` or `Exhaustive` ends with.
+const PRUNED: &str = "path cannot be longer than the longest found";
+/// The error a path ends with when a resumed exploration already explored it.
+const EXPLORED: &str = "path explored before the exploration was resumed";
+
+/// Find the longest path through `entry` by exploring every path
+/// depth-first, stopping once one reaches the static bound, and abandoning
+/// those that cannot be longer than the longest found. The exploration is
+/// saved to `frontier`, and resumed from it.
+fn search_all_paths<'p>(
+    entry: &str,
+    project: &'p Project,
+    mut config: Config<'p, DefaultBackend>,
+    frontier: Option<&str>,
+) -> Result<FoundPath<'p>, String> {
+    config.longest_path_optimizations = false;
+    let frontier = match frontier {
+        Some(file) => Some(Rc::new(RefCell::new(Frontier::load(file, project)?))),
+        None => None,
+    };
+    if let Some(frontier) = &frontier {
+        let frontier = Rc::clone(frontier);
+        config
+            .callbacks
+            .add_terminator_callback(move |_term, state| {
+                match frontier.borrow().is_explored(state.get_path()) {
+                    true => Err(haybale::Error::OtherError(EXPLORED.to_owned())),
+                    false => Ok(()),
+                }
+            });
+    }
+    let bound = static_ir_bound(project, entry, &mut HashMap::new(), &mut HashSet::new());
+    let best = Rc::new(Cell::new(0));
+    {
+        let best = Rc::clone(&best);
+        let remaining = RefCell::new(RemainingBound::new(project));
+        config
+            .callbacks
+            .add_terminator_callback(move |_term, state| {
+                let path = state.get_path();
+                let so_far = blocks_length(path);
+                match remaining.borrow_mut().of_path(path) {
+                    Some(remaining) if best.get() > 0 && so_far + remaining <= best.get() => {
+                        Err(haybale::Error::OtherError(PRUNED.to_owned()))
+                    }
+                    _ => Ok(()),
+                }
+            });
+    }
+    let mut em = symex_function(entry, project, config, None).map_err(|e| e.to_string())?;
+    let mut longest: Option<FoundPath> = None;
+    while let Some(return_value) = em.next() {
+        if let Some(frontier) = &frontier {
+            frontier.borrow_mut().path_done(em.state().get_path())?;
+        }
+        match return_value {
+            Ok(ReturnValue::Throw(_)) | Ok(ReturnValue::Abort) => continue,
+            Ok(_) | Err(haybale::Error::UnreachableInstruction) => (),
+            Err(haybale::Error::OtherError(e)) if e == PRUNED || e == EXPLORED => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+        let state = em.state();
+        let ir = path_ir_length(state.get_path());
+        let longer = match &longest {
+            Some(l) => ir > l.ir,
+            None => true,
+        };
+        if longer {
+            longest = Some(FoundPath {
+                ir,
+                path: state.get_path().clone(),
+                llvm_instructions: state.pretty_path_llvm_instructions(),
+            });
+            best.set(ir);
+            if let Some(frontier) = &frontier {
+                frontier.borrow_mut().longest_found(state.get_path());
+            }
+        }
+        if bound == Some(ir) {
+            // no path left to explore can be longer
+            break;
+        }
+    }
+    if let Some(frontier) = &frontier {
+        frontier.borrow().finish();
+    }
+    longest.ok_or_else(|| format!("no path through {} returns", entry))
+}
+
+/// Blocks each round of `Exhaustive`'s search extends the paths by.
+const ROUND_BLOCKS: usize = 16;
+/// The error a path ends with when it leaves the prefixes a round extends.
+const OFF_FRONTIER: &str = "path is not in the frontier of the round";
+/// The error a path ends with when it is stopped for the next round.
+const NEXT_ROUND: &str = "path continues in the next round";
+
+/// Length of `path` as its pruning counts it: every instruction of the blocks
+/// it entered, terminators included.
+fn blocks_length(path: &[PathEntry]) -> usize {
+    path.iter().map(|e| e.0.bb.instrs.len() + 1).sum()
+}
+
+fn block_of<'p>(entry: &PathEntry<'p>) -> Block<'p> {
+    (entry.0.func.name.as_str(), &entry.0.bb.name)
+}
+
+/// Where a path is relative to the prefixes a round extends.
+enum Position {
+    /// Along a prefix, not past its end
+    Within,
+    /// Past the end of the prefix of that many blocks, the longest it took
+    Extends(usize),
+    /// Off every prefix
+    Off,
+}
+
+/// The prefixes a round of the breadth-first search extends, as a trie of
+/// their blocks.
+struct PrefixTrie<'p> {
+    children: HashMap<(usize, Block<'p>), usize>,
+    /// The nodes prefixes end at
+    ends: HashSet<usize>,
+}
+
+impl<'p> PrefixTrie<'p> {
+    fn new(prefixes: &[Prefix<'p>]) -> Self {
+        let mut trie = PrefixTrie {
+            children: HashMap::new(),
+            ends: HashSet::new(),
+        };
+        for prefix in prefixes {
+            let mut node = 0;
+            for &block in &prefix.blocks {
+                let next = trie.children.len() + 1;
+                node = *trie.children.entry((node, block)).or_insert(next);
+            }
+            trie.ends.insert(node);
+        }
+        trie
+    }
+
+    fn locate(&self, path: &[PathEntry<'p>]) -> Position {
+        let mut node = 0;
+        let mut extends = None;
+        for (depth, entry) in path.iter().enumerate() {
+            if self.ends.contains(&node) {
+                extends = Some(depth);
+            }
+            match self.children.get(&(node, block_of(entry))) {
+                Some(&child) => node = child,
+                None => return extends.map_or(Position::Off, Position::Extends),
+            }
+        }
+        Position::Within
+    }
+}
+
+/// What a round of the breadth-first search shares with its callback.
+struct Round<'p> {
+    trie: PrefixTrie<'p>,
+    /// Prefixes of the paths stopped for the next round
+    next: Vec<Prefix<'p>>,
+}
+
+/// Find the longest path through `entry` by exploring every path
+/// breadth-first, in rounds extending the prefixes of the paths left by
+/// `ROUND_BLOCKS` blocks, dropping those that cannot be longer than the
+/// longest found, and stopping once one reaches the static bound. The
+/// frontier is saved to `frontier` between rounds, and resumed from it.
+fn search_breadth_first<'p>(
+    entry: &str,
+    project: &'p Project,
+    mut config: Config<'p, DefaultBackend>,
+    frontier: Option<&str>,
+) -> Result<FoundPath<'p>, String> {
+    config.longest_path_optimizations = false;
+    let (mut saved, resumed) = match frontier {
+        Some(file) => {
+            let (saved, resumed) = RoundFrontier::load(file, project)?;
+            (Some(saved), resumed)
+        }
+        None => (None, None),
+    };
+    let mut prefixes = match resumed {
+        // the longest path found before is run again to find it again
+        Some((mut prefixes, longest)) => {
+            if !longest.is_empty() {
+                prefixes.push(Prefix {
+                    blocks: longest,
+                    bound: None,
+                });
+            }
+            prefixes
+        }
+        None => vec![Prefix {
+            blocks: vec![],
+            bound: None,
+        }],
+    };
+    let bound = static_ir_bound(project, entry, &mut HashMap::new(), &mut HashSet::new());
+    let best = Rc::new(Cell::new(0));
+    let round = Rc::new(RefCell::new(Round {
+        trie: PrefixTrie::new(&[]),
+        next: vec![],
+    }));
+    {
+        let best = Rc::clone(&best);
+        let round = Rc::clone(&round);
+        let remaining = RefCell::new(RemainingBound::new(project));
+        config
+            .callbacks
+            .add_terminator_callback(move |_term, state| {
+                let path = state.get_path();
+                let mut round = round.borrow_mut();
+                let start = match round.trie.locate(path) {
+                    Position::Within => return Ok(()),
+                    Position::Extends(start) => start,
+                    Position::Off => {
+                        return Err(haybale::Error::OtherError(OFF_FRONTIER.to_owned()))
+                    }
+                };
+                let bound = remaining
+                    .borrow_mut()
+                    .of_path(path)
+                    .map(|remaining| blocks_length(path) + remaining);
+                match bound {
+                    Some(bound) if best.get() > 0 && bound <= best.get() => {
+                        return Err(haybale::Error::OtherError(PRUNED.to_owned()))
+                    }
+                    _ => (),
+                }
+                let ends = matches!(
+                    path.last().unwrap().0.bb.term,
+                    Terminator::Ret(_) | Terminator::Unreachable(_) | Terminator::Resume(_)
+                );
+                if path.len() - start >= ROUND_BLOCKS && !ends {
+                    round.next.push(Prefix {
+                        blocks: path.iter().map(block_of).collect(),
+                        bound,
+                    });
+                    return Err(haybale::Error::OtherError(NEXT_ROUND.to_owned()));
+                }
+                Ok(())
+            });
+    }
+    let mut longest: Option<FoundPath> = None;
+    'rounds: while !prefixes.is_empty() {
+        round.borrow_mut().trie = PrefixTrie::new(&prefixes);
+        let mut em =
+            symex_function(entry, project, config.clone(), None).map_err(|e| e.to_string())?;
+        while let Some(return_value) = em.next() {
+            match return_value {
+                Ok(ReturnValue::Throw(_)) | Ok(ReturnValue::Abort) => continue,
+                Ok(_) | Err(haybale::Error::UnreachableInstruction) => (),
+                Err(haybale::Error::OtherError(e))
+                    if e == PRUNED || e == OFF_FRONTIER || e == NEXT_ROUND =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+            let state = em.state();
+            best.set(best.get().max(blocks_length(state.get_path())));
+            let ir = path_ir_length(state.get_path());
+            let longer = match &longest {
+                Some(l) => ir > l.ir,
+                None => true,
+            };
+            if longer {
+                longest = Some(FoundPath {
+                    ir,
+                    path: state.get_path().clone(),
+                    llvm_instructions: state.pretty_path_llvm_instructions(),
+                });
+            }
+            if bound == Some(ir) {
+                // no path left to explore can be longer
+                break 'rounds;
+            }
+        }
+        prefixes = std::mem::take(&mut round.borrow_mut().next);
+        prefixes.retain(|prefix| !matches!(prefix.bound, Some(bound) if bound <= best.get()));
+        if let Some(saved) = &mut saved {
+            let longest: Vec<Block> = match &longest {
+                Some(l) => l.path.iter().map(block_of).collect(),
+                None => vec![],
+            };
+            saved.round_done(&prefixes, &longest)?;
+        }
+    }
+    if let Some(saved) = &saved {
+        saved.finish();
+    }
+    longest.ok_or_else(|| format!("no path through {} returns", entry))
+}
+
+/// The longest path from a block, once computed: unknown if it loops.
+type Longest = Option<Option<usize>>;
+
+/// Upper bounds on the IR instructions a path can still run, from the
+/// static control flow graphs of the functions on its call stack.
+struct RemainingBound<'p> {
+    project: &'p Project,
+    bounds: HashMap<&'p str, Option<usize>>,
+    /// The graph of each function, with the longest path from each block
+    graphs: HashMap<&'p str, (BlockGraph<'p>, Vec<Longest>)>,
+}
+
+impl<'p> RemainingBound<'p> {
+    fn new(project: &'p Project) -> Self {
+        RemainingBound {
+            project,
+            bounds: HashMap::new(),
+            graphs: HashMap::new(),
+        }
+    }
+
+    /// Longest path from block `bb` of `function` to its return.
+    fn block_bound(&mut self, function: &'p Function, bb: &Name) -> Option<usize> {
+        if !self.graphs.contains_key(function.name.as_str()) {
+            let graph = block_graph(
+                self.project,
+                function,
+                &mut self.bounds,
+                &mut HashSet::new(),
+            );
+            let memo = vec![None; graph.costs.len()];
+            self.graphs.insert(&function.name, (graph, memo));
+        }
+        let (graph, memo) = self.graphs.get_mut(function.name.as_str()).unwrap();
+        let i = *graph.index.get(bb)?;
+        longest_from(
+            i,
+            &graph.costs,
+            &graph.successors,
+            memo,
+            &mut vec![false; graph.costs.len()],
+        )
+    }
+
+    /// Longest the rest of `path` can be: the longest path from the block
+    /// each function on its call stack is in. The call stack is rebuilt
+    /// from the functions of the path's blocks: a block of the caller of
+    /// the current function returns to it, one of another function calls it.
+    fn of_path(&mut self, path: &[PathEntry<'p>]) -> Option<usize> {
+        let mut stack: Vec<(&'p Function, &'p Name)> = vec![];
+        for entry in path {
+            let location = &entry.0;
+            let frame = (location.func, &location.bb.name);
+            let depth = stack.len();
+            if depth >= 1 && stack[depth - 1].0.name == location.func.name {
+                stack[depth - 1] = frame;
+            } else if depth >= 2 && stack[depth - 2].0.name == location.func.name {
+                stack.pop();
+                stack[depth - 2] = frame;
+            } else {
+                stack.push(frame);
+            }
+        }
+        stack
+            .into_iter()
+            .map(|(function, bb)| self.block_bound(function, bb))
+            .sum()
+    }
+}
+
+/// Upper bound on the LLVM IR instructions of any path through `func_name`
+/// and the functions it calls, counted like the paths' lengths. Functions
+/// without a body (declarations, intrinsics) count for nothing. There is no
+/// bound if the function loops, recurses or calls through a pointer.
+fn static_ir_bound<'p>(
+    project: &'p Project,
+    func_name: &'p str,
+    bounds: &mut HashMap<&'p str, Option<usize>>,
+    visiting: &mut HashSet<&'p str>,
+) -> Option<usize> {
+    if let Some(&bound) = bounds.get(func_name) {
+        return bound;
+    }
+    let function = match project.get_func_by_name(func_name) {
+        Some((f, _m)) if !f.basic_blocks.is_empty() => f,
+        _ => return Some(0),
+    };
+    if !visiting.insert(func_name) {
+        return None;
+    }
+    let graph = block_graph(project, function, bounds, visiting);
+    let bound = longest_from(
+        0,
+        &graph.costs,
+        &graph.successors,
+        &mut vec![None; graph.costs.len()],
+        &mut vec![false; graph.costs.len()],
+    );
+    visiting.remove(func_name);
+    bounds.insert(func_name, bound);
+    bound
+}
+
+/// The control flow graph of a function: the length of each block, with
+/// the static bounds of the functions it calls, and the blocks it branches
+/// to, unknown for calls without a bound and unsupported terminators.
+struct BlockGraph<'p> {
+    index: HashMap<&'p Name, usize>,
+    costs: Vec<Option<usize>>,
+    successors: Vec<Option<Vec<usize>>>,
+}
+
+fn block_graph<'p>(
+    project: &'p Project,
+    function: &'p Function,
+    bounds: &mut HashMap<&'p str, Option<usize>>,
+    visiting: &mut HashSet<&'p str>,
+) -> BlockGraph<'p> {
+    let index: HashMap<&Name, usize> = function
+        .basic_blocks
+        .iter()
+        .enumerate()
+        .map(|(i, bb)| (&bb.name, i))
+        .collect();
+    let mut costs = vec![];
+    let mut successors = vec![];
+    for bb in &function.basic_blocks {
+        let mut cost = Some(bb.instrs.len() + 1);
+        for instr in &bb.instrs {
+            if let Instruction::Call(call) = instr {
+                let callee = match &call.function {
+                    llvm_ir::Either::Left(_asm) => Some(0),
+                    operand => called_function_name(operand)
+                        .and_then(|name| static_ir_bound(project, name, bounds, visiting)),
+                };
+                cost = cost.zip(callee).map(|(c, callee)| c + callee);
+            }
+        }
+        let targets: Option<Vec<&Name>> = match &bb.term {
+            Terminator::Ret(_) | Terminator::Unreachable(_) | Terminator::Resume(_) => Some(vec![]),
+            Terminator::Br(br) => Some(vec![&br.dest]),
+            Terminator::CondBr(br) => Some(vec![&br.true_dest, &br.false_dest]),
+            Terminator::Switch(switch) => Some(
+                switch
+                    .dests
+                    .iter()
+                    .map(|(_value, dest)| dest)
+                    .chain(Some(&switch.default_dest))
+                    .collect(),
+            ),
+            Terminator::Invoke(invoke) => {
+                let callee = called_function_name(&invoke.function)
+                    .and_then(|name| static_ir_bound(project, name, bounds, visiting));
+                cost = cost.zip(callee).map(|(c, callee)| c + callee);
+                Some(vec![&invoke.return_label, &invoke.exception_label])
+            }
+            _ => None,
+        };
+        costs.push(cost);
+        successors.push(targets.and_then(|targets| {
+            targets
+                .into_iter()
+                .map(|t| index.get(t).copied())
+                .collect::<Option<Vec<usize>>>()
+        }));
+    }
+    BlockGraph {
+        index,
+        costs,
+        successors,
+    }
+}
+
+/// Longest path from block `i` of a control flow graph, if it is acyclic.
+fn longest_from(
+    i: usize,
+    costs: &[Option<usize>],
+    successors: &[Option<Vec<usize>>],
+    memo: &mut [Option<Option<usize>>],
+    on_stack: &mut [bool],
+) -> Option<usize> {
+    if let Some(bound) = memo[i] {
+        return bound;
+    }
+    if on_stack[i] {
+        // a loop
+        return None;
+    }
+    on_stack[i] = true;
+    let mut longest = Some(0);
+    for &next in successors[i].as_deref().unwrap_or(&[]) {
+        let from_next = longest_from(next, costs, successors, memo, on_stack);
+        longest = longest.zip(from_next).map(|(a, b)| a.max(b));
+    }
+    if successors[i].is_none() {
+        longest = None;
+    }
+    on_stack[i] = false;
+    let bound = costs[i].zip(longest).map(|(cost, rest)| cost + rest);
+    memo[i] = Some(bound);
+    bound
+}
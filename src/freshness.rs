@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+use crate::drivers::rust_sources;
+
+/// Name of the file recording which sources the bitcode in a target directory was built from.
+const FINGERPRINT_FILE: &str = "wcet-rs-fingerprint";
+
+/// Whether the bitcode in a target directory matches the current sources.
+pub enum Freshness {
+    Fresh,
+    Stale,
+    /// The bitcode was not built by this tool, so its sources are unknown
+    Unknown,
+}
+
+// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across toolchains
+pub fn fnv1a(hash: &mut u64, bytes: &[u8]) {
+    for &b in bytes {
+        *hash ^= b as u64;
+        *hash = hash.wrapping_mul(0x100000001b3);
+    }
+}
+
+/// Hash of the paths and contents of every Rust source file (and lockfile)
+/// under `roots`.
+pub fn source_fingerprint(roots: &[&Path]) -> String {
+    let mut hash = 0xcbf29ce484222325;
+    for root in roots {
+        let mut files: Vec<PathBuf> = vec![];
+        rust_sources(root, &mut files);
+        files.push(root.join("Cargo.lock"));
+        files.sort();
+        for file in files {
+            if let Ok(contents) = std::fs::read(&file) {
+                let relative = file.strip_prefix(root).unwrap_or(&file);
+                fnv1a(&mut hash, relative.to_string_lossy().as_bytes());
+                fnv1a(&mut hash, &contents);
+            }
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Record that the bitcode in `target_dir` was just built from sources with `fingerprint`.
+pub fn record(target_dir: &str, fingerprint: &str) -> Result<(), String> {
+    let path = Path::new(target_dir).join(FINGERPRINT_FILE);
+    std::fs::write(&path, fingerprint)
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+/// Compare the sources the bitcode in `target_dir` was built from against `fingerprint`.
+pub fn check(target_dir: &str, fingerprint: &str) -> Freshness {
+    match std::fs::read_to_string(Path::new(target_dir).join(FINGERPRINT_FILE)) {
+        Ok(recorded) if recorded.trim() == fingerprint => Freshness::Fresh,
+        Ok(_) => Freshness::Stale,
+        Err(_) => Freshness::Unknown,
+    }
+}
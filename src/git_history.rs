@@ -0,0 +1,125 @@
+use git2::{DiffFormat, Repository, StatusOptions};
+use serde::{Deserialize, Serialize};
+
+/// A submodule of a repository, and whether it is checked out at the commit
+/// the repository records for it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmoduleState {
+    pub path: String,
+    /// Commit recorded for it in the repository's HEAD
+    pub recorded: Option<String>,
+    /// Commit checked out in the working tree, if initialized
+    pub checked_out: Option<String>,
+}
+
+/// The state of a git checkout when a run started, saved in the manifest
+/// with `--save-git-history`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RepoState {
+    /// Root of the working tree
+    pub path: String,
+    pub commit: String,
+    /// Branch checked out, unless HEAD is detached
+    pub branch: Option<String>,
+    /// Whether tracked files are modified
+    pub dirty: bool,
+    /// Changes of tracked files relative to HEAD, as `git diff HEAD` shows
+    pub diff: String,
+    pub submodules: Vec<SubmoduleState>,
+}
+
+fn open(path: &str) -> Result<Repository, String> {
+    Repository::discover(path).map_err(|e| format!("no git repository at {}: {}", path, e))
+}
+
+/// The commit checked out in the repository containing `path`.
+pub fn head_commit(path: &str) -> Option<String> {
+    let repo = open(path).ok()?;
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Capture the state of the repository containing `path`.
+pub fn capture(path: &str) -> Result<RepoState, String> {
+    let repo = open(path)?;
+    let git_err = |e: git2::Error| format!("failed to read git repository at {}: {}", path, e);
+    let head = repo.head().map_err(git_err)?;
+    let commit = head.peel_to_commit().map_err(git_err)?;
+    let branch = match repo.head_detached().map_err(git_err)? {
+        true => None,
+        false => head.shorthand().map(|s| s.to_owned()),
+    };
+    let dirty = !repo
+        .statuses(Some(
+            StatusOptions::new()
+                .include_untracked(false)
+                .exclude_submodules(true),
+        ))
+        .map_err(git_err)?
+        .is_empty();
+    let tree = commit.tree().map_err(git_err)?;
+    let mut diff = String::new();
+    repo.diff_tree_to_workdir_with_index(Some(&tree), None)
+        .map_err(git_err)?
+        .print(DiffFormat::Patch, |_, _, line| {
+            if let '+' | '-' | ' ' = line.origin() {
+                diff.push(line.origin());
+            }
+            diff += &String::from_utf8_lossy(line.content());
+            true
+        })
+        .map_err(git_err)?;
+    let submodules = repo
+        .submodules()
+        .map_err(git_err)?
+        .iter()
+        .map(|submodule| SubmoduleState {
+            path: submodule.path().to_string_lossy().into_owned(),
+            recorded: submodule.head_id().map(|id| id.to_string()),
+            checked_out: submodule.workdir_id().map(|id| id.to_string()),
+        })
+        .collect();
+    Ok(RepoState {
+        path: repo
+            .workdir()
+            .unwrap_or_else(|| repo.path())
+            .to_string_lossy()
+            .into_owned(),
+        commit: commit.id().to_string(),
+        branch,
+        dirty,
+        diff,
+        submodules,
+    })
+}
+
+/// The first `lines` lines of the history of the repository containing
+/// `path`, formatted like `git log`.
+pub fn log(path: &str, lines: usize) -> Result<String, String> {
+    let repo = open(path)?;
+    let git_err = |e: git2::Error| format!("failed to read git history at {}: {}", path, e);
+    let mut revwalk = repo.revwalk().map_err(git_err)?;
+    revwalk.push_head().map_err(git_err)?;
+    let mut log = vec![];
+    for id in revwalk {
+        if log.len() >= lines {
+            break;
+        }
+        let commit = repo.find_commit(id.map_err(git_err)?).map_err(git_err)?;
+        if !log.is_empty() {
+            log.push(String::new());
+        }
+        log.push(format!("commit {}", commit.id()));
+        log.push(format!("Author: {}", commit.author()));
+        log.push(String::new());
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        log.extend(
+            message
+                .trim_end()
+                .lines()
+                .map(|line| format!("    {}", line)),
+        );
+    }
+    log.truncate(lines);
+    Ok(log.iter().map(|line| line.to_owned() + "\n").collect())
+}
@@ -0,0 +1,157 @@
+use haybale::backend::{Backend, DefaultBackend, BV};
+use haybale::function_hooks::FunctionHooks;
+use haybale::{IsCall, PathEntry, Project, ReturnValue, State};
+use llvm_ir::{Instruction, Name, Terminator, Type};
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::call_graph::called_function_name;
+use crate::config_file::HookConfig;
+use crate::instruction_counter::entry_range;
+use crate::PathLength;
+
+/// The hooks of the config file, by mangled name of each function they hook.
+/// Functions matched by several hooks take the first.
+pub fn resolve(
+    project: &Project,
+    configs: &[HookConfig],
+) -> Result<HashMap<String, HookConfig>, String> {
+    let mut hooks = HashMap::new();
+    for config in configs {
+        let regex = Regex::new(&config.function)
+            .map_err(|e| format!("invalid hook function regex {:?}: {}", config.function, e))?;
+        let mut matched = false;
+        for (f, _m) in project.all_functions() {
+            if regex.is_match(&format!("{:#}", rustc_demangle::demangle(&f.name))) {
+                hooks
+                    .entry(f.name.clone())
+                    .or_insert_with(|| config.clone());
+                matched = true;
+            }
+        }
+        if !matched {
+            println!(
+                "Warning: no function matches the hook {:?}",
+                config.function
+            );
+        }
+    }
+    Ok(hooks)
+}
+
+/// Return from `call` without executing the callee, with a fresh value which,
+/// if `values` are given, is constrained to be one of them.
+fn constrained_return<'p, B: Backend>(
+    state: &mut State<'p, B>,
+    call: &'p dyn IsCall,
+    values: Option<&[u64]>,
+) -> haybale::Result<ReturnValue<B::BV>> {
+    let return_type = state.type_of(call);
+    if let Type::VoidType = &*return_type {
+        return Ok(ReturnValue::ReturnVoid);
+    }
+    let width = state.size_in_bits(&return_type).ok_or_else(|| {
+        haybale::Error::OtherError("hooked function returns a value of unknown size".to_owned())
+    })?;
+    let retval = state.new_bv_with_name(Name::from("wcet_rs.hook.retval"), width)?;
+    if let Some(values) = values.filter(|values| !values.is_empty()) {
+        let allowed = values
+            .iter()
+            .map(|&value| retval._eq(&state.bv_from_u64(value, width)))
+            .reduce(|a, b| a.or(&b))
+            .unwrap();
+        allowed.assert()?;
+    }
+    Ok(ReturnValue::Return(retval))
+}
+
+/// A hook of the config file, returning from the call it hooks.
+type ReturnHook<'p> = dyn Fn(
+        &'p Project,
+        &mut State<'p, DefaultBackend>,
+        &'p dyn IsCall,
+    ) -> haybale::Result<ReturnValue<<DefaultBackend as Backend>::BV>>
+    + 'p;
+
+/// The hooks of an analysis, each capturing its return values. haybale adds
+/// hooks by reference, so the analysis owns them for as long as its config.
+pub struct ReturnHooks<'p> {
+    hooks: Vec<(String, Box<ReturnHook<'p>>)>,
+}
+
+impl<'p> ReturnHooks<'p> {
+    /// Hooks for each function of `hooks` but `func_name`, the function analyzed.
+    pub fn new(hooks: &HashMap<String, HookConfig>, func_name: &str) -> Self {
+        let hooks = hooks
+            .iter()
+            .filter(|(name, _config)| name.as_str() != func_name)
+            .map(|(name, config)| {
+                let values = config.return_values.clone();
+                let hook: Box<ReturnHook<'p>> = Box::new(
+                    move |_project: &'p Project,
+                          state: &mut State<'p, DefaultBackend>,
+                          call: &'p dyn IsCall| {
+                        constrained_return(state, call, values.as_deref())
+                    },
+                );
+                (name.clone(), hook)
+            })
+            .collect();
+        ReturnHooks { hooks }
+    }
+
+    /// Hook the functions in `function_hooks`.
+    pub fn add_to(&'p self, function_hooks: &mut FunctionHooks<'p, DefaultBackend>) {
+        for (name, hook) in &self.hooks {
+            function_hooks.add(name.clone(), hook);
+        }
+    }
+}
+
+/// Total cost of the calls to hooked functions along a path, a listing of
+/// those calls in path order, and the demangled names of the hooked functions
+/// called, once each. Hooked calls return without entering the callee, so the
+/// path itself only counts the call instruction.
+pub fn path_cost(
+    path: &[PathEntry],
+    hooks: &HashMap<String, HookConfig>,
+) -> (PathLength, String, Vec<String>) {
+    let mut cost = PathLength {
+        ir: 0,
+        machine: 0,
+        cycles: 0,
+    };
+    let mut listing = String::new();
+    let mut called: Vec<String> = vec![];
+    if hooks.is_empty() {
+        return (cost, listing, called);
+    }
+    for i in 0..path.len() {
+        let bb = &path[i].0.bb;
+        for index in entry_range(path, i) {
+            let callee = match (bb.instrs.get(index), &bb.term) {
+                (Some(Instruction::Call(call)), _) => &call.function,
+                (None, Terminator::Invoke(invoke)) => &invoke.function,
+                _ => continue,
+            };
+            let (name, hook) = match called_function_name(callee)
+                .and_then(|name| hooks.get(name).map(|hook| (name, hook)))
+            {
+                Some(hooked) => hooked,
+                None => continue,
+            };
+            cost.ir += hook.ir;
+            cost.machine += hook.machine;
+            cost.cycles += hook.cycles;
+            let demangled = rustc_demangle::demangle(name).to_string();
+            listing += &format!(
+                "hooked: {} | ir: {} | machine: {} | cycles: {}\n",
+                demangled, hook.ir, hook.machine, hook.cycles
+            );
+            if !called.contains(&demangled) {
+                called.push(demangled);
+            }
+        }
+    }
+    (cost, listing, called)
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directories LLVM 13 is installed to by Homebrew, which does not put its
+/// tools on the `PATH`.
+const LLVM_DIRS: &[&str] = &[
+    "/opt/homebrew/opt/llvm@13/bin",
+    "/usr/local/opt/llvm@13/bin",
+];
+
+/// A command running `command` through the host's shell: `sh` on Unix hosts,
+/// `cmd` on Windows.
+pub fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+fn executable(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    match path.is_file() {
+        true => Some(path),
+        false => None,
+    }
+}
+
+/// The LLVM version line `--version` of the tool at `path` prints, e.g.
+/// `LLVM version 13.0.1`, if it prints one.
+fn llvm_version(path: &Path) -> Option<String> {
+    let output = Command::new(path).arg("--version").output().ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("LLVM version"))
+        .map(str::to_owned)
+}
+
+/// Whether the tool at `path` is from LLVM 13, the version of the bitcode
+/// haybale reads.
+fn is_llvm_13(path: &Path) -> bool {
+    matches!(llvm_version(path), Some(v) if v.starts_with("LLVM version 13."))
+}
+
+/// The LLVM 13 tool `name`, e.g. `llc`: from the directory in `WCET_LLVM_BIN`
+/// if set, otherwise the first of `name-13` (as Debian names them) or `name`
+/// (as macOS and Windows installs do) on the `PATH` or in Homebrew's LLVM 13
+/// which reports LLVM 13 with `--version`.
+pub fn llvm_tool(name: &str) -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os("WCET_LLVM_BIN") {
+        let path = executable(Path::new(&dir), name)
+            .ok_or_else(|| format!("{} not found in WCET_LLVM_BIN", name))?;
+        return match is_llvm_13(&path) {
+            true => Ok(path),
+            false => Err(format!(
+                "{} is not from LLVM 13 ({})",
+                path.display(),
+                llvm_version(&path).unwrap_or_else(|| "unknown version".to_owned())
+            )),
+        };
+    }
+    let mut dirs: Vec<PathBuf> = match std::env::var_os("PATH") {
+        Some(path) => std::env::split_paths(&path).collect(),
+        None => vec![],
+    };
+    dirs.extend(LLVM_DIRS.iter().map(PathBuf::from));
+    let mut other_versions = vec![];
+    for candidate in &[format!("{}-13", name), name.to_owned()] {
+        for path in dirs.iter().filter_map(|dir| executable(dir, candidate)) {
+            if is_llvm_13(&path) {
+                return Ok(path);
+            }
+            other_versions.push(format!(
+                "{} ({})",
+                path.display(),
+                llvm_version(&path).unwrap_or_else(|| "unknown version".to_owned())
+            ));
+        }
+    }
+    match other_versions.is_empty() {
+        true => Err(format!(
+            "neither {0}-13 nor {0} found, install LLVM 13 or set WCET_LLVM_BIN",
+            name
+        )),
+        false => Err(format!(
+            "no {} from LLVM 13 found, only {}; install LLVM 13 or set WCET_LLVM_BIN",
+            name,
+            other_versions.join(", ")
+        )),
+    }
+}
+
+/// `path` without trailing separators.
+pub fn trim_path(path: &str) -> &str {
+    path.trim_end_matches(std::path::is_separator)
+}
+
+/// The last component of `path`, e.g. the name of a board from its
+/// directory, with either separator on Windows.
+pub fn last_component(path: &str) -> String {
+    match Path::new(trim_path(path)).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_owned(),
+    }
+}
+
+/// Longest file name kept, in bytes, leaving room for the extensions added
+/// to it within the 255 bytes file systems allow.
+const MAX_FILE_NAME: usize = 200;
+
+/// `name` made a valid file name on every host: the characters Windows
+/// reserves (`<>:"/\|?*`), whitespace and control characters are replaced
+/// with `_`, and a name longer than `MAX_FILE_NAME` is cut, ending with a
+/// hash of the whole name to keep it unique.
+pub fn file_name(name: &str) -> String {
+    let mut file_name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_whitespace() || c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if file_name.len() > MAX_FILE_NAME {
+        let mut hash = 0xcbf29ce484222325;
+        crate::freshness::fnv1a(&mut hash, name.as_bytes());
+        let mut end = MAX_FILE_NAME - 17;
+        while !file_name.is_char_boundary(end) {
+            end -= 1;
+        }
+        file_name.truncate(end);
+        file_name += &format!("-{:016x}", hash);
+    }
+    file_name
+}
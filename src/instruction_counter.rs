@@ -1,19 +1,210 @@
 use glob::glob;
 use once_cell::sync::Lazy;
+use rayon::prelude::*;
 use regex::Regex;
-use std::{path::PathBuf, process::Command};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-use haybale::{backend::Backend, Location, State};
+use haybale::{BBInstrIndex, PathEntry};
+
+use crate::host;
 
 pub type Disassem = Vec<String>;
 
-// matches any line that is a machine instruction
-static INST: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)([^@_\s\.])(.*)$").unwrap());
-// matches the start of a function
-static ANY_FUNC: Lazy<Regex> = Lazy::new(|| Regex::new("^_.+:$").unwrap());
-// matches the start of a function or bb
-static ANY_BB_OR_FUNC: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"(^_.+:$)|(^@\s*%bb\.\d+:.*$)|(^\.LBB.*:$)").unwrap());
+/// Lines of a block's machine instructions written to a path listing.
+const MAX_BLOCK_LINES: usize = 1000;
+/// Blocks of a path counted at once, so that only their listings are held in
+/// memory before being written.
+const BLOCKS_PER_CHUNK: usize = 1024;
+
+// matches any line that is a machine instruction, not a comment (`@` on ARM,
+// `#` on RISC-V), label or directive
+static INST: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)([^@#_\s\.])(.*)$").unwrap());
+// matches the start of a function: a label in the first column, other than
+// local labels (`.Ltmp3:`, `.LBB0_2:`), possibly followed by a comment naming
+// it (`foo:  # @foo` on RISC-V)
+static ANY_FUNC: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[^\s.@#][^\s:]*:(?:\s+[@#].*)?$").unwrap());
+// matches the start of a function or bb; bb labels may be followed by a
+// comment naming the IR block
+static ANY_BB_OR_FUNC: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(^[^\s.@#][^\s:]*:(?:\s+[@#].*)?$)|(^[@#]\s*%bb\.\d+:.*$)|(^\.LBB\w*:(\s.*)?$)")
+        .unwrap()
+});
+// matches a label, possibly followed by a comment, capturing its name
+static LABEL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([^\s:]+):(?:\s+[@#].*)?$").unwrap());
+// matches the name of a numbered IR block, capturing its number
+static BB_NUM: Lazy<Regex> = Lazy::new(|| Regex::new(r"%(bb)?(\d+)").unwrap());
+// matches the name of an IR block exiting an inlined function
+static BB_EXIT: Lazy<Regex> = Lazy::new(|| Regex::new(r"%_.*\.exit").unwrap());
+// matches the end of a function's code
+static FUNC_END: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\.Lfunc_end\d+:").unwrap());
+// matches the start of a literal pool (or inline jump table), data placed
+// between the code
+static LITERAL_POOL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\.L(CPI|JTI)\d+_\d+:").unwrap());
+// matches a PC-relative load, usually from a literal pool
+static LITERAL_LOAD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*v?ldr(?:\.w|\.n)?\s+\w+,\s*(?:\.LCPI\w+|\[pc(?:,\s*#-?\d+)?\])").unwrap()
+});
+// matches a symbol defined as another, e.g. by merging identical functions,
+// capturing the alias and the symbol it stands for
+static ALIAS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:\.set\s+(\S+?),\s*|(\S+?)\s*=\s*)(\S+)\s*$").unwrap());
+// matches a conditional branch, capturing its condition code and target. The
+// compare-and-branch instructions have no condition code, as they do not read
+// the flags: ARM's `cbz r0, .LBB0_2` and RISC-V's branches comparing two
+// registers (`bgeu a0, a1, .LBB0_2`) or one with zero (`beqz a0, .LBB0_2`)
+static COND_BRANCH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?:b(?:eq|ne|lt|ge|gt|le)u?\s+\w+,\s*\w+,|b(?:eq|ne|lt|ge|gt|le)z\s+\w+,|b(eq|ne|cs|hs|cc|lo|mi|pl|vs|vc|hi|ls|ge|lt|gt|le)(?:\.w|\.n)?\s+|cbn?z\s+\w+,)\s*(\S+)",
+    )
+    .unwrap()
+});
+// matches the markers the assembler output puts around inline assembly,
+// capturing `NO_` at its end
+static INLINE_ASM_MARKER: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*[@#](NO_)?APP\s*$").unwrap());
+// matches a label or directive, which inline assembly may contain
+static ASM_LABEL_OR_DIRECTIVE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(\S+:|\.\w+\b.*)$").unwrap());
+// matches the start of an IT block, capturing the then/else pattern of the
+// instructions after the first, and their condition
+static IT_BLOCK: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*it([te]{0,3})\s+(\w+)").unwrap());
+
+/// Condition codes and their inverses.
+const CONDITIONS: &[(&str, &str)] = &[
+    ("eq", "ne"),
+    ("hs", "lo"),
+    ("mi", "pl"),
+    ("vs", "vc"),
+    ("hi", "ls"),
+    ("ge", "lt"),
+    ("gt", "le"),
+];
+
+/// Number of cycles each machine instruction takes, keyed by mnemonic
+/// (e.g. `ldr`, `bl`). Width suffixes such as `.w` fall back to the plain
+/// mnemonic, and anything not listed costs `default` cycles.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CostModel {
+    #[serde(default = "CostModel::default_cycles")]
+    pub default: u64,
+    #[serde(default)]
+    pub mnemonics: HashMap<String, u64>,
+    /// Cycles of a conditional branch the path takes, e.g. including the
+    /// pipeline refill, instead of its mnemonic's cost
+    pub branch_taken: Option<u64>,
+    /// Cycles of a conditional branch the path falls through
+    pub branch_not_taken: Option<u64>,
+    /// Cycles of a PC-relative load (e.g. `ldr r0, .LCPI3_0` from a literal
+    /// pool), instead of its mnemonic's cost
+    pub literal_load: Option<u64>,
+    /// Cycles of an `it` instruction starting an IT block, whatever its
+    /// pattern (`itt`, `ite`, ...)
+    pub it_block: Option<u64>,
+    /// Cycles of an instruction of an IT block whose condition fails, instead
+    /// of its mnemonic's cost
+    pub predicated_skipped: Option<u64>,
+    /// Exception entry and return costs of the core, added to the results of
+    /// interrupt handlers
+    pub exception: Option<ExceptionCosts>,
+    /// Machine instructions per LLVM IR instruction, to estimate the blocks of
+    /// a path which are not found in the disassembly. Such blocks are not
+    /// counted if unset
+    pub ir_ratio: Option<f64>,
+}
+
+/// Cycles the core takes to enter an exception handler (stacking registers
+/// and fetching the vector) and to return from it (the `bx lr` or `pop {pc}`
+/// to an `EXC_RETURN` value, beyond its own cost), and to instead tail-chain
+/// into another pending handler.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ExceptionCosts {
+    pub entry: u64,
+    pub exit: u64,
+    pub tail_chain: Option<u64>,
+}
+
+/// Cycles of a handler including the core's exception handling.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ExceptionCycles {
+    /// Entering the handler, running it and returning
+    pub isolated: u64,
+    /// Running the handler tail-chained after another one, without
+    /// returning in between: each further back-to-back interrupt adds this
+    pub tail_chained: Option<u64>,
+}
+
+impl ExceptionCosts {
+    pub fn handler(&self, cycles: u64) -> ExceptionCycles {
+        ExceptionCycles {
+            isolated: self.entry + cycles + self.exit,
+            tail_chained: self.tail_chain.map(|tail_chain| tail_chain + cycles),
+        }
+    }
+}
+
+impl CostModel {
+    fn default_cycles() -> u64 {
+        1
+    }
+
+    /// Cycles taken by the instruction on a line of disassembly.
+    pub fn cycles(&self, line: &str) -> u64 {
+        if let (Some(cycles), true) = (self.literal_load, LITERAL_LOAD.is_match(line)) {
+            return cycles;
+        }
+        if let (Some(cycles), true) = (self.it_block, IT_BLOCK.is_match(line)) {
+            return cycles;
+        }
+        let mnemonic = match line.split_whitespace().next() {
+            Some(m) => m.to_lowercase(),
+            None => return 0,
+        };
+        let unsuffixed = mnemonic
+            .strip_suffix(".w")
+            .or_else(|| mnemonic.strip_suffix(".n"));
+        self.mnemonics
+            .get(&mnemonic)
+            .or_else(|| unsuffixed.and_then(|m| self.mnemonics.get(m)))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Cycles taken by a conditional branch, depending on whether the path
+    /// takes it. If that is not known, the worse of both is assumed.
+    fn branch_cycles(&self, line: &str, taken: Option<bool>) -> u64 {
+        let taken_cycles = self.branch_taken.unwrap_or_else(|| self.cycles(line));
+        let not_taken_cycles = self.branch_not_taken.unwrap_or_else(|| self.cycles(line));
+        match taken {
+            Some(true) => taken_cycles,
+            Some(false) => not_taken_cycles,
+            None => taken_cycles.max(not_taken_cycles),
+        }
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            default: Self::default_cycles(),
+            mnemonics: HashMap::new(),
+            branch_taken: None,
+            branch_not_taken: None,
+            literal_load: None,
+            it_block: None,
+            predicated_skipped: None,
+            exception: None,
+            ir_ratio: None,
+        }
+    }
+}
 
 /// Find the bc file to be passed to llc
 fn find_bc_file(bc_dir: &String, board_name: &String) -> String {
@@ -29,19 +220,16 @@ fn find_bc_file(bc_dir: &String, board_name: &String) -> String {
 }
 
 /// Read the output of llc from disc
-fn read_llc_output(mut bc_path: String) -> String {
-    let len = bc_path.len();
-    // change the file extension from ".bc" to ".s"
-    bc_path.truncate(len - 2);
-    bc_path.push_str("s");
-    std::fs::read_to_string(bc_path).expect("could not open llc output")
+fn read_llc_output(bc_path: String) -> String {
+    let s_path = Path::new(&bc_path).with_extension("s");
+    std::fs::read_to_string(s_path).expect("could not open llc output")
 }
 
 /// Generate annotated disassembly using llc
 pub fn get_disassembly(bc_dir: &String, board_name: &String) -> Disassem {
     let bc_path = find_bc_file(bc_dir, board_name);
 
-    let mut llc_command = Command::new("llc-13");
+    let mut llc_command = Command::new(host::llvm_tool("llc").expect("could not find llc"));
     llc_command.arg(&bc_path);
     llc_command.status().expect("llc process failed to execute");
 
@@ -49,6 +237,40 @@ pub fn get_disassembly(bc_dir: &String, board_name: &String) -> Disassem {
     llc_output.lines().map(|s| s.to_owned()).collect()
 }
 
+/// Read the annotated disassembly previously generated by `get_disassembly`
+pub fn read_disassembly(bc_dir: &String, board_name: &String) -> Disassem {
+    let bc_path = find_bc_file(bc_dir, board_name);
+    let llc_output = read_llc_output(bc_path);
+    llc_output.lines().map(|s| s.to_owned()).collect()
+}
+
+/// Read the annotated assembly the build emitted next to the bitcode, with
+/// rustc's `--emit=asm`, instead of generating it with llc. It must be at
+/// least as recent as the bitcode, or the build did not emit it.
+pub fn read_build_assembly(bc_dir: &String, board_name: &String) -> Result<Disassem, String> {
+    let bc_path = PathBuf::from(find_bc_file(bc_dir, board_name));
+    let asm_path = bc_path.with_extension("s");
+    let modified = |path: &PathBuf| path.metadata().and_then(|m| m.modified()).ok();
+    match (modified(&bc_path), modified(&asm_path)) {
+        (Some(bc), Some(asm)) if asm >= bc => {}
+        (_, Some(_)) => {
+            return Err(format!(
+                "{} is older than the bitcode, configure the build to emit assembly",
+                asm_path.display()
+            ))
+        }
+        _ => {
+            return Err(format!(
+                "{} not found, configure the build to emit assembly",
+                asm_path.display()
+            ))
+        }
+    }
+    let asm = std::fs::read_to_string(&asm_path)
+        .map_err(|e| format!("failed to read {}: {}", asm_path.display(), e))?;
+    Ok(asm.lines().map(|s| s.to_owned()).collect())
+}
+
 /// Apply this transformation:
 ///     %bb_name → %"bb_name"
 fn quote_bb_name(bb_name: &String) -> String {
@@ -61,153 +283,662 @@ fn quote_bb_name(bb_name: &String) -> String {
     res
 }
 
-/// Build the regexes used to find the function and basic block location
-fn build_func_and_bb_patterns(location: &Location) -> (Regex, Regex) {
-    // matches the start of the desired function
-    let func_name = &location.func.name;
-    let func_pat = format!(r"^{}:$", regex::escape(func_name));
-    let func_re = Regex::new(&func_pat).unwrap();
+/// Where each function (or other label) of a disassembly starts, and which
+/// symbols alias others, built once per disassembly so that counting a path
+/// does not scan it again.
+#[derive(Debug, Default, Clone)]
+pub struct DisassemblyIndex {
+    /// The line of each label, by name
+    starts: HashMap<String, usize>,
+    /// The symbols defined as aliases of other symbols, e.g. functions whose
+    /// identical bodies were merged, mapped to those symbols
+    aliases: HashMap<String, String>,
+}
+
+impl DisassemblyIndex {
+    pub fn new(disassembly: &Disassem) -> Self {
+        let mut starts = HashMap::new();
+        for (i, line) in disassembly.iter().enumerate() {
+            if let Some(label) = LABEL.captures(line) {
+                starts.entry(label[1].to_owned()).or_insert(i);
+            }
+        }
+        let aliases = disassembly
+            .iter()
+            .filter_map(|line| {
+                let alias = ALIAS.captures(line)?;
+                let name = alias.get(1).or_else(|| alias.get(2))?.as_str();
+                Some((name.to_owned(), alias.get(3)?.as_str().to_owned()))
+            })
+            .collect();
+        DisassemblyIndex { starts, aliases }
+    }
+
+    /// The line starting the label `name`.
+    pub fn start(&self, name: &str) -> Option<usize> {
+        self.starts.get(name).copied()
+    }
+
+    /// The symbol whose body `name` shares, following chains of aliases.
+    pub fn resolve_alias(&self, name: &str) -> Option<&str> {
+        let mut target = self.aliases.get(name)?;
+        // bounded, in case of a cycle
+        for _ in 0..self.aliases.len() {
+            match self.aliases.get(target) {
+                Some(next) => target = next,
+                None => break,
+            }
+        }
+        Some(target)
+    }
+}
+
+/// Build the regex used to find the basic block location; functions are
+/// found by their label, see `DisassemblyIndex`
+fn build_bb_pattern(bb_name: &str) -> Regex {
+    bb_pattern(bb_name).unwrap_or_else(|| panic!("bb name format not recognized: {}", bb_name))
+}
 
-    // matches the start of the desired bb
-    let bb_name = &location.bb.name.to_string();
-    let bb_num_pat = Regex::new(r"%(bb)?(\d+)").unwrap();
-    let bb_exit_pat = Regex::new(r"%_.*\.exit").unwrap();
+/// The regex matching the start of the basic block `bb_name` in the
+/// disassembly of its function, if its name is of a recognized format.
+pub fn bb_pattern(bb_name: &str) -> Option<Regex> {
     let bb_pat = if bb_name == "%start" {
         // Start of function maps to anything
-        r"^.*(@\s*%bb\.0:).*$".to_owned()
-    } else if bb_num_pat.is_match(bb_name) {
-        let caps = bb_num_pat.captures(bb_name).unwrap();
-        let num_str = caps.get(2).unwrap().as_str();
-        let num_opt = num_str.parse::<i32>();
-        match num_opt {
-            Ok(num) => format!(r"^((.*(@\s*%bb\.{}:))|(\.LBB\d+_{}:)).*$", num, num),
-            Err(_) => panic!("cannot parse int: {}", num_str),
-        }
-    } else if bb_exit_pat.is_match(bb_name) {
-        format!(r"^.*(@ {}).*$", regex::escape(&quote_bb_name(bb_name)))
+        r"^.*([@#]\s*%bb\.0:).*$".to_owned()
+    } else if let Some(caps) = BB_NUM.captures(bb_name) {
+        match caps.get(1) {
+            // llc numbers machine blocks in their final layout, so a named IR
+            // block is found by the comment naming it after its label
+            Some(_) => return Some(named_bb_pattern(bb_name)),
+            // unnamed IR blocks are not named in the comments
+            None => {
+                let num = caps.get(2).unwrap().as_str().parse::<i32>().ok()?;
+                format!(r"^((.*([@#]\s*%bb\.{}:))|(\.LBB\d+_{}:)).*$", num, num)
+            }
+        }
+    } else if BB_EXIT.is_match(bb_name) {
+        format!(
+            r"^.*([@#] {}).*$",
+            regex::escape(&quote_bb_name(&bb_name.to_owned()))
+        )
     } else {
-        panic!("bb name format not recognized: {}", bb_name);
+        return None;
     };
-    let bb_re = Regex::new(&bb_pat).unwrap();
+    Some(Regex::new(&bb_pat).unwrap())
+}
 
-    (func_re, bb_re)
+/// The regex matching the label of a machine block that llc notes, in the
+/// comment after the label, as (part of) the IR block `bb_name`.
+fn named_bb_pattern(bb_name: &str) -> Regex {
+    Regex::new(&format!(
+        r#"^(\.LBB\w+:|[@#]\s*%bb\.\d+:)\s*[@#] %"?{}"?\s*$"#,
+        regex::escape(bb_name.trim_start_matches('%'))
+    ))
+    .unwrap()
 }
 
 fn find_outlined_function(
     instr: &str,
     disassembly: &Disassem,
+    index: &DisassemblyIndex,
     instr_re: &Regex,
-) -> (String, usize) {
-    let mut func_name = instr[4..].to_owned();
-    func_name.push(':');
-
-    let mut i = 0;
-
-    while i < disassembly.len() && !(disassembly[i] == func_name) {
-        i += 1;
-    }
-    i += 1;
+    cost_model: &CostModel,
+) -> (String, usize, u64) {
+    let func_name = &instr[4..];
+    let mut i = match index.start(func_name) {
+        Some(start) => start + 1,
+        None => disassembly.len(),
+    };
 
-    let mut res = func_name;
-    res.push('\n');
+    let mut res = format!("{}:\n", func_name);
     let mut func_len = 0;
+    let mut func_cycles = 0;
+    let mut in_literal_pool = false;
     while i < disassembly.len() && !disassembly[i].contains(".Lfunc_end") {
-        if instr_re.is_match(&disassembly[i]) {
+        if starts_literal_pool(disassembly, i) {
+            in_literal_pool = true;
+        } else if ANY_BB_OR_FUNC.is_match(&disassembly[i]) {
+            in_literal_pool = false;
+        } else if !in_literal_pool && instr_re.is_match(&disassembly[i]) {
             res.push_str(&disassembly[i]);
             res.push('\n');
             func_len += 1;
+            func_cycles += cost_model.cycles(&disassembly[i]);
         }
         i += 1;
     }
     res.push_str("OUTLINED_FUNCTION_END\n");
 
-    (res, func_len)
+    (res, func_len, func_cycles)
+}
+
+/// The condition code `code` (e.g. `eq`, `cs`) in the form used in
+/// `CONDITIONS`, with its inverse.
+fn condition(code: &str) -> Option<(&'static str, &'static str)> {
+    let code = match code {
+        "cs" => "hs",
+        "cc" => "lo",
+        other => other,
+    };
+    CONDITIONS.iter().find_map(|&(a, b)| match code {
+        _ if code == a => Some((a, b)),
+        _ if code == b => Some((b, a)),
+        _ => None,
+    })
+}
+
+/// Whether an instruction may change the condition flags. Calls are assumed
+/// to. The condition suffix of `predicated` instructions is ignored.
+fn sets_flags(line: &str, predicated: bool) -> bool {
+    let mnemonic = line.split_whitespace().next().unwrap_or("").to_lowercase();
+    let mut mnemonic = mnemonic.split('.').next().unwrap_or("");
+    if predicated && mnemonic.len() > 2 {
+        mnemonic = &mnemonic[..mnemonic.len() - 2];
+    }
+    ["cmp", "cmn", "tst", "teq", "msr", "vmrs", "bl"]
+        .iter()
+        .any(|m| mnemonic.starts_with(m))
+        || (mnemonic.ends_with('s')
+            && (!mnemonic.starts_with('b') || mnemonic == "bics")
+            && !mnemonic.starts_with('v')
+            && !["mls", "mrs"].contains(&mnemonic))
+}
+
+/// Whether the condition `code` holds, given that `fact.0` is known to hold
+/// or not as `fact.1`.
+fn holds(code: &str, fact: (&str, bool)) -> Option<bool> {
+    let (code, inverse) = condition(code)?;
+    match fact {
+        (c, holds) if c == code => Some(holds),
+        (c, holds) if c == inverse => Some(!holds),
+        _ => None,
+    }
+}
+
+/// Whether each instruction of `block` predicated by an IT block executes,
+/// where the path tells: from the direction of a conditional branch on the
+/// same flags, one the path fell through before the IT block, or one after
+/// it. `taken` is the direction of each of the block's conditional branches,
+/// if known. Instructions not predicated are `None`.
+fn predicated_execution(block: &[&str], taken: &[Option<bool>]) -> Vec<Option<bool>> {
+    let mut executed = vec![None; block.len()];
+    // a condition known to hold or not, and the flags unchanged since
+    let mut known: Option<(&str, bool)> = None;
+    let mut k = 0;
+    while k < block.len() {
+        if let Some(it) = IT_BLOCK.captures(block[k]) {
+            let first = it.get(2).unwrap().as_str();
+            let conditions: Vec<Option<&str>> = std::iter::once(Some(first))
+                .chain(it[1].chars().map(|c| match c {
+                    't' => Some(first),
+                    _ => condition(first).map(|(_, inverse)| inverse),
+                }))
+                .collect();
+            let end = (k + 1 + conditions.len()).min(block.len());
+            // a branch after the IT block on the same flags
+            let later = || -> Option<(&str, bool)> {
+                for j in k + 1..block.len() {
+                    if let Some(branch) = COND_BRANCH.captures(block[j]) {
+                        match (branch.get(1), taken[j]) {
+                            (Some(c), Some(t)) => return Some((condition(c.as_str())?.0, t)),
+                            (None, _) => continue,
+                            _ => return None,
+                        }
+                    }
+                    if sets_flags(block[j], j < end) {
+                        return None;
+                    }
+                }
+                None
+            };
+            if let Some(fact) = known.or_else(later) {
+                for (j, code) in (k + 1..end).zip(&conditions) {
+                    executed[j] = code.and_then(|c| holds(c, fact));
+                }
+            }
+            if block[k + 1..end].iter().any(|line| sets_flags(line, true)) {
+                known = None;
+            }
+            k = end;
+            continue;
+        }
+        match COND_BRANCH.captures(block[k]) {
+            Some(branch) => {
+                if let (Some(c), Some(false)) = (branch.get(1), taken[k]) {
+                    known = condition(c.as_str()).map(|(code, _)| (code, false));
+                }
+            }
+            None if sets_flags(block[k], false) => known = None,
+            None => {}
+        }
+        k += 1;
+    }
+    executed
+}
+
+/// The label of the block matching `bb_re` in the function whose first line
+/// is `i`, e.g. `.LBB3_2`, or the comment standing for it (`@ %bb.1`) if
+/// only falling through reaches the block.
+fn block_label<'d>(disassembly: &'d Disassem, i: usize, bb_re: &Regex) -> Option<&'d str> {
+    disassembly[i.min(disassembly.len())..]
+        .iter()
+        .take_while(|line| !ANY_FUNC.is_match(line))
+        .find(|line| bb_re.is_match(line))
+        .and_then(|line| line.split(':').next())
 }
 
 /// Given an index i that points to the first line of a function,
 /// find the desired basic block within it and append the instructions
-/// contained within to res. Return whether the basic block was found
-/// and the number of instructions it contains.
+/// contained within to res. Return whether the basic block was found,
+/// the number of instructions it contains and the cycles they take.
+/// `bb_patterns` match the label of the block, and the machine blocks
+/// continuing it: llc splits IR blocks (e.g. a range check before a jump
+/// table) into machine blocks named after them, which the block continues
+/// through as it falls through to them. `successor` is the
+/// label of the block the path continues with in this function, if found: a
+/// conditional branch to it is taken, and ends the block's execution, while
+/// other conditional branches fall through.
 fn find_bb_and_count(
     disassembly: &Disassem,
+    disassembly_index: &DisassemblyIndex,
     i: usize,
-    bb_re: &Regex,
+    bb_patterns: &(Regex, Regex),
+    successor: Option<&str>,
     res: &mut String,
-) -> (bool, usize) {
+    cost_model: &CostModel,
+) -> (bool, usize, u64, usize) {
     let mut current_block_instr_len = 0;
+    let mut current_block_cycles = 0;
+    let mut inline_asm_len = 0;
     let mut index = i;
+    let (bb_re, continued) = bb_patterns;
 
     // skip to the start of the basic block
     while index < disassembly.len() && !bb_re.is_match(&disassembly[index]) {
         if ANY_FUNC.is_match(&disassembly[index]) {
-            return (false, 0);
+            return (false, 0, 0, 0);
         }
         index += 1;
     }
+    if index == disassembly.len() {
+        // the last function of the disassembly does not have it either
+        return (false, 0, 0, 0);
+    }
     index += 1;
 
-    // append every machine instruction encountered
-    while index < disassembly.len() && !ANY_BB_OR_FUNC.is_match(&disassembly[index]) {
-        if INST.is_match(&disassembly[index]) {
-            res.push_str(&disassembly[index]);
-            res.push('\n');
-            current_block_instr_len += 1;
+    // the machine instructions of the block, up to the next block, the end of
+    // the function or the literal pool following the block, which is never
+    // executed, and whether each comes from inline assembly, which is a single
+    // call in the IR
+    let mut block: Vec<&str> = vec![];
+    let mut inline_asm: Vec<bool> = vec![];
+    let mut in_inline_asm = false;
+    while index < disassembly.len()
+        && (!ANY_BB_OR_FUNC.is_match(&disassembly[index])
+            || continued.is_match(&disassembly[index]))
+        && !FUNC_END.is_match(&disassembly[index])
+        && !starts_literal_pool(disassembly, index)
+    {
+        let line = &disassembly[index];
+        if let Some(marker) = INLINE_ASM_MARKER.captures(line) {
+            in_inline_asm = marker.get(1).is_none();
+        } else if in_inline_asm && ASM_LABEL_OR_DIRECTIVE.is_match(line) {
+            // labels of inline assembly (`1:`, printed as `.Ltmp0:`) are not
+            // instructions
+        } else if INST.is_match(line) {
+            block.push(line);
+            inline_asm.push(in_inline_asm);
+        }
+        index += 1;
+    }
+    let taken: Vec<Option<bool>> = block
+        .iter()
+        .map(|line| {
+            let branch = COND_BRANCH.captures(line)?;
+            successor.map(|label| label == &branch[2])
+        })
+        .collect();
+    let executed = predicated_execution(&block, &taken);
 
-            if disassembly[index].contains("bl	OUTLINED_FUNCTION") {
-                let (outlined_str, outlined_len) =
-                    find_outlined_function(&disassembly[index], disassembly, &INST);
-                res.push_str(&outlined_str);
-                current_block_instr_len += outlined_len;
+    // append every machine instruction the path executes
+    for (k, line) in block.iter().enumerate() {
+        if executed[k] == Some(false) {
+            res.push_str(line);
+            res.push_str(" @ skipped\n");
+            current_block_cycles += cost_model
+                .predicated_skipped
+                .unwrap_or_else(|| cost_model.cycles(line));
+            continue;
+        }
+        res.push_str(line);
+        if inline_asm[k] {
+            res.push_str(" @ inline asm");
+            inline_asm_len += 1;
+        }
+        res.push('\n');
+        current_block_instr_len += 1;
+        if COND_BRANCH.is_match(line) {
+            current_block_cycles += cost_model.branch_cycles(line, taken[k]);
+            if taken[k] == Some(true) {
+                break;
             }
+            continue;
+        }
+        current_block_cycles += cost_model.cycles(line);
+
+        if line.contains("bl	OUTLINED_FUNCTION") {
+            let (outlined_str, outlined_len, outlined_cycles) =
+                find_outlined_function(line, disassembly, disassembly_index, &INST, cost_model);
+            res.push_str(&outlined_str);
+            current_block_instr_len += outlined_len;
+            current_block_cycles += outlined_cycles;
         }
-        index += 1;
     }
 
-    (true, current_block_instr_len)
+    if inline_asm_len > 0 {
+        res.push_str(&format!(
+            "Inline asm: {} machine instructions\n",
+            inline_asm_len
+        ));
+    }
+
+    (
+        true,
+        current_block_instr_len,
+        current_block_cycles,
+        inline_asm_len,
+    )
 }
 
-/// Count the number of machine instructions corresponding to the current path
-pub fn count_instructions<'p, B: Backend>(
-    disassembly: &Disassem,
-    state: &State<'p, B>,
-) -> Result<(String, usize), String> {
-    let mut res = String::new();
-    let mut num_instrs = 0;
+/// The LLVM IR instructions executed by the `i`th entry of a path, as a range
+/// of indices into its basic block, where the index past the last instruction
+/// is the terminator. Each entry runs from its first instruction up to the end
+/// of its basic block, or, if it calls a function whose execution is part of
+/// the path, up to where the block's execution resumes after that call.
+pub fn entry_range(path: &[PathEntry], i: usize) -> std::ops::Range<usize> {
+    let location = &path[i].0;
+    let start = match location.instr {
+        BBInstrIndex::Instr(first) => first,
+        BBInstrIndex::Terminator => location.bb.instrs.len(),
+    };
+    // the terminator is counted as an instruction
+    let mut end = location.bb.instrs.len() + 1;
+    for PathEntry(later) in &path[i + 1..] {
+        if later.func.name == location.func.name && later.bb.name == location.bb.name {
+            if let BBInstrIndex::Instr(resumed) = later.instr {
+                if resumed > start {
+                    end = resumed;
+                }
+            }
+            break;
+        }
+    }
+    start..end.max(start)
+}
 
-    for path_entry in state.get_path().iter() {
-        let location = &path_entry.0;
+/// Number of LLVM IR instructions executed by the `i`th entry of a path.
+pub fn entry_ir_length(path: &[PathEntry], i: usize) -> usize {
+    entry_range(path, i).len()
+}
 
-        // log meta-information about the current bb
-        res.push_str(&format!(
-            "module: {} | func: {} | bb: {}\n",
-            &location.module.name, &location.func.name, &location.bb.name
-        ));
+/// Number of LLVM IR instructions executed along a path.
+pub fn path_ir_length(path: &[PathEntry]) -> usize {
+    (0..path.len()).map(|i| entry_ir_length(path, i)).sum()
+}
 
-        let (func_re, bb_re) = build_func_and_bb_patterns(location);
+/// A basic block executed by a path, with what counting its machine
+/// instructions needs to know about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathBlock {
+    pub module: String,
+    pub func: String,
+    /// Name of the block in the IR, e.g. `%bb3` or `%start`
+    pub bb: String,
+    /// Number of LLVM IR instructions the path executes in the block
+    pub ir_len: usize,
+}
 
-        let mut func_found = false;
-        let mut bb_found = false;
-        let mut current_block_instr_len = 0;
-        for (i, line) in disassembly.iter().enumerate() {
-            if func_re.is_match(line) {
-                func_found = true;
+impl PathBlock {
+    /// The blocks executed by a path found by symbolic execution, in order.
+    pub fn from_path(path: &[PathEntry]) -> Vec<PathBlock> {
+        path.iter()
+            .enumerate()
+            .map(|(i, PathEntry(location))| PathBlock {
+                module: location.module.name.clone(),
+                func: location.func.name.clone(),
+                bb: location.bb.name.to_string(),
+                ir_len: entry_ir_length(path, i),
+            })
+            .collect()
+    }
+}
 
-                (bb_found, current_block_instr_len) =
-                    find_bb_and_count(disassembly, i + 1, &bb_re, &mut res);
+/// The machine instructions counted for a path.
+#[derive(Debug, Default)]
+pub struct PathCount {
+    pub machine: usize,
+    /// Cycles of the machine instructions according to the cost model
+    pub cycles: u64,
+    /// Machine instructions estimated from the IR of blocks not found in the
+    /// disassembly, included in `machine`
+    pub estimated: usize,
+    /// Machine instructions of inline assembly, included in `machine`
+    pub inline_asm: usize,
+    /// Machine instructions counted for each block of the path, in order
+    pub per_block: Vec<usize>,
+}
 
-                break;
-            }
+/// Whether line `i` of the disassembly starts a literal pool or inline jump
+/// table. A Thumb-2 table branch (`tbb`, `tbh`) is labeled like a pool, as
+/// the anchor of its table's offsets, but the label is followed by code.
+fn starts_literal_pool(disassembly: &Disassem, i: usize) -> bool {
+    LITERAL_POOL.is_match(&disassembly[i])
+        && !matches!(disassembly.get(i + 1), Some(next) if INST.is_match(next))
+}
+
+/// Whether a line of the disassembly starts a function.
+pub fn is_function_start(line: &str) -> bool {
+    ANY_FUNC.is_match(line)
+}
+
+/// Count the number of machine instructions corresponding to the current path,
+/// and the cycles they take according to `cost_model`, writing the machine
+/// instructions of each block to `listing`, if any.
+pub fn count_instructions(
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+    path: &[PathEntry],
+    cost_model: &CostModel,
+    listing: Option<&mut dyn Write>,
+) -> Result<PathCount, String> {
+    count_path_blocks(
+        disassembly,
+        index,
+        &PathBlock::from_path(path),
+        cost_model,
+        listing,
+    )
+}
+
+/// Write the listing of a block, keeping at most `MAX_BLOCK_LINES` lines: a
+/// block listed once per iteration of a long loop, or calling a large
+/// outlined function, would otherwise drown the rest. The counts are not
+/// affected.
+fn write_block(out: &mut dyn Write, listing: &str) -> std::io::Result<()> {
+    let mut lines = listing.lines();
+    for line in lines.by_ref().take(MAX_BLOCK_LINES) {
+        writeln!(out, "{}", line)?;
+    }
+    match lines.count() {
+        0 => Ok(()),
+        omitted => writeln!(out, "... {} more lines of this block not listed", omitted),
+    }
+}
+
+/// The machine instructions counted for one block of a path.
+struct BlockTally {
+    listing: String,
+    /// Including those estimated from the IR
+    machine: usize,
+    cycles: u64,
+    estimated: usize,
+    inline_asm: usize,
+}
+
+/// The next block of the same function each block of `path` continues
+/// with, after any calls, found in one pass from the end of the path.
+fn successors(path: &[PathBlock]) -> Vec<Option<usize>> {
+    let mut successors = vec![None; path.len()];
+    // the earliest block of each function seen so far, and its successor
+    let mut next: HashMap<&str, (usize, Option<usize>)> = HashMap::new();
+    for (i, block) in path.iter().enumerate().rev() {
+        let successor = match next.get(block.func.as_str()) {
+            Some(&(later, _)) if path[later].bb != block.bb => Some(later),
+            // the successor of a later run of the same block
+            Some(&(_, after)) => after,
+            None => None,
+        };
+        successors[i] = successor;
+        next.insert(&block.func, (i, successor));
+    }
+    successors
+}
+
+/// Count the machine instructions of the `entry_index`th block of `path`,
+/// given the block of its function it continues with, and the patterns of
+/// the path's blocks by name.
+fn count_block(
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+    path: &[PathBlock],
+    entry_index: usize,
+    successor: Option<usize>,
+    patterns: &HashMap<&str, (Regex, Regex)>,
+    cost_model: &CostModel,
+) -> BlockTally {
+    let block = &path[entry_index];
+    // log meta-information about the current bb
+    let mut res = format!(
+        "module: {} | func: {} | bb: {}\n",
+        &block.module, &block.func, &block.bb
+    );
+
+    let mut func_start = index.start(&block.func);
+    if func_start.is_none() {
+        // functions without their own body are found through their alias
+        if let Some(target) = index.resolve_alias(&block.func) {
+            res.push_str(&format!("aliases: {}\n", target));
+            func_start = index.start(target);
         }
+    }
+
+    let func_found = func_start.is_some();
+    let mut bb_found = false;
+    let mut current_block_instr_len = 0;
+    let mut current_block_cycles = 0;
+    let mut current_block_inline_asm = 0;
+    if let Some(i) = func_start {
+        (
+            bb_found,
+            current_block_instr_len,
+            current_block_cycles,
+            current_block_inline_asm,
+        ) = find_bb_and_count(
+            disassembly,
+            index,
+            i + 1,
+            &patterns[block.bb.as_str()],
+            successor.and_then(|later| {
+                block_label(disassembly, i + 1, &patterns[path[later].bb.as_str()].0)
+            }),
+            &mut res,
+            cost_model,
+        );
+    }
+
+    if !func_found {
+        res.push_str("Function not found...\n");
+    } else if !bb_found {
+        res.push_str("Basic block not found...\n");
+    } else if current_block_instr_len == 0 {
+        res.push_str("Basic block is empty...\n");
+    }
+    let mut estimated = 0;
+    if let (false, Some(ratio)) = (func_found && bb_found, cost_model.ir_ratio) {
+        estimated = (block.ir_len as f64 * ratio).ceil() as usize;
+        res.push_str(&format!(
+            "Estimated {} machine instructions from the IR...\n",
+            estimated
+        ));
+        current_block_cycles += estimated as u64 * cost_model.default;
+        current_block_instr_len += estimated;
+    }
+    BlockTally {
+        listing: res,
+        machine: current_block_instr_len,
+        cycles: current_block_cycles,
+        estimated,
+        inline_asm: current_block_inline_asm,
+    }
+}
 
-        num_instrs += current_block_instr_len;
-        if !func_found {
-            res.push_str("Function not found...\n");
-        } else if !bb_found {
-            res.push_str("Basic block not found...\n");
-        } else if current_block_instr_len == 0 {
-            res.push_str("Basic block is empty...\n");
+/// Count the machine instructions of the blocks a path executes, like
+/// `count_instructions`.
+pub fn count_path_blocks(
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+    path: &[PathBlock],
+    cost_model: &CostModel,
+    mut listing: Option<&mut dyn Write>,
+) -> Result<PathCount, String> {
+    let mut num_instrs = 0;
+    let mut num_cycles = 0;
+    let mut num_estimated = 0;
+    let mut num_inline_asm = 0;
+    let mut per_block = Vec::with_capacity(path.len());
+    let successors = successors(path);
+    // compiled once for every block of the path with the same name
+    let mut patterns = HashMap::new();
+    for block in path {
+        patterns
+            .entry(block.bb.as_str())
+            .or_insert_with(|| (build_bb_pattern(&block.bb), named_bb_pattern(&block.bb)));
+    }
+    // blocks are counted independently, a chunk at a time, and their
+    // listings written in order
+    for chunk in (0..path.len()).step_by(BLOCKS_PER_CHUNK) {
+        let blocks: Vec<BlockTally> = (chunk..path.len().min(chunk + BLOCKS_PER_CHUNK))
+            .into_par_iter()
+            .map(|entry_index| {
+                count_block(
+                    disassembly,
+                    index,
+                    path,
+                    entry_index,
+                    successors[entry_index],
+                    &patterns,
+                    cost_model,
+                )
+            })
+            .collect();
+        for block in blocks {
+            if let Some(out) = listing.as_mut() {
+                write_block(*out, &block.listing)
+                    .map_err(|e| format!("failed to write the path listing: {}", e))?;
+            }
+            num_instrs += block.machine;
+            num_cycles += block.cycles;
+            num_estimated += block.estimated;
+            num_inline_asm += block.inline_asm;
+            per_block.push(block.machine);
         }
     }
 
-    Ok((res, num_instrs))
+    Ok(PathCount {
+        machine: num_instrs,
+        cycles: num_cycles,
+        estimated: num_estimated,
+        inline_asm: num_inline_asm,
+        per_block,
+    })
 }
@@ -0,0 +1,191 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::config_file::InterruptSourceConfig;
+use crate::syscall_abi::SyscallAbi;
+use crate::{matches_work_type, KernelWorkType, PathLength};
+
+/// Client callbacks dispatched by chip-level handlers, by a module name prefix
+/// of the handler (e.g. `sam4l::usart`).
+const CLIENT_KINDS: &[(&str, KernelWorkType)] = &[
+    ("uart", KernelWorkType::Uart),
+    ("usart", KernelWorkType::Uart),
+    ("uarte", KernelWorkType::Uart),
+    ("alarm", KernelWorkType::Alarms),
+    ("ast", KernelWorkType::Alarms),
+    ("rtc", KernelWorkType::Alarms),
+    ("timer", KernelWorkType::Alarms),
+    ("i2c", KernelWorkType::I2c),
+    ("twi", KernelWorkType::I2c),
+    ("spi", KernelWorkType::Spi),
+    ("radio", KernelWorkType::Radio),
+    ("ble", KernelWorkType::Radio),
+];
+
+/// The worst case of one interrupt source: its handler followed by the worst
+/// of the client callbacks it dispatches to.
+#[derive(Serialize, Debug)]
+pub struct InterruptSource {
+    pub name: String,
+    /// Demangled name of the worst handler
+    pub handler: Option<String>,
+    /// Demangled name of the worst client callback, if any was analyzed
+    pub client: Option<String>,
+    /// Sum of the worst handler and client, if all of them were analyzed
+    /// successfully. Each measure is the sum of the worst of each
+    pub total: Option<PathLength>,
+}
+
+/// The worst result of the functions in `results` satisfying `selected`, and
+/// the function with the most IR instructions. `None` if no function is
+/// selected, and an error if the analysis of any of them failed. Each measure
+/// is the worst of any function, so a single path may not reach all of them.
+fn worst(
+    results: &HashMap<String, Result<PathLength, String>>,
+    selected: impl Fn(&str) -> bool,
+) -> (Option<&str>, Option<Result<PathLength, ()>>) {
+    let mut name = None;
+    let mut worst: Option<Result<PathLength, ()>> = None;
+    for (f, result) in results.iter().filter(|(f, _)| selected(f)) {
+        worst = match (worst, result) {
+            (Some(Err(())), _) | (_, Err(_)) => Some(Err(())),
+            (None, Ok(length)) => {
+                name = Some(f.as_str());
+                Some(Ok(*length))
+            }
+            (Some(Ok(w)), Ok(length)) => {
+                if length.ir > w.ir {
+                    name = Some(f.as_str());
+                }
+                Some(Ok(PathLength {
+                    ir: w.ir.max(length.ir),
+                    machine: w.machine.max(length.machine),
+                    cycles: w.cycles.max(length.cycles),
+                }))
+            }
+        };
+    }
+    (name, worst)
+}
+
+fn demangled_matches(re: &Regex, name: &str) -> bool {
+    re.is_match(&rustc_demangle::demangle(name).to_string())
+}
+
+/// The client callbacks dispatched by a chip-level handler, found from the
+/// module its handler is defined in.
+fn client_kind(handler: &str) -> Option<KernelWorkType> {
+    let demangled = format!("{:#}", rustc_demangle::demangle(handler)).to_lowercase();
+    demangled.split("::").find_map(|segment| {
+        let segment = segment.trim_start_matches(|c: char| !c.is_alphanumeric());
+        CLIENT_KINDS
+            .iter()
+            .find(|(prefix, _)| segment.starts_with(prefix))
+            .map(|(_, kind)| *kind)
+    })
+}
+
+fn source(
+    name: String,
+    results: &HashMap<String, Result<PathLength, String>>,
+    is_handler: impl Fn(&str) -> bool,
+    is_client: impl Fn(&str) -> bool,
+) -> InterruptSource {
+    let (handler, handler_length) = worst(results, &is_handler);
+    let (client, client_length) = worst(results, |f| !is_handler(f) && is_client(f));
+    let total = match (handler_length, client_length) {
+        (Some(Ok(h)), None) => Some(h),
+        (Some(Ok(h)), Some(Ok(c))) => Some(PathLength {
+            ir: h.ir + c.ir,
+            machine: h.machine + c.machine,
+            cycles: h.cycles + c.cycles,
+        }),
+        _ => None,
+    };
+    let demangle = |f: &str| format!("{:#}", rustc_demangle::demangle(f));
+    InterruptSource {
+        name,
+        handler: handler.map(demangle),
+        client: client.map(demangle),
+        total,
+    }
+}
+
+/// Combine the results of chip-level interrupt handlers with those of the
+/// client callbacks they dispatch to, into one bound per interrupt source.
+/// Sources are taken from `configs` if any, and otherwise each analyzed
+/// handler is a source, combined with the analyzed callbacks of the kind its
+/// module name suggests (e.g. UART callbacks for `usart` handlers).
+pub fn aggregate(
+    configs: &[InterruptSourceConfig],
+    results: &HashMap<String, Result<PathLength, String>>,
+    abi: SyscallAbi,
+) -> Result<Vec<InterruptSource>, String> {
+    let mut sources = vec![];
+    if configs.is_empty() {
+        let mut handlers: Vec<&String> = results
+            .keys()
+            .filter(|f| matches_work_type(f, &KernelWorkType::Interrupts, abi))
+            .collect();
+        handlers.sort();
+        for handler in handlers {
+            let kind = client_kind(handler);
+            sources.push(source(
+                format!("{:#}", rustc_demangle::demangle(handler)),
+                results,
+                |f| f == handler,
+                |f| match kind {
+                    Some(kind) => {
+                        matches_work_type(f, &kind, abi)
+                            && !matches_work_type(f, &KernelWorkType::Interrupts, abi)
+                    }
+                    None => false,
+                },
+            ));
+        }
+    }
+    for config in configs {
+        let handler = Regex::new(&config.handler)
+            .map_err(|e| format!("invalid handler pattern for {}: {}", config.name, e))?;
+        let clients = config
+            .clients
+            .iter()
+            .map(|c| Regex::new(c))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid client pattern for {}: {}", config.name, e))?;
+        sources.push(source(
+            config.name.clone(),
+            results,
+            |f| demangled_matches(&handler, f),
+            |f| clients.iter().any(|c| demangled_matches(c, f)),
+        ));
+    }
+    Ok(sources)
+}
+
+/// Render the bound of each interrupt source as text.
+pub fn render(sources: &[InterruptSource]) -> String {
+    let mut out = String::new();
+    for source in sources {
+        out += &format!("{}\n", source.name);
+        out += &format!(
+            "  handler: {}\n",
+            source.handler.as_deref().unwrap_or("not analyzed")
+        );
+        out += &format!(
+            "  client: {}\n",
+            source.client.as_deref().unwrap_or("none analyzed")
+        );
+        match &source.total {
+            Some(total) => {
+                out += &format!(
+                    "  total: {} IR instructions, {} machine instructions, {} cycles\n",
+                    total.ir, total.machine, total.cycles
+                )
+            }
+            None => out += "  total: unknown, the analysis of the handler or a client failed\n",
+        }
+    }
+    out
+}
@@ -0,0 +1,379 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::instruction_counter::{read_disassembly, Disassem, DisassemblyIndex};
+use crate::results::FunctionResult;
+use crate::{
+    analyze_project_function, function_results_stem, load_project, AnalysisSettings, Outcome,
+    PathLength,
+};
+
+/// Marks the line of a child's output holding the result of its analysis.
+const RESULT_PREFIX: &str = "wcet-rs result: ";
+
+/// Everything a child process needs to analyze a single function.
+#[derive(Serialize, Deserialize)]
+pub struct Job {
+    pub bc_dir: String,
+    /// The bitcode files of the project
+    pub bitcode: Vec<PathBuf>,
+    pub bitcode_name: String,
+    pub board_path_str: String,
+    pub func_name: String,
+    pub settings: AnalysisSettings,
+    /// Directory of the run's results
+    pub board_results: String,
+    /// Trace the solver queries of an analysis failing on a timeout
+    pub dump_timeouts: bool,
+}
+
+/// What allocators print when an allocation fails: Rust's default handler,
+/// C++'s `std::bad_alloc`, and C libraries such as boolector.
+const ALLOCATION_FAILURES: &[&str] = &["memory allocation of", "bad_alloc", "out of memory"];
+
+/// Limit the address space of the calling process, so allocations beyond
+/// the limit fail and abort it instead of exhausting the machine's memory.
+#[cfg(unix)]
+fn limit_memory(bytes: u64) -> std::io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: bytes as libc::rlim_t,
+        rlim_max: bytes as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Limit the memory of the child process `command` starts to `limit_mb`.
+#[cfg(unix)]
+fn set_memory_limit(command: &mut Command, limit_mb: u64) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+    let bytes = limit_mb * 1024 * 1024;
+    // Only async-signal-safe calls are made between fork and exec
+    unsafe {
+        command.pre_exec(move || limit_memory(bytes));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_memory_limit(_command: &mut Command, limit_mb: u64) -> Result<(), String> {
+    Err(format!(
+        "a memory limit of {} MB was requested, but limits are only supported on unix hosts",
+        limit_mb
+    ))
+}
+
+/// Why a child process which reported no result failed. Failed allocations
+/// abort the process, or crash it if the failure goes unchecked, after
+/// printing one of `ALLOCATION_FAILURES`; any other signal is a crash.
+#[cfg(unix)]
+fn failure(
+    status: std::process::ExitStatus,
+    memory_limit_mb: Option<u64>,
+    allocation_failed: bool,
+) -> String {
+    use std::os::unix::process::ExitStatusExt;
+    match (status.signal(), memory_limit_mb) {
+        (Some(libc::SIGABRT | libc::SIGKILL | libc::SIGSEGV), Some(limit)) if allocation_failed => {
+            format!("memory limit of {} MB exceeded", limit)
+        }
+        (Some(signal), _) => format!("analysis process crashed with signal {}", signal),
+        (None, _) => format!("analysis process exited with {}", status),
+    }
+}
+
+#[cfg(not(unix))]
+fn failure(
+    status: std::process::ExitStatus,
+    _memory_limit_mb: Option<u64>,
+    _allocation_failed: bool,
+) -> String {
+    format!("analysis process exited with {}", status)
+}
+
+/// Whether an analysis failed because a solver query timed out, or it
+/// exceeded its time budget.
+fn is_timeout(result: &Result<PathLength, String>) -> bool {
+    match result {
+        Err(e) => {
+            let e = e.to_lowercase();
+            e.contains("timeout") || e.contains("timed out") || e.contains("time budget")
+        }
+        Ok(_) => false,
+    }
+}
+
+/// Where boolector traces the solver calls of a job.
+fn trace_path(job: &Job) -> String {
+    function_results_stem(&job.board_results, &job.func_name) + ".btortrace"
+}
+
+/// Largest solver trace kept, in bytes. Traces grow with every solver call,
+/// so the analysis tracing them is stopped beyond this.
+const MAX_TRACE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// How often the trace of a child process is checked while it runs.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Analyzes functions in child processes of this tool, so that each can be
+/// stopped on its own: their memory is limited to `memory_limit_mb`, so that
+/// only the function exceeding it fails, rather than the whole batch. Each
+/// function of a memory limited run gets a child of its own, as the memory a
+/// child keeps from earlier functions would count towards the next one's
+/// limit. Otherwise the child is kept for the next function, so that it reads
+/// and indexes the disassembly once, and replaced once it fails.
+pub struct Worker {
+    memory_limit_mb: Option<u64>,
+    child: Option<Child>,
+}
+
+impl Worker {
+    pub fn new(memory_limit_mb: Option<u64>) -> Self {
+        Worker {
+            memory_limit_mb,
+            child: None,
+        }
+    }
+
+    /// Analyze the function of `job`. Tracing every analysis would write the
+    /// solver calls of all of them, so with `dump_timeouts` an analysis
+    /// failing on a timeout is run again in a child of its own with
+    /// boolector tracing its calls, and the trace is kept if it times out
+    /// again before reaching `MAX_TRACE_BYTES`.
+    pub fn analyze(&mut self, job: &Job) -> FunctionResult {
+        let mut result = match self.child.take() {
+            Some(child) => self.run(child, job, None),
+            None => match Child::spawn(self.memory_limit_mb, None) {
+                Ok(child) => self.run(child, job, None),
+                Err(e) => failed(job, e),
+            },
+        };
+        if !job.dump_timeouts || !is_timeout(&result.outcome()) {
+            return result;
+        }
+        let trace = trace_path(job);
+        let traced = match Child::spawn(self.memory_limit_mb, Some(&trace)) {
+            Ok(child) => child.analyze(job, self.memory_limit_mb, Some(&trace)).0,
+            Err(e) => failed(job, e),
+        };
+        match traced.outcome() {
+            outcome if is_timeout(&outcome) => result.stats.solver_trace = Some(trace),
+            outcome => {
+                let _ = std::fs::remove_file(&trace);
+                println!(
+                    "Not keeping the solver trace of {}: {}",
+                    rustc_demangle::demangle(&job.func_name),
+                    match outcome {
+                        Ok(_) => "the traced analysis completed".to_owned(),
+                        Err(e) => e,
+                    }
+                );
+            }
+        }
+        result
+    }
+
+    /// Analyze `job` in `child`, keeping the child for the next job if it
+    /// reported a result and memory is not limited.
+    fn run(&mut self, child: Child, job: &Job, trace: Option<&str>) -> FunctionResult {
+        let (result, child) = child.analyze(job, self.memory_limit_mb, trace);
+        if self.memory_limit_mb.is_none() {
+            self.child = child;
+        }
+        result
+    }
+}
+
+/// The result of a job which did not run to completion.
+fn failed(job: &Job, error: String) -> FunctionResult {
+    let mut result = FunctionResult::new(&job.func_name, &job.board_path_str, &job.settings);
+    result.error = Some(error);
+    result
+}
+
+/// A child process analyzing the jobs written to its stdin, one per line.
+struct Child {
+    process: std::process::Child,
+    jobs: Option<ChildStdin>,
+    /// The lines of its output, read on another thread so that the child
+    /// never blocks on a full pipe
+    output: Receiver<String>,
+    /// Whether an allocation failed since the current job was sent
+    allocation_failed: Arc<AtomicBool>,
+    /// Passes the errors on as they come, until the child exits
+    errors: Option<thread::JoinHandle<()>>,
+}
+
+impl Child {
+    /// Start a child, with boolector tracing its calls to `trace`, if given.
+    fn spawn(memory_limit_mb: Option<u64>, trace: Option<&str>) -> Result<Self, String> {
+        let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+        let mut command = Command::new(exe);
+        command
+            .arg("--analysis-worker")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // haybale does not expose its queries, but boolector can trace every call
+        // made to it, which replays the queries leading up to the timeout
+        if let Some(trace) = trace {
+            command.env("BTORAPITRACE", trace);
+        }
+        if let Some(limit) = memory_limit_mb {
+            set_memory_limit(&mut command, limit)?;
+        }
+        let mut process = command
+            .spawn()
+            .map_err(|e| format!("could not start analysis process: {}", e))?;
+
+        let stdout = process.stdout.take().unwrap();
+        let (lines, output) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if lines.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        // watching for failed allocations
+        let stderr = process.stderr.take().unwrap();
+        let allocation_failed = Arc::new(AtomicBool::new(false));
+        let failures = allocation_failed.clone();
+        let errors = thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                if ALLOCATION_FAILURES.iter().any(|m| line.contains(m)) {
+                    failures.store(true, Ordering::SeqCst);
+                }
+                eprintln!("{}", line);
+            }
+        });
+        Ok(Child {
+            jobs: process.stdin.take(),
+            process,
+            output,
+            allocation_failed,
+            errors: Some(errors),
+        })
+    }
+
+    /// Analyze `job`, stopping once the trace, if any, exceeds
+    /// `MAX_TRACE_BYTES`. The child is given back if it is still running.
+    fn analyze(
+        mut self,
+        job: &Job,
+        memory_limit_mb: Option<u64>,
+        trace: Option<&str>,
+    ) -> (FunctionResult, Option<Child>) {
+        self.allocation_failed.store(false, Ordering::SeqCst);
+        let sent = self.jobs.as_mut().map(|jobs| {
+            writeln!(jobs, "{}", serde_json::to_string(job).unwrap()).and_then(|()| jobs.flush())
+        });
+        if let Some(Ok(())) = sent {
+            loop {
+                match self.output.recv_timeout(POLL_INTERVAL) {
+                    Ok(line) => match line.strip_prefix(RESULT_PREFIX) {
+                        Some(json) => match serde_json::from_str(json) {
+                            Ok(result) => return (result, Some(self)),
+                            Err(e) => return (failed(job, format!("invalid result: {}", e)), None),
+                        },
+                        None => println!("{}", line),
+                    },
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // the child exited
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                let trace_bytes = trace
+                    .and_then(|trace| std::fs::metadata(trace).ok())
+                    .map(|metadata| metadata.len());
+                if trace_bytes
+                    .filter(|&bytes| bytes > MAX_TRACE_BYTES)
+                    .is_some()
+                {
+                    let _ = self.process.kill();
+                    return (
+                        failed(
+                            job,
+                            format!(
+                                "solver trace exceeded {} MB",
+                                MAX_TRACE_BYTES / (1024 * 1024)
+                            ),
+                        ),
+                        None,
+                    );
+                }
+            }
+        }
+        let error = match self.process.wait() {
+            Ok(status) => {
+                // all of its errors are read once it exited
+                if let Some(errors) = self.errors.take() {
+                    let _ = errors.join();
+                }
+                let allocation_failed = self.allocation_failed.load(Ordering::SeqCst);
+                failure(status, memory_limit_mb, allocation_failed)
+            }
+            Err(e) => e.to_string(),
+        };
+        (failed(job, error), None)
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        // closing its stdin ends a child waiting for jobs
+        self.jobs = None;
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Entry point of a child process: analyze the functions of the serialized
+/// jobs read from stdin, one per line, and report each result to the parent
+/// on stdout. The disassembly is read and indexed once, and again only if a
+/// job is of another bitcode file.
+pub fn run_worker() -> Result<Outcome, String> {
+    let mut disassembly: Option<(String, String, Disassem, DisassemblyIndex)> = None;
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("failed to read analysis job: {}", e))?;
+        let job: Job =
+            serde_json::from_str(&line).map_err(|e| format!("invalid analysis job: {}", e))?;
+        let current = match &disassembly {
+            Some((bc_dir, bitcode_name, _, _)) => {
+                *bc_dir == job.bc_dir && *bitcode_name == job.bitcode_name
+            }
+            None => false,
+        };
+        if !current {
+            let lines = read_disassembly(&job.bc_dir, &job.bitcode_name);
+            let index = DisassemblyIndex::new(&lines);
+            disassembly = Some((job.bc_dir.clone(), job.bitcode_name.clone(), lines, index));
+        }
+        let (_, _, lines, index) = disassembly.as_ref().unwrap();
+        let result = match load_project(&job.bitcode) {
+            Ok(project) => analyze_project_function(
+                &project,
+                &job.board_path_str,
+                &job.func_name,
+                &job.settings,
+                lines,
+                index,
+            ),
+            Err(e) => failed(&job, e),
+        };
+        println!(
+            "{}{}",
+            RESULT_PREFIX,
+            serde_json::to_string(&result).unwrap()
+        );
+    }
+    Ok(Outcome::AllSucceeded)
+}
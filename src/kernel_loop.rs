@@ -0,0 +1,171 @@
+use haybale::Project;
+use once_cell::sync::Lazy;
+use regex::RegexSet;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::interrupt_sources::InterruptSource;
+use crate::syscall_abi::SyscallAbi;
+use crate::{matches_work_type, KernelWorkType, PathLength};
+
+// matches the scheduler's decisions in each iteration of the kernel loop, on
+// `{:#}` demangled names. `kernel_loop_operation` itself is not included, as
+// with the scheduler and chip statically dispatched it already contains the
+// work it dispatches to.
+const SCHEDULER_PATTERNS: &[&str] = &[
+    r" as kernel::scheduler::Scheduler<.*>>::next$",
+    r" as kernel::scheduler::Scheduler<.*>>::result$",
+];
+
+static SCHEDULER: Lazy<RegexSet> = Lazy::new(|| RegexSet::new(SCHEDULER_PATTERNS).unwrap());
+
+/// The scheduler functions of the project, analyzed to bound the kernel loop.
+pub fn scheduler_functions(project: &Project) -> Vec<&str> {
+    let mut functions: Vec<&str> = project
+        .all_functions()
+        .map(|(f, _m)| f.name.as_str())
+        .filter(|name| SCHEDULER.is_match(&format!("{:#}", rustc_demangle::demangle(name))))
+        .collect();
+    functions.sort_unstable();
+    functions.dedup();
+    functions
+}
+
+/// A bound on one iteration of the kernel loop, which services one pending
+/// piece of work.
+#[derive(Serialize, Debug)]
+pub struct KernelLoopBound {
+    /// Worst case of each scheduler decision, summed
+    pub scheduler: Option<PathLength>,
+    /// Worst case of each kind of work an iteration may service
+    pub work: Vec<(String, Option<PathLength>)>,
+    /// Scheduler overhead plus the worst kind of work, if all are known
+    pub total: Option<PathLength>,
+}
+
+fn add(a: PathLength, b: PathLength) -> PathLength {
+    PathLength {
+        ir: a.ir + b.ir,
+        machine: a.machine + b.machine,
+        cycles: a.cycles + b.cycles,
+    }
+}
+
+/// The worst of two bounds, each measure separately; unknown if either is.
+fn max(a: Option<PathLength>, b: Option<PathLength>) -> Option<PathLength> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(PathLength {
+            ir: a.ir.max(b.ir),
+            machine: a.machine.max(b.machine),
+            cycles: a.cycles.max(b.cycles),
+        }),
+        _ => None,
+    }
+}
+
+/// The worst of the results satisfying `selected`: `Some(None)` if any
+/// failed, and `None` if there are none.
+fn worst(
+    results: &HashMap<String, Result<PathLength, String>>,
+    selected: impl Fn(&str) -> bool,
+) -> Option<Option<PathLength>> {
+    results
+        .iter()
+        .filter(|(name, _)| selected(name))
+        .map(|(_, result)| result.as_ref().ok().copied())
+        .reduce(max)
+}
+
+/// Combine the analyzed scheduler overhead with the worst of every kind of
+/// work the kernel loop services: an interrupt source (handler and client),
+/// a deferred call, or a system call (dispatch and handler). Kinds of work
+/// that were not analyzed are left out, so all should be analyzed.
+pub fn bound(
+    results: &HashMap<String, Result<PathLength, String>>,
+    interrupt_sources: &[InterruptSource],
+    abi: SyscallAbi,
+) -> KernelLoopBound {
+    // every decision must have been analyzed
+    let mut scheduler = Some(PathLength {
+        ir: 0,
+        machine: 0,
+        cycles: 0,
+    });
+    for i in 0..SCHEDULER.len() {
+        let decision = worst(results, |name| {
+            SCHEDULER
+                .matches(&format!("{:#}", rustc_demangle::demangle(name)))
+                .matched(i)
+        });
+        scheduler = match (scheduler, decision) {
+            (Some(sum), Some(Some(length))) => Some(add(sum, length)),
+            _ => None,
+        };
+    }
+
+    let mut work = vec![];
+    if !interrupt_sources.is_empty() {
+        let interrupts = interrupt_sources
+            .iter()
+            .map(|s| s.total)
+            .reduce(max)
+            .flatten();
+        work.push(("interrupt source".to_owned(), interrupts));
+    }
+    if let Some(deferred) = worst(results, |name| {
+        matches_work_type(name, &KernelWorkType::DeferredCalls, abi)
+    }) {
+        work.push(("deferred call".to_owned(), deferred));
+    }
+    let dispatch = worst(results, |name| {
+        matches_work_type(name, &KernelWorkType::SyscallDispatch, abi)
+    });
+    let handler = worst(results, |name| {
+        [
+            KernelWorkType::Commands,
+            KernelWorkType::Subscribes,
+            KernelWorkType::Allows,
+        ]
+        .iter()
+        .any(|kind| matches_work_type(name, kind, abi))
+    });
+    match (dispatch, handler) {
+        (None, None) => {}
+        (Some(Some(d)), Some(Some(h))) => work.push(("system call".to_owned(), Some(add(d, h)))),
+        (Some(Some(length)), None) | (None, Some(Some(length))) => {
+            work.push(("system call".to_owned(), Some(length)))
+        }
+        _ => work.push(("system call".to_owned(), None)),
+    }
+
+    let worst_work = work.iter().map(|(_, length)| *length).reduce(max).flatten();
+    let total = match (scheduler, worst_work) {
+        (Some(s), Some(w)) => Some(add(s, w)),
+        _ => None,
+    };
+    KernelLoopBound {
+        scheduler,
+        work,
+        total,
+    }
+}
+
+fn describe(length: &Option<PathLength>) -> String {
+    match length {
+        Some(l) => format!(
+            "{} IR instructions, {} machine instructions, {} cycles",
+            l.ir, l.machine, l.cycles
+        ),
+        None => "unknown".to_owned(),
+    }
+}
+
+/// Render the kernel loop bound as text.
+pub fn render(bound: &KernelLoopBound) -> String {
+    let mut out = format!("Scheduler: {}\n", describe(&bound.scheduler));
+    for (kind, length) in &bound.work {
+        out += &format!("Worst {}: {}\n", kind, describe(length));
+    }
+    out += &format!("Kernel loop iteration: {}\n", describe(&bound.total));
+    out
+}
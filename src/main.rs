@@ -2,11 +2,15 @@ use clap::arg_enum;
 use glob::glob;
 use haybale::backend::*;
 use haybale::*;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use simple_logger::SimpleLogger;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::process::{Command, Stdio};
+use std::path::PathBuf;
+use std::process::Command;
 use std::result::Result;
 use std::string::String;
 use std::sync::{Arc, Mutex};
@@ -19,140 +23,876 @@ extern crate log;
 
 mod instruction_counter;
 use instruction_counter::*;
+mod alarms;
+mod allocations;
+mod allow;
+mod asm_stubs;
+mod assumptions;
+mod budgets;
+mod build_features;
+mod calibration;
+mod call_graph;
+mod cargo_build;
+mod checkpoint;
+mod compare;
+mod config_file;
+mod constraints;
+#[cfg(test)]
+mod counter_fixtures;
+use config_file::ExclusionProfile;
+mod daemon;
+mod dedup;
+mod difficulty;
+mod disassembly_stats;
+mod dma;
+mod drivers;
+mod engine;
+mod freshness;
+mod git_history;
+mod hooks;
+mod host;
+mod interrupt_sources;
+mod isolate;
+mod kernel_loop;
+mod manifest;
+mod metrics;
+mod panics;
+mod parametric;
+mod picker;
+mod process_state;
+mod profile;
+mod query;
+use panics::PanicPaths;
+mod report;
+mod resources;
+mod results;
+mod results_db;
+mod return_values;
+mod run_dir;
+use results::{FunctionResult, ReportDetail, ReportFormat};
+mod schedulability;
+mod scheduler;
+mod sinks;
+mod solver_time;
+mod subsystems;
+mod suggestions;
+mod syscall_abi;
+use syscall_abi::SyscallAbi;
+mod trend;
+mod upcalls;
+mod upload;
+mod virtualizers;
+mod watch;
 
 arg_enum! {
-    #[derive(Debug)]
+    /// How the board under analysis is built
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Builder {
+        Make,
+        Cargo,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy)]
     enum KernelWorkType {
         DeferredCalls,
         Interrupts,
         Commands,
         Subscribes,
         Allows,
+        SyscallDispatch,
         Memops,
+        Alarms,
+        Uart,
+        I2c,
+        Spi,
+        Radio,
+        PanicPrint,
+        Startup,
+        Upcalls,
         All,
     }
 }
 
+/// Name of the trait capsules implement to handle system calls, in the
+/// kernel's syscall ABI.
+fn driver_trait(abi: SyscallAbi) -> &'static str {
+    match abi {
+        SyscallAbi::SyscallDriver => "SyscallDriver",
+        SyscallAbi::Legacy | SyscallAbi::Unknown => "Driver",
+    }
+}
+
+/// Whether a function belongs to the given (non-`All`) kind of kernel work,
+/// by matching on its mangled name. Syscall handlers are matched by the
+/// driver trait of the kernel's syscall ABI `abi`.
+fn matches_work_type(name: &str, kind: &KernelWorkType, abi: SyscallAbi) -> bool {
+    // TODO: Filtering on demangled function names should allow for more precise matches with fewer
+    // false positives
+    //let demangled = rustc_demangle::demangle(func_name);
+    match kind {
+        // TODO: Allow handle_xx_interrupt pattern as well
+        KernelWorkType::Interrupts => name.contains("handle_interrupt"),
+        KernelWorkType::DeferredCalls => name.contains("handle_deferred_call"),
+        KernelWorkType::Commands => {
+            name.contains("command")
+                && name.contains(driver_trait(abi))
+                && !name.contains("closure") //manual exclusion
+                && !name.contains("command_complete") //manual exclusion
+        }
+        KernelWorkType::Allows => {
+            name.contains("allow") && name.contains(driver_trait(abi)) && !name.contains("closure")
+        }
+        // Tock 2.x kernels subscribe upcalls without calling the capsule
+        KernelWorkType::Subscribes => {
+            abi != SyscallAbi::SyscallDriver
+                && name.contains("subscribe")
+                && name.contains(driver_trait(abi))
+                && !name.contains("closure")
+        }
+        // The kernel's dispatch of a syscall to the capsule handling it
+        KernelWorkType::SyscallDispatch => name.contains("handle_syscall"),
+        // Alarm callbacks, including the virtual alarm mux's dispatch to its clients
+        KernelWorkType::Alarms => {
+            (name.contains("AlarmClient") && name.contains("5alarm"))
+                || (name.contains("time..Client") && name.contains("5fired"))
+        }
+        // Completion callbacks of bus drivers, which run in interrupt context
+        KernelWorkType::Uart => {
+            name.contains("uart")
+                && (name.contains("transmitted_buffer") || name.contains("received_buffer"))
+                && !name.contains("closure")
+        }
+        KernelWorkType::I2c => {
+            name.contains("i2c") && name.contains("command_complete") && !name.contains("closure")
+        }
+        KernelWorkType::Spi => {
+            name.contains("spi") && name.contains("read_write_done") && !name.contains("closure")
+        }
+        // 802.15.4 and BLE radio interrupts, and the radio HIL's client callbacks
+        KernelWorkType::Radio => {
+            (name.contains("radio") || name.contains("ble_advertising"))
+                && [
+                    "handle_interrupt",
+                    "send_done",
+                    "7receive",
+                    "config_done",
+                    "receive_event",
+                    "transmit_event",
+                ]
+                .iter()
+                .any(|event| name.contains(event))
+                && !name.contains("closure")
+        }
+        // The kernel's printing of the panic and of the state of the processes
+        // and the CPU, which runs only after a panic (or a fault)
+        KernelWorkType::PanicPrint => {
+            (name.contains("5debug")
+                && [
+                    "11panic_print",
+                    "12panic_banner",
+                    "15panic_cpu_state",
+                    "18panic_process_info",
+                ]
+                .iter()
+                .any(|part| name.contains(part)))
+                || (name.contains("process_printer") && name.contains("14print_overview"))
+        }
+        // The board's initialization, from the reset handler (Tock 1.x) or
+        // the board's `main`, both unmangled, up to entering the kernel loop
+        KernelWorkType::Startup => name == "reset_handler" || name == "main",
+        // The steps of delivering an upcall to a process, from the capsule
+        // scheduling it to switching to the process
+        KernelWorkType::Upcalls => upcalls::step_of(name).is_some(),
+        KernelWorkType::Memops => panic!("Memop support not yet implemented"),
+        KernelWorkType::All => panic!("All is a union of the other kinds of kernel work"),
+    }
+}
+
+// matches the kernel loop, which startup paths end at, on `{:#}` demangled names
+static KERNEL_LOOP: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^kernel::(kernel|sched)::Kernel::kernel_loop$").unwrap());
+
+/// The kind of kernel work a function was selected as, used to label results.
+fn work_type_of(name: &str, abi: SyscallAbi) -> Option<KernelWorkType> {
+    vec![
+        KernelWorkType::Commands,
+        KernelWorkType::Subscribes,
+        KernelWorkType::Allows,
+        KernelWorkType::SyscallDispatch,
+        // before interrupts, to label radio interrupt handlers as radio events
+        KernelWorkType::Radio,
+        KernelWorkType::Interrupts,
+        KernelWorkType::DeferredCalls,
+        KernelWorkType::Alarms,
+        KernelWorkType::Uart,
+        KernelWorkType::I2c,
+        KernelWorkType::Spi,
+        KernelWorkType::PanicPrint,
+        KernelWorkType::Startup,
+        KernelWorkType::Upcalls,
+    ]
+    .into_iter()
+    .find(|kind| matches_work_type(name, kind, abi))
+}
+
+/// Whether a function is a capsule's command, subscribe or allow handler.
+fn is_syscall_handler(name: &str, abi: SyscallAbi) -> bool {
+    matches!(
+        work_type_of(name, abi),
+        Some(KernelWorkType::Commands | KernelWorkType::Subscribes | KernelWorkType::Allows)
+    )
+}
+
+/// Label for the kind of kernel work a function was selected as. Functions
+/// selected by name rather than by category are labeled "Custom".
+fn category_name(name: &str, abi: SyscallAbi) -> String {
+    work_type_of(name, abi).map_or("Custom".to_owned(), |k| k.to_string())
+}
+
+/// Crate a function belongs to, from the first component of its symbol path:
+/// the defining crate, or for trait methods that of the implementing type.
+fn crate_name(name: &str) -> String {
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    let path = demangled.trim_start_matches(&['<', '&', '*'][..]);
+    let path = ["mut ", "const ", "dyn "]
+        .iter()
+        .fold(path, |p, prefix| p.strip_prefix(prefix).unwrap_or(p));
+    path.split("::").next().unwrap_or_default().to_owned()
+}
+
 /// Function for retrieving the types of Tock functions which this tool is capable of profiling,
 /// by matching on the mangled function names.
 fn retrieve_functions_for_analysis<'p>(
     project: &'p Project,
     kind: KernelWorkType,
+    abi: SyscallAbi,
 ) -> Box<dyn Iterator<Item = (&llvm_ir::function::Function, &llvm_ir::module::Module)> + 'p> {
-    // TODO: Filtering on demangled function names should allow for more precise matches with fewer
-    // false positives
-    //let demangled = rustc_demangle::demangle(func_name);
     match kind {
-        // TODO: Allow handle_xx_interrupt pattern as well
-        KernelWorkType::Interrupts => Box::new(
-            project
-                .all_functions()
-                .filter(|(f, _m)| f.name.contains("handle_interrupt")),
-        ),
-        KernelWorkType::DeferredCalls => Box::new(
-            project
-                .all_functions()
-                .filter(|(f, _m)| f.name.contains("handle_deferred_call")),
-        ),
-        KernelWorkType::Commands => Box::new(project.all_functions().filter(|(f, _m)| {
-            f.name.contains("command")
-                && f.name.contains("Driver")
-                && !f.name.contains("closure") //manual exclusion
-                && !f.name.contains("command_complete") //manual exclusion
-        })),
-        KernelWorkType::Allows => Box::new(project.all_functions().filter(|(f, _m)| {
-            f.name.contains("allow") && f.name.contains("Driver") && !f.name.contains("closure")
-        })),
-        KernelWorkType::Subscribes => Box::new(project.all_functions().filter(|(f, _m)| {
-            f.name.contains("subscribe") && f.name.contains("Driver") && !f.name.contains("closure")
-        })),
         KernelWorkType::Memops => panic!("Memop support not yet implemented"),
         KernelWorkType::All => {
             let command_syscalls =
-                retrieve_functions_for_analysis(&project, KernelWorkType::Commands);
+                retrieve_functions_for_analysis(&project, KernelWorkType::Commands, abi);
 
             let subscribe_syscalls =
-                retrieve_functions_for_analysis(&project, KernelWorkType::Subscribes);
-            let allow_syscalls = retrieve_functions_for_analysis(&project, KernelWorkType::Allows);
+                retrieve_functions_for_analysis(&project, KernelWorkType::Subscribes, abi);
+            let allow_syscalls =
+                retrieve_functions_for_analysis(&project, KernelWorkType::Allows, abi);
+            let syscall_dispatch =
+                retrieve_functions_for_analysis(project, KernelWorkType::SyscallDispatch, abi);
 
             let interrupt_handlers =
-                retrieve_functions_for_analysis(&project, KernelWorkType::Interrupts);
+                retrieve_functions_for_analysis(&project, KernelWorkType::Interrupts, abi);
+            let alarm_callbacks =
+                retrieve_functions_for_analysis(project, KernelWorkType::Alarms, abi);
+            let uart_callbacks =
+                retrieve_functions_for_analysis(project, KernelWorkType::Uart, abi);
+            let i2c_callbacks = retrieve_functions_for_analysis(project, KernelWorkType::I2c, abi);
+            let spi_callbacks = retrieve_functions_for_analysis(project, KernelWorkType::Spi, abi);
+            // radio interrupt handlers are already selected as interrupts
+            let radio_events = retrieve_functions_for_analysis(project, KernelWorkType::Radio, abi)
+                .filter(move |(f, _m)| {
+                    !matches_work_type(&f.name, &KernelWorkType::Interrupts, abi)
+                });
             Box::new(
                 command_syscalls
                     .chain(subscribe_syscalls)
                     .chain(allow_syscalls)
-                    .chain(interrupt_handlers),
+                    .chain(syscall_dispatch)
+                    .chain(interrupt_handlers)
+                    .chain(alarm_callbacks)
+                    .chain(uart_callbacks)
+                    .chain(i2c_callbacks)
+                    .chain(spi_callbacks)
+                    .chain(radio_events),
             )
         }
+        kind => Box::new(
+            project
+                .all_functions()
+                .filter(move |(f, _m)| matches_work_type(&f.name, &kind, abi)),
+        ),
+    }
+}
+
+/// Find the mangled name of a function given by the user. Accepts the
+/// mangled name, or the demangled name with or without its hash. Fails if no
+/// function or several functions (e.g. monomorphizations) have that name,
+/// suggesting names to use instead.
+fn resolve_function<'p>(project: &'p Project, name: &str) -> Result<&'p str, String> {
+    let mut matches: Vec<&str> = project
+        .all_functions()
+        .map(|(f, _m)| f.name.as_str())
+        .filter(|mangled| {
+            let demangled = rustc_demangle::demangle(mangled);
+            *mangled == name || demangled.to_string() == name || format!("{:#}", demangled) == name
+        })
+        .collect();
+    // functions may be defined in several modules
+    matches.sort_unstable();
+    matches.dedup();
+    match matches.as_slice() {
+        [mangled] => Ok(mangled),
+        [] => {
+            // suggest functions with the same last path component
+            let last = name.rsplit("::").next().unwrap_or(name);
+            let mut similar: Vec<String> = project
+                .all_functions()
+                .map(|(f, _m)| format!("{:#}", rustc_demangle::demangle(&f.name)))
+                .filter(|demangled| demangled.contains(last))
+                .collect();
+            similar.sort_unstable();
+            similar.dedup();
+            let mut message = format!("no function named {}", name);
+            if !similar.is_empty() {
+                message += ", similar functions:";
+                for demangled in similar.iter().take(10) {
+                    message += &format!("\n  {}", demangled);
+                }
+            }
+            Err(message)
+        }
+        several => {
+            let mut message = format!(
+                "{} functions are named {}, pass one of their names with its hash:",
+                several.len(),
+                name
+            );
+            for mangled in several {
+                message += &format!("\n  {}", rustc_demangle::demangle(mangled));
+            }
+            Err(message)
+        }
+    }
+}
+
+/// Per-analysis settings that can be adjusted from the command line
+/// (or per request, in daemon mode).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct AnalysisSettings {
+    /// Solver query timeout, in seconds
+    timeout_s: u64,
+    /// Maximum number of times a loop may be unrolled
+    loop_bound: usize,
+    time_results: bool,
+    cost_model: CostModel,
+    /// Core clock frequency, to report results in microseconds
+    clock_mhz: Option<f64>,
+    /// Symbolic execution engine searching for the longest paths
+    engine: engine::EngineKind,
+    /// Correction factor of the board's cycle counts, from its calibration
+    calibration_factor: Option<f64>,
+    /// Total time the analysis of a function may take, in seconds
+    time_budget_s: Option<u64>,
+    /// Format of the per-function result files
+    format: ReportFormat,
+    /// How much of the longest path the per-function result files include
+    detail: ReportDetail,
+    /// Whether the per-function result files are gzipped
+    compress: bool,
+    /// Directory the instructions of each function's longest path are listed
+    /// in, next to its results, with `ReportDetail::Full`
+    listings: Option<String>,
+    /// Loop bounds of the virtualizers' dispatch over their clients, by mangled name
+    dispatch_bounds: HashMap<String, usize>,
+    /// DMA channel and descriptor counts by chip crate, bounding the DMA handlers' loops
+    dma: HashMap<String, config_file::DmaLimits>,
+    /// Also find the longest path for each distinct value the function returns
+    per_return_value: bool,
+    /// Assumptions about the state of processes
+    process_state: process_state::ProcessState,
+    /// Constraints on the arguments of functions, by mangled name
+    argument_constraints: HashMap<String, Vec<config_file::ArgumentConstraint>>,
+    /// Argument and values of the functions analyzed parametrically, by mangled name
+    parametric: HashMap<String, config_file::ParametricConfig>,
+    /// Argument and driver numbers of the syscall dispatch functions, by mangled name
+    dispatch_drivers: HashMap<String, config_file::ParametricConfig>,
+    /// Directory the exhaustive and bounded engines save the exploration of each function
+    /// to while it runs, resumed by `--resume`
+    frontiers: Option<String>,
+    /// Number of times each function is analyzed, failing it unless all runs agree
+    stability_runs: usize,
+    /// Which panic functions are hooked, and how paths raising them are treated
+    panics: panics::PanicSettings,
+    /// Hooks of the config file, by mangled name of the function they hook
+    hooks: HashMap<String, config_file::HookConfig>,
+    /// Trusted costs of the config file, reported instead of analyzing the
+    /// function, by mangled name
+    fixed_costs: HashMap<String, PathLength>,
+    /// Subsystems the instructions of longest paths are attributed to
+    subsystems: subsystems::Subsystems,
+    /// Syscall ABI of the kernel, which decides what syscall handlers are
+    /// named
+    syscall_abi: SyscallAbi,
+}
+
+/// Length of the longest path found through a function.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct PathLength {
+    /// In LLVM IR instructions, as counted by symbolic execution
+    ir: usize,
+    /// In machine instructions, from the disassembly of the path
+    machine: usize,
+    /// In cycles, according to the cost model
+    cycles: u64,
+}
+
+impl PathLength {
+    fn micros(&self, clock_mhz: f64) -> f64 {
+        self.cycles as f64 / clock_mhz
     }
+
+    /// Cycles corrected by a board's calibration factor, rounded up.
+    fn calibrated(&self, factor: f64) -> u64 {
+        (self.cycles as f64 * factor).ceil() as u64
+    }
+}
+
+/// Load every bitcode file in the bc directory into a project.
+fn load_project(bitcode: &[PathBuf]) -> Result<Project, String> {
+    Project::from_bc_paths(bitcode.iter().cloned())
+}
+
+/// The bitcode files of the project: exactly those the build reported, or
+/// every file in `bc_dir` when the board was not built by cargo here. The
+/// entry points generated for constrained functions are added to them.
+fn project_bitcode(bc_dir: &str, built: &[PathBuf]) -> Vec<PathBuf> {
+    if !built.is_empty() {
+        return built.to_vec();
+    }
+    glob(&[bc_dir, "/**/*.bc"].concat())
+        .unwrap()
+        .map(|x| x.unwrap())
+        .filter(|path| !constraints::is_entry_module(path))
+        .collect()
 }
 
 /// Given a bc directory and a function name to analyze, this function
-/// will symbolically execute the passed function, and write the results to a file.
+/// will symbolically execute the passed function and return its results.
 /// This is useful for performing multiple symbolic executions simultaneously,
 /// especially because each execution is single threaded.
-fn analyze_and_save_results(
-    bc_dir: &str,
+fn analyze_function(
+    bitcode: &[PathBuf],
     board_path_str: &str,
     func_name: &str,
-    timeout_s: u64,
-    resultspath: &str,
-    time_results: bool,
+    settings: &AnalysisSettings,
     disassembly: &Disassem,
-) -> Result<String, String> {
-    let paths = glob(&[bc_dir, "/**/*.bc"].concat())
-        .unwrap()
-        .map(|x| x.unwrap());
-    let project = Project::from_bc_paths(paths)?;
+    index: &DisassemblyIndex,
+) -> FunctionResult {
+    match load_project(bitcode) {
+        Ok(project) => analyze_project_function(
+            &project,
+            board_path_str,
+            func_name,
+            settings,
+            disassembly,
+            index,
+        ),
+        Err(e) => {
+            let mut result = FunctionResult::new(func_name, board_path_str, settings);
+            result.error = Some(e);
+            result
+        }
+    }
+}
+
+/// The path, without extension, of the file the results of analyzing
+/// `func_name` are written to, in the run's directory `board_results`: its
+/// demangled name, made a valid file name on every host.
+fn function_results_stem(board_results: &str, func_name: &str) -> String {
+    let demangled = rustc_demangle::demangle(func_name).to_string();
+    format!("{}/{}", board_results, host::file_name(&demangled))
+}
+
+/// The file listing the instructions of the longest path of `func_name`.
+fn listing_file(listings: &str, func_name: &str, compress: bool) -> String {
+    let stem = function_results_stem(listings, func_name);
+    match compress {
+        true => format!("{}.listing.txt.gz", stem),
+        false => format!("{}.listing.txt", stem),
+    }
+}
 
+/// The loop bound of the analysis of `func_name`, and what it is taken from
+/// if not the configured one.
+fn loop_bound(
+    project: &Project,
+    func_name: &str,
+    settings: &AnalysisSettings,
+) -> (usize, Option<&'static str>) {
+    let mut bound = (settings.loop_bound, None);
+    // haybale bounds every loop of an analysis alike, so these only raise the
+    // bound: lowering it would also cut the other loops of the function and
+    // its callees
+    if let Some(&clients) = settings.dispatch_bounds.get(func_name) {
+        // The mux iterates over its clients, whose list is not known symbolically
+        if clients > bound.0 {
+            bound = (
+                clients,
+                Some("raised to the number of clients of the virtualizers, for every loop"),
+            );
+        }
+    }
+    if let Some(limits) = dma::handler_chip(func_name).and_then(|chip| settings.dma.get(&chip)) {
+        // DMA handlers iterate over the chip's channels and their descriptors
+        if dma::loop_bound(limits) > bound.0 {
+            bound = (
+                dma::loop_bound(limits),
+                Some("raised to the chip's DMA channels and descriptors, for every loop"),
+            );
+        }
+    }
+    if let Some(num_procs) = settings.process_state.process_loop_bound(project) {
+        // Loops over the process array run once per process slot
+        if num_procs > bound.0 && process_state::iterates_processes(project, func_name) {
+            bound = (
+                num_procs,
+                Some("raised to the number of process slots, for every loop"),
+            );
+        }
+    }
+    bound
+}
+
+/// Symbolic execution config for analyzing `func_name`, with the panic paths
+/// selected with `separate` either pruned or, if `count_separate`, counted.
+fn analysis_config<'p>(
+    project: &'p Project,
+    func_name: &str,
+    settings: &AnalysisSettings,
+    return_hooks: &'p hooks::ReturnHooks<'p>,
+    count_separate: bool,
+) -> Result<Config<'p, DefaultBackend>, String> {
     let mut config: Config<DefaultBackend> = Config::default();
     config.null_pointer_checking = config::NullPointerChecking::None; // In the Tock kernel, we trust that Rust safety mechanisms prevent null pointer dereferences.
-    config.loop_bound = 100; // default is 10, raise if larger loops exist
-    config.solver_query_timeout = Some(std::time::Duration::new(timeout_s, 0)); // extend query timeout
-    config
-        .function_hooks
-        .add_rust_demangled("kernel::debug::panic", &function_hooks::abort_hook);
+    config.loop_bound = loop_bound(project, func_name, settings).0; // default is 10, raise if larger loops exist
+    config.solver_query_timeout = Some(std::time::Duration::new(settings.timeout_s, 0)); // extend query timeout
+    solver_time::add_callbacks(&mut config.callbacks, settings.time_budget_s);
+
+    // The panic printing path is analyzed as a whole, rather than aborted at
+    // the next printing function
+    let printing = matches_work_type(func_name, &KernelWorkType::PanicPrint, settings.syscall_abi);
+    for name in panics::hooked_functions(project, &settings.panics, count_separate)? {
+        if name != func_name
+            && !(printing
+                && matches_work_type(name, &KernelWorkType::PanicPrint, settings.syscall_abi))
+        {
+            config
+                .function_hooks
+                .add(name.to_owned(), &function_hooks::abort_hook);
+        }
+    }
+    // arguments excluded by the constraints of an entry point end its path
     config
         .function_hooks
-        .add_rust_demangled("core::panicking::panic_fmt", &function_hooks::abort_hook);
+        .add(constraints::EXCLUDED, &function_hooks::abort_hook);
+    if matches_work_type(func_name, &KernelWorkType::Startup, settings.syscall_abi) {
+        // Startup ends where the kernel loop starts: the loop returns at once,
+        // and the `unreachable` after its call ends the path
+        for (f, _m) in project.all_functions().filter(|(f, _m)| {
+            KERNEL_LOOP.is_match(&format!("{:#}", rustc_demangle::demangle(&f.name)))
+        }) {
+            config
+                .function_hooks
+                .add(f.name.clone(), &function_hooks::generic_stub_hook);
+        }
+    }
+    process_state::add_hooks(&mut config.function_hooks, project, &settings.process_state);
+    return_hooks.add_to(&mut config.function_hooks);
+    if let Some(KernelWorkType::SyscallDispatch) = work_type_of(func_name, settings.syscall_abi) {
+        // Capsule handlers are analyzed and reported on their own, so only count
+        // the kernel's overhead up to reaching them. Unless entered at a driver
+        // number, this bounds the dispatch overhead of every driver.
+        for (f, _m) in project
+            .all_functions()
+            .filter(|(f, _m)| is_syscall_handler(&f.name, settings.syscall_abi))
+        {
+            config
+                .function_hooks
+                .add(f.name.clone(), &function_hooks::generic_stub_hook);
+        }
+    }
+    Ok(config)
+}
+
+/// Find the longest path through `func_name`, with the panic paths selected
+/// with `separate` either pruned or, if `count_separate`, counted.
+fn longest_path(
+    project: &Project,
+    func_name: &str,
+    settings: &AnalysisSettings,
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+    count_separate: bool,
+) -> Result<(PathLength, results::LongestPath), String> {
+    // Functions with constrained arguments are entered through a wrapper
+    // passing on only the arguments satisfying the constraints
+    let entry = match settings.argument_constraints.contains_key(func_name) {
+        true => constraints::wrapper_name(func_name),
+        false => func_name.to_owned(),
+    };
+    longest_path_from(
+        project,
+        func_name,
+        &entry,
+        settings,
+        disassembly,
+        index,
+        count_separate,
+    )
+}
+
+/// Find the longest path through `func_name`, entered through `entry`.
+fn longest_path_from(
+    project: &Project,
+    func_name: &str,
+    entry: &str,
+    settings: &AnalysisSettings,
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+    count_separate: bool,
+) -> Result<(PathLength, results::LongestPath), String> {
+    let return_hooks = hooks::ReturnHooks::new(&settings.hooks, func_name);
+    let mut config = analysis_config(project, func_name, settings, &return_hooks, count_separate)?;
     config.longest_path_optimizations = true;
-    let board_name = board_path_str
-        .get(board_path_str.rfind('/').unwrap() + 1..)
-        .unwrap();
-    let demangled = rustc_demangle::demangle(func_name).to_string();
-    let filename = format!("{}/{}/{}.txt", resultspath, board_name, demangled);
-    println!("{:?}", filename);
-    let path = std::path::Path::new(&filename);
-    let prefix = path.parent().unwrap();
-    std::fs::create_dir_all(prefix).unwrap();
-    let mut file = File::create(path).unwrap();
-    let ret =
-        match haybale::dyn_dispatch::find_longest_path(func_name, &project, config, time_results) {
-            Ok((len, state)) => {
-                let (raw_instruction_str, raw_instruction_count) =
-                    count_instructions(disassembly, &state)
-                        .expect("failed to get raw instruction count");
-
-                let data = "Assembly len: ".to_owned()
-                    + &raw_instruction_count.to_string()
-                    + "\n"
-                    + &raw_instruction_str
-                    + "IR len: "
-                    + &len.to_string()
-                    + "\n"
-                    + &state.pretty_path_llvm_instructions();
-                // + "\n"
-                //+ &state.pretty_path_source();
-                file.write_all(data.as_bytes()).unwrap();
-                Ok(len.to_string())
-            }
-            Err(e) => {
-                println!("{}", e);
-                file.write_all(e.as_bytes()).unwrap();
-                Err("Fail: ".to_string() + &e)
-            }
-        };
-    ret
+    let time_results = settings.time_results;
+    // only the exhaustive engine ends paths at the kernel loop's `unreachable`
+    let engine = match matches_work_type(func_name, &KernelWorkType::Startup, settings.syscall_abi)
+    {
+        true => engine::EngineKind::Exhaustive,
+        false => settings.engine,
+    };
+    let frontier = settings
+        .frontiers
+        .as_ref()
+        .map(|dir| checkpoint::frontier_file(dir, entry, count_separate));
+    let found =
+        engine
+            .engine()
+            .longest_path(entry, project, config, time_results, frontier.as_deref())?;
+    // the checks of a generated entry point are not part of the function
+    let entry_ir: usize = (0..found.path.len())
+        .filter(|&i| constraints::is_entry_point(&found.path[i].0.func.name))
+        .map(|i| entry_ir_length(&found.path, i))
+        .sum();
+    let function_path: Vec<PathEntry> = found
+        .path
+        .iter()
+        .filter(|entry| !constraints::is_entry_point(&entry.0.func.name))
+        .cloned()
+        .collect();
+    // hooked calls do not execute their callee, which costs what its hook says
+    let (hooked, hooked_calls, hooked_functions) =
+        hooks::path_cost(&function_path, &settings.hooks);
+    // only the function's own longest path is listed, written as it is counted
+    let listing = match (&settings.listings, count_separate) {
+        (Some(dir), false)
+            if entry == func_name || entry == constraints::wrapper_name(func_name) =>
+        {
+            Some(listing_file(dir, func_name, settings.compress))
+        }
+        _ => None,
+    };
+    let count = match &listing {
+        Some(file) => {
+            let mut count = Err(String::new());
+            results::write_file(file, settings.compress, |out| {
+                count = count_instructions(
+                    disassembly,
+                    index,
+                    &function_path,
+                    &settings.cost_model,
+                    Some(&mut *out),
+                );
+                out.write_all(hooked_calls.as_bytes())?;
+                writeln!(out, "LLVM IR instructions:")?;
+                out.write_all(found.llvm_instructions.as_bytes())
+            })?;
+            count
+        }
+        None => count_instructions(
+            disassembly,
+            index,
+            &function_path,
+            &settings.cost_model,
+            None,
+        ),
+    }
+    .expect("failed to get raw instruction count");
+    let length = PathLength {
+        ir: found.ir - entry_ir + hooked.ir,
+        machine: count.machine + hooked.machine,
+        cycles: count.cycles + hooked.cycles,
+    };
+    let blocks = PathBlock::from_path(&function_path);
+    let subsystems =
+        subsystems::attribute(&settings.subsystems, &blocks, &count.per_block, &hooked);
+    let path = results::LongestPath {
+        listing,
+        estimated_machine: count.estimated,
+        inline_asm_machine: count.inline_asm,
+        subsystems,
+        allocations: allocations::on_path(&function_path),
+        hooked_functions,
+        blocks: blocks
+            .into_iter()
+            .zip(&count.per_block)
+            .map(|(block, &machine)| results::BlockCount {
+                func: block.func,
+                bb: block.bb,
+                ir: block.ir_len,
+                machine,
+            })
+            .collect(),
+    };
+    Ok((length, path))
+}
+
+/// Summary of an analysis for comparing repeated runs.
+fn describe_outcome(outcome: &Result<(PathLength, results::LongestPath), String>) -> String {
+    match outcome {
+        Ok((length, path)) => format!(
+            "{} IR instructions, {} machine instructions ({} blocks)",
+            length.ir,
+            length.machine,
+            path.blocks.len()
+        ),
+        Err(e) => format!("error {:?}", e),
+    }
+}
+
+/// Symbolically execute the passed function of an already loaded project.
+fn analyze_project_function(
+    project: &Project,
+    board_path_str: &str,
+    func_name: &str,
+    settings: &AnalysisSettings,
+    disassembly: &Disassem,
+    index: &DisassemblyIndex,
+) -> FunctionResult {
+    let mut result = FunctionResult::new(func_name, board_path_str, settings);
+    if is_syscall_handler(func_name, settings.syscall_abi) {
+        result.metadata.syscall_method = Some(drivers::trait_method(func_name));
+        result.metadata.arity = drivers::method_arity(project, func_name);
+    }
+    let start = Instant::now();
+    solver_time::reset();
+    if let Some(&length) = settings.fixed_costs.get(func_name) {
+        // A trusted cost replaces the analysis
+        result.set_length(length);
+        result.assumptions = assumptions::collect(project, func_name, settings, None);
+        result.set_analysis_time(start.elapsed());
+        return result;
+    }
+    let first = longest_path(project, func_name, settings, disassembly, index, false);
+    // Repeated analyses must agree, or the results cannot be trusted
+    let mut instability = None;
+    for run in 2..=settings.stability_runs {
+        let again = longest_path(project, func_name, settings, disassembly, index, false);
+        if again != first {
+            instability = Some(format!(
+                "nondeterministic analysis: run 1 found {}, run {} found {}",
+                describe_outcome(&first),
+                run,
+                describe_outcome(&again)
+            ));
+            break;
+        }
+    }
+    match first {
+        Ok((length, path)) => {
+            result.set_length(length);
+            result.path = Some(path);
+        }
+        Err(e) => {
+            println!("{}", e);
+            result.error = Some(e);
+        }
+    }
+    if let Some(e) = instability {
+        println!("{}", e);
+        result.error = Some(e);
+    }
+    if let (Some(exception), Some(length), None) = (
+        &settings.cost_model.exception,
+        &result.length,
+        &result.error,
+    ) {
+        if matches_work_type(func_name, &KernelWorkType::Interrupts, settings.syscall_abi) {
+            result.exception = Some(exception.handler(length.cycles));
+        }
+    }
+    if settings.per_return_value && result.error.is_none() {
+        let return_hooks = hooks::ReturnHooks::new(&settings.hooks, func_name);
+        result.by_return_value = Some(
+            analysis_config(project, func_name, settings, &return_hooks, false).and_then(
+                |config| {
+                    return_values::longest_by_return_value(
+                        project,
+                        func_name,
+                        settings.argument_constraints.get(func_name),
+                        config,
+                        settings.engine.engine(),
+                        &|path| {
+                            count_instructions(disassembly, index, path, &settings.cost_model, None)
+                        },
+                    )
+                },
+            ),
+        );
+    }
+    if let (Some(parameter), None) = (settings.parametric.get(func_name), &result.error) {
+        let points = parameter
+            .values
+            .iter()
+            .map(|&value| parametric::ParametricPoint {
+                value,
+                length: longest_path_from(
+                    project,
+                    func_name,
+                    &constraints::parametric_name(func_name, value),
+                    settings,
+                    disassembly,
+                    index,
+                    false,
+                )
+                .map(|(length, _path)| length),
+            })
+            .collect();
+        result.parametric = Some(parametric::ParametricResult::new(parameter.arg, points));
+    }
+    if let (Some(dispatch), None) = (settings.dispatch_drivers.get(func_name), &result.error) {
+        result.by_driver = Some(
+            dispatch
+                .values
+                .iter()
+                .map(|&value| parametric::ParametricPoint {
+                    value,
+                    length: longest_path_from(
+                        project,
+                        func_name,
+                        &constraints::driver_name(func_name, value),
+                        settings,
+                        disassembly,
+                        index,
+                        false,
+                    )
+                    .map(|(length, _path)| length),
+                })
+                .collect(),
+        );
+    }
+    if settings.panics.any_separate() && result.error.is_none() {
+        result.with_panic_paths = Some(
+            longest_path(project, func_name, settings, disassembly, index, true)
+                .map(|(length, _path)| length),
+        );
+    }
+    result.assumptions = assumptions::collect(project, func_name, settings, result.path.as_ref());
+    result.stats.solver_time = solver_time::spent();
+    result.set_analysis_time(start.elapsed());
+    result
 }
 
 #[derive(StructOpt, Debug)]
@@ -166,15 +906,42 @@ struct Opt {
     #[structopt(long)]
     skip_compile: bool,
 
+    /// With --skip-compile, analyze the existing bitcode even if the sources
+    /// changed since it was built, instead of failing
+    #[structopt(long)]
+    allow_stale: bool,
+
+    /// Build the board with its Makefile, or by invoking `cargo build` directly
+    /// (which also builds crates outside of Tock, from the current directory)
+    #[structopt(long, possible_values = &Builder::variants(), case_insensitive = true, default_value = "make")]
+    builder: Builder,
+
+    /// Count machine instructions in the assembly the build emits next to the
+    /// bitcode (rustc's `--emit=asm`), compiled with the build's own flags,
+    /// instead of running llc on the bitcode
+    #[structopt(long)]
+    emit_asm_from_build: bool,
+
     /// Verbose mode (-v, -vv, -vvv, etc.)
     #[structopt(short, long, parse(from_occurrences))]
     verbose: u8,
 
-    /// Timeout passed to Haybale runs (in seconds)
+    /// Preset timeouts, loop bound, engine and time budget: `quick` for
+    /// smoke checks, `normal`, or `thorough` for release qualification.
+    /// Options given explicitly override the profile's
+    #[structopt(long, possible_values = &profile::Profile::variants(), case_insensitive = true, default_value = "normal")]
+    profile: profile::Profile,
+
+    /// Timeout passed to Haybale runs (in seconds), 75 with the normal profile.
     /// This is only the timeout for the initial runs,
     /// not the partitioned runs
-    #[structopt(short, long, default_value = "75")]
-    timeout: u64,
+    #[structopt(short, long)]
+    timeout: Option<u64>,
+
+    /// Maximum number of times symbolic execution may unroll a loop, 100
+    /// with the normal profile
+    #[structopt(long)]
+    loop_bound: Option<usize>,
 
     /// Name of the tock board to analyze
     #[structopt(short, long, default_value = "imixmini")]
@@ -194,6 +961,67 @@ struct Opt {
     #[structopt(short = "c", long)]
     func_name_contains: Option<Vec<String>>,
 
+    /// Run on every function whose demangled name matches this regex,
+    /// e.g. '--func-regex "capsules::console::.*::command"'
+    /// Not compatible with function_index or func_name_contains
+    #[structopt(long)]
+    func_regex: Option<String>,
+
+    /// Choose the functions to analyze interactively, by fuzzy search over the
+    /// demangled names of the functions selected by the other flags
+    #[structopt(long)]
+    pick: bool,
+
+    /// Measure the functions configured under [calibration] on hardware or in
+    /// an emulator, and save the factor correcting the board's cycle counts,
+    /// which later reports of the board apply
+    #[structopt(long)]
+    calibrate: bool,
+
+    /// Also analyze the scheduler, and bound one iteration of the kernel loop
+    /// by the scheduler's overhead plus the worst of the work it services
+    #[structopt(long)]
+    kernel_loop: bool,
+
+    /// Analyze only the function with exactly this name: mangled, or demangled
+    /// with or without its hash. Fails if no function or several functions
+    /// have that name
+    /// Not compatible with function_index, func_name_contains or func_regex
+    #[structopt(long)]
+    func_exact: Option<String>,
+
+    /// Analyze this function (mangled or demangled name) and every function
+    /// statically reachable from it, each individually
+    /// Not compatible with function_index, func_name_contains or func_regex
+    #[structopt(long)]
+    call_graph_root: Option<String>,
+
+    /// Write the static call graph of the analyzed functions (and everything
+    /// they call) to this file, annotated with the results. Written as JSON if
+    /// the file name ends in `.json`, and in Graphviz DOT format otherwise
+    #[structopt(long)]
+    call_graph_out: Option<String>,
+
+    /// Only analyze functions of this crate (e.g. `capsules_extra`), combined
+    /// with the other selection flags. Can be passed multiple times
+    #[structopt(long = "crate", number_of_values = 1)]
+    crates: Vec<String>,
+
+    /// Skip functions whose demangled name matches this regex. Can be passed
+    /// multiple times, and is combined with the exclusions in the config file
+    #[structopt(long, number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// `production` skips debugging machinery that production builds compile
+    /// out (the process console, debug writer and low-level debug capsule),
+    /// `debug` analyzes it like everything else
+    #[structopt(long, possible_values = &ExclusionProfile::variants(), case_insensitive = true, default_value = "debug")]
+    exclusion_profile: ExclusionProfile,
+
+    /// TOML file with additional configuration, such as exclusion patterns
+    #[structopt(long)]
+    config: Option<String>,
+
     /// Types of function for which to find longest path
     #[structopt(short, long, possible_values = &KernelWorkType::variants(), case_insensitive = true, default_value = "all")]
     functions: KernelWorkType,
@@ -201,22 +1029,500 @@ struct Opt {
     #[structopt(short = "p", long = "tockpath", default_value = "tock")]
     tockpath: String,
 
+    /// Path to a board crate outside of the tock tree (e.g. in a separate
+    /// repository depending on Tock), analyzed instead of `--board`. Its target
+    /// directory is found through `cargo metadata` unless --target-dir is passed
+    #[structopt(long)]
+    board_path: Option<String>,
+
+    /// Cargo target directory the board is built into, defaults to the
+    /// `target` directory of the tock checkout
+    #[structopt(long)]
+    target_dir: Option<String>,
+
+    /// Target triple the board is built for, inferred from the board by default
+    #[structopt(long)]
+    target: Option<String>,
+
+    /// Analyze a crate that is not a Tock board. The board name is then the
+    /// crate name, and bitcode must already have been built into --target-dir
+    #[structopt(long)]
+    no_tock: bool,
+
     #[structopt(short = "r", long = "resultspath", default_value = "results")]
     resultspath: String,
 
+    /// SQLite database accumulating the results of every run, by default
+    /// results.sqlite in the results path. Each board's results also have a
+    /// database of their last run
+    #[structopt(long)]
+    results_db: Option<String>,
+
+    /// How paths reaching `unreachable!()` are treated: pruned, counted, or
+    /// pruned with the length when counted reported separately
+    #[structopt(long, possible_values = &PanicPaths::variants(), case_insensitive = true, default_value = "prune")]
+    unreachable_paths: PanicPaths,
+
+    /// How paths failing assertions (`assert_eq!`, `debug_assert_eq!`, ...) are treated
+    #[structopt(long, possible_values = &PanicPaths::variants(), case_insensitive = true, default_value = "prune")]
+    assert_paths: PanicPaths,
+
+    /// How paths panicking on integer overflow or division by zero are treated
+    #[structopt(long, possible_values = &PanicPaths::variants(), case_insensitive = true, default_value = "prune")]
+    overflow_paths: PanicPaths,
+
+    /// Format of the per-function result files. With "markdown", the summary
+    /// (compared with --baseline) and --diff-boards are also written as
+    /// markdown tables, for posting as pull request comments
+    #[structopt(long, possible_values = &ReportFormat::variants(), case_insensitive = true, default_value = "text")]
+    format: ReportFormat,
+
+    /// How much of the longest path the per-function result files include:
+    /// "summary" only its length, "path" also the blocks it goes through, or
+    /// "full" also the machine and IR instructions of each
+    #[structopt(long, possible_values = &ReportDetail::variants(), case_insensitive = true, default_value = "full")]
+    detail: ReportDetail,
+
+    /// Gzip the per-function result files, e.g. `foo.txt.gz`
+    #[structopt(long)]
+    compress: bool,
+
+    /// Engine searching for the longest path: "longest", haybale's search
+    /// skipping paths shorter than one already found, "exhaustive", a
+    /// breadth-first branch-and-bound search dropping paths whose length so
+    /// far plus their static bound from where they are cannot beat the
+    /// longest found, and stopping once one reaches the function's static
+    /// bound, which can be faster on wide but shallow code such as capsule
+    /// commands, or "bounded", the same search depth-first. Defaults to the
+    /// profile's, "longest" unless thorough
+    #[structopt(long, possible_values = &engine::EngineKind::variants(), case_insensitive = true)]
+    engine: Option<engine::EngineKind>,
+
     #[structopt(short = "g", long)]
     save_git_history: bool,
 
     #[structopt(long = "time")]
     time_results: bool,
 
+    /// Core clock frequency of the board, in MHz. When given, results are
+    /// also reported in microseconds, using the cycle cost model from the
+    /// config file (one cycle per instruction by default)
+    #[structopt(long)]
+    clock_mhz: Option<f64>,
+
     #[structopt(long = "print")]
     print_function_names: bool,
+
+    /// Like `--print`, listing the static metrics of each function on its
+    /// own (basic blocks, IR instructions, loops, callees and dynamic calls),
+    /// largest first
+    #[structopt(long)]
+    print_metrics: bool,
+
+    /// Print what would be analyzed (board, target directory, matched functions
+    /// and their settings) without building or analyzing anything
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Print a completion script for the given shell and exit
+    #[structopt(long, possible_values = &clap::Shell::variants(), case_insensitive = true)]
+    completions: Option<clap::Shell>,
+
+    /// Instruction budget for the longest path of each analyzed function.
+    /// A function exceeding it, unless its failure is tolerated in the config
+    /// file, makes the run exit with code 2 and counts as a failure for
+    /// `--fail-fast`; it is also flagged with `--github-annotations`
+    #[structopt(long)]
+    budget: Option<usize>,
+
+    /// Summary file from a previous run to compare results against
+    #[structopt(long)]
+    baseline: Option<String>,
+
+    /// Percentage by which a function's longest path may grow relative to
+    /// the baseline before it is flagged as a regression
+    #[structopt(long, default_value = "5")]
+    regression_threshold: f64,
+
+    /// Changes of a longest path by at most this many instructions are
+    /// treated as unchanged in comparisons (the numbers are still listed)
+    #[structopt(long, default_value = "0")]
+    noise_instructions: usize,
+
+    /// Changes of a longest path by at most this percentage are treated as
+    /// unchanged in comparisons (the numbers are still listed)
+    #[structopt(long, default_value = "0")]
+    noise_percent: f64,
+
+    /// Flag results with fewer machine instructions per LLVM IR instruction
+    /// on the longest path than this, which usually means blocks of the path
+    /// were not found in the disassembly. 0 disables the check
+    #[structopt(long, default_value = "0.25")]
+    min_machine_ratio: f64,
+
+    /// Shell command run as each analyzed function finishes or fails, e.g. to
+    /// send a notification, with the function in WCET_FUNCTION (mangled) and
+    /// WCET_FUNCTION_DEMANGLED, "ok" or "failed" in WCET_STATUS, its result in
+    /// WCET_RESULT, and the full result as JSON on its standard input
+    #[structopt(long)]
+    on_complete: Option<String>,
+
+    /// Only run the --on-complete command for functions whose demangled name
+    /// matches this regex
+    #[structopt(long)]
+    on_complete_functions: Option<String>,
+
+    /// Print `::warning` workflow commands for functions exceeding their budget
+    /// or regressing beyond the threshold, so GitHub Actions annotates the
+    /// offending source directly
+    #[structopt(long)]
+    github_annotations: bool,
+
+    /// Write per-function metrics in the Prometheus text format to this file,
+    /// e.g. for the node exporter's textfile collector
+    #[structopt(long)]
+    prometheus_file: Option<String>,
+
+    /// URL of a Prometheus Pushgateway to push per-function metrics to
+    #[structopt(long)]
+    pushgateway: Option<String>,
+
+    /// Upload the board's results and manifest after the run, to an s3:// or
+    /// gs:// prefix (with the aws or gsutil tools) or an http(s):// endpoint
+    /// receiving them as JSON
+    #[structopt(long)]
+    upload: Option<String>,
+
+    /// Instead of analyzing functions immediately, keep the project loaded
+    /// and serve analysis requests over HTTP on this address (e.g. 127.0.0.1:8080)
+    #[structopt(long)]
+    serve: Option<String>,
+
+    /// Watch the tock sources, and rebuild the board and re-analyze the selected
+    /// function whenever they change, printing how its longest path changed
+    #[structopt(long)]
+    watch: bool,
+
+    /// Resume an interrupted run: functions it finished analyzing, as recorded
+    /// in the board's checkpoint.json, are not analyzed again. The run must
+    /// use the same sources and analysis settings. Only `--engine exhaustive`
+    /// and `bounded` (and the thorough profile) save a function's exploration
+    /// while it runs: with the default engine, the function the run was
+    /// interrupted in is analyzed again from the start
+    #[structopt(long)]
+    resume: bool,
+
+    /// Niceness to analyze at, so a run on a shared machine yields to other work
+    #[structopt(long)]
+    nice: Option<i32>,
+
+    /// Run the analysis (every worker thread and analysis process) only on
+    /// these CPUs, e.g. "0-3,6". Linux only
+    #[structopt(long)]
+    cpus: Option<String>,
+
+    /// Hold back all but one worker thread before each function while the
+    /// load average of the last minute exceeds this
+    #[structopt(long)]
+    max_load: Option<f64>,
+
+    /// Compare results across these boards (comma separated, or passed multiple
+    /// times) in a matrix written to `comparison.tsv` in the results path.
+    /// Boards without a summary in the results path are analyzed first, in
+    /// parallel, with the other arguments of this run
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    compare_boards: Vec<String>,
+
+    /// Tabulate how each function's longest path changed across the runs
+    /// accumulated in this directory (found by their manifest.json and
+    /// summary.txt), writing trend.txt to the results path
+    #[structopt(long)]
+    trend: Option<String>,
+
+    /// Answer a common question from the results database accumulating every
+    /// run (see --results-db): "top" lists the longest paths of each board's
+    /// last run, "by-capsule" how much of them each capsule accounts for,
+    /// "failures" the functions that failed, and "regressions-since <run>"
+    /// the functions that grew or failed since a run (its id in the database,
+    /// or a Tock commit) in its board's last run
+    #[structopt(long, min_values = 1, max_values = 2)]
+    query: Vec<String>,
+
+    /// Compare the results of two board crates (comma separated paths), e.g. a
+    /// board with and without a capsule enabled, writing how each function
+    /// both share changed to `diff.tsv` in the results path. Boards without a
+    /// summary in the results path are analyzed first, with the other
+    /// arguments of this run
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    diff_boards: Vec<String>,
+
+    /// Compare boards with --diff-boards even if they were built with
+    /// different cargo features or --cfg flags
+    #[structopt(long)]
+    allow_feature_mismatch: bool,
+
+    /// Analyze functions in child processes limited to this much memory
+    /// (in MB). A function exceeding it fails with a "memory limit"
+    /// result instead of the whole run being killed
+    #[structopt(long)]
+    memory_limit_mb: Option<u64>,
+
+    /// Total time (in seconds) the analysis of a single function may spend in
+    /// the solver, across all of its queries. Functions exceeding it are
+    /// aborted and fail. 120 with the quick profile, unbounded otherwise
+    #[structopt(long)]
+    time_budget: Option<u64>,
+
+    /// When the analysis of a function fails because a solver query timed out,
+    /// keep boolector's trace of the queries next to its results, so the query
+    /// can be replayed and reported upstream. Functions are then analyzed in
+    /// separate processes
+    #[structopt(long)]
+    dump_timeouts: bool,
+
+    /// Analyze deterministically: functions are analyzed in order of their
+    /// mangled names, and the seed is passed to the solver and recorded in the
+    /// run's manifest. Combine with `--jobs` for a stable assignment of
+    /// functions to threads
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// Number of threads analyzing functions, each taking the functions in
+    /// turn. By default every function is analyzed on its own thread
+    #[structopt(long)]
+    jobs: Option<usize>,
+
+    /// Analyze each function only after the selected functions it calls,
+    /// directly or not, analyzing independent call trees in parallel on up
+    /// to `--jobs` threads, by default as many as the host has CPUs
+    #[structopt(long)]
+    dependency_order: bool,
+
+    /// Stop starting new analyses after the first function fails or exceeds
+    /// `--budget`, unless its failure is tolerated in the config file
+    #[structopt(long)]
+    fail_fast: bool,
+
+    /// Analyze each function this many times, and fail functions whose runs
+    /// disagree on the longest path, to detect nondeterminism before trusting
+    /// results in CI
+    #[structopt(long, default_value = "1")]
+    stability_runs: usize,
+
+    /// Also report the longest path for each distinct value a function
+    /// returns, e.g. for syscall handlers the worst successful and the worst
+    /// failing path. This explores every path, so it is only practical for
+    /// functions with a moderate number of paths
+    #[structopt(long)]
+    per_return_value: bool,
+
+    /// Internal: analyze the functions of the jobs read from stdin, as a
+    /// child process of another run
+    #[structopt(long, hidden = true)]
+    analysis_worker: bool,
+}
+
+/// Run a shell command from the board's directory, appending the command and
+/// everything it printed to `log`. Returns whether it succeeded.
+fn run_build_command(
+    command: &str,
+    board_path_str: &str,
+    env: &HashMap<String, String>,
+    log: &mut String,
+) -> Result<bool, String> {
+    let output = host::shell(command)
+        .current_dir(board_path_str)
+        .envs(env)
+        .output()
+        .map_err(|e| format!("failed to execute {:?}: {}", command, e))?;
+    *log += &format!("$ {}\n", command);
+    *log += &String::from_utf8_lossy(&output.stdout);
+    *log += &String::from_utf8_lossy(&output.stderr);
+    Ok(output.status.success())
+}
+
+/// Build the board using its Makefile, or the commands from the config file,
+/// optionally cleaning first.
+fn build_board(
+    board_path_str: &str,
+    clean: bool,
+    build_config: &config_file::BuildConfig,
+    log: &mut String,
+) -> Result<(), String> {
+    println!("Compiling {:?}, please wait...", board_path_str);
+
+    let clean_command = build_config
+        .clean_command
+        .as_deref()
+        .unwrap_or("make clean");
+    if clean && !run_build_command(clean_command, board_path_str, &build_config.env, log)? {
+        return Err(format!("{:?} failed", clean_command));
+    }
+    let command = build_config.command.as_deref().unwrap_or("make");
+    if !run_build_command(command, board_path_str, &build_config.env, log)? {
+        return Err(format!("{:?} failed", command));
+    }
+    println!("Finished building");
+    Ok(())
+}
+
+/// Build the board with the selected builder, returning the bitcode files
+/// produced if the builder reports them. The build output is saved to `log_path`.
+fn build(
+    opt: &Opt,
+    board_path_str: &str,
+    triple: &str,
+    target_root: &str,
+    clean: bool,
+    build_config: &config_file::BuildConfig,
+    log_path: &str,
+) -> Result<Vec<std::path::PathBuf>, String> {
+    let mut log = String::new();
+    let result = match opt.builder {
+        Builder::Make => build_board(board_path_str, clean, build_config, &mut log).map(|_| vec![]),
+        Builder::Cargo => {
+            let crate_dir = if opt.no_tock { "." } else { board_path_str };
+            cargo_build::build(
+                crate_dir,
+                triple,
+                target_root,
+                clean,
+                opt.emit_asm_from_build,
+                &build_config.env,
+                &mut log,
+            )
+        }
+    };
+    if let Some(dir) = std::path::Path::new(log_path).parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(log_path, log).map_err(|e| format!("failed to write {}: {}", log_path, e))?;
+    result.map_err(|e| format!("Build failed: {}, see {}", e, log_path))
+}
+
+/// The board's annotated disassembly, generated from its bitcode by llc, or
+/// emitted by the build itself.
+fn load_disassembly(opt: &Opt, bc_dir: &String, bitcode_name: &String) -> Result<Disassem, String> {
+    match opt.emit_asm_from_build {
+        true => read_build_assembly(bc_dir, bitcode_name),
+        false => Ok(get_disassembly(bc_dir, bitcode_name)),
+    }
+}
+
+/// The target triple the board under analysis is compiled for.
+fn target_triple(opt: &Opt, board_path_str: &str) -> String {
+    if let Some(target) = &opt.target {
+        return target.clone();
+    }
+    if opt.no_tock {
+        // Outside of Tock we cannot guess from the board name, so use whichever
+        // target has bitcode in the target directory.
+        let target_dir = opt
+            .target_dir
+            .as_ref()
+            .expect("--no-tock requires --target-dir");
+        return glob(&format!("{}/*/release/deps/*.bc", target_dir))
+            .unwrap()
+            .filter_map(|p| p.ok())
+            .filter_map(|p| {
+                let triple = p.parent()?.parent()?.parent()?.file_name()?;
+                Some(triple.to_str()?.to_owned())
+            })
+            .next()
+            .expect("no bitcode found in target directory, pass --target");
+    }
+    // For now, assume target under analysis, located in the tock submodule of this crate.
+    // Assume it is a thumbv7 target unless it is one of three whitelisted riscv targets.
+    if board_path_str.contains("opentitan")
+        || board_path_str.contains("arty_e21")
+        || board_path_str.contains("hifive1")
+    {
+        "riscv32imc-unknown-none-elf".to_owned()
+    } else {
+        "thumbv7em-none-eabi".to_owned()
+    }
+}
+
+/// The crate name (as it appears in bitcode file names) and target directory
+/// of the board crate in `crate_dir`, according to `cargo metadata`.
+fn board_crate_metadata(crate_dir: &str) -> Result<(String, String), String> {
+    let manifest_path = std::path::Path::new(crate_dir).join("Cargo.toml");
+    let output = Command::new("cargo")
+        .arg("metadata")
+        .arg("--no-deps")
+        .arg("--format-version=1")
+        .arg("--manifest-path")
+        .arg(&manifest_path)
+        .output()
+        .map_err(|e| format!("failed to execute cargo metadata: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed for {}: {}",
+            crate_dir,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("failed to parse cargo metadata: {}", e))?;
+
+    let manifest_path = std::fs::canonicalize(&manifest_path)
+        .map_err(|e| format!("failed to find {}: {}", manifest_path.display(), e))?;
+    let crate_name = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|p| p["manifest_path"].as_str().map(std::path::Path::new) == Some(&manifest_path))
+        .and_then(|p| p["name"].as_str())
+        .ok_or_else(|| format!("no package found in {}", crate_dir))?
+        .replace('-', "_");
+    let target_dir = metadata["target_directory"]
+        .as_str()
+        .ok_or("cargo metadata did not report a target directory")?
+        .to_owned();
+    Ok((crate_name, target_dir))
 }
 
-fn main() -> Result<(), String> {
+/// How a run ended, reported through the exit code so that scripts and CI
+/// can branch on it. Errors of the tool itself exit with `TOOL_ERROR`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outcome {
+    AllSucceeded = 0,
+    /// The analysis of at least one function failed
+    SomeFailed = 1,
+    /// The longest path of at least one function exceeds `--budget`, or the
+    /// budget annotated in its source
+    BudgetExceeded = 2,
+}
+
+const TOOL_ERROR: i32 = 3;
+
+fn main() {
+    // Panics abort the process, so exit with the right code before that happens
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        std::process::exit(TOOL_ERROR);
+    }));
+
+    let code = match run() {
+        Ok(outcome) => outcome as i32,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            TOOL_ERROR
+        }
+    };
+    std::process::exit(code);
+}
+
+fn run() -> Result<Outcome, String> {
     let opt = Opt::from_args(); // get CLI inputs
 
+    if let Some(shell) = opt.completions {
+        Opt::clap().gen_completions_to("wcet-rs", shell, &mut std::io::stdout());
+        return Ok(Outcome::AllSucceeded);
+    }
+
     if opt.verbose >= 1 {
         // Enable logs in Haybale. Useful for debugging
         // but dramatically slow down executions and increase memory use.
@@ -224,102 +1530,335 @@ fn main() -> Result<(), String> {
         SimpleLogger::new().init().unwrap();
     }
 
+    if opt.analysis_worker {
+        return isolate::run_worker();
+    }
+
+    if !opt.compare_boards.is_empty() {
+        let summaries = compare::load_or_run(&opt.compare_boards, &opt.resultspath)?;
+        let matrix = compare::matrix(&summaries);
+        let filename = format!("{}/comparison.tsv", opt.resultspath);
+        std::fs::write(&filename, &matrix).expect("failed to write board comparison");
+        print!("{}", matrix);
+        return Ok(Outcome::AllSucceeded);
+    }
+
+    let noise = report::Noise {
+        absolute: opt.noise_instructions,
+        relative_pct: opt.noise_percent,
+    };
+
+    if let Some(dir) = &opt.trend {
+        let runs = trend::load_runs(std::path::Path::new(dir))?;
+        println!("Found {} runs", runs.len());
+        let trend = trend::render(runs);
+        let filename = format!("{}/trend.txt", opt.resultspath);
+        std::fs::write(&filename, &trend).expect("failed to write trend report");
+        print!("{}", trend);
+        return Ok(Outcome::AllSucceeded);
+    }
+
+    if !opt.query.is_empty() {
+        let path = opt.results_db.clone().unwrap_or(format!(
+            "{}/{}",
+            opt.resultspath,
+            results_db::FILE_NAME
+        ));
+        if !std::path::Path::new(&path).exists() {
+            return Err(format!("no results database at {}", path));
+        }
+        let db = results_db::open(&path)?;
+        print!(
+            "{}",
+            query::run(&db, &opt.query, opt.regression_threshold, &noise)?
+        );
+        return Ok(Outcome::AllSucceeded);
+    }
+
+    if !opt.diff_boards.is_empty() {
+        if opt.diff_boards.len() != 2 {
+            return Err("--diff-boards takes exactly two board paths".to_owned());
+        }
+        let summaries = compare::load_or_run_paths(&opt.diff_boards, &opt.resultspath)?;
+        compare::check_build_features(&summaries, &opt.resultspath, opt.allow_feature_mismatch)?;
+        let diff = compare::diff(&summaries[0], &summaries[1], &noise);
+        let filename = format!("{}/diff.tsv", opt.resultspath);
+        std::fs::write(&filename, &diff).expect("failed to write board diff");
+        if opt.format == ReportFormat::Markdown {
+            let markdown = report::markdown_comparison(
+                &format!("WCET: {} vs {}", summaries[0].0, summaries[1].0),
+                &compare::diff_rows(&summaries[0], &summaries[1]),
+                opt.regression_threshold,
+                &noise,
+            );
+            let filename = format!("{}/diff.md", opt.resultspath);
+            std::fs::write(&filename, &markdown).expect("failed to write board diff");
+            print!("{}", markdown);
+        } else {
+            print!("{}", diff);
+        }
+        return Ok(Outcome::AllSucceeded);
+    }
+
+    let config = match &opt.config {
+        Some(path) => config_file::ConfigFile::load(path)?,
+        None => config_file::ConfigFile::default(),
+    };
+    let exclusions = config_file::Exclusions::new(&config, &opt.exclude, opt.exclusion_profile)?;
+
     // set to board to be evaluated. Currently, not all tock boards are supported.
     // This works because this crate uses the same rust toolchain as Tock.
-    let board_path_str = opt.tockpath.to_owned() + "/boards/" + &opt.board.to_owned();
-    if !opt.skip_compile {
-        println!("Compiling {:?}, please wait...", board_path_str);
-
-        assert!(Command::new("make")
-            .arg("-C")
-            .arg(&board_path_str)
-            .arg("clean")
-            .output()
-            .expect("failed to execute make clean")
-            .status
-            .success());
-        let output = Command::new("make")
-            .arg("-C")
-            .arg(&board_path_str)
-            .output()
-            .expect("failed to execute make");
-        assert!(output.status.success());
-        let str_output = String::from_utf8(output.stderr).unwrap();
-        if !str_output.contains("Finished release") {
-            panic!("Build failed, output: {}", str_output);
-        }
-        println!("Finished building");
+    let board_path_str = if let Some(path) = &opt.board_path {
+        host::trim_path(path).to_owned()
+    } else if opt.no_tock {
+        opt.board.clone()
+    } else {
+        opt.tockpath.to_owned() + "/boards/" + &opt.board.to_owned()
+    };
+    // Out-of-tree boards are named after their directory, and their bitcode
+    // after their crate
+    let board_crate = match &opt.board_path {
+        Some(path) => Some(board_crate_metadata(path)?),
+        None => None,
+    };
+    let board = match &opt.board_path {
+        Some(_) => host::last_component(&board_path_str),
+        None => opt.board.clone(),
+    };
+    let bitcode_name = match &board_crate {
+        Some((crate_name, _)) => crate_name.clone(),
+        None => opt.board.clone(),
+    };
+    let target_root: String = opt
+        .target_dir
+        .clone()
+        .or_else(|| board_crate.as_ref().map(|(_, dir)| dir.clone()))
+        .unwrap_or_else(|| opt.tockpath.clone() + "/target");
+    let triple = target_triple(&opt, &board_path_str);
+    let target_dir: String = target_root.clone() + "/" + &triple + "/release/";
+
+    // Each run writes to its own directory, so that runs of the same board
+    // do not overwrite each other's results
+    let run_dir = match opt.dry_run {
+        true => None,
+        false => Some(run_dir::RunDirectory::create(
+            &opt.resultspath,
+            &board,
+            opt.resume,
+        )?),
+    };
+    let board_results = match &run_dir {
+        Some(dir) => dir.path.clone(),
+        None => run_dir::latest(&opt.resultspath, &board),
+    };
+
+    let skip_compile = opt.skip_compile || opt.dry_run;
+    let build_log = format!("{}/build.log", board_results);
+    let mut built_bitcode = vec![];
+    // crates outside of Tock have no Makefile to build them with
+    let unbuildable = opt.no_tock && opt.builder == Builder::Make;
+    let built = !skip_compile && !unbuildable;
+    if unbuildable && !skip_compile {
+        println!("Not a Tock board, skipping compilation; build with `--emit=llvm-bc` first");
+    } else if built {
+        built_bitcode = build(
+            &opt,
+            &board_path_str,
+            &triple,
+            &target_root,
+            true,
+            &config.build,
+            &build_log,
+        )?;
+    }
+
+    // Make sure the bitcode corresponds to the sources the results are attributed to
+    let source_roots: Vec<&std::path::Path> = if opt.no_tock {
+        vec![std::path::Path::new(".")]
+    } else if let Some(path) = &opt.board_path {
+        vec![
+            std::path::Path::new(path),
+            std::path::Path::new(&opt.tockpath),
+        ]
+    } else {
+        vec![std::path::Path::new(&opt.tockpath)]
+    };
+    let fingerprint = freshness::source_fingerprint(&source_roots);
+    let freshness = if built {
+        freshness::record(&target_dir, &fingerprint)?;
+        freshness::Freshness::Fresh
+    } else {
+        freshness::check(&target_dir, &fingerprint)
+    };
+    let freshness_str = match freshness {
+        freshness::Freshness::Fresh => "fresh",
+        freshness::Freshness::Stale if opt.allow_stale || opt.dry_run => {
+            println!("Warning: bitcode is stale, the sources changed since it was built");
+            "stale"
+        }
+        freshness::Freshness::Stale => {
+            return Err(
+                "bitcode is stale, the sources changed since it was built. Rebuild, or pass --allow-stale"
+                    .to_owned(),
+            )
+        }
+        freshness::Freshness::Unknown => {
+            println!("Warning: bitcode was not built by wcet-rs, cannot verify it matches the sources");
+            "unknown"
+        }
+    };
+    if !opt.dry_run {
+        std::fs::write(
+            format!("{}/fingerprint.txt", board_results),
+            format!("sources: {}\nbitcode: {}\n", fingerprint, freshness_str),
+        )
+        .expect("failed to write fingerprint");
     }
     println!("starting");
 
+    let mut git_states = vec![];
     if opt.save_git_history {
-        // Save current program state into a file, for reproducability later
-        // Uses git commands for this
-        let git_diff_filename = (&opt.resultspath).to_owned() + "/git_diff.txt";
-        let git_diff_file = File::create(git_diff_filename).unwrap();
-        assert!(Command::new("git")
-            .current_dir(opt.tockpath.to_owned())
-            .arg("diff")
-            .stdout(git_diff_file)
-            .status()
-            .expect("Failed to execute git diff")
-            .success());
-
-        // Do same thing for git log
-        let git_log_filename = (&opt.resultspath).to_owned() + "/git_log.txt";
-        let git_log_file = File::create(git_log_filename).unwrap();
-
-        let git_log_out = Command::new("git")
-            .current_dir(opt.tockpath.to_owned())
-            .arg("log")
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("Failed to execute git log")
-            .stdout
-            .expect("Failed to open log stdout");
-
-        assert!(Command::new("head")
-            .stdin(Stdio::from(git_log_out))
-            .stdout(git_log_file)
-            .arg("-n")
-            .arg("60")
-            .status()
-            .expect("Failed to execute head")
-            .success());
+        // Save current program state, for reproducability later: the state
+        // of this tool's checkout and of the tock tree go into the manifest
+        match git_history::capture(env!("CARGO_MANIFEST_DIR")) {
+            Ok(state) => git_states.push(state),
+            Err(e) => println!("Warning: not recording the state of wcet-rs: {}", e),
+        }
+        let tock = git_history::capture(&opt.tockpath)?;
+        let git_diff_filename = format!("{}/git_diff.txt", board_results);
+        std::fs::write(git_diff_filename, &tock.diff).expect("Failed to write git diff");
+        git_states.push(tock);
+
+        // Do same thing for the first 60 lines of git log
+        let git_log_filename = format!("{}/git_log.txt", board_results);
+        std::fs::write(git_log_filename, git_history::log(&opt.tockpath, 60)?)
+            .expect("Failed to write git log");
     }
 
-    // For now, assume target under analysis, located in the tock submodule of this crate.
-    // Assume it is a thumbv7 target unless it is one of three whitelisted riscv targets.
-    let target_dir: String = opt.tockpath.clone()
-        + if board_path_str.contains("opentitan")
-            || board_path_str.contains("arty_e21")
-            || board_path_str.contains("hifive1")
-        {
-            "/target/riscv32imc-unknown-none-elf/release/"
-        } else {
-            "/target/thumbv7em-none-eabi/release/"
-        };
+    // Kernel options are cargo features, recorded so that runs of different
+    // configurations are not compared
+    let build_features = build_features::detect(&target_dir);
+    for (name, features) in &build_features.features {
+        if !features.is_empty() {
+            println!("Built {} with features {}", name, features.join(", "));
+        }
+    }
 
-    let bc_dir: String = target_dir + "deps/";
-    let disassembly = get_disassembly(&bc_dir, &opt.board);
+    // Prefer the location the build reported over the conventional layout
+    let bc_dir: String = match cargo_build::bitcode_dir(&built_bitcode, &bitcode_name) {
+        Some(dir) => format!("{}/", dir.display()),
+        None => target_dir.clone() + "deps/",
+    };
 
-    let paths = glob(&[&bc_dir, "/**/*.bc"].concat())
-        .unwrap()
-        .map(|x| x.unwrap());
-    println!("globbed");
-    let project = Project::from_bc_paths(paths)?;
+    // Driver numbers userspace reaches the board's capsules through
+    let drivers = match opt.no_tock {
+        true => HashMap::new(),
+        false => drivers::driver_numbers(
+            std::path::Path::new(&opt.tockpath),
+            std::path::Path::new(&board_path_str),
+        ),
+    };
+    let dispatch = config
+        .syscall_dispatch
+        .as_ref()
+        .map(|dispatch| drivers::dispatch_entry(dispatch, &drivers));
+    let mut bitcode = project_bitcode(&bc_dir, &built_bitcode);
+    let entry_points = constraints::generate(
+        &bc_dir,
+        &bitcode,
+        &config.arguments,
+        &config.parametric,
+        config.allow.as_ref(),
+        dispatch.as_ref(),
+    )?;
+    bitcode.extend(entry_points.module.clone());
+    println!("Loading {} bitcode files", bitcode.len());
+    let project = Project::from_bc_paths(bitcode.iter().cloned())?;
     println!("Project loaded");
+    let abi = syscall_abi::detect(&project);
+    match abi {
+        SyscallAbi::Legacy => println!("Kernel uses the Tock 1.x Driver syscall ABI"),
+        SyscallAbi::SyscallDriver => println!("Kernel uses the Tock 2.x SyscallDriver syscall ABI"),
+        SyscallAbi::Unknown => {}
+    }
 
-    let mut functions_to_analyze = vec![];
-    let mut func_name_iter = retrieve_functions_for_analysis(&project, opt.functions);
+    let mut functions_to_analyze: Vec<&str> = vec![];
+    // crate names are written with underscores in symbols
+    let crates: Vec<String> = opt.crates.iter().map(|c| c.replace('-', "_")).collect();
+    let is_excluded = |name: &str| {
+        exclusions.is_excluded(name, &category_name(name, abi))
+            || (!crates.is_empty() && !crates.contains(&crate_name(name)))
+    };
+
+    let mut func_name_iter = retrieve_functions_for_analysis(&project, opt.functions, abi)
+        .filter(|(f, _m)| !is_excluded(&f.name));
     if opt.print_function_names {
         for f in func_name_iter {
             println!("{:?}", f.0.name);
         }
-        return Ok(());
+        return Ok(Outcome::AllSucceeded);
+    }
+    if opt.print_metrics {
+        let mut estimator = difficulty::Estimator::new(&project);
+        let mut metrics: Vec<(&str, difficulty::FunctionMetrics)> = func_name_iter
+            .map(|(f, _m)| (f.name.as_str(), estimator.metrics(&f.name)))
+            .collect();
+        print!("{}", difficulty::render_metrics(&mut metrics));
+        return Ok(Outcome::AllSucceeded);
     }
-    if opt.func_name_contains.is_some() {
-        let vec = opt.func_name_contains.unwrap().clone();
+    if opt.calibrate {
+        functions_to_analyze.extend(calibration::suite(&project, &config.calibration)?);
+        println!("Calibrating with {} functions", functions_to_analyze.len());
+        if functions_to_analyze.is_empty() {
+            return Err("no calibration functions found".to_owned());
+        }
+    } else if opt.pick {
+        let mut names: Vec<&str> = func_name_iter.map(|(f, _m)| f.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        functions_to_analyze.extend(picker::pick(&names)?);
+        if functions_to_analyze.is_empty() {
+            return Err("no functions picked".to_owned());
+        }
+    } else if let Some(name) = &opt.func_exact {
+        let func_name = resolve_function(&project, name)?;
+        println!("Profiling {:?}", func_name);
+        functions_to_analyze.push(func_name);
+    } else if let Some(root) = &opt.call_graph_root {
+        let root = resolve_function(&project, root)?;
+        let graph = call_graph::CallGraph::new(&project);
+        functions_to_analyze.extend(
+            graph
+                .reachable_from(&[root])
+                .into_iter()
+                .filter(|name| *name == root || !is_excluded(name)),
+        );
+        println!(
+            "{} functions reachable from {}",
+            functions_to_analyze.len(),
+            rustc_demangle::demangle(root)
+        );
+    } else if let Some(pattern) = &opt.func_regex {
+        let re = Regex::new(pattern).map_err(|e| format!("invalid --func-regex: {}", e))?;
+        functions_to_analyze.extend(
+            project
+                .all_functions()
+                .filter(|(f, _m)| {
+                    re.is_match(&rustc_demangle::demangle(&f.name).to_string())
+                        && !is_excluded(&f.name)
+                })
+                .map(|(f, _m)| f.name.as_str()),
+        );
+        println!(
+            "{} functions matched {:?}",
+            functions_to_analyze.len(),
+            pattern
+        );
+        if functions_to_analyze.is_empty() {
+            return Err("no functions matched --func-regex".to_owned());
+        }
+    } else if opt.func_name_contains.is_some() {
+        let vec = opt.func_name_contains.clone().unwrap();
         println!("func_name_contains: {:?}", vec);
         let func_name = &project
             .all_functions()
@@ -346,44 +1885,559 @@ fn main() -> Result<(), String> {
         println!("Profiling {:?}", func_name);
         functions_to_analyze.push(func_name);
     } else if opt.function_index == 0 {
-        functions_to_analyze.extend(func_name_iter.map(|(f, _m)| &f.name));
+        functions_to_analyze.extend(func_name_iter.map(|(f, _m)| f.name.as_str()));
     } else {
         functions_to_analyze.push(&func_name_iter.nth(opt.function_index - 1).unwrap().0.name);
     }
 
+    if opt.kernel_loop {
+        let scheduler = kernel_loop::scheduler_functions(&project);
+        if scheduler.is_empty() {
+            println!("Warning: no scheduler found to bound the kernel loop");
+        }
+        for name in scheduler {
+            if !functions_to_analyze.contains(&name) {
+                functions_to_analyze.push(name);
+            }
+        }
+    }
+
+    if let Some(seed) = opt.seed {
+        functions_to_analyze.sort_unstable();
+        functions_to_analyze.dedup();
+        // Read by boolector when creating solvers, here and in child processes
+        std::env::set_var("BTORSEED", seed.to_string());
+    }
+
+    let dispatch_functions = virtualizers::dispatch_functions(&project);
+    let mut mux_clients = virtualizers::client_counts(&project);
+    let alarm_muxes: Vec<&virtualizers::Mux> = dispatch_functions
+        .iter()
+        .map(|(_, mux)| mux)
+        .filter(|mux| mux.mux == virtualizers::ALARM_MUX)
+        .collect();
+    let alarm_clients = match config.alarm_clients {
+        Some(clients) => Some(clients),
+        None if opt.no_tock => None,
+        None if alarm_muxes.iter().any(|mux| mux_clients.contains_key(mux)) => None,
+        // None found means the alarms are created in a way this does not recognize
+        None => Some(alarms::virtual_alarm_count(
+            std::path::Path::new(&board_path_str),
+            std::path::Path::new(&opt.tockpath),
+        ))
+        .filter(|&clients| clients > 0),
+    };
+    if let Some(clients) = alarm_clients {
+        println!("Bounding the virtual alarm mux to {} clients", clients);
+        for mux in alarm_muxes {
+            mux_clients.insert(mux.clone(), clients);
+        }
+    }
+    let dispatch_bounds: HashMap<String, usize> = dispatch_functions
+        .iter()
+        .filter_map(|(name, mux)| {
+            virtualizers::dispatch_bound(mux, &mux_clients).map(|bound| (name.to_string(), bound))
+        })
+        .collect();
+
+    let baseline = opt.baseline.as_ref().map(|path| {
+        let contents = std::fs::read_to_string(path).expect("failed to read baseline summary");
+        report::parse_summary(&contents)
+    });
+
+    // a new calibration measures uncorrected counts
+    let calibration_factor = match opt.calibrate {
+        true => None,
+        false => calibration::Calibration::load(&run_dir::board_dir(&opt.resultspath, &board))?
+            .map(|c| c.factor),
+    };
+    if let Some(factor) = calibration_factor {
+        println!(
+            "Correcting cycle counts by the board's calibration factor {:.3}",
+            factor
+        );
+    }
+
+    // functions written in assembly or C are stubbed with their cost, and
+    // those with a trusted cost replaced by it, unless hooked
+    let mut hooks = asm_stubs::hooks(&project, &config.asm_stubs, &target_dir);
+    let cost_hooks: Vec<config_file::HookConfig> =
+        config.symbol_costs.iter().map(|c| c.hook()).collect();
+    let fixed_costs: HashMap<String, PathLength> = hooks::resolve(&project, &cost_hooks)?
+        .into_iter()
+        .map(|(name, hook)| {
+            let length = PathLength {
+                ir: hook.ir,
+                machine: hook.machine,
+                cycles: hook.cycles,
+            };
+            hooks.insert(name.clone(), hook);
+            (name, length)
+        })
+        .collect();
+    hooks.extend(hooks::resolve(&project, &config.hooks)?);
+
+    let profile = opt.profile.settings();
+    let settings = AnalysisSettings {
+        timeout_s: opt.timeout.unwrap_or(profile.timeout_s),
+        loop_bound: opt.loop_bound.unwrap_or(profile.loop_bound),
+        time_results: opt.time_results,
+        cost_model: config.cost_model.clone(),
+        clock_mhz: opt.clock_mhz,
+        engine: opt.engine.unwrap_or(profile.engine),
+        calibration_factor,
+        time_budget_s: opt.time_budget.or(profile.time_budget_s),
+        format: opt.format,
+        detail: opt.detail,
+        compress: opt.compress,
+        listings: match opt.detail {
+            ReportDetail::Full => Some(board_results.clone()),
+            _ => None,
+        },
+        dispatch_bounds,
+        dma: dma::limits(&config.dma),
+        stability_runs: opt.stability_runs,
+        per_return_value: opt.per_return_value,
+        argument_constraints: entry_points.arguments,
+        parametric: entry_points.parametric,
+        dispatch_drivers: entry_points.drivers,
+        frontiers: run_dir
+            .as_ref()
+            .map(|dir| format!("{}/frontiers", dir.path)),
+        process_state: config.process_state.clone(),
+        panics: panics::PanicSettings {
+            extra_patterns: config.panic_functions.clone(),
+            unreachable: opt.unreachable_paths,
+            assertion: opt.assert_paths,
+            overflow: opt.overflow_paths,
+        },
+        hooks,
+        fixed_costs,
+        subsystems: subsystems::Subsystems {
+            board_crate: board.replace('-', "_"),
+            by_crate: config.subsystems.clone(),
+        },
+        syscall_abi: abi,
+    };
+
+    // Cheap static estimates of how hard each function is to analyze, to
+    // analyze the hardest last (unless ordered by name for a seeded run)
+    let mut estimator = difficulty::Estimator::new(&project);
+    let difficulties: HashMap<&str, difficulty::Difficulty> = functions_to_analyze
+        .iter()
+        .map(|&f| (f, estimator.estimate(f)))
+        .collect();
+    if opt.seed.is_none() {
+        difficulty::order_hardest_last(&mut functions_to_analyze, &difficulties);
+    }
+
+    // Report which panic functions are cut from every analyzed path
+    let panic_functions = panics::hooked_functions(&project, &settings.panics, false)?;
+    let mut panic_report = String::new();
+    for name in &panic_functions {
+        panic_report += &format!("{}\n", rustc_demangle::demangle(name));
+    }
+    println!("Hooking {} panic functions", panic_functions.len());
+    if settings.process_state.bound_process_loops {
+        match settings.process_state.process_loop_bound(&project) {
+            Some(num_procs) => println!("Bounding process array loops to {} processes", num_procs),
+            None => println!(
+                "Warning: no PROCESSES array found, set num_procs under [process_state] to bound process array loops"
+            ),
+        }
+    }
+    if !opt.dry_run {
+        std::fs::write(format!("{}/panic_hooks.txt", board_results), &panic_report)
+            .map_err(|e| format!("failed to write panic hook report: {}", e))?;
+        std::fs::write(
+            format!("{}/difficulty.tsv", board_results),
+            difficulty::render(&functions_to_analyze, &difficulties),
+        )
+        .map_err(|e| format!("failed to write difficulty estimates: {}", e))?;
+    }
+
+    if opt.dry_run {
+        println!("Board path: {}", board_path_str);
+        println!("Target dir: {}", target_dir);
+        println!("Results path: {}", board_results);
+        println!(
+            "{} functions would be analyzed:",
+            functions_to_analyze.len()
+        );
+        for f in functions_to_analyze {
+            println!(
+                "  [{}] {}",
+                category_name(f, abi),
+                rustc_demangle::demangle(f)
+            );
+            let (bound, source) = loop_bound(&project, f, &settings);
+            match source {
+                Some(source) => println!(
+                    "      timeout: {}s, loop bound: {} ({})",
+                    settings.timeout_s, bound, source
+                ),
+                None => println!(
+                    "      timeout: {}s, loop bound: {}",
+                    settings.timeout_s, bound
+                ),
+            }
+            let d = &difficulties[f];
+            println!(
+                "      {} blocks, {} loops, call depth {}, {} dynamic calls: about {}s",
+                d.blocks,
+                d.loops,
+                d.call_depth,
+                d.dynamic_calls,
+                d.predicted_s()
+            );
+            if d.likely_timeout(settings.timeout_s, settings.time_budget_s) {
+                println!("      Warning: likely to time out");
+            }
+        }
+        return Ok(Outcome::AllSucceeded);
+    }
+
+    if opt.watch {
+        let func_name = match functions_to_analyze.as_slice() {
+            [f] => (*f).to_owned(),
+            _ => return Err("--watch requires selecting a single function".to_owned()),
+        };
+        let root = std::path::Path::new(opt.board_path.as_ref().unwrap_or(&opt.tockpath));
+        let mut previous = None;
+        loop {
+            let last_seen = watch::latest_source_mtime(root);
+            let disassembly = load_disassembly(&opt, &bc_dir, &bitcode_name)?;
+            let disassembly_index = DisassemblyIndex::new(&disassembly);
+            let result = analyze_function(
+                &bitcode,
+                &board_path_str,
+                &func_name,
+                &settings,
+                &disassembly,
+                &disassembly_index,
+            );
+            result.save(
+                &function_results_stem(&board_results, &func_name),
+                settings.format,
+                settings.detail,
+                settings.compress,
+            )?;
+            let result = result.outcome();
+            watch::print_delta(&func_name, &result, previous);
+            if let Ok(length) = result {
+                previous = Some(length.ir);
+            }
+            // Keep waiting until the changed sources build again. Crates
+            // outside of Tock are rebuilt by the user.
+            let mut changed = watch::wait_for_change(root, last_seen);
+            if !opt.no_tock || opt.builder == Builder::Cargo {
+                let built = loop {
+                    match build(
+                        &opt,
+                        &board_path_str,
+                        &triple,
+                        &target_root,
+                        false,
+                        &config.build,
+                        &build_log,
+                    ) {
+                        Ok(built) => break built,
+                        Err(e) => println!("{}", e),
+                    }
+                    changed = watch::wait_for_change(root, changed);
+                };
+                // the entry points only call into functions by name
+                bitcode = project_bitcode(&bc_dir, &built);
+                bitcode.extend(entry_points.module.clone());
+                freshness::record(&target_dir, &freshness::source_fingerprint(&source_roots))?;
+            }
+        }
+    }
+
+    let disassembly = load_disassembly(&opt, &bc_dir, &bitcode_name)?;
+    // where each function starts, found once for all the paths counted
+    let disassembly_index = DisassemblyIndex::new(&disassembly);
+
+    // How reliable machine instruction counts are, by how much of each
+    // module's code the counting finds in the disassembly
+    let disassembly_stats = disassembly_stats::collect(&project, &disassembly, &disassembly_index);
+    let indexed = disassembly_stats::total(&disassembly_stats);
+    println!(
+        "Found {} of functions and {} of basic blocks in the disassembly, see disassembly_stats.tsv",
+        disassembly_stats::percent(indexed.functions_found, indexed.functions),
+        disassembly_stats::percent(indexed.blocks_found, indexed.blocks)
+    );
+    std::fs::write(
+        format!("{}/disassembly_stats.tsv", board_results),
+        disassembly_stats::render(&disassembly_stats),
+    )
+    .map_err(|e| format!("failed to write disassembly statistics: {}", e))?;
+
+    if let Some(addr) = &opt.serve {
+        daemon::serve(addr, &project, |func_name, overrides| {
+            let mut settings = settings.clone();
+            overrides.apply(&mut settings);
+            let result = analyze_project_function(
+                &project,
+                &board_path_str,
+                func_name,
+                &settings,
+                &disassembly,
+                &disassembly_index,
+            );
+            result.save(
+                &function_results_stem(&board_results, func_name),
+                settings.format,
+                settings.detail,
+                settings.compress,
+            )?;
+            result.outcome()
+        })?;
+        return Ok(Outcome::AllSucceeded);
+    }
+
+    // Outcomes are checkpointed as each function completes
+    let checkpoint = match opt.resume {
+        true => checkpoint::Checkpoint::resume(&board_results, &fingerprint, &settings)?,
+        false => checkpoint::Checkpoint::new(&fingerprint, &settings),
+    };
+    let resumed: HashMap<String, Result<PathLength, String>> = checkpoint
+        .outcomes
+        .iter()
+        .filter(|(f, _result)| functions_to_analyze.contains(&f.as_str()))
+        .map(|(f, result)| (f.clone(), result.clone()))
+        .collect();
+    if opt.resume {
+        println!(
+            "Resuming: {} of {} functions already analyzed",
+            resumed.len(),
+            functions_to_analyze.len()
+        );
+    }
+    functions_to_analyze.retain(|f| !resumed.contains_key(*f));
+    let checkpoint = Arc::new(Mutex::new(checkpoint));
+
+    if let Some(niceness) = opt.nice {
+        resources::set_niceness(niceness)?;
+    }
+    if let Some(list) = &opt.cpus {
+        let cpus = resources::parse_cpu_list(list)?;
+        resources::pin_to_cpus(&cpus)?;
+        println!("Analyzing on CPUs {:?}", cpus);
+    }
+
+    let tock_commit = metrics::tock_commit(&opt.tockpath);
+    let (manifest, scheduler) = match opt.dependency_order {
+        true => {
+            let graph = call_graph::CallGraph::new(&project);
+            let deps = scheduler::dependencies(&graph, &functions_to_analyze);
+            let levels = scheduler::levels(&functions_to_analyze, &deps);
+            println!(
+                "Analyzing in dependency order: {} levels of functions",
+                levels.len()
+            );
+            let manifest = manifest::Manifest {
+                build_features: build_features.clone(),
+                ..manifest::Manifest::new(
+                    &board,
+                    &tock_commit,
+                    opt.seed,
+                    &settings,
+                    &[],
+                    &levels,
+                    &git_states,
+                )
+            };
+            (
+                manifest,
+                scheduler::Scheduler::by_dependencies(&functions_to_analyze, deps),
+            )
+        }
+        false => {
+            let workers = manifest::assign_workers(&functions_to_analyze, opt.jobs);
+            let manifest = manifest::Manifest {
+                build_features: build_features.clone(),
+                ..manifest::Manifest::new(
+                    &board,
+                    &tock_commit,
+                    opt.seed,
+                    &settings,
+                    &workers,
+                    &[],
+                    &git_states,
+                )
+            };
+            (manifest, scheduler::Scheduler::assigned(workers))
+        }
+    };
+    manifest.save(&board_results)?;
+    let threads = match opt.dependency_order {
+        // rather than a thread per function, of which a kernel has thousands
+        true => opt.jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|cpus| cpus.get())
+                .unwrap_or(1)
+                .min(functions_to_analyze.len().max(1))
+        }),
+        false => scheduler.queues(),
+    };
+    let scheduler = Arc::new(scheduler);
+
+    let mut sinks: Vec<Box<dyn sinks::ResultSink>> = vec![
+        Box::new(sinks::FunctionFiles {
+            board_results: board_results.clone(),
+            format: settings.format,
+            detail: settings.detail,
+            compress: settings.compress,
+        }),
+        Box::new(sinks::SummaryFile {
+            path: format!("{}/summary.txt", board_results),
+            clock_mhz: opt.clock_mhz,
+            calibration_factor,
+            min_machine_ratio: opt.min_machine_ratio,
+        }),
+    ];
+    if settings.format == ReportFormat::Markdown {
+        sinks.push(Box::new(sinks::MarkdownComparison {
+            path: format!("{}/summary.md", board_results),
+            board: board.clone(),
+            baseline: baseline.clone(),
+            regression_threshold: opt.regression_threshold,
+            noise,
+        }));
+    }
+    let database = {
+        let mut checkpoint = checkpoint.lock().unwrap();
+        let database = results_db::ResultsDatabase::new(
+            &format!("{}/{}", board_results, results_db::FILE_NAME),
+            &opt.results_db.clone().unwrap_or(format!(
+                "{}/{}",
+                opt.resultspath,
+                results_db::FILE_NAME
+            )),
+            &manifest,
+            opt.resume,
+            checkpoint.accumulated_run.as_ref(),
+        )?;
+        // saved at once, for a run interrupted before its first result
+        checkpoint.accumulated_run = Some(database.accumulated_run());
+        checkpoint.save(&board_results)?;
+        database
+    };
+    sinks.push(Box::new(database));
+    sinks.push(Box::new(sinks::AllocationReport {
+        path: format!("{}/allocations.txt", board_results),
+        allocating: vec![],
+    }));
+    if let Some(command) = &opt.on_complete {
+        let functions = match &opt.on_complete_functions {
+            Some(re) => Some(
+                Regex::new(re).map_err(|e| format!("invalid --on-complete-functions: {}", e))?,
+            ),
+            None => None,
+        };
+        sinks.push(Box::new(sinks::Notification::new(
+            command.clone(),
+            functions,
+        )));
+    }
+    sinks.extend(sinks::configured(&config.sinks, &board));
+    let sinks = Arc::new(Mutex::new(sinks));
+    let failures = Arc::new(config_file::Failures::new(&config.failures)?);
+    let budget = opt.budget;
+    let over_budget = move |result: &Result<PathLength, String>| matches!((result, budget), (Ok(length), Some(budget)) if length.ir > budget);
+
     let mut children = vec![];
-    let all_results = Mutex::new(HashMap::new());
+    let all_results = Mutex::new(resumed);
     let arc = Arc::new(all_results);
-    let timeout = opt.timeout;
+    // analysis time of each function, to suggest overrides after a slow run
+    let times: Arc<Mutex<HashMap<String, f64>>> = Arc::new(Mutex::new(HashMap::new()));
+    // syscall dispatch to each driver, by driver number
+    let dispatch_by_driver: Arc<Mutex<drivers::DispatchByDriver>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    // longest paths listed so far, each listed once
+    let paths = Arc::new(Mutex::new(dedup::PathIndex::default()));
     let start = Instant::now();
-    for f in functions_to_analyze {
-        let f = f.clone();
+    for worker in 0..threads {
+        let scheduler = scheduler.clone();
         let arc = arc.clone();
+        let times = times.clone();
+        let dispatch_by_driver = dispatch_by_driver.clone();
+        let paths = paths.clone();
+        let checkpoint = checkpoint.clone();
+        let sinks = sinks.clone();
+        let board_results = board_results.clone();
         let name = board_path_str.clone();
+        let bitcode = bitcode.clone();
         let bc_dir_cpy = bc_dir.clone();
-        let resultspath = opt.resultspath.clone();
         let disassembly_cpy: Disassem = disassembly.clone();
-        let time_results = opt.time_results;
+        let index_cpy = disassembly_index.clone();
+        let settings = settings.clone();
+        let bitcode_name = bitcode_name.clone();
+        let memory_limit_mb = opt.memory_limit_mb;
+        let dump_timeouts = opt.dump_timeouts;
+        let max_load = opt.max_load;
+        let failures = failures.clone();
+        let fail_fast = opt.fail_fast;
         children.push(thread::spawn(move || {
-            match analyze_and_save_results(
-                &bc_dir_cpy,
-                &name,
-                &f,
-                timeout,
-                &resultspath,
-                time_results,
-                &disassembly_cpy,
-            ) {
-                Ok(s) => {
-                    arc.lock().map_or((), |mut map| {
-                        map.insert(f, s);
-                    });
+            let mut child = isolate::Worker::new(memory_limit_mb);
+            while let Some(claim) = scheduler.next(worker) {
+                let f = claim.function.clone();
+                if let Some(max_load) = max_load {
+                    resources::wait_for_load(max_load, worker);
+                }
+                let isolated = memory_limit_mb.is_some() || dump_timeouts;
+                let mut result = if isolated {
+                    let job = isolate::Job {
+                        bc_dir: bc_dir_cpy.clone(),
+                        bitcode: bitcode.clone(),
+                        bitcode_name: bitcode_name.clone(),
+                        board_path_str: name.clone(),
+                        func_name: f.clone(),
+                        settings: settings.clone(),
+                        board_results: board_results.clone(),
+                        dump_timeouts,
+                    };
+                    child.analyze(&job)
+                } else {
+                    analyze_function(&bitcode, &name, &f, &settings, &disassembly_cpy, &index_cpy)
+                };
+                if let Ok(mut paths) = paths.lock() {
+                    result.same_path_as = paths.same_path_as(&result);
+                }
+                if let Ok(mut sinks) = sinks.lock() {
+                    for sink in sinks.iter_mut() {
+                        if let Err(e) = sink.function_done(&result) {
+                            println!("{}", e);
+                        }
+                    }
+                }
+                if let Ok(mut times) = times.lock() {
+                    times.insert(f.clone(), result.stats.analysis_time_s);
                 }
-                Err(e) => {
-                    arc.lock().map_or((), |mut map| {
-                        map.insert(f, e);
-                    });
+                if let (Some(points), Ok(mut dispatch)) =
+                    (&result.by_driver, dispatch_by_driver.lock())
+                {
+                    drivers::record_dispatch(&mut dispatch, points);
+                }
+                let result = result.outcome();
+                if let Ok(mut checkpoint) = checkpoint.lock() {
+                    checkpoint.outcomes.insert(f.clone(), result.clone());
+                    if let Err(e) = checkpoint.save(&board_results) {
+                        println!("{}", e);
+                    }
                 }
+                let failed = result.is_err() || over_budget(&result);
+                if failed && !failures.tolerates(&f) && (fail_fast || failures.aborts(&f)) {
+                    println!(
+                        "{} failed, not starting further analyses",
+                        rustc_demangle::demangle(&f)
+                    );
+                    scheduler.stop();
+                }
+                arc.lock().map_or((), |mut map| {
+                    map.insert(f, result);
+                });
             }
         }));
     }
@@ -393,31 +2447,243 @@ fn main() -> Result<(), String> {
         let _ = child.join();
     }
     // Now, result of each thread is in all_results.
-    let filename = (&opt.resultspath).to_owned() + "/" + &opt.board + "/summary.txt";
-    println!("{:?}", filename);
-    let mut file = File::create(filename).unwrap();
-
-    let mut data = String::new();
-    let data = arc
-        .lock()
-        .map(|map| {
-            for (k, v) in map.iter() {
-                data = data + k + ": " + v + "\n";
-            }
-            data
-        })
-        .unwrap();
-    file.write_all(data.as_bytes()).unwrap();
+    let skipped = {
+        let map = arc.lock().unwrap();
+        functions_to_analyze
+            .iter()
+            .filter(|f| !map.contains_key(**f))
+            .count()
+    };
+    if skipped > 0 {
+        println!("Run stopped early, {} functions not analyzed", skipped);
+    }
+    let duplicates = paths.lock().unwrap().duplicates;
+    if duplicates > 0 {
+        println!(
+            "{} results have the same longest path as another function's, listed only once",
+            duplicates
+        );
+    }
+
+    let times = times.lock().unwrap();
+    if times.values().sum::<f64>() >= suggestions::SLOW_RUN_S {
+        let suggested = suggestions::suggest(&project, &times, &settings.hooks);
+        if !suggested.is_empty() {
+            let suggested = suggestions::render(&suggested);
+            std::fs::write(format!("{}/suggestions.txt", board_results), &suggested)
+                .expect("failed to write override suggestions");
+            print!("{}", suggested);
+        }
+    }
+    // like a function's, a sink failing at the end of the run does not keep
+    // the others from writing their files
+    for sink in sinks.lock().unwrap().iter_mut() {
+        if let Err(e) = sink.run_done(&arc.lock().unwrap()) {
+            println!("{}", e);
+        }
+    }
+
+    if opt.github_annotations {
+        let map = arc.lock().unwrap();
+        for annotation in report::github_annotations(
+            &project,
+            &map,
+            opt.budget,
+            baseline.as_ref(),
+            opt.regression_threshold,
+            &opt.tockpath,
+        ) {
+            println!("{}", annotation);
+        }
+    }
+
+    if !opt.no_tock
+        && arc
+            .lock()
+            .unwrap()
+            .keys()
+            .any(|name| is_syscall_handler(name, abi))
+    {
+        // Label syscall results with the driver number userspace reaches them through
+        let map = arc.lock().unwrap();
+        let grouped = drivers::group_by_driver(
+            &drivers,
+            &map,
+            &dispatch_by_driver.lock().unwrap(),
+            |name| is_syscall_handler(name, abi),
+        );
+        let drivers_filename = format!("{}/drivers.txt", board_results);
+        std::fs::write(drivers_filename, grouped).expect("failed to write driver summary");
+        std::fs::write(
+            format!("{}/syscall_classes.tsv", board_results),
+            drivers::class_aggregates(&project, &map, |name| is_syscall_handler(name, abi)),
+        )
+        .expect("failed to write syscall class aggregates");
+    }
+
+    if !config.interrupt_sources.is_empty()
+        || arc
+            .lock()
+            .unwrap()
+            .keys()
+            .any(|name| matches_work_type(name, &KernelWorkType::Interrupts, abi))
+    {
+        // One bound per interrupt source, combining handlers with their clients
+        let map = arc.lock().unwrap();
+        let sources = interrupt_sources::aggregate(&config.interrupt_sources, &map, abi)?;
+        std::fs::write(
+            format!("{}/interrupt_sources.txt", board_results),
+            interrupt_sources::render(&sources),
+        )
+        .expect("failed to write interrupt source summary");
+    }
+
+    if opt.calibrate {
+        let calibration =
+            calibration::calibrate(&config.calibration, &arc.lock().unwrap(), &board_path_str)?;
+        // kept next to the board's runs for later ones, and with this run's results
+        calibration.save(&run_dir::board_dir(&opt.resultspath, &board))?;
+        calibration.save(&board_results)?;
+        println!("Calibration factor of {}: {:.3}", board, calibration.factor);
+    }
+
+    if opt.kernel_loop {
+        let map = arc.lock().unwrap();
+        let sources = interrupt_sources::aggregate(&config.interrupt_sources, &map, abi)?;
+        let bound = kernel_loop::bound(&map, &sources, abi);
+        std::fs::write(
+            format!("{}/kernel_loop.txt", board_results),
+            kernel_loop::render(&bound),
+        )
+        .expect("failed to write kernel loop bound");
+    }
+
+    {
+        let map = arc.lock().unwrap();
+        if map
+            .keys()
+            .any(|name| virtualizers::dispatch_of(name).is_some())
+        {
+            std::fs::write(
+                format!("{}/virtualizers.txt", board_results),
+                virtualizers::render(&mux_clients, &map),
+            )
+            .expect("failed to write virtualizer fan-out");
+        }
+    }
+
+    if let KernelWorkType::Upcalls = opt.functions {
+        let bound = upcalls::bound(&arc.lock().unwrap());
+        std::fs::write(
+            format!("{}/upcall_delivery.txt", board_results),
+            upcalls::render(&bound),
+        )
+        .expect("failed to write upcall delivery bound");
+    }
+
+    if !config.tasks.is_empty() {
+        let map = arc.lock().unwrap();
+        let mut tasks =
+            schedulability::task_set(&config.tasks, &map, opt.clock_mhz, calibration_factor)?;
+        schedulability::response_time_analysis(&mut tasks);
+        let unit = schedulability::unit(opt.clock_mhz);
+        std::fs::write(
+            format!("{}/taskset.json", board_results),
+            serde_json::to_string_pretty(&schedulability::TaskSet {
+                unit,
+                tasks: &tasks,
+            })
+            .unwrap(),
+        )
+        .expect("failed to write task set");
+        std::fs::write(
+            format!("{}/schedulability.txt", board_results),
+            schedulability::render(&tasks, unit),
+        )
+        .expect("failed to write schedulability report");
+    }
+
+    if let Some(path) = &opt.call_graph_out {
+        let map = arc.lock().unwrap();
+        let analyzed: Vec<&str> = map.keys().map(|name| name.as_str()).collect();
+        let graph = call_graph::CallGraph::new(&project);
+        let nodes = graph.reachable_from(&analyzed);
+        let contents = if path.ends_with(".json") {
+            serde_json::to_string_pretty(&graph.to_json(&nodes, &map)).unwrap()
+        } else {
+            graph.to_dot(&nodes, &map)
+        };
+        std::fs::write(path, contents).expect("failed to write call graph");
+    }
+
+    if opt.prometheus_file.is_some() || opt.pushgateway.is_some() {
+        let map = arc.lock().unwrap();
+        let categories = map
+            .keys()
+            .map(|name| (name.clone(), category_name(name, abi)))
+            .collect();
+        let commit = metrics::tock_commit(&opt.tockpath);
+        let labels = metrics::RunLabels {
+            board: &board,
+            commit: &commit,
+        };
+        let text = metrics::prometheus_text(&labels, &map, &categories);
+        if let Some(path) = &opt.prometheus_file {
+            std::fs::write(path, &text).expect("failed to write prometheus file");
+        }
+        if let Some(gateway) = &opt.pushgateway {
+            metrics::push_to_gateway(gateway, &board, &text)?;
+        }
+    }
 
     if opt.time_results {
         // Write how long the entire operation took
-        // This might go at board level instead, not sure
-        let time_filename = (&opt.resultspath).to_owned() + "/time.txt";
+        let time_filename = format!("{}/time.txt", board_results);
         let mut time_file = File::create(time_filename).unwrap();
         let total_duration = end.duration_since(start);
         let duration_str = format!("Elapsed: {:?}", total_duration);
         time_file.write_all(duration_str.as_bytes()).unwrap();
     }
 
-    Ok(())
+    // Budgets annotated in the sources, e.g. `// wcet: max 2000 instructions`
+    let mut source_dirs = vec![std::path::Path::new(&board_path_str)];
+    if !opt.no_tock {
+        source_dirs.push(std::path::Path::new(&opt.tockpath));
+    }
+    let source_budgets = budgets::scan(&source_dirs);
+    let mut source_budgets_exceeded = 0;
+    if !source_budgets.is_empty() {
+        let (report, exceeded) = budgets::check(&project, &source_budgets, &arc.lock().unwrap());
+        std::fs::write(format!("{}/budgets.txt", board_results), report)
+            .expect("failed to write source budget report");
+        println!(
+            "{} functions exceed the budget annotated in their source",
+            exceeded
+        );
+        source_budgets_exceeded = exceeded;
+    }
+
+    if let Some(run_dir) = &run_dir {
+        run_dir.publish()?;
+    }
+
+    if let Some(destination) = &opt.upload {
+        upload::upload(destination, &board, &tock_commit, &board_results)?;
+        println!("Uploaded the results to {}", destination);
+    }
+
+    let map = arc.lock().unwrap();
+    let failing: Vec<&Result<PathLength, String>> = map
+        .iter()
+        .filter(|(f, _)| !failures.tolerates(f))
+        .map(|(_, r)| r)
+        .collect();
+    let outcome = if failing.iter().any(|r| over_budget(r)) || source_budgets_exceeded > 0 {
+        Outcome::BudgetExceeded
+    } else if failing.iter().any(|r| r.is_err()) {
+        Outcome::SomeFailed
+    } else {
+        Outcome::AllSucceeded
+    };
+    Ok(outcome)
 }
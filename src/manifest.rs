@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::build_features::BuildFeatures;
+use crate::git_history::RepoState;
+use crate::AnalysisSettings;
+
+/// How a run's results were obtained, saved as `manifest.json` next to them
+/// so that runs can be reproduced and compared.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub tool_version: String,
+    /// Command line arguments of the run
+    pub args: Vec<String>,
+    pub board: String,
+    /// Commit of the Tock tree the board was built from
+    #[serde(default)]
+    pub tock_commit: String,
+    /// When the run started, in seconds since the Unix epoch
+    #[serde(default)]
+    pub started_at: u64,
+    /// Seed of a deterministic run, also passed to the solver
+    pub seed: Option<u64>,
+    pub settings: AnalysisSettings,
+    /// Functions analyzed by each worker thread, in the order they were
+    /// analyzed, unless they were analyzed in dependency order
+    pub workers: Vec<Vec<String>>,
+    /// With `--dependency-order`, the functions by depth of their
+    /// dependencies: each group is analyzed after those it calls from the
+    /// groups before it
+    #[serde(default)]
+    pub dependency_levels: Vec<Vec<String>>,
+    /// With `--save-git-history`, the state of this tool's checkout and of
+    /// the Tock tree
+    #[serde(default)]
+    pub git: Vec<RepoState>,
+    /// Features and `--cfg` flags the board was built with
+    #[serde(default)]
+    pub build_features: BuildFeatures,
+}
+
+impl Manifest {
+    pub fn new(
+        board: &str,
+        tock_commit: &str,
+        seed: Option<u64>,
+        settings: &AnalysisSettings,
+        workers: &[Vec<String>],
+        dependency_levels: &[Vec<String>],
+        git: &[RepoState],
+    ) -> Self {
+        Manifest {
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+            args: std::env::args().skip(1).collect(),
+            board: board.to_owned(),
+            tock_commit: tock_commit.to_owned(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            seed,
+            settings: settings.clone(),
+            workers: workers.to_vec(),
+            dependency_levels: dependency_levels.to_vec(),
+            git: git.to_vec(),
+            build_features: BuildFeatures::default(),
+        }
+    }
+
+    pub fn save(&self, board_results: &str) -> Result<(), String> {
+        let path = format!("{}/manifest.json", board_results);
+        std::fs::write(&path, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|e| format!("failed to write {}: {}", path, e))
+    }
+}
+
+/// Assign functions to `jobs` workers round-robin, in the given order, or
+/// each to its own worker if the number of workers is not limited.
+pub fn assign_workers(functions: &[&str], jobs: Option<usize>) -> Vec<Vec<String>> {
+    match jobs {
+        Some(jobs) => {
+            let mut workers = vec![vec![]; jobs.max(1).min(functions.len().max(1))];
+            let count = workers.len();
+            for (i, f) in functions.iter().enumerate() {
+                workers[i % count].push(f.to_string());
+            }
+            workers
+        }
+        None => functions.iter().map(|f| vec![f.to_string()]).collect(),
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use crate::git_history;
+use crate::PathLength;
+
+/// Labels attached to every metric of a run.
+pub struct RunLabels<'a> {
+    pub board: &'a str,
+    pub commit: &'a str,
+}
+
+/// Retrieve the commit the tock checkout is at, so metrics can be tracked over time.
+pub fn tock_commit(tockpath: &str) -> String {
+    git_history::head_commit(tockpath).unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Escape a label value per the Prometheus text exposition format.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Render results in the Prometheus text exposition format. `categories` maps
+/// each function to the kind of kernel work it was selected as.
+pub fn prometheus_text(
+    labels: &RunLabels,
+    results: &HashMap<String, Result<PathLength, String>>,
+    categories: &HashMap<String, String>,
+) -> String {
+    let mut names: Vec<&String> = results.keys().collect();
+    names.sort();
+    let mut unhashed: HashMap<String, usize> = HashMap::new();
+    for name in &names {
+        *unhashed
+            .entry(format!("{:#}", rustc_demangle::demangle(name)))
+            .or_default() += 1;
+    }
+
+    let mut lengths = String::from(
+        "# HELP wcet_longest_path_instructions Length of the longest path through the function, in LLVM IR instructions.\n\
+         # TYPE wcet_longest_path_instructions gauge\n",
+    );
+    let mut machine = String::from(
+        "# HELP wcet_longest_path_machine_instructions Length of the longest path through the function, in machine instructions.\n\
+         # TYPE wcet_longest_path_machine_instructions gauge\n",
+    );
+    let mut cycles = String::from(
+        "# HELP wcet_longest_path_cycles Cycles the longest path through the function takes, according to the cost model.\n\
+         # TYPE wcet_longest_path_cycles gauge\n",
+    );
+    let mut successes = String::from(
+        "# HELP wcet_analysis_success Whether the longest path analysis of the function succeeded.\n\
+         # TYPE wcet_analysis_success gauge\n",
+    );
+    for name in names {
+        // without the hash, so that a function keeps its series across builds,
+        // unless that leaves instances of a generic function with the same one
+        let function = match format!("{:#}", rustc_demangle::demangle(name)) {
+            function if unhashed[&function] == 1 => function,
+            _ => rustc_demangle::demangle(name).to_string(),
+        };
+        let function_labels = format!(
+            "board=\"{}\",function=\"{}\",category=\"{}\",commit=\"{}\"",
+            escape_label(labels.board),
+            escape_label(&function),
+            escape_label(categories.get(name).map_or("unknown", |c| c.as_str())),
+            escape_label(labels.commit),
+        );
+        match &results[name] {
+            Ok(length) => {
+                lengths.push_str(&format!(
+                    "wcet_longest_path_instructions{{{}}} {}\n",
+                    function_labels, length.ir
+                ));
+                machine.push_str(&format!(
+                    "wcet_longest_path_machine_instructions{{{}}} {}\n",
+                    function_labels, length.machine
+                ));
+                cycles.push_str(&format!(
+                    "wcet_longest_path_cycles{{{}}} {}\n",
+                    function_labels, length.cycles
+                ));
+                successes.push_str(&format!("wcet_analysis_success{{{}}} 1\n", function_labels));
+            }
+            Err(_) => {
+                successes.push_str(&format!("wcet_analysis_success{{{}}} 0\n", function_labels));
+            }
+        }
+    }
+    lengths + &machine + &cycles + &successes
+}
+
+/// Percent-encode `segment` for a URL path, keeping only unreserved
+/// characters.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Push metrics to a Prometheus Pushgateway, replacing any metrics previously
+/// pushed for this board.
+pub fn push_to_gateway(gateway: &str, board: &str, metrics: &str) -> Result<(), String> {
+    let url = format!(
+        "{}/metrics/job/wcet-rs/board/{}",
+        gateway.trim_end_matches('/'),
+        encode_path_segment(board)
+    );
+    ureq::put(&url)
+        .set("Content-Type", "text/plain; version=0.0.4")
+        .send_string(metrics)
+        .map(|_| ())
+        .map_err(|e| format!("failed to push metrics to {}: {}", url, e))
+}
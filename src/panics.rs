@@ -0,0 +1,126 @@
+use clap::arg_enum;
+use haybale::Project;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+
+/// Demangled names (without crate hashes) of functions that only run once the
+/// kernel has panicked. Paths through them are not part of the WCET, and
+/// exploring them explodes the number of paths, so they are all hooked to abort.
+const PANIC_PATTERNS: &[&str] = &[
+    r"^core::panicking::",
+    r"^core::result::unwrap_failed$",
+    r"^core::option::(expect|unwrap)_failed$",
+    r"^core::slice::index::slice_\w+_fail$",
+    r"^core::str::slice_error_fail",
+    r"^core::cell::panic_already(_mutably)?_borrowed$",
+    r"^alloc::alloc::handle_alloc_error$",
+    r"^kernel::debug::panic",
+    // the board's `#[panic_handler]`
+    r"^rust_begin_unwind$",
+    r"(^|::)panic_fmt$",
+];
+
+static BUILTIN: Lazy<RegexSet> = Lazy::new(|| RegexSet::new(PANIC_PATTERNS).unwrap());
+
+/// Panics whose paths may be treated differently from other panics, e.g.
+/// because they are only compiled in with overflow checks or debug assertions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PanicKind {
+    Unreachable,
+    Assertion,
+    Overflow,
+}
+
+// Recognized by the function raising the panic. Toolchains without `panic_const`
+// raise overflow panics through `core::panicking::panic`, like other panics.
+static KIND_PATTERNS: Lazy<Vec<(PanicKind, Regex)>> = Lazy::new(|| {
+    vec![
+        (
+            PanicKind::Unreachable,
+            r"^core::panicking::unreachable_display$",
+        ),
+        (PanicKind::Assertion, r"^core::panicking::assert_failed"),
+        (
+            PanicKind::Overflow,
+            r"^core::panicking::panic_const::panic_const_\w+_(overflow|by_zero)$",
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, pattern)| (kind, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+fn kind_of(demangled: &str) -> Option<PanicKind> {
+    KIND_PATTERNS
+        .iter()
+        .find(|(_, re)| re.is_match(demangled))
+        .map(|(kind, _)| *kind)
+}
+
+arg_enum! {
+    /// How paths raising a kind of panic are treated
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum PanicPaths {
+        // The path ends where the panic is raised
+        Prune,
+        // The panic function runs up to the panic handler, and counts towards the result
+        Count,
+        // Pruned, with the length when counted reported alongside the result
+        Separate,
+    }
+}
+
+/// How paths raising each kind of panic are treated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PanicSettings {
+    /// Regexes on demangled names of panic functions, besides the built-in ones
+    pub extra_patterns: Vec<String>,
+    pub unreachable: PanicPaths,
+    pub assertion: PanicPaths,
+    pub overflow: PanicPaths,
+}
+
+impl PanicSettings {
+    fn treatment(&self, kind: PanicKind) -> PanicPaths {
+        match kind {
+            PanicKind::Unreachable => self.unreachable,
+            PanicKind::Assertion => self.assertion,
+            PanicKind::Overflow => self.overflow,
+        }
+    }
+
+    /// Whether a second analysis is needed to report some panic paths separately.
+    pub fn any_separate(&self) -> bool {
+        [self.unreachable, self.assertion, self.overflow].contains(&PanicPaths::Separate)
+    }
+}
+
+/// Mangled names of the panic functions in the project to hook to abort.
+/// `count_separate` selects the analysis counting the `separate` panic paths.
+pub fn hooked_functions<'p>(
+    project: &'p Project,
+    settings: &PanicSettings,
+    count_separate: bool,
+) -> Result<Vec<&'p str>, String> {
+    let extra = RegexSet::new(&settings.extra_patterns)
+        .map_err(|e| format!("invalid panic function pattern: {}", e))?;
+    let mut names: Vec<&str> = project
+        .all_functions()
+        .map(|(f, _m)| f.name.as_str())
+        .filter(|name| {
+            let demangled = format!("{:#}", rustc_demangle::demangle(name));
+            if !BUILTIN.is_match(&demangled) && !extra.is_match(&demangled) {
+                return false;
+            }
+            match kind_of(&demangled).map(|kind| settings.treatment(kind)) {
+                Some(PanicPaths::Count) => false,
+                Some(PanicPaths::Separate) => !count_separate,
+                Some(PanicPaths::Prune) | None => true,
+            }
+        })
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    Ok(names)
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::PathLength;
+
+/// The longest path when an argument takes one concrete value.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ParametricPoint {
+    pub value: u64,
+    pub length: Result<PathLength, String>,
+}
+
+/// Cycles along the longest path as a linear function of the argument,
+/// `fixed + per_unit * value`, fitted by least squares.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LinearFit {
+    pub fixed: f64,
+    pub per_unit: f64,
+    /// Largest difference between the fit and a point, in cycles
+    pub max_error: f64,
+}
+
+/// The longest path through a function at each analyzed value of an argument.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ParametricResult {
+    /// Position of the argument in the function's LLVM signature
+    pub arg: usize,
+    pub points: Vec<ParametricPoint>,
+    /// Only fitted with at least two distinct values analyzed successfully
+    pub fit: Option<LinearFit>,
+}
+
+impl ParametricResult {
+    pub fn new(arg: usize, points: Vec<ParametricPoint>) -> Self {
+        let fit = fit_linear(&points);
+        ParametricResult { arg, points, fit }
+    }
+}
+
+fn fit_linear(points: &[ParametricPoint]) -> Option<LinearFit> {
+    let samples: Vec<(f64, f64)> = points
+        .iter()
+        .filter_map(|p| match &p.length {
+            Ok(length) => Some((p.value as f64, length.cycles as f64)),
+            Err(_) => None,
+        })
+        .collect();
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let var_x: f64 = samples.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if samples.len() < 2 || var_x == 0.0 {
+        return None;
+    }
+    let cov: f64 = samples
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let per_unit = cov / var_x;
+    let fixed = mean_y - per_unit * mean_x;
+    let max_error = samples
+        .iter()
+        .map(|(x, y)| (fixed + per_unit * x - y).abs())
+        .fold(0.0, f64::max);
+    Some(LinearFit {
+        fixed,
+        per_unit,
+        max_error,
+    })
+}
@@ -0,0 +1,104 @@
+use std::io::{self, BufRead, Write};
+
+/// Number of matches listed for a query.
+const SHOWN: usize = 20;
+
+/// Score of `candidate` for a fuzzy `query`: the characters of the query must
+/// appear in order in the candidate (ignoring case), and runs of consecutive
+/// characters and matches at the start of a path component score higher.
+/// Whitespace separates terms which must all match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut total = 0;
+    for term in query.to_lowercase().split_whitespace() {
+        let mut score = 0;
+        let mut pos = 0;
+        let mut previous: Option<usize> = None;
+        for c in term.chars() {
+            let found = pos + candidate[pos..].iter().position(|&d| d == c)?;
+            score += 1;
+            if previous.map(|p| p + 1 == found).unwrap_or(false) {
+                score += 4;
+            }
+            if found == 0 || !candidate[found - 1].is_alphanumeric() {
+                score += 2;
+            }
+            previous = Some(found);
+            pos = found + 1;
+        }
+        total += score;
+    }
+    Some(total)
+}
+
+/// The best matches of `query` among `demangled`, as indices into it.
+fn best_matches(query: &str, demangled: &[String]) -> Vec<usize> {
+    let mut scored: Vec<(usize, usize)> = demangled
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| fuzzy_score(query, name).map(|score| (score, i)))
+        .collect();
+    // best score first, shorter names first among equal scores
+    scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), demangled[i].len(), i));
+    scored.into_iter().take(SHOWN).map(|(_, i)| i).collect()
+}
+
+/// Interactively choose functions among `names` (mangled), by searching their
+/// demangled names. Each line entered is either a search, listing the best
+/// matches, or the numbers of matches of the last search to select; an empty
+/// line finishes. Returns the selected functions in the order selected.
+pub fn pick<'p>(names: &[&'p str]) -> Result<Vec<&'p str>, String> {
+    let demangled: Vec<String> = names
+        .iter()
+        .map(|name| rustc_demangle::demangle(name).to_string())
+        .collect();
+    let mut selected: Vec<&'p str> = vec![];
+    let mut listed: Vec<usize> = vec![];
+    println!(
+        "{} functions. Type a search, then the numbers of the functions to analyze; an empty line finishes.",
+        names.len()
+    );
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{} selected> ", selected.len());
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let line = match lines.next() {
+            Some(line) => line.map_err(|e| format!("failed to read selection: {}", e))?,
+            None => break,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        let numbers: Option<Vec<usize>> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse().ok())
+            .collect();
+        match numbers {
+            Some(numbers) if !listed.is_empty() => {
+                for n in numbers {
+                    match listed.get(n.wrapping_sub(1)) {
+                        Some(&i) if !selected.contains(&names[i]) => {
+                            println!("  selected {}", demangled[i]);
+                            selected.push(names[i]);
+                        }
+                        Some(_) => {}
+                        None => println!("  no match numbered {}", n),
+                    }
+                }
+            }
+            _ => {
+                listed = best_matches(line, &demangled);
+                if listed.is_empty() {
+                    println!("  no matches");
+                }
+                for (n, &i) in listed.iter().enumerate() {
+                    println!("  {:2}. {}", n + 1, demangled[i]);
+                }
+            }
+        }
+    }
+    Ok(selected)
+}
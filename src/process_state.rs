@@ -0,0 +1,106 @@
+use haybale::backend::Backend;
+use haybale::function_hooks::FunctionHooks;
+use haybale::{IsCall, Project, ReturnValue, State};
+use llvm_ir::Type;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::call_graph::CallGraph;
+
+// matches the kernel's helpers iterating over every process slot
+static PROCESS_ITERATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^kernel::(kernel|sched)::Kernel::(process_each\w*|process_until|get_process_iter|process_iter\w*)$")
+        .unwrap()
+});
+
+/// Assumptions about the state of the processes a kernel path works on,
+/// which rule out paths that only run once per process (e.g. allocating its
+/// grants) and so do not bound the steady-state latency.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessState {
+    /// Grants of every process are already allocated, so entering a grant
+    /// never allocates it
+    #[serde(default)]
+    pub grants_allocated: bool,
+    /// Bound the loops over the process array by the number of process
+    /// slots, when it is above the loop bound. haybale has one bound for
+    /// every loop of an analysis, so this raises the others alike
+    #[serde(default)]
+    pub bound_process_loops: bool,
+    /// Number of process slots, instead of the length of the board's
+    /// `PROCESSES` array
+    pub num_procs: Option<usize>,
+}
+
+impl ProcessState {
+    /// The loop bound of functions iterating over the process array, if
+    /// those loops are bounded by the number of process slots.
+    pub fn process_loop_bound(&self, project: &Project) -> Option<usize> {
+        match self.bound_process_loops {
+            true => self.num_procs.or_else(|| num_procs(project)),
+            false => None,
+        }
+    }
+}
+
+/// The board's `NUM_PROCS`, as the length of its `PROCESSES` array.
+pub fn num_procs(project: &Project) -> Option<usize> {
+    project
+        .all_global_vars()
+        .filter(|(g, _m)| {
+            format!("{:#}", rustc_demangle::demangle(&g.name.to_string())).ends_with("::PROCESSES")
+        })
+        .find_map(|(g, _m)| {
+            // global variables are typed as pointers to their contents
+            let contents = match &*g.ty {
+                Type::PointerType { pointee_type, .. } => pointee_type.clone(),
+                _ => g.ty.clone(),
+            };
+            match &*contents {
+                Type::ArrayType { num_elements, .. } => Some(*num_elements),
+                _ => None,
+            }
+        })
+}
+
+/// Whether `func_name` calls one of the kernel's helpers iterating over the
+/// process array. Calls through trait objects, and helpers inlined into their
+/// callers, are not found.
+pub fn iterates_processes(project: &Project, func_name: &str) -> bool {
+    let root = match project.get_func_by_name(func_name) {
+        Some((f, _m)) => f.name.as_str(),
+        None => return false,
+    };
+    CallGraph::new(project)
+        .reachable_from(&[root])
+        .into_iter()
+        .any(|name| PROCESS_ITERATION.is_match(&format!("{:#}", rustc_demangle::demangle(name))))
+}
+
+/// Hook for functions returning a `bool`, always returning `true`.
+fn true_hook<'p, B: Backend>(
+    _proj: &'p Project,
+    state: &mut State<'p, B>,
+    _call: &'p dyn IsCall,
+) -> haybale::Result<ReturnValue<B::BV>> {
+    Ok(ReturnValue::Return(state.bv_from_u64(1, 1)))
+}
+
+/// Hook the functions of `project` which the assumptions of `process_state` decide.
+pub fn add_hooks<'p, B: Backend + 'p>(
+    hooks: &mut FunctionHooks<'p, B>,
+    project: &'p Project,
+    process_state: &ProcessState,
+) {
+    if process_state.grants_allocated {
+        // `Process::grant_is_allocated`, which `Grant::enter` checks before
+        // allocating the grant in the process's memory
+        for (f, _m) in project.all_functions().filter(|(f, _m)| {
+            format!("{:#}", rustc_demangle::demangle(&f.name)).ends_with("::grant_is_allocated")
+        }) {
+            hooks.add(f.name.clone(), &true_hook);
+        }
+    }
+}
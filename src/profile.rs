@@ -0,0 +1,53 @@
+use clap::arg_enum;
+
+use crate::engine::EngineKind;
+
+arg_enum! {
+    /// Preset analysis effort, adjusting the settings not given explicitly
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Profile {
+        Quick,
+        Normal,
+        Thorough,
+    }
+}
+
+/// The settings a profile chooses.
+pub struct ProfileSettings {
+    /// Solver query timeout, in seconds
+    pub timeout_s: u64,
+    pub loop_bound: usize,
+    pub engine: EngineKind,
+    /// Total time the analysis of a function may take, in seconds
+    pub time_budget_s: Option<u64>,
+}
+
+impl Profile {
+    /// `quick` keeps a run to minutes, for smoke checks of pull requests:
+    /// short timeouts, few loop iterations, and at most two minutes per
+    /// function. `thorough` is for release qualification and may take hours:
+    /// long timeouts, deep loops, and every path explored instead of pruning
+    /// those shorter than one already found. `normal` is in between.
+    pub fn settings(self) -> ProfileSettings {
+        match self {
+            Profile::Quick => ProfileSettings {
+                timeout_s: 15,
+                loop_bound: 20,
+                engine: EngineKind::Longest,
+                time_budget_s: Some(120),
+            },
+            Profile::Normal => ProfileSettings {
+                timeout_s: 75,
+                loop_bound: 100,
+                engine: EngineKind::Longest,
+                time_budget_s: None,
+            },
+            Profile::Thorough => ProfileSettings {
+                timeout_s: 600,
+                loop_bound: 500,
+                engine: EngineKind::Exhaustive,
+                time_budget_s: None,
+            },
+        }
+    }
+}
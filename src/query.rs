@@ -0,0 +1,348 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::report::Noise;
+
+/// Number of functions `top` lists.
+const TOP: usize = 20;
+
+/// The runs queries look at by default: the last of each board.
+const LATEST_RUNS: &str = "SELECT MAX(id) FROM runs GROUP BY board";
+
+// the capsule module a function belongs to, e.g. `capsules_core::virtualizers`
+// for `<capsules_core::virtualizers::virtual_alarm::MuxAlarm<..> as ..>::alarm`
+static CAPSULE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(capsules\w*::\w+)").unwrap());
+
+/// The canned queries, for the help text.
+pub const QUERIES: &str = "top, by-capsule, failures, regressions-since <run>";
+
+/// Answer the canned query `query` (its name, then its argument if it takes
+/// one) from the results database `db`, as tab-separated text.
+pub fn run(
+    db: &Connection,
+    query: &[String],
+    regression_threshold: f64,
+    noise: &Noise,
+) -> Result<String, String> {
+    let answer = match query {
+        [name] if name == "top" => top(db),
+        [name] if name == "by-capsule" => by_capsule(db),
+        [name] if name == "failures" => failures(db),
+        [name, run] if name == "regressions-since" => {
+            regressions_since(db, run, regression_threshold, noise)
+        }
+        _ => {
+            return Err(format!(
+                "unknown query {:?}, expected one of {}",
+                query, QUERIES
+            ))
+        }
+    };
+    answer.map_err(|e| format!("query failed: {}", e))
+}
+
+/// The longest paths of the last run of each board, most cycles first.
+fn top(db: &Connection) -> rusqlite::Result<String> {
+    let mut statement = db.prepare(&format!(
+        "SELECT r.board, f.demangled, f.ir, f.machine, f.cycles
+         FROM functions f JOIN runs r ON r.id = f.run_id
+         WHERE f.run_id IN ({}) AND f.error IS NULL
+         ORDER BY f.cycles DESC, f.demangled LIMIT {}",
+        LATEST_RUNS, TOP
+    ))?;
+    let mut out = "board\tfunction\tIR\tmachine\tcycles\n".to_owned();
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        out += &format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, i64>(4)?
+        );
+    }
+    Ok(out)
+}
+
+/// How much of the last runs' longest paths each capsule accounts for: the
+/// paths going through it, the machine instructions spent in it over all of
+/// them, and the path spending the most in it.
+fn by_capsule(db: &Connection) -> rusqlite::Result<String> {
+    let mut statement = db.prepare(&format!(
+        "SELECT b.run_id, f.demangled, b.demangled_func, b.machine
+         FROM blocks b JOIN functions f USING (run_id, function)
+         WHERE b.run_id IN ({}) AND b.crate LIKE 'capsules%'",
+        LATEST_RUNS
+    ))?;
+    // machine instructions of each capsule, by path
+    let mut capsules: BTreeMap<String, HashMap<(i64, String), i64>> = BTreeMap::new();
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let func: String = row.get(2)?;
+        let capsule = match CAPSULE.captures(&func) {
+            Some(captures) => captures[1].to_owned(),
+            None => continue,
+        };
+        *capsules
+            .entry(capsule)
+            .or_default()
+            .entry((row.get(0)?, row.get(1)?))
+            .or_insert(0) += row.get::<_, i64>(3)?;
+    }
+    let mut totals: Vec<(String, usize, i64, (i64, String))> = capsules
+        .into_iter()
+        .map(|(capsule, paths)| {
+            let total = paths.values().sum();
+            let (worst, machine) = paths
+                .iter()
+                .max_by(|a, b| a.1.cmp(b.1).then(b.0 .1.cmp(&a.0 .1)))
+                .map(|((_run, function), machine)| (function.clone(), *machine))
+                .unwrap();
+            (capsule, paths.len(), total, (machine, worst))
+        })
+        .collect();
+    totals.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+    let mut out = "capsule\tpaths\tmachine\tworst path\tits machine\n".to_owned();
+    for (capsule, paths, total, (machine, worst)) in totals {
+        out += &format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            capsule, paths, total, worst, machine
+        );
+    }
+    Ok(out)
+}
+
+/// The functions whose analysis failed in the last run of each board.
+fn failures(db: &Connection) -> rusqlite::Result<String> {
+    let mut statement = db.prepare(&format!(
+        "SELECT r.board, f.demangled, f.error
+         FROM functions f JOIN runs r ON r.id = f.run_id
+         WHERE f.run_id IN ({}) AND f.error IS NOT NULL
+         ORDER BY r.board, f.demangled",
+        LATEST_RUNS
+    ))?;
+    let mut out = "board\tfunction\terror\n".to_owned();
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        out += &format!(
+            "{}\t{}\t{}\n",
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?.replace('\n', " ")
+        );
+    }
+    Ok(out)
+}
+
+/// A run of the database, for the heading of a comparison.
+struct Run {
+    id: i64,
+    board: String,
+    tock_commit: String,
+}
+
+fn to_run(row: &rusqlite::Row) -> rusqlite::Result<Run> {
+    Ok(Run {
+        id: row.get(0)?,
+        board: row.get(1)?,
+        tock_commit: row.get(2)?,
+    })
+}
+
+/// The run `run` designates: its id, or the last run at a Tock commit
+/// starting with it.
+fn find_run(db: &Connection, run: &str) -> rusqlite::Result<Option<Run>> {
+    match run.parse::<i64>() {
+        Ok(id) => db
+            .query_row(
+                "SELECT id, board, tock_commit FROM runs WHERE id = ?1",
+                params![id],
+                to_run,
+            )
+            .optional(),
+        Err(_) => db
+            .query_row(
+                "SELECT id, board, tock_commit FROM runs WHERE tock_commit LIKE ?1 || '%'
+                 ORDER BY id DESC LIMIT 1",
+                params![run],
+                to_run,
+            )
+            .optional(),
+    }
+}
+
+/// Longest path of each function of a run, by demangled name without the
+/// crate hash (keeping the worst of instances sharing a name), or the error
+/// it failed with.
+fn lengths(db: &Connection, run: i64) -> rusqlite::Result<BTreeMap<String, Result<i64, String>>> {
+    let mut statement =
+        db.prepare("SELECT function, ir, error FROM functions WHERE run_id = ?1")?;
+    let mut lengths: BTreeMap<String, Result<i64, String>> = BTreeMap::new();
+    let mut rows = statement.query(params![run])?;
+    while let Some(row) = rows.next()? {
+        let name = format!("{:#}", rustc_demangle::demangle(&row.get::<_, String>(0)?));
+        let length = match row.get::<_, Option<i64>>(1)? {
+            Some(ir) => Ok(ir),
+            None => Err(row.get::<_, Option<String>>(2)?.unwrap_or_default()),
+        };
+        let worst = match (lengths.remove(&name), length) {
+            (None, length) => length,
+            (Some(Ok(worst)), Ok(length)) => Ok(worst.max(length)),
+            (Some(Err(e)), _) | (Some(Ok(_)), Err(e)) => Err(e),
+        };
+        lengths.insert(name, worst);
+    }
+    Ok(lengths)
+}
+
+/// The functions whose longest path (in IR instructions) grew by more than
+/// `threshold` percent, beyond noise, or that failed, from `run` to the last
+/// run of its board.
+fn regressions_since(
+    db: &Connection,
+    run: &str,
+    threshold: f64,
+    noise: &Noise,
+) -> rusqlite::Result<String> {
+    let before = match find_run(db, run)? {
+        Some(before) => before,
+        None => return Ok(format!("No run {} in the database\n", run)),
+    };
+    let after = db.query_row(
+        "SELECT id, board, tock_commit FROM runs WHERE board = ?1 ORDER BY id DESC LIMIT 1",
+        params![before.board],
+        to_run,
+    )?;
+    let mut out = format!(
+        "{}: run {} (commit {}) to run {} (commit {})\n",
+        after.board, before.id, before.tock_commit, after.id, after.tock_commit
+    );
+    if after.id == before.id {
+        return Ok(out + "No later run of the board\n");
+    }
+    let (old, new) = (lengths(db, before.id)?, lengths(db, after.id)?);
+    let mut regressions = vec![];
+    let mut newly_failed = vec![];
+    for (name, new) in &new {
+        match (old.get(name), new) {
+            (Some(Ok(old)), Ok(new)) => {
+                let pct = (new - old) as f64 / (*old).max(1) as f64 * 100.0;
+                if new > old && pct > threshold && !noise.is_noise(*old as usize, *new as usize) {
+                    regressions.push((name, *old, *new, pct));
+                }
+            }
+            (Some(Ok(_)), Err(_)) => {
+                newly_failed.push(name);
+            }
+            _ => {}
+        }
+    }
+    regressions.sort_by(|a, b| b.3.partial_cmp(&a.3).unwrap().then(a.0.cmp(b.0)));
+    out += "function\tbefore\tafter\tdelta %\n";
+    for (name, old, new, pct) in regressions {
+        out += &format!("{}\t{}\t{}\t{:+.1}\n", name, old, new, pct);
+    }
+    for name in newly_failed {
+        out += &format!("{}\t{}\tfailed\t\n", name, old[name].as_ref().unwrap());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_run(db: &Connection, board: &str, commit: &str) -> i64 {
+        db.execute(
+            "INSERT INTO runs (board, tock_commit, started_at, tool_version, args, manifest)
+             VALUES (?1, ?2, 0, '', '', '{}')",
+            params![board, commit],
+        )
+        .unwrap();
+        db.last_insert_rowid()
+    }
+
+    fn insert_function(db: &Connection, run: i64, function: &str, result: Result<i64, &str>) {
+        db.execute(
+            "INSERT INTO functions (run_id, function, demangled, category, ir, error, analysis_time_s)
+             VALUES (?1, ?2, ?2, 'syscall', ?3, ?4, 0)",
+            params![run, function, result.ok(), result.err()],
+        )
+        .unwrap();
+    }
+
+    /// Two runs of imix with a run of hail in between.
+    fn database() -> Connection {
+        let db = crate::results_db::open(":memory:").unwrap();
+        let before = insert_run(&db, "imix", "aaaa1111");
+        let hail = insert_run(&db, "hail", "aaaa1111");
+        let after = insert_run(&db, "imix", "bbbb2222");
+        for (function, old, new) in [
+            ("_ZN4test3foo17h0123456789abcdefE", Ok(100), Ok(120)),
+            // one instruction more is noise
+            ("_ZN4test3bar17h0123456789abcdefE", Ok(10), Ok(11)),
+            // below the threshold
+            ("_ZN4test3baz17h0123456789abcdefE", Ok(50), Ok(52)),
+            ("_ZN4test3qux17h0123456789abcdefE", Ok(20), Err("timed out")),
+            ("_ZN4test4gone17h0123456789abcdefE", Ok(5), Err("not found")),
+            // instances sharing a name are compared by the worst of them
+            ("_ZN4test5multi17h0123456789abcdefE", Ok(30), Ok(50)),
+            ("_ZN4test5multi17h0123456789abcdeeE", Ok(40), Ok(10)),
+        ] {
+            insert_function(&db, before, function, old);
+            insert_function(&db, after, function, new);
+        }
+        insert_function(
+            &db,
+            before,
+            "_ZN4test4flaky17h0123456789abcdefE",
+            Err("timed out"),
+        );
+        insert_function(&db, after, "_ZN4test4flaky17h0123456789abcdefE", Ok(500));
+        insert_function(&db, after, "_ZN4test5fresh17h0123456789abcdefE", Ok(500));
+        insert_function(&db, hail, "_ZN4test3foo17h0123456789abcdefE", Ok(1));
+        db
+    }
+
+    fn regressions(db: &Connection, run: &str) -> String {
+        let noise = Noise {
+            absolute: 1,
+            relative_pct: 0.0,
+        };
+        regressions_since(db, run, 5.0, &noise).unwrap()
+    }
+
+    #[test]
+    fn regressions_since_run() {
+        assert_eq!(
+            regressions(&database(), "1"),
+            "\
+imix: run 1 (commit aaaa1111) to run 3 (commit bbbb2222)
+function\tbefore\tafter\tdelta %
+test::multi\t40\t50\t+25.0
+test::foo\t100\t120\t+20.0
+test::gone\t5\tfailed\t
+test::qux\t20\tfailed\t
+"
+        );
+    }
+
+    #[test]
+    fn regressions_since_commit() {
+        let db = database();
+        // the last run at the commit is hail's, which has no later run
+        assert_eq!(
+            regressions(&db, "aaaa"),
+            "hail: run 2 (commit aaaa1111) to run 2 (commit aaaa1111)\nNo later run of the board\n"
+        );
+        assert_eq!(
+            regressions(&db, "bbbb2"),
+            "imix: run 3 (commit bbbb2222) to run 3 (commit bbbb2222)\nNo later run of the board\n"
+        );
+        assert_eq!(regressions(&db, "cccc"), "No run cccc in the database\n");
+        assert_eq!(regressions(&db, "4"), "No run 4 in the database\n");
+    }
+}
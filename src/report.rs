@@ -0,0 +1,380 @@
+use haybale::Project;
+use llvm_ir::DebugLoc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::PathLength;
+
+/// Parse a summary file written by a previous run into a map from function name
+/// to longest path length. Failed functions are skipped.
+pub fn parse_summary(contents: &str) -> HashMap<String, usize> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            // mangled names never contain ": ", but error messages can
+            let (name, value) = line.split_once(": ")?;
+            // the IR length is followed by the other counts
+            let len = value.split_whitespace().next()?.parse::<usize>().ok()?;
+            Some((name.to_owned(), len))
+        })
+        .collect()
+}
+
+/// Whether a result has fewer than `min_ratio` machine instructions per LLVM
+/// IR instruction, too few to be plausible: blocks of the path were likely
+/// not found in the disassembly, silently undercounting it.
+pub fn suspicious_machine_count(length: &PathLength, min_ratio: f64) -> bool {
+    (length.machine as f64) < length.ir as f64 * min_ratio
+}
+
+/// Render the summary of a run: successfully analyzed functions from the
+/// longest path down (ties broken by name, so the output is deterministic),
+/// followed by the functions whose analysis failed. Results whose machine
+/// instruction count is implausibly low for their IR length are flagged.
+pub fn summary(
+    results: &HashMap<String, Result<PathLength, String>>,
+    clock_mhz: Option<f64>,
+    calibration_factor: Option<f64>,
+    min_machine_ratio: f64,
+) -> String {
+    let mut successes = vec![];
+    let mut failures = vec![];
+    for (name, result) in results {
+        match result {
+            Ok(length) => successes.push((name, length)),
+            Err(e) => failures.push((name, e)),
+        }
+    }
+    successes.sort_by(|(a_name, a), (b_name, b)| b.ir.cmp(&a.ir).then(a_name.cmp(b_name)));
+    failures.sort();
+
+    let mut out = String::new();
+    for (name, length) in successes {
+        out += &format!(
+            "{}: {} IR instructions, {} machine instructions, {} cycles",
+            name, length.ir, length.machine, length.cycles
+        );
+        if let Some(clock_mhz) = clock_mhz {
+            out += &format!(", {:.3} us", length.micros(clock_mhz));
+        }
+        if let Some(factor) = calibration_factor {
+            out += &format!(", {} calibrated cycles", length.calibrated(factor));
+        }
+        if suspicious_machine_count(length, min_machine_ratio) {
+            out += &format!(
+                ", suspicious: only {:.2} machine instructions per IR instruction",
+                length.machine as f64 / length.ir as f64
+            );
+        }
+        out += "\n";
+    }
+    if !failures.is_empty() {
+        out += "\n# failed\n";
+        for (name, e) in failures {
+            out += &format!("{}: {}\n", name, e);
+        }
+    }
+    out
+}
+
+/// Changes in a longest path small enough to be noise (e.g. register
+/// allocation churn), which comparisons treat as unchanged.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Noise {
+    /// Changes of at most this many instructions
+    pub absolute: usize,
+    /// Changes of at most this percentage
+    pub relative_pct: f64,
+}
+
+impl Noise {
+    pub fn is_noise(&self, old: usize, new: usize) -> bool {
+        let delta = (new as i64 - old as i64).unsigned_abs() as usize;
+        delta <= self.absolute || delta as f64 / old.max(1) as f64 * 100.0 <= self.relative_pct
+    }
+}
+
+/// A function's longest path before and after a change, in LLVM IR instructions.
+pub struct ComparisonRow {
+    /// Demangled name of the function
+    pub function: String,
+    /// Longest path before, if the function was analyzed successfully then
+    pub old: Option<usize>,
+    /// Longest path after, if the function was analyzed successfully
+    pub new: Option<usize>,
+    /// Whether the analysis after the change failed
+    pub failed: bool,
+}
+
+impl ComparisonRow {
+    /// How the function changed, counting growth beyond `threshold` percent
+    /// as a regression.
+    fn status(&self, threshold: f64, noise: &Noise) -> &'static str {
+        match (self.old, self.new) {
+            _ if self.failed => "failed",
+            (None, Some(_)) => "new",
+            (Some(_), None) => "removed",
+            (None, None) => "not analyzed",
+            (Some(old), Some(new)) if noise.is_noise(old, new) => "unchanged",
+            (Some(old), Some(new)) => {
+                let growth = (new as f64 - old as f64) / old.max(1) as f64 * 100.0;
+                if growth > threshold {
+                    "regressed"
+                } else if new < old {
+                    "improved"
+                } else if new == old {
+                    "unchanged"
+                } else {
+                    "within threshold"
+                }
+            }
+        }
+    }
+}
+
+/// Order of the statuses in a markdown comparison, most pressing first.
+const STATUS_ORDER: &[&str] = &[
+    "regressed",
+    "failed",
+    "new",
+    "removed",
+    "within threshold",
+    "improved",
+    "not analyzed",
+];
+
+/// Compact markdown table of how functions changed, e.g. for a CI bot to post
+/// as a pull request comment. Unchanged functions, including those whose
+/// changes are `noise`, are only counted.
+pub fn markdown_comparison(
+    heading: &str,
+    rows: &[ComparisonRow],
+    threshold: f64,
+    noise: &Noise,
+) -> String {
+    let mut listed: Vec<(usize, &ComparisonRow, i64)> = vec![];
+    let mut unchanged = 0;
+    for row in rows {
+        let status = row.status(threshold, noise);
+        match STATUS_ORDER.iter().position(|s| *s == status) {
+            Some(rank) => {
+                let delta = row.new.unwrap_or(0) as i64 - row.old.unwrap_or(0) as i64;
+                listed.push((rank, row, delta));
+            }
+            None => unchanged += 1,
+        }
+    }
+    listed.sort_by(|(a_rank, a, a_delta), (b_rank, b, b_delta)| {
+        a_rank
+            .cmp(b_rank)
+            .then(b_delta.abs().cmp(&a_delta.abs()))
+            .then(a.function.cmp(&b.function))
+    });
+
+    let cell = |len: Option<usize>| len.map_or_else(|| "-".to_owned(), |len| len.to_string());
+    let mut out = format!("### {}\n\n", heading);
+    if !listed.is_empty() {
+        out += "| Function | Old | New | Delta | Status |\n|---|---:|---:|---:|---|\n";
+        for (rank, row, delta) in &listed {
+            let delta = match (row.old, row.new) {
+                (Some(old), Some(_)) => {
+                    format!(
+                        "{:+} ({:+.1}%)",
+                        delta,
+                        *delta as f64 / old.max(1) as f64 * 100.0
+                    )
+                }
+                _ => "-".to_owned(),
+            };
+            out += &format!(
+                "| `{}` | {} | {} | {} | {} |\n",
+                row.function,
+                cell(row.old),
+                cell(row.new),
+                delta,
+                STATUS_ORDER[*rank]
+            );
+        }
+        out += "\n";
+    }
+    out += &format!(
+        "{} functions changed, {} unchanged.\n",
+        listed.len(),
+        unchanged
+    );
+    out
+}
+
+/// Rows comparing a run's results with a baseline summary, for the functions
+/// the run analyzed.
+pub fn baseline_comparison(
+    results: &HashMap<String, Result<PathLength, String>>,
+    baseline: Option<&HashMap<String, usize>>,
+) -> Vec<ComparisonRow> {
+    results
+        .iter()
+        .map(|(name, result)| ComparisonRow {
+            function: format!("{:#}", rustc_demangle::demangle(name)),
+            old: baseline.and_then(|b| b.get(name).copied()),
+            new: result.as_ref().ok().map(|length| length.ir),
+            failed: result.is_err(),
+        })
+        .collect()
+}
+
+/// Build the path reported to GitHub for a function's debug location, relative
+/// to the root of the tock checkout when possible.
+fn annotation_path(loc: &DebugLoc, tockpath: &str) -> String {
+    let filename = Path::new(&loc.filename);
+    let full: PathBuf = match &loc.directory {
+        Some(dir) if !filename.is_absolute() => Path::new(dir).join(filename),
+        _ => filename.to_path_buf(),
+    };
+    let relative = std::fs::canonicalize(tockpath)
+        .ok()
+        .and_then(|root| full.strip_prefix(root).ok().map(|p| p.to_path_buf()));
+    match relative {
+        Some(p) => p.display().to_string(),
+        // rustc usually emits paths relative to the workspace root already
+        None => full.display().to_string(),
+    }
+}
+
+/// The message of a workflow command, with the characters ending or
+/// corrupting the command escaped as GitHub Actions expects.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A property value of a workflow command, where `:` and `,` also separate
+/// properties.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Generate `::warning` workflow commands for every function whose longest path
+/// exceeds `budget`, or grew by more than `regression_threshold` percent
+/// relative to `baseline`. GitHub Actions turns these into inline annotations
+/// on the source of the offending function.
+pub fn github_annotations(
+    project: &Project,
+    results: &HashMap<String, Result<PathLength, String>>,
+    budget: Option<usize>,
+    baseline: Option<&HashMap<String, usize>>,
+    regression_threshold: f64,
+    tockpath: &str,
+) -> Vec<String> {
+    let mut names: Vec<&String> = results.keys().collect();
+    names.sort();
+
+    let mut annotations = vec![];
+    for name in names {
+        let len = match &results[name] {
+            Ok(length) => length.ir,
+            Err(_) => continue,
+        };
+        let demangled = rustc_demangle::demangle(name).to_string();
+
+        let mut messages = vec![];
+        if let Some(budget) = budget {
+            if len > budget {
+                messages.push(format!(
+                    "longest path of {} is {} instructions, exceeding the budget of {}",
+                    demangled, len, budget
+                ));
+            }
+        }
+        if let Some(&old) = baseline.and_then(|b| b.get(name)) {
+            let growth = (len as f64 - old as f64) / old.max(1) as f64 * 100.0;
+            if growth > regression_threshold {
+                messages.push(format!(
+                    "longest path of {} grew from {} to {} instructions (+{:.1}%)",
+                    demangled, old, len, growth
+                ));
+            }
+        }
+
+        let mut params = vec![];
+        if let Some(loc) = project
+            .get_func_by_name(name)
+            .and_then(|(f, _m)| f.debugloc.as_ref())
+        {
+            params.push(format!(
+                "file={}",
+                escape_property(&annotation_path(loc, tockpath))
+            ));
+            params.push(format!("line={}", loc.line));
+        }
+        params.push("title=WCET".to_owned());
+        for message in messages {
+            annotations.push(format!(
+                "::warning {}::{}",
+                params.join(","),
+                escape_data(&message)
+            ));
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_round_trips() {
+        let mut results = HashMap::new();
+        results.insert(
+            "_ZN4test3foo17h0123456789abcdefE".to_owned(),
+            Ok(PathLength {
+                ir: 12,
+                machine: 30,
+                cycles: 41,
+            }),
+        );
+        results.insert(
+            "_ZN4test3bar17h0123456789abcdeeE".to_owned(),
+            Ok(PathLength {
+                ir: 20,
+                machine: 2,
+                cycles: 2,
+            }),
+        );
+        results.insert(
+            "_ZN4test3baz17h0123456789abcdedE".to_owned(),
+            Err("solver error: timed out after 10 s".to_owned()),
+        );
+        let parsed = parse_summary(&summary(&results, Some(16.0), Some(1.5), 0.5));
+        let mut expected = HashMap::new();
+        expected.insert("_ZN4test3foo17h0123456789abcdefE".to_owned(), 12);
+        expected.insert("_ZN4test3bar17h0123456789abcdeeE".to_owned(), 20);
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn parse_summary_skips_other_lines() {
+        let parsed =
+            parse_summary("foo: 7 IR instructions\n\n# failed\nbar: 3: not a length\nbaz\n");
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed["foo"], 7);
+    }
+
+    #[test]
+    fn noise() {
+        let noise = Noise {
+            absolute: 2,
+            relative_pct: 5.0,
+        };
+        assert!(noise.is_noise(10, 12));
+        assert!(noise.is_noise(12, 10));
+        assert!(!noise.is_noise(10, 13));
+        assert!(noise.is_noise(100, 105));
+        assert!(!noise.is_noise(100, 106));
+        // growth from nothing is relative to 1
+        assert!(!noise.is_noise(0, 3));
+        assert!(Noise::default().is_noise(5, 5));
+        assert!(!Noise::default().is_noise(5, 6));
+    }
+}
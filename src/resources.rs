@@ -0,0 +1,125 @@
+use std::thread;
+use std::time::Duration;
+
+/// How often a throttled worker checks the load again.
+const LOAD_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Parse a CPU list like `0-3,6`.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("invalid CPU list {:?}, expected e.g. 0-3,6", list);
+    let mut cpus = vec![];
+    for part in list.split(',').map(str::trim) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first, last),
+            None => (part, part),
+        };
+        let first: usize = first.trim().parse().map_err(|_| invalid())?;
+        let last: usize = last.trim().parse().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// Lower the scheduling priority of this process to `niceness`. Worker
+/// threads and analysis processes started afterwards inherit it.
+#[cfg(unix)]
+pub fn set_niceness(niceness: i32) -> Result<(), String> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, niceness) } != 0 {
+        return Err(format!(
+            "failed to set niceness {}: {}",
+            niceness,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn set_niceness(_niceness: i32) -> Result<(), String> {
+    Err("--nice is only supported on unix hosts".to_owned())
+}
+
+/// Restrict this process to `cpus`. Worker threads and analysis processes
+/// started afterwards inherit the restriction.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpus(cpus: &[usize]) -> Result<(), String> {
+    let mut set = unsafe { std::mem::zeroed::<libc::cpu_set_t>() };
+    for &cpu in cpus {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(format!("CPU {} is out of range", cpu));
+        }
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    if unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) } != 0 {
+        return Err(format!(
+            "failed to pin to CPUs {:?}: {}",
+            cpus,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpus(_cpus: &[usize]) -> Result<(), String> {
+    Err("--cpus is only supported on linux hosts".to_owned())
+}
+
+/// The system's load average over the last minute.
+#[cfg(unix)]
+fn load_average() -> Option<f64> {
+    let mut loads = [0.0; 3];
+    match unsafe { libc::getloadavg(loads.as_mut_ptr(), 3) } {
+        n if n >= 1 => Some(loads[0]),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn load_average() -> Option<f64> {
+    None
+}
+
+/// Wait until the system's load average drops to `max_load`. The first worker
+/// never waits, so that the run always makes progress however loaded the
+/// machine is.
+pub fn wait_for_load(max_load: f64, worker: usize) {
+    if worker == 0 {
+        return;
+    }
+    let mut announced = false;
+    while let Some(load) = load_average().filter(|&load| load > max_load) {
+        if !announced {
+            println!(
+                "Worker {} waiting for the load average ({:.1}) to drop to {:.1}",
+                worker, load, max_load
+            );
+            announced = true;
+        }
+        thread::sleep(LOAD_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,6"), Ok(vec![0, 1, 2, 3, 6]));
+        assert_eq!(parse_cpu_list(" 4 , 1 - 2 "), Ok(vec![1, 2, 4]));
+        assert_eq!(parse_cpu_list("2,1-3,3-3"), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn invalid_cpu_list() {
+        for list in ["", "3-1", "1,,2", "-1", "1-", "a", "0-3;6"] {
+            assert!(parse_cpu_list(list).is_err(), "{:?} was accepted", list);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use clap::arg_enum;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use crate::allocations::{self, Allocation};
+use crate::assumptions::{self, Assumption};
+use crate::host;
+use crate::instruction_counter::ExceptionCycles;
+use crate::parametric::{ParametricPoint, ParametricResult};
+use crate::return_values::ReturnValuePath;
+use crate::solver_time::SolverTime;
+use crate::subsystems::{self, SubsystemShare};
+use crate::{category_name, AnalysisSettings, PathLength};
+
+arg_enum! {
+    /// Format of the per-function result files
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum ReportFormat {
+        Text,
+        Json,
+        Markdown,
+    }
+}
+
+arg_enum! {
+    /// How much of the longest path the per-function result files include:
+    /// only its length, also the blocks it goes through, or also the
+    /// instructions of each
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum ReportDetail {
+        Summary,
+        Path,
+        Full,
+    }
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Text => "txt",
+            ReportFormat::Json => "json",
+            ReportFormat::Markdown => "md",
+        }
+    }
+}
+
+/// The instructions along the longest path found.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LongestPath {
+    /// File listing the machine instructions of each basic block on the path,
+    /// from the disassembly, and its LLVM IR instructions, as executed by
+    /// haybale. Only written with `ReportDetail::Full`.
+    #[serde(default)]
+    pub listing: Option<String>,
+    /// Machine instructions estimated from the IR of blocks not found in the
+    /// disassembly, included in the machine instruction count
+    #[serde(default)]
+    pub estimated_machine: usize,
+    /// Machine instructions of inline assembly blocks, which the IR counts as
+    /// a single call each, included in the machine instruction count
+    #[serde(default)]
+    pub inline_asm_machine: usize,
+    /// Instructions of the path spent in each subsystem, most first
+    #[serde(default)]
+    pub subsystems: Vec<SubsystemShare>,
+    /// Calls allocating memory on the path, in path order
+    #[serde(default)]
+    pub allocations: Vec<Allocation>,
+    /// Demangled names of the hooked functions called on the path
+    #[serde(default)]
+    pub hooked_functions: Vec<String>,
+    /// The basic blocks of the path, in order
+    #[serde(default)]
+    pub blocks: Vec<BlockCount>,
+}
+
+/// A basic block executed by the longest path, with its instructions counted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockCount {
+    /// Mangled name of the block's function
+    pub func: String,
+    pub bb: String,
+    /// LLVM IR instructions the path executes in the block
+    pub ir: usize,
+    /// Machine instructions counted for the block
+    pub machine: usize,
+}
+
+/// How the analysis itself went.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Stats {
+    pub analysis_time_s: f64,
+    pub time_budget_s: Option<u64>,
+    /// Time spent in the solver, by what its queries were for
+    #[serde(default)]
+    pub solver_time: SolverTime,
+    /// Boolector API trace kept because a solver query timed out
+    pub solver_trace: Option<String>,
+}
+
+/// The context a result was obtained in.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Metadata {
+    pub board: String,
+    pub category: String,
+    /// Driver trait method of a syscall handler, e.g. `command`
+    #[serde(default)]
+    pub syscall_method: Option<String>,
+    /// Number of arguments of a syscall handler besides `self`, as compiled
+    #[serde(default)]
+    pub arity: Option<usize>,
+    pub loop_bound: usize,
+    pub solver_timeout_s: u64,
+    pub clock_mhz: Option<f64>,
+    /// Correction factor of the board's cycle counts, if it was calibrated
+    #[serde(default)]
+    pub calibration_factor: Option<f64>,
+}
+
+/// Everything known about the analysis of a single function.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FunctionResult {
+    /// Mangled name of the function
+    pub function: String,
+    pub demangled: String,
+    pub length: Option<PathLength>,
+    /// Length of the longest path in microseconds, if the clock frequency is known
+    pub time_us: Option<f64>,
+    /// Cycles of the longest path corrected by the board's calibration factor
+    #[serde(default)]
+    pub calibrated_cycles: Option<u64>,
+    pub error: Option<String>,
+    pub path: Option<LongestPath>,
+    /// Demangled name of the function (e.g. another instantiation of the same
+    /// generic function) whose result file lists the same longest path, which
+    /// this one's then does not
+    #[serde(default)]
+    pub same_path_as: Option<String>,
+    /// Length of the longest path when the panic paths selected to be reported
+    /// separately are counted
+    pub with_panic_paths: Option<Result<PathLength, String>>,
+    /// Longest path for each distinct return value, if requested
+    pub by_return_value: Option<Result<Vec<ReturnValuePath>, String>>,
+    /// Longest path at each analyzed value of an argument, if configured
+    pub parametric: Option<ParametricResult>,
+    /// Longest path of a syscall dispatch function at each driver number of
+    /// the board, if configured
+    #[serde(default)]
+    pub by_driver: Option<Vec<ParametricPoint>>,
+    /// Cycles of an interrupt handler including exception entry and return,
+    /// if the cost model has their costs
+    pub exception: Option<ExceptionCycles>,
+    /// What the result relies on, from the analysis configuration and the path
+    #[serde(default)]
+    pub assumptions: Vec<Assumption>,
+    pub stats: Stats,
+    pub metadata: Metadata,
+}
+
+impl FunctionResult {
+    /// A result without an outcome yet, for `func_name` analyzed with `settings`.
+    pub fn new(func_name: &str, board_path_str: &str, settings: &AnalysisSettings) -> Self {
+        FunctionResult {
+            function: func_name.to_owned(),
+            demangled: rustc_demangle::demangle(func_name).to_string(),
+            length: None,
+            time_us: None,
+            calibrated_cycles: None,
+            error: None,
+            path: None,
+            same_path_as: None,
+            with_panic_paths: None,
+            by_return_value: None,
+            parametric: None,
+            by_driver: None,
+            exception: None,
+            assumptions: vec![],
+            stats: Stats {
+                time_budget_s: settings.time_budget_s,
+                ..Stats::default()
+            },
+            metadata: Metadata {
+                board: host::last_component(board_path_str),
+                category: category_name(func_name, settings.syscall_abi),
+                syscall_method: None,
+                arity: None,
+                loop_bound: settings.loop_bound,
+                solver_timeout_s: settings.timeout_s,
+                clock_mhz: settings.clock_mhz,
+                calibration_factor: settings.calibration_factor,
+            },
+        }
+    }
+
+    pub fn set_length(&mut self, length: PathLength) {
+        self.time_us = self.metadata.clock_mhz.map(|mhz| length.micros(mhz));
+        self.calibrated_cycles = self
+            .metadata
+            .calibration_factor
+            .map(|factor| length.calibrated(factor));
+        self.length = Some(length);
+    }
+
+    pub fn set_analysis_time(&mut self, elapsed: Duration) {
+        self.stats.analysis_time_s = elapsed.as_secs_f64();
+    }
+
+    /// The result as recorded for the summary and the other reports.
+    pub fn outcome(&self) -> Result<PathLength, String> {
+        match (&self.length, &self.error) {
+            (Some(length), None) => Ok(*length),
+            (_, Some(e)) => Err(format!("Fail: {}", e)),
+            (None, None) => Err("Fail: not analyzed".to_owned()),
+        }
+    }
+
+    fn write_text(&self, out: &mut dyn Write, detail: ReportDetail) -> std::io::Result<()> {
+        let solver = &self.stats.solver_time;
+        let mut stats = format!("Analysis time: {:.1}s\n", self.stats.analysis_time_s);
+        stats += &format!("Solver time: {:.1}s", solver.total_s());
+        if let Some(budget) = self.stats.time_budget_s {
+            stats += &format!(" of {}s budget", budget);
+        }
+        stats += &format!(
+            " (branches {:.1}s, memory accesses {:.1}s, calls {:.1}s)\n",
+            solver.branches_s, solver.memory_s, solver.calls_s
+        );
+        if let Some(trace) = &self.stats.solver_trace {
+            stats += &format!(
+                "Solver trace of the timed out analysis: {} (replay with boolector's btoruntrace)\n",
+                trace
+            );
+        }
+
+        let length = match (&self.length, &self.error) {
+            (Some(length), None) => length,
+            (_, error) => {
+                return write!(
+                    out,
+                    "{}\n{}{}",
+                    error.as_deref().unwrap_or(""),
+                    stats,
+                    assumptions::render(&self.assumptions)
+                )
+            }
+        };
+        let mut timing = format!("Cycles: {}\n", length.cycles);
+        if let (Some(time_us), Some(clock_mhz)) = (self.time_us, self.metadata.clock_mhz) {
+            timing += &format!("Time: {:.3} us at {} MHz\n", time_us, clock_mhz);
+        }
+        if let (Some(calibrated), Some(factor)) =
+            (self.calibrated_cycles, self.metadata.calibration_factor)
+        {
+            timing += &format!(
+                "Calibrated: {} cycles (x{:.3} measured on the board)\n",
+                calibrated, factor
+            );
+        }
+        if let Some(exception) = &self.exception {
+            timing += &format!(
+                "As an exception handler: {} cycles with entry and return\n",
+                exception.isolated
+            );
+            if let Some(tail_chained) = exception.tail_chained {
+                timing += &format!(
+                    "As an exception handler: {} cycles when tail-chained\n",
+                    tail_chained
+                );
+            }
+        }
+        match &self.with_panic_paths {
+            Some(Ok(with_panics)) => {
+                timing += &format!(
+                    "With separately reported panic paths: {} IR instructions, {} machine instructions, {} cycles\n",
+                    with_panics.ir, with_panics.machine, with_panics.cycles
+                )
+            }
+            Some(Err(e)) => timing += &format!("With separately reported panic paths: {}\n", e),
+            None => {}
+        }
+        match &self.by_return_value {
+            Some(Ok(paths)) => {
+                timing += "Longest path by return value:\n";
+                for path in paths {
+                    timing += &format!(
+                        "  {}: {} IR instructions, {} machine instructions, {} cycles ({} paths)\n",
+                        path.return_value,
+                        path.length.ir,
+                        path.length.machine,
+                        path.length.cycles,
+                        path.paths
+                    );
+                }
+            }
+            Some(Err(e)) => timing += &format!("Longest path by return value: {}\n", e),
+            None => {}
+        }
+        if let Some(parametric) = &self.parametric {
+            timing += &format!("Longest path by value of argument {}:\n", parametric.arg);
+            for point in &parametric.points {
+                match &point.length {
+                    Ok(length) => {
+                        timing += &format!(
+                            "  {}: {} IR instructions, {} machine instructions, {} cycles\n",
+                            point.value, length.ir, length.machine, length.cycles
+                        )
+                    }
+                    Err(e) => timing += &format!("  {}: {}\n", point.value, e),
+                }
+            }
+            if let Some(fit) = &parametric.fit {
+                timing += &format!(
+                    "  Fit: {:.1} + {:.3} cycles per unit (max error {:.1} cycles)\n",
+                    fit.fixed, fit.per_unit, fit.max_error
+                );
+            }
+        }
+        if let Some(points) = &self.by_driver {
+            timing += "Longest path by driver number:\n";
+            for point in points {
+                match &point.length {
+                    Ok(length) => {
+                        timing += &format!(
+                            "  {:#x}: {} IR instructions, {} machine instructions, {} cycles\n",
+                            point.value, length.ir, length.machine, length.cycles
+                        )
+                    }
+                    Err(e) => timing += &format!("  {:#x}: {}\n", point.value, e),
+                }
+            }
+        }
+        if let Some(path) = self.path.as_ref().filter(|p| !p.allocations.is_empty()) {
+            timing += &format!(
+                "Allocations: {}\n",
+                allocations::describe(&path.allocations)
+            );
+        }
+        if let Some(path) = self
+            .path
+            .as_ref()
+            .filter(|path| !path.subsystems.is_empty())
+        {
+            timing += &subsystems::render(&path.subsystems);
+        }
+        let (estimated, inline_asm) = match &self.path {
+            Some(path) => (path.estimated_machine, path.inline_asm_machine),
+            None => (0, 0),
+        };
+        let estimated = match estimated {
+            0 => String::new(),
+            n => format!(" (partially estimated: {} from the IR)", n),
+        };
+        let inline_asm = match inline_asm {
+            0 => String::new(),
+            n => format!(" (inline asm: {})", n),
+        };
+        write!(
+            out,
+            "Assembly len: {}{}{}\n{}{}",
+            length.machine, estimated, inline_asm, timing, stats
+        )?;
+        match (detail, &self.path, &self.same_path_as) {
+            (ReportDetail::Summary, _, _) => {}
+            (_, Some(_), Some(canonical)) => writeln!(
+                out,
+                "Path: the same as the longest path of {}, listed in its results",
+                canonical
+            )?,
+            (_, Some(path), None) => {
+                writeln!(out, "Path: {} blocks", path.blocks.len())?;
+                for block in &path.blocks {
+                    writeln!(
+                        out,
+                        "  {} {}: {} IR, {} machine",
+                        rustc_demangle::demangle(&block.func),
+                        block.bb,
+                        block.ir,
+                        block.machine
+                    )?;
+                }
+                if let Some(listing) = &path.listing {
+                    writeln!(out, "Instructions: listed in {}", listing)?;
+                }
+            }
+            _ => {}
+        }
+        writeln!(out, "IR len: {}", length.ir)?;
+        out.write_all(assumptions::render(&self.assumptions).as_bytes())
+    }
+
+    /// Write the result in `format` to `out` with the path in `detail`.
+    pub fn write(
+        &self,
+        out: &mut dyn Write,
+        format: ReportFormat,
+        detail: ReportDetail,
+    ) -> std::io::Result<()> {
+        match (format, detail) {
+            (ReportFormat::Text, _) => self.write_text(out, detail),
+            (ReportFormat::Json, ReportDetail::Summary) => {
+                let mut value = serde_json::to_value(self)?;
+                value["path"] = serde_json::Value::Null;
+                serde_json::to_writer_pretty(out, &value).map_err(Into::into)
+            }
+            (ReportFormat::Json, _) => serde_json::to_writer_pretty(out, self).map_err(Into::into),
+            (ReportFormat::Markdown, _) => {
+                write!(
+                    out,
+                    "## `{:#}`\n\n```\n",
+                    rustc_demangle::demangle(&self.function)
+                )?;
+                self.write_text(out, detail)?;
+                out.write_all(b"```\n")
+            }
+        }
+    }
+
+    /// Write the result to `{stem}.txt`, `{stem}.json` or `{stem}.md`, depending on `format`,
+    /// gzipped to `{stem}.txt.gz` (etc.) if `compress`.
+    pub fn save(
+        &self,
+        stem: &str,
+        format: ReportFormat,
+        detail: ReportDetail,
+        compress: bool,
+    ) -> Result<(), String> {
+        let filename = match compress {
+            true => format!("{}.{}.gz", stem, format.extension()),
+            false => format!("{}.{}", stem, format.extension()),
+        };
+        println!("{:?}", filename);
+        write_file(&filename, compress, |out| self.write(out, format, detail))
+    }
+}
+
+/// Create `filename` and its directory, and have `write` write it piece by
+/// piece, gzipped if `compress`.
+pub fn write_file(
+    filename: &str,
+    compress: bool,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> Result<(), String> {
+    let path = std::path::Path::new(filename);
+    std::fs::create_dir_all(path.parent().unwrap())
+        .map_err(|e| format!("failed to create results directory: {}", e))?;
+    let file = std::fs::File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("failed to write {}: {}", filename, e))?;
+    let written = match compress {
+        true => {
+            let mut gz = GzEncoder::new(file, Compression::default());
+            write(&mut gz)
+                .and_then(|_| gz.finish())
+                .and_then(|mut file| file.flush())
+        }
+        false => {
+            let mut file = file;
+            write(&mut file).and_then(|_| file.flush())
+        }
+    };
+    written.map_err(|e| format!("failed to write {}: {}", filename, e))
+}
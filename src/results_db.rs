@@ -0,0 +1,249 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::crate_name;
+use crate::manifest::Manifest;
+use crate::results::FunctionResult;
+use crate::sinks::ResultSink;
+use crate::PathLength;
+
+/// File name of the results database, in a board's results and at the top
+/// of the results path.
+pub const FILE_NAME: &str = "results.sqlite";
+
+// `manifest` is the run's manifest.json, for `json_extract` on its settings.
+// Lengths of functions whose analysis failed are NULL, with their `error`.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    board TEXT NOT NULL,
+    tock_commit TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    tool_version TEXT NOT NULL,
+    args TEXT NOT NULL,
+    manifest TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS functions (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    function TEXT NOT NULL,
+    demangled TEXT NOT NULL,
+    category TEXT NOT NULL,
+    ir INTEGER,
+    machine INTEGER,
+    cycles INTEGER,
+    time_us REAL,
+    error TEXT,
+    analysis_time_s REAL NOT NULL,
+    PRIMARY KEY (run_id, function)
+);
+CREATE TABLE IF NOT EXISTS paths (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    function TEXT NOT NULL,
+    blocks INTEGER NOT NULL,
+    estimated_machine INTEGER NOT NULL,
+    inline_asm_machine INTEGER NOT NULL,
+    allocations INTEGER NOT NULL,
+    hooked_functions TEXT NOT NULL,
+    PRIMARY KEY (run_id, function)
+);
+CREATE TABLE IF NOT EXISTS blocks (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    function TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    func TEXT NOT NULL,
+    demangled_func TEXT NOT NULL,
+    crate TEXT NOT NULL,
+    bb TEXT NOT NULL,
+    ir INTEGER NOT NULL,
+    machine INTEGER NOT NULL,
+    PRIMARY KEY (run_id, function, position)
+);
+CREATE INDEX IF NOT EXISTS blocks_by_crate ON blocks (crate);
+";
+
+/// Open the results database at `path`, creating it and its tables if needed.
+pub fn open(path: &str) -> Result<Connection, String> {
+    if let Some(dir) = Path::new(path).parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create the directory of {}: {}", path, e))?;
+    }
+    let db = Connection::open(path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+    // runs of other boards may be writing to the accumulated database
+    db.busy_timeout(Duration::from_secs(60))
+        .and_then(|_| db.execute_batch(SCHEMA))
+        .map_err(|e| format!("failed to set up {}: {}", path, e))?;
+    Ok(db)
+}
+
+fn insert_run(db: &Connection, manifest: &Manifest) -> rusqlite::Result<i64> {
+    db.execute(
+        "INSERT INTO runs (board, tock_commit, started_at, tool_version, args, manifest)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            manifest.board,
+            manifest.tock_commit,
+            manifest.started_at as i64,
+            manifest.tool_version,
+            manifest.args.join(" "),
+            serde_json::to_string(manifest).unwrap(),
+        ],
+    )?;
+    Ok(db.last_insert_rowid())
+}
+
+fn insert_function(db: &Connection, run: i64, result: &FunctionResult) -> rusqlite::Result<()> {
+    let tx = db.unchecked_transaction()?;
+    for table in &["functions", "paths", "blocks"] {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE run_id = ?1 AND function = ?2", table),
+            params![run, result.function],
+        )?;
+    }
+    let length = result.length.as_ref();
+    tx.execute(
+        "INSERT INTO functions (run_id, function, demangled, category, ir, machine, cycles,
+                                time_us, error, analysis_time_s)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            run,
+            result.function,
+            result.demangled,
+            result.metadata.category,
+            length.map(|l| l.ir as i64),
+            length.map(|l| l.machine as i64),
+            length.map(|l| l.cycles as i64),
+            result.time_us,
+            result.error,
+            result.stats.analysis_time_s,
+        ],
+    )?;
+    if let Some(path) = &result.path {
+        tx.execute(
+            "INSERT INTO paths (run_id, function, blocks, estimated_machine, inline_asm_machine,
+                                allocations, hooked_functions)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                run,
+                result.function,
+                path.blocks.len() as i64,
+                path.estimated_machine as i64,
+                path.inline_asm_machine as i64,
+                path.allocations.len() as i64,
+                path.hooked_functions.join("\n"),
+            ],
+        )?;
+        let mut insert = tx.prepare(
+            "INSERT INTO blocks (run_id, function, position, func, demangled_func, crate, bb, ir,
+                                 machine)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+        for (position, block) in path.blocks.iter().enumerate() {
+            insert.execute(params![
+                run,
+                result.function,
+                position as i64,
+                block.func,
+                rustc_demangle::demangle(&block.func).to_string(),
+                crate_name(&block.func),
+                block.bb,
+                block.ir as i64,
+                block.machine as i64,
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Writes each function's result, its longest path and the path's blocks
+/// into the run's database in the board's results, and into the database
+/// accumulating every run.
+pub struct ResultsDatabase {
+    /// Each database, with the id of the run in it
+    databases: Vec<(String, Connection, i64)>,
+}
+
+impl ResultsDatabase {
+    /// Record the run of `manifest` in the run's database at `run_path`,
+    /// replacing any previous run there unless `resume` continues it, and in
+    /// the accumulated database at `accumulated_path`. A resumed run
+    /// continues `resumed_run`, its run in the accumulated database as saved
+    /// with its checkpoint, if that is still there, so that the database
+    /// holds it as one run.
+    pub fn new(
+        run_path: &str,
+        accumulated_path: &str,
+        manifest: &Manifest,
+        resume: bool,
+        resumed_run: Option<&(String, i64)>,
+    ) -> Result<Self, String> {
+        if !resume && Path::new(run_path).exists() {
+            std::fs::remove_file(run_path)
+                .map_err(|e| format!("failed to remove {}: {}", run_path, e))?;
+        }
+        let run_db = open(run_path)?;
+        let resumed: Option<i64> = run_db
+            .query_row("SELECT MAX(id) FROM runs", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("failed to read {}: {}", run_path, e))?
+            .flatten();
+        let run = match resumed {
+            Some(run) => run,
+            None => insert_run(&run_db, manifest)
+                .map_err(|e| format!("failed to write {}: {}", run_path, e))?,
+        };
+        let accumulated = open(accumulated_path)?;
+        let resumed = match resumed_run {
+            Some((path, run)) if resume && path == accumulated_path => accumulated
+                .query_row(
+                    "SELECT id FROM runs WHERE id = ?1 AND board = ?2",
+                    params![run, manifest.board],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("failed to read {}: {}", accumulated_path, e))?,
+            _ => None,
+        };
+        let accumulated_run = match resumed {
+            Some(run) => run,
+            None => insert_run(&accumulated, manifest)
+                .map_err(|e| format!("failed to write {}: {}", accumulated_path, e))?,
+        };
+        Ok(ResultsDatabase {
+            databases: vec![
+                (run_path.to_owned(), run_db, run),
+                (accumulated_path.to_owned(), accumulated, accumulated_run),
+            ],
+        })
+    }
+}
+
+impl ResultsDatabase {
+    /// The accumulated database and the id of the run in it, to save with
+    /// the run's checkpoint.
+    pub fn accumulated_run(&self) -> (String, i64) {
+        let (path, _db, run) = &self.databases[1];
+        (path.clone(), *run)
+    }
+}
+
+impl ResultSink for ResultsDatabase {
+    fn function_done(&mut self, result: &FunctionResult) -> Result<(), String> {
+        for (path, db, run) in &self.databases {
+            insert_function(db, *run, result)
+                .map_err(|e| format!("failed to write {}: {}", path, e))?;
+        }
+        Ok(())
+    }
+
+    fn run_done(
+        &mut self,
+        _outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        for (path, _db, _run) in &self.databases {
+            println!("{:?}", path);
+        }
+        Ok(())
+    }
+}
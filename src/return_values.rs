@@ -0,0 +1,105 @@
+use haybale::backend::DefaultBackend;
+use haybale::{Config, ParameterVal, PathEntry, Project};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config_file::ArgumentConstraint;
+use crate::engine::{Engine, ExploredPath};
+use crate::instruction_counter::{path_ir_length, PathCount};
+use crate::PathLength;
+
+/// The longest of the paths through a function returning the same value.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReturnValuePath {
+    /// The concrete value returned, directly or through an `sret` pointer,
+    /// "void", or "symbolic" if a path may return more than one value
+    pub return_value: String,
+    pub length: PathLength,
+    /// Number of paths returning this value
+    pub paths: usize,
+}
+
+/// Constraints on the arguments of `func_name` as haybale parameter values.
+/// Sets of values are approximated by the range they span, and integers
+/// inside other arguments are left unconstrained.
+fn parameter_values(
+    project: &Project,
+    func_name: &str,
+    constraints: &[ArgumentConstraint],
+) -> Result<Vec<ParameterVal>, String> {
+    let (function, _) = project
+        .get_func_by_name(func_name)
+        .ok_or_else(|| format!("no function named {}", func_name))?;
+    let mut params: Vec<ParameterVal> = function
+        .parameters
+        .iter()
+        .map(|_| ParameterVal::Unconstrained)
+        .collect();
+    for constraint in constraints.iter().filter(|c| c.offset.is_none()) {
+        let (min, max) = match &constraint.values {
+            Some(values) => (
+                values.iter().copied().min().unwrap_or(0),
+                values.iter().copied().max().unwrap_or(0),
+            ),
+            None => (
+                constraint.min.unwrap_or(0),
+                constraint.max.unwrap_or(u64::MAX),
+            ),
+        };
+        let param = params
+            .get_mut(constraint.arg)
+            .ok_or_else(|| format!("{} has no argument {}", func_name, constraint.arg))?;
+        *param = match min == max {
+            true => ParameterVal::ExactValue(min),
+            false => ParameterVal::Range(min, max),
+        };
+    }
+    Ok(params)
+}
+
+/// Explore every path through `func_name`, and find the longest path for each
+/// distinct value it returns. Unlike the longest path search, this cannot
+/// skip paths which are shorter than one already found, so it only completes
+/// for functions with a moderate number of paths. `count` counts the machine
+/// instructions of a path.
+pub fn longest_by_return_value<'p>(
+    project: &'p Project,
+    func_name: &str,
+    constraints: Option<&Vec<ArgumentConstraint>>,
+    mut config: Config<'p, DefaultBackend>,
+    engine: &dyn Engine,
+    count: &dyn Fn(&[PathEntry]) -> Result<PathCount, String>,
+) -> Result<Vec<ReturnValuePath>, String> {
+    config.longest_path_optimizations = false;
+    let params = match constraints {
+        Some(constraints) => Some(parameter_values(project, func_name, constraints)?),
+        None => None,
+    };
+    let mut longest: BTreeMap<String, ReturnValuePath> = BTreeMap::new();
+    let mut visit = |ExploredPath { return_value, path }: ExploredPath| {
+        let ir = path_ir_length(path);
+        let entry = longest
+            .entry(return_value.clone())
+            .or_insert(ReturnValuePath {
+                return_value,
+                length: PathLength {
+                    ir: 0,
+                    machine: 0,
+                    cycles: 0,
+                },
+                paths: 0,
+            });
+        entry.paths += 1;
+        if entry.paths == 1 || ir > entry.length.ir {
+            let count = count(path)?;
+            entry.length = PathLength {
+                ir,
+                machine: count.machine,
+                cycles: count.cycles,
+            };
+        }
+        Ok(())
+    };
+    engine.explore(func_name, project, config, params, &mut visit)?;
+    Ok(longest.into_values().collect())
+}
@@ -0,0 +1,180 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Name of the link, in a board's results, to the last run to finish. On
+/// hosts without symbolic links, it is a file holding the run's name.
+pub const LATEST: &str = "latest";
+/// File locked by the run writing to a run directory, holding its process id.
+const LOCK: &str = "run.lock";
+
+/// The directory holding the runs of `board`, and what outlives them, such
+/// as the board's calibration.
+pub fn board_dir(resultspath: &str, board: &str) -> String {
+    format!("{}/{}", resultspath, board)
+}
+
+/// The results of the last run of `board` to finish.
+pub fn latest(resultspath: &str, board: &str) -> String {
+    let board_dir = board_dir(resultspath, board);
+    let latest = format!("{}/{}", board_dir, LATEST);
+    if cfg!(unix) {
+        return latest;
+    }
+    match std::fs::read_to_string(&latest) {
+        Ok(name) => format!("{}/{}", board_dir, name.trim()),
+        Err(_) => latest,
+    }
+}
+
+/// Make `path` designate the run directory `name` next to it.
+#[cfg(unix)]
+fn link(name: &str, path: &str) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(name, path)
+}
+
+#[cfg(not(unix))]
+fn link(name: &str, path: &str) -> std::io::Result<()> {
+    std::fs::write(path, name)
+}
+
+/// Open the lock file `path` and lock it for this process, or `None` if
+/// another process holds it. The lock is the OS's, so it is released however
+/// the process ends, and a run killed during its run leaves no stale lock.
+#[cfg(unix)]
+fn open_locked(path: &str) -> std::io::Result<Option<File>> {
+    use std::os::unix::io::AsRawFd;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        // the process id of the holder is kept until the lock is taken
+        .truncate(false)
+        .open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = std::io::Error::last_os_error();
+        return match e.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            true => Ok(None),
+            false => Err(e),
+        };
+    }
+    Ok(Some(file))
+}
+
+/// Open the lock file `path` and lock it for this process, or `None` if
+/// another process holds it: no other process may open the file while this
+/// one has it open.
+#[cfg(windows)]
+fn open_locked(path: &str) -> std::io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+    // ERROR_SHARING_VIOLATION
+    const SHARING_VIOLATION: i32 = 32;
+    let opened = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .share_mode(0)
+        .open(path);
+    match opened {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Take the lock of the run directory `dir` for this process, held until
+/// the returned file is closed. The file holds the process id of the run
+/// holding it, for the error of another run finding it locked.
+fn lock(dir: &str) -> Result<File, String> {
+    let path = format!("{}/{}", dir, LOCK);
+    let mut file = match open_locked(&path) {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            let holder = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|pid| pid.trim().parse::<u32>().ok());
+            return Err(match holder {
+                Some(pid) => format!("{} is being written by another run (process {})", dir, pid),
+                None => format!("{} is being written by another run", dir),
+            });
+        }
+        Err(e) => return Err(format!("failed to lock {}: {}", path, e)),
+    };
+    file.set_len(0)
+        .and_then(|()| write!(file, "{}", std::process::id()))
+        .map_err(|e| format!("failed to write {}: {}", path, e))?;
+    Ok(file)
+}
+
+/// The last run directory of a board, by name, which starts with the time
+/// the run started.
+fn last_run(board_dir: &Path) -> Option<String> {
+    std::fs::read_dir(board_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_digit()))
+        .max()
+}
+
+/// The directory a run writes its results to, `<resultspath>/<board>/<run>`,
+/// so that runs of a board, concurrent or over time, do not overwrite each
+/// other's files. Runs are named after the time they started and their
+/// process id, and `latest` next to them links to the last one to finish.
+pub struct RunDirectory {
+    pub path: String,
+    board_dir: String,
+    name: String,
+    /// The locked lock file, unlocked when the run ends
+    _lock: File,
+}
+
+impl RunDirectory {
+    /// Create the directory of a new run of `board`, or with `resume`, take
+    /// over the board's last run, and lock it for this process.
+    pub fn create(resultspath: &str, board: &str, resume: bool) -> Result<Self, String> {
+        let board_dir = board_dir(resultspath, board);
+        std::fs::create_dir_all(&board_dir)
+            .map_err(|e| format!("failed to create {}: {}", board_dir, e))?;
+        let resumed = match resume {
+            true => last_run(Path::new(&board_dir)),
+            false => None,
+        };
+        let name = match resumed {
+            Some(name) => name,
+            None => {
+                let started_at = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let name = format!("{}-{}", started_at, std::process::id());
+                let path = format!("{}/{}", board_dir, name);
+                std::fs::create_dir(&path)
+                    .map_err(|e| format!("failed to create {}: {}", path, e))?;
+                name
+            }
+        };
+        let path = format!("{}/{}", board_dir, name);
+        let lock = lock(&path)?;
+        Ok(RunDirectory {
+            path,
+            board_dir,
+            name,
+            _lock: lock,
+        })
+    }
+
+    /// Point the board's `latest` link to this run, replacing the link
+    /// atomically so that readers never find it missing.
+    pub fn publish(&self) -> Result<(), String> {
+        let latest = format!("{}/{}", self.board_dir, LATEST);
+        let staged = format!("{}.{}", latest, std::process::id());
+        let _ = std::fs::remove_file(&staged);
+        link(&self.name, &staged)
+            .and_then(|_| std::fs::rename(&staged, &latest))
+            .map_err(|e| format!("failed to link {} to {}: {}", latest, self.path, e))
+    }
+}
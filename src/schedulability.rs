@@ -0,0 +1,282 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::config_file::TaskConfig;
+use crate::PathLength;
+
+/// The task set as written to `taskset.json`, with the unit of its times.
+#[derive(Serialize)]
+pub struct TaskSet<'a> {
+    pub unit: &'a str,
+    pub tasks: &'a [Task],
+}
+
+/// Unit of the task WCETs: microseconds with a clock frequency, cycles
+/// otherwise.
+pub fn unit(clock_mhz: Option<f64>) -> &'static str {
+    match clock_mhz {
+        Some(_) => "us",
+        None => "cycles",
+    }
+}
+
+/// Time of a longest path in the unit of `unit(clock_mhz)`, rounded up, from
+/// its cycles, calibrated if a calibration factor is known.
+fn time(length: &PathLength, clock_mhz: Option<f64>, calibration_factor: Option<f64>) -> u64 {
+    let cycles = length.cycles as f64 * calibration_factor.unwrap_or(1.0);
+    match clock_mhz {
+        Some(mhz) => (cycles / mhz).ceil() as u64,
+        None => cycles.ceil() as u64,
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Task {
+    pub name: String,
+    /// Worst case execution time, if every matched function was analyzed
+    /// successfully, in the task set's unit
+    pub wcet: Option<u64>,
+    pub period: u64,
+    pub deadline: u64,
+    pub priority: u32,
+    /// Worst case response time from response-time analysis, if it converged
+    pub response_time: Option<u64>,
+    /// Whether the task meets its deadline, unknown if it or a higher
+    /// priority task has no known WCET
+    pub schedulable: Option<bool>,
+}
+
+/// Build the task set from the configured tasks and the analysis results.
+/// Tasks without an explicit priority are assigned rate-monotonic priorities
+/// below all explicitly prioritized ones. WCETs are times, from the cycles of
+/// the results, so periods and deadlines are in the same unit.
+pub fn task_set(
+    configs: &[TaskConfig],
+    results: &HashMap<String, Result<PathLength, String>>,
+    clock_mhz: Option<f64>,
+    calibration_factor: Option<f64>,
+) -> Result<Vec<Task>, String> {
+    let mut by_period: Vec<&TaskConfig> = configs.iter().filter(|t| t.priority.is_none()).collect();
+    by_period.sort_by_key(|t| t.period);
+    let lowest_explicit = configs.iter().filter_map(|t| t.priority).max().unwrap_or(0);
+
+    let mut tasks = vec![];
+    for (i, config) in configs.iter().enumerate() {
+        if configs[..i].iter().any(|t| t.name == config.name) {
+            return Err(format!("task {} is configured twice", config.name));
+        }
+        if config.period == 0 {
+            return Err(format!("task {} has a period of 0", config.name));
+        }
+        let re = Regex::new(&config.function)
+            .map_err(|e| format!("invalid function pattern for task {}: {}", config.name, e))?;
+        let matched: Vec<&Result<PathLength, String>> = results
+            .iter()
+            .filter(|(name, _)| re.is_match(&rustc_demangle::demangle(name).to_string()))
+            .map(|(_, r)| r)
+            .collect();
+        let wcet = if matched.is_empty() || matched.iter().any(|r| r.is_err()) {
+            None
+        } else {
+            matched
+                .iter()
+                .filter_map(|r| r.as_ref().ok())
+                .map(|length| time(length, clock_mhz, calibration_factor))
+                .max()
+        };
+        let priority = config.priority.unwrap_or_else(|| {
+            let rank = by_period
+                .iter()
+                .position(|t| t.name == config.name)
+                .unwrap();
+            lowest_explicit + 1 + rank as u32
+        });
+        tasks.push(Task {
+            name: config.name.clone(),
+            wcet,
+            period: config.period,
+            deadline: config.deadline.unwrap_or(config.period),
+            priority,
+            response_time: None,
+            schedulable: None,
+        });
+    }
+    tasks.sort_by_key(|t| t.priority);
+    Ok(tasks)
+}
+
+/// Fixed-priority preemptive response-time analysis. Tasks of the same
+/// priority may run in either order, so they interfere with each other like
+/// tasks of higher priority. A task's response time is only computed if it
+/// and every task interfering with it have a known WCET, and is left unset if
+/// it exceeds the deadline, in which case the task is unschedulable.
+pub fn response_time_analysis(tasks: &mut [Task]) {
+    for i in 0..tasks.len() {
+        // the period and WCET of each task interfering with this one
+        let interfering: Vec<(u64, Option<u64>)> = tasks
+            .iter()
+            .enumerate()
+            .filter(|(j, t)| *j != i && t.priority <= tasks[i].priority)
+            .map(|(_, t)| (t.period, t.wcet))
+            .collect();
+        let task = &mut tasks[i];
+        let wcet = match task.wcet {
+            Some(wcet) if interfering.iter().all(|(_, wcet)| wcet.is_some()) => wcet,
+            _ => continue,
+        };
+        let mut response = wcet;
+        loop {
+            let interference: u64 = interfering
+                .iter()
+                .map(|&(period, other)| {
+                    // number of releases of the interfering task within the window
+                    let releases = response / period + (response % period != 0) as u64;
+                    releases * other.unwrap()
+                })
+                .sum();
+            let next = wcet + interference;
+            if next > task.deadline {
+                task.schedulable = Some(false);
+                break;
+            }
+            if next == response {
+                task.response_time = Some(response);
+                task.schedulable = Some(true);
+                break;
+            }
+            response = next;
+        }
+    }
+}
+
+/// Human readable report of the analyzed task set, with times in `unit`.
+pub fn render(tasks: &[Task], unit: &str) -> String {
+    let mut out = format!(
+        "# times in {}\npriority\tname\twcet\tperiod\tdeadline\tresponse time\tschedulable\n",
+        unit
+    );
+    for t in tasks {
+        let fmt = |v: Option<u64>| v.map_or("unknown".to_owned(), |v| v.to_string());
+        let schedulable = match t.schedulable {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+        out += &format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            t.priority,
+            t.name,
+            fmt(t.wcet),
+            t.period,
+            t.deadline,
+            fmt(t.response_time),
+            schedulable
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str, period: u64, priority: Option<u32>) -> TaskConfig {
+        TaskConfig {
+            name: name.to_owned(),
+            function: format!("^{}$", name),
+            period,
+            deadline: None,
+            priority,
+        }
+    }
+
+    fn analyze(configs: &[TaskConfig], wcets: &[(&str, Option<u64>)]) -> Vec<Task> {
+        let results = wcets
+            .iter()
+            .map(|&(name, wcet)| {
+                let result = wcet
+                    .map(|cycles| PathLength {
+                        ir: 0,
+                        machine: 0,
+                        cycles,
+                    })
+                    .ok_or_else(|| "timed out".to_owned());
+                (name.to_owned(), result)
+            })
+            .collect();
+        let mut tasks = task_set(configs, &results, None, None).unwrap();
+        response_time_analysis(&mut tasks);
+        tasks
+    }
+
+    fn outcome(tasks: &[Task]) -> Vec<(&str, Option<u64>, Option<bool>)> {
+        tasks
+            .iter()
+            .map(|t| (t.name.as_str(), t.response_time, t.schedulable))
+            .collect()
+    }
+
+    #[test]
+    fn schedulable() {
+        let configs = [
+            config("a", 4, None),
+            config("b", 6, None),
+            config("c", 13, None),
+        ];
+        let tasks = analyze(&configs, &[("a", Some(1)), ("b", Some(2)), ("c", Some(3))]);
+        assert_eq!(
+            outcome(&tasks),
+            vec![
+                ("a", Some(1), Some(true)),
+                ("b", Some(3), Some(true)),
+                ("c", Some(10), Some(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unschedulable() {
+        let configs = [config("a", 5, None), config("b", 7, None)];
+        let tasks = analyze(&configs, &[("a", Some(3)), ("b", Some(3))]);
+        assert_eq!(
+            outcome(&tasks),
+            vec![("a", Some(3), Some(true)), ("b", None, Some(false))]
+        );
+    }
+
+    #[test]
+    fn equal_priorities_interfere() {
+        let configs = [config("a", 10, Some(1)), config("b", 10, Some(1))];
+        let tasks = analyze(&configs, &[("a", Some(2)), ("b", Some(2))]);
+        assert_eq!(
+            outcome(&tasks),
+            vec![("a", Some(4), Some(true)), ("b", Some(4), Some(true))]
+        );
+    }
+
+    #[test]
+    fn unknown_wcet_propagates_to_lower_priorities() {
+        let configs = [
+            config("a", 10, None),
+            config("b", 20, None),
+            config("c", 40, None),
+        ];
+        let tasks = analyze(&configs, &[("a", Some(1)), ("b", None), ("c", Some(1))]);
+        assert_eq!(tasks[1].wcet, None);
+        assert_eq!(
+            outcome(&tasks),
+            vec![
+                ("a", Some(1), Some(true)),
+                ("b", None, None),
+                ("c", None, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let configs = [config("a", 10, None), config("a", 20, None)];
+        assert!(task_set(&configs, &HashMap::new(), None, None).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Condvar, Mutex};
+
+use crate::call_graph::CallGraph;
+
+/// The analyzed functions each of `functions` depends on: those it calls,
+/// directly or not. Functions calling each other (recursion) do not depend
+/// on each other, so they never wait for one another.
+pub fn dependencies<'p>(
+    graph: &CallGraph<'p>,
+    functions: &[&'p str],
+) -> HashMap<String, Vec<String>> {
+    let analyzed: HashSet<&str> = functions.iter().copied().collect();
+    let reachable: HashMap<&str, HashSet<&str>> = functions
+        .iter()
+        .map(|&f| {
+            let callees = graph
+                .reachable_from(&[f])
+                .into_iter()
+                .filter(|&g| g != f && analyzed.contains(g))
+                .collect();
+            (f, callees)
+        })
+        .collect();
+    functions
+        .iter()
+        .map(|&f| {
+            let deps = functions
+                .iter()
+                .filter(|&&g| reachable[f].contains(g) && !reachable[g].contains(f))
+                .map(|g| g.to_string())
+                .collect();
+            (f.to_owned(), deps)
+        })
+        .collect()
+}
+
+/// `functions` grouped by how deep their dependencies go: those without any
+/// first, then those depending only on the first group, and so on.
+pub fn levels(functions: &[&str], deps: &HashMap<String, Vec<String>>) -> Vec<Vec<String>> {
+    let mut level: HashMap<&str, usize> = HashMap::new();
+    // dependencies never form a cycle, so each pass places at least one function
+    while level.len() < functions.len() {
+        for &f in functions {
+            if level.contains_key(f) {
+                continue;
+            }
+            let dep_levels: Option<Vec<usize>> = deps[f]
+                .iter()
+                .map(|d| level.get(d.as_str()).copied())
+                .collect();
+            if let Some(dep_levels) = dep_levels {
+                level.insert(f, dep_levels.into_iter().max().map_or(0, |l| l + 1));
+            }
+        }
+    }
+    let mut levels = vec![vec![]; level.values().max().map_or(0, |l| l + 1)];
+    for &f in functions {
+        levels[level[f]].push(f.to_owned());
+    }
+    levels
+}
+
+struct State {
+    /// Functions not handed out yet, per worker or shared by all workers
+    queues: Vec<VecDeque<String>>,
+    deps: HashMap<String, Vec<String>>,
+    done: HashSet<String>,
+}
+
+/// Hands out the functions to analyze to the worker threads, each only once
+/// the functions it depends on are analyzed.
+pub struct Scheduler {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Scheduler {
+    /// Each worker takes the functions assigned to it, in order.
+    pub fn assigned(workers: Vec<Vec<String>>) -> Self {
+        Scheduler::new(
+            workers.into_iter().map(VecDeque::from).collect(),
+            HashMap::new(),
+        )
+    }
+
+    /// Workers take any function whose dependencies are analyzed, in the
+    /// order of `functions`, so that independent call trees are analyzed in
+    /// parallel.
+    pub fn by_dependencies(functions: &[&str], deps: HashMap<String, Vec<String>>) -> Self {
+        Scheduler::new(
+            vec![functions.iter().map(|f| f.to_string()).collect()],
+            deps,
+        )
+    }
+
+    fn new(queues: Vec<VecDeque<String>>, deps: HashMap<String, Vec<String>>) -> Self {
+        Scheduler {
+            state: Mutex::new(State {
+                queues,
+                deps,
+                done: HashSet::new(),
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Number of queues, i.e. of workers if functions are assigned to them.
+    pub fn queues(&self) -> usize {
+        self.state.lock().unwrap().queues.len()
+    }
+
+    /// Hand out no further functions; those being analyzed still complete.
+    pub fn stop(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.queues.iter_mut().for_each(VecDeque::clear);
+        }
+        self.changed.notify_all();
+    }
+
+    /// The next function for `worker` to analyze, waiting until one is ready,
+    /// or `None` once none are left. The function counts as analyzed when the
+    /// returned claim is dropped.
+    pub fn next(&self, worker: usize) -> Option<Claim<'_>> {
+        let mut state = self.state.lock().unwrap();
+        let index = match state.queues.len() {
+            1 => 0,
+            _ => worker,
+        };
+        loop {
+            let State { queues, deps, done } = &mut *state;
+            let queue = queues.get_mut(index)?;
+            if queue.is_empty() {
+                return None;
+            }
+            let ready = queue.iter().position(|f| match deps.get(f) {
+                Some(deps) => deps.iter().all(|d| done.contains(d)),
+                None => true,
+            });
+            if let Some(i) = ready {
+                return Some(Claim {
+                    function: queue.remove(i).unwrap(),
+                    scheduler: self,
+                });
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+}
+
+/// A function handed out for analysis.
+pub struct Claim<'s> {
+    pub function: String,
+    scheduler: &'s Scheduler,
+}
+
+impl Drop for Claim<'_> {
+    // also runs if the worker panics, so functions depending on this one
+    // are not left waiting forever
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.scheduler.state.lock() {
+            state.done.insert(self.function.clone());
+        }
+        self.scheduler.changed.notify_all();
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::mpsc::{channel, Sender};
+use std::thread::{self, JoinHandle};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::allocations;
+use crate::config_file::SinkConfig;
+use crate::host;
+use crate::report::{self, Noise};
+use crate::results::{FunctionResult, ReportDetail, ReportFormat};
+use crate::{function_results_stem, PathLength};
+
+/// Receives the results of a run: each function's as soon as its analysis
+/// completes, then the outcomes of all of them once the run is done. The
+/// result files and summaries are written by sinks; others can be added to
+/// the list `run` builds, or configured as commands under `[[sinks]]`.
+pub trait ResultSink: Send {
+    /// Called with the result of each analyzed function, from the worker
+    /// that analyzed it. Errors are reported without stopping the run.
+    fn function_done(&mut self, result: &FunctionResult) -> Result<(), String>;
+
+    /// Called once every function is analyzed, with the outcome of each,
+    /// including those of functions resumed from a checkpoint. Errors are
+    /// reported without keeping the other sinks from finishing.
+    fn run_done(
+        &mut self,
+        _outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Writes each function's result to its own file, in the run's format.
+pub struct FunctionFiles {
+    pub board_results: String,
+    pub format: ReportFormat,
+    pub detail: ReportDetail,
+    pub compress: bool,
+}
+
+impl ResultSink for FunctionFiles {
+    fn function_done(&mut self, result: &FunctionResult) -> Result<(), String> {
+        let stem = function_results_stem(&self.board_results, &result.function);
+        result.save(&stem, self.format, self.detail, self.compress)
+    }
+}
+
+/// Writes `summary.txt`, and warns about results with suspiciously few
+/// machine instructions.
+pub struct SummaryFile {
+    pub path: String,
+    pub clock_mhz: Option<f64>,
+    pub calibration_factor: Option<f64>,
+    pub min_machine_ratio: f64,
+}
+
+impl ResultSink for SummaryFile {
+    fn function_done(&mut self, _result: &FunctionResult) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn run_done(
+        &mut self,
+        outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        println!("{:?}", self.path);
+        let data = report::summary(
+            outcomes,
+            self.clock_mhz,
+            self.calibration_factor,
+            self.min_machine_ratio,
+        );
+        std::fs::write(&self.path, data)
+            .map_err(|e| format!("failed to write {}: {}", self.path, e))?;
+        let suspicious = outcomes
+            .values()
+            .filter(|result| match result {
+                Ok(length) => report::suspicious_machine_count(length, self.min_machine_ratio),
+                Err(_) => false,
+            })
+            .count();
+        if suspicious > 0 {
+            println!(
+                "Warning: {} results have suspiciously few machine instructions for their IR path length, see the summary",
+                suspicious
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Writes `summary.md`, a markdown table of how each function changed
+/// relative to the baseline summary, if any.
+pub struct MarkdownComparison {
+    pub path: String,
+    pub board: String,
+    pub baseline: Option<HashMap<String, usize>>,
+    pub regression_threshold: f64,
+    pub noise: Noise,
+}
+
+impl ResultSink for MarkdownComparison {
+    fn function_done(&mut self, _result: &FunctionResult) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn run_done(
+        &mut self,
+        outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        let markdown = report::markdown_comparison(
+            &format!("WCET of {}", self.board),
+            &report::baseline_comparison(outcomes, self.baseline.as_ref()),
+            self.regression_threshold,
+            &self.noise,
+        );
+        std::fs::write(&self.path, markdown)
+            .map_err(|e| format!("failed to write {}: {}", self.path, e))
+    }
+}
+
+/// Run `command` through the host's shell with the environment variables `env`, writing
+/// `input` to its standard input.
+fn run_command(command: &str, env: &[(&str, &str)], input: &str) -> Result<(), String> {
+    let mut child = host::shell(command)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {:?}: {}", command, e))?;
+    // a command not reading its input closes the pipe early, which is fine
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let status = child
+        .wait()
+        .map_err(|e| format!("failed to run {:?}: {}", command, e))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{:?} exited with {}", command, status)),
+    }
+}
+
+/// Writes `allocations.txt`, listing the functions whose longest path
+/// allocates memory, and warns about them.
+pub struct AllocationReport {
+    pub path: String,
+    /// The allocations of each function allocating on its longest path
+    pub allocating: Vec<(String, String)>,
+}
+
+impl ResultSink for AllocationReport {
+    fn function_done(&mut self, result: &FunctionResult) -> Result<(), String> {
+        if let Some(path) = result.path.as_ref().filter(|p| !p.allocations.is_empty()) {
+            self.allocating.push((
+                result.demangled.clone(),
+                allocations::describe(&path.allocations),
+            ));
+        }
+        Ok(())
+    }
+
+    fn run_done(
+        &mut self,
+        _outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        self.allocating.sort();
+        let mut out = String::new();
+        for (function, allocations) in &self.allocating {
+            out += &format!("{}: {}\n", function, allocations);
+        }
+        std::fs::write(&self.path, out)
+            .map_err(|e| format!("failed to write {}: {}", self.path, e))?;
+        if !self.allocating.is_empty() {
+            println!(
+                "Warning: {} functions allocate memory on their longest path, see allocations.txt",
+                self.allocating.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Runs a configured command through the host's shell for each result, with the result
+/// as JSON on its standard input and `WCET_EVENT=function`, and once at the
+/// end of the run with the outcomes of all functions and `WCET_EVENT=run`.
+/// Like `--on-complete`, the commands run one at a time on a thread of their
+/// own, so that a slow command holds up neither the workers nor the other
+/// sinks; their errors are printed.
+pub struct CommandSink {
+    /// The event and JSON input of each command to run
+    inputs: Option<Sender<(&'static str, String)>>,
+    runner: Option<JoinHandle<()>>,
+}
+
+impl CommandSink {
+    pub fn new(command: String, board: String) -> Self {
+        let (inputs, received) = channel::<(&'static str, String)>();
+        let runner = thread::spawn(move || {
+            for (event, input) in received {
+                let env = [("WCET_EVENT", event), ("WCET_BOARD", board.as_str())];
+                if let Err(e) = run_command(&command, &env, &input) {
+                    println!("{}", e);
+                }
+            }
+        });
+        CommandSink {
+            inputs: Some(inputs),
+            runner: Some(runner),
+        }
+    }
+
+    fn send(&self, event: &'static str, input: String) -> Result<(), String> {
+        match &self.inputs {
+            Some(inputs) => inputs
+                .send((event, input))
+                .map_err(|_| "a [[sinks]] command runner stopped".to_owned()),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait for the commands sent so far to finish.
+    fn finish(&mut self) -> Result<(), String> {
+        self.inputs = None;
+        match self.runner.take().map(JoinHandle::join) {
+            Some(Err(_)) => Err("a [[sinks]] command runner panicked".to_owned()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for CommandSink {
+    // a run stopped early still runs the commands of the results it sent
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+impl ResultSink for CommandSink {
+    fn function_done(&mut self, result: &FunctionResult) -> Result<(), String> {
+        self.send("function", serde_json::to_string(result).unwrap())
+    }
+
+    fn run_done(
+        &mut self,
+        outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        self.send("run", serde_json::to_string(outcomes).unwrap())?;
+        self.finish()
+    }
+}
+
+/// What `--on-complete` is told about a function: its outcome, without the
+/// path, which can be large.
+#[derive(Serialize)]
+struct Notice {
+    function: String,
+    demangled: String,
+    status: &'static str,
+    length: Option<PathLength>,
+    error: Option<String>,
+}
+
+/// Runs the `--on-complete` command as each function, or each of those
+/// selected by `functions`, finishes or fails. The commands run one at a time
+/// on a thread of their own, so that a slow command does not hold up the
+/// workers reporting their results.
+pub struct Notification {
+    /// Regex on demangled names of the functions to notify about
+    functions: Option<Regex>,
+    notices: Option<Sender<Notice>>,
+    notifier: Option<JoinHandle<()>>,
+}
+
+impl Notification {
+    pub fn new(command: String, functions: Option<Regex>) -> Self {
+        let (notices, received) = channel::<Notice>();
+        let notifier = thread::spawn(move || {
+            for notice in received {
+                let summary = match (&notice.length, &notice.error) {
+                    (Some(length), _) => format!(
+                        "{} IR instructions, {} machine instructions, {} cycles",
+                        length.ir, length.machine, length.cycles
+                    ),
+                    (None, error) => error.clone().unwrap_or_default(),
+                };
+                let env = [
+                    ("WCET_FUNCTION", notice.function.as_str()),
+                    ("WCET_FUNCTION_DEMANGLED", notice.demangled.as_str()),
+                    ("WCET_STATUS", notice.status),
+                    ("WCET_RESULT", summary.as_str()),
+                ];
+                let input = serde_json::to_string(&notice).unwrap();
+                if let Err(e) = run_command(&command, &env, &input) {
+                    println!("{}", e);
+                }
+            }
+        });
+        Notification {
+            functions,
+            notices: Some(notices),
+            notifier: Some(notifier),
+        }
+    }
+
+    /// Wait for the notifications sent so far to be delivered.
+    fn finish(&mut self) -> Result<(), String> {
+        self.notices = None;
+        match self.notifier.take().map(JoinHandle::join) {
+            Some(Err(_)) => Err("the --on-complete notifier panicked".to_owned()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Notification {
+    // a run stopped early still delivers its notifications
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+impl ResultSink for Notification {
+    fn function_done(&mut self, result: &FunctionResult) -> Result<(), String> {
+        if let Some(functions) = &self.functions {
+            if !functions.is_match(&result.demangled) {
+                return Ok(());
+            }
+        }
+        let (status, length, error) = match result.outcome() {
+            Ok(length) => ("ok", Some(length), None),
+            Err(e) => ("failed", None, Some(e)),
+        };
+        let notice = Notice {
+            function: result.function.clone(),
+            demangled: result.demangled.clone(),
+            status,
+            length,
+            error,
+        };
+        match &self.notices {
+            Some(notices) => notices
+                .send(notice)
+                .map_err(|_| "the --on-complete notifier stopped".to_owned()),
+            None => Ok(()),
+        }
+    }
+
+    fn run_done(
+        &mut self,
+        _outcomes: &HashMap<String, Result<PathLength, String>>,
+    ) -> Result<(), String> {
+        self.finish()
+    }
+}
+
+/// The command sinks of the config file.
+pub fn configured(configs: &[SinkConfig], board: &str) -> Vec<Box<dyn ResultSink>> {
+    configs
+        .iter()
+        .map(|config| {
+            Box::new(CommandSink::new(config.command.clone(), board.to_owned()))
+                as Box<dyn ResultSink>
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+use haybale::backend::DefaultBackend;
+use haybale::callbacks::Callbacks;
+use llvm_ir::Instruction;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// Time spent in the solver by the analysis of a function, across all of its
+/// queries, by what the queries were for. haybale does not report its
+/// queries, so the time between an instruction which queries the solver and
+/// the next instruction executed is counted: branching (whether each
+/// successor is feasible), accessing memory (the possible addresses of a
+/// pointer) and calls (hooks solving for their arguments, and indirect calls
+/// for their targets).
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SolverTime {
+    pub branches_s: f64,
+    pub memory_s: f64,
+    pub calls_s: f64,
+}
+
+impl SolverTime {
+    pub fn total_s(&self) -> f64 {
+        self.branches_s + self.memory_s + self.calls_s
+    }
+}
+
+/// What the last instruction executed may have queried the solver for.
+#[derive(Clone, Copy)]
+enum Query {
+    Branch,
+    Memory,
+    Call,
+    None,
+}
+
+struct Accounting {
+    spent: SolverTime,
+    /// The last instruction executed, and when
+    last: Option<(Query, Instant)>,
+}
+
+thread_local! {
+    // each thread analyzes one function at a time
+    static ACCOUNTING: RefCell<Accounting> = RefCell::new(Accounting {
+        spent: SolverTime::default(),
+        last: None,
+    });
+}
+
+/// Start accounting for the analysis of a new function on this thread.
+pub fn reset() {
+    ACCOUNTING.with(|a| {
+        let mut a = a.borrow_mut();
+        a.spent = SolverTime::default();
+        a.last = None;
+    });
+}
+
+/// The solver time of the function analyzed on this thread so far.
+pub fn spent() -> SolverTime {
+    ACCOUNTING.with(|a| a.borrow().spent)
+}
+
+/// Count the time since the last instruction, and note that `query` was
+/// just executed. Fails once the time exceeds `budget`.
+fn account(query: Query, budget: Option<Duration>) -> haybale::Result<()> {
+    ACCOUNTING.with(|a| {
+        let mut a = a.borrow_mut();
+        let now = Instant::now();
+        if let Some((last, at)) = a.last {
+            let elapsed = now.duration_since(at).as_secs_f64();
+            match last {
+                Query::Branch => a.spent.branches_s += elapsed,
+                Query::Memory => a.spent.memory_s += elapsed,
+                Query::Call => a.spent.calls_s += elapsed,
+                Query::None => {}
+            }
+        }
+        a.last = Some((query, now));
+        match budget {
+            Some(budget) if a.spent.total_s() > budget.as_secs_f64() => {
+                Err(haybale::Error::OtherError(format!(
+                    "solver time budget of {}s exceeded",
+                    budget.as_secs()
+                )))
+            }
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Account the solver time of the analysis `callbacks` configure, failing it
+/// once the function's analyses spent more than `budget_s` in the solver.
+pub fn add_callbacks(callbacks: &mut Callbacks<'_, DefaultBackend>, budget_s: Option<u64>) {
+    let budget = budget_s.map(Duration::from_secs);
+    // the time before this analysis starts is not the solver's
+    ACCOUNTING.with(|a| a.borrow_mut().last = None);
+    callbacks.add_instruction_callback(move |instr, _state| {
+        let query = match instr {
+            Instruction::Load(_)
+            | Instruction::Store(_)
+            | Instruction::AtomicRMW(_)
+            | Instruction::CmpXchg(_) => Query::Memory,
+            Instruction::Call(_) => Query::Call,
+            _ => Query::None,
+        };
+        account(query, budget)
+    });
+    callbacks.add_terminator_callback(move |_term, _state| account(Query::Branch, budget));
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::instruction_counter::PathBlock;
+use crate::{crate_name, PathLength};
+
+/// Crates of the Rust libraries and of Tock's support libraries.
+const LIBRARY_CRATES: &[&str] = &[
+    "core",
+    "alloc",
+    "compiler_builtins",
+    "tock_registers",
+    "tock_cells",
+    "tock_tbf",
+    "enum_primitive",
+];
+
+/// How the crates of a board are grouped into Tock's subsystems.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Subsystems {
+    /// Name of the board's own crate
+    pub board_crate: String,
+    /// Subsystems of crates, overriding the ones guessed from their names
+    pub by_crate: HashMap<String, String>,
+}
+
+impl Subsystems {
+    /// The subsystem of crate `name`: the kernel core, capsules, the
+    /// architecture, the board, libraries, or else the chip.
+    pub fn of_crate(&self, name: &str) -> String {
+        if let Some(subsystem) = self.by_crate.get(name) {
+            return subsystem.clone();
+        }
+        let subsystem = if name == "kernel" {
+            "kernel"
+        } else if name.starts_with("capsules") {
+            "capsules"
+        } else if name.starts_with("cortexm")
+            || name.starts_with("cortexv")
+            || name.starts_with("riscv")
+            || name == "rv32i"
+        {
+            "arch"
+        } else if name == self.board_crate {
+            "board"
+        } else if LIBRARY_CRATES.contains(&name) {
+            "libraries"
+        } else {
+            "chip"
+        };
+        subsystem.to_owned()
+    }
+}
+
+/// Instructions of a path spent in one subsystem.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SubsystemShare {
+    pub subsystem: String,
+    pub ir: usize,
+    pub machine: usize,
+}
+
+/// Attribute the instructions of each block of a path, with `machine` counted
+/// for each, to the subsystem of its function's crate (for trait impls, that
+/// of the implementing type). Costs of hooked calls are
+/// their own share, so that the shares cover the whole path.
+pub fn attribute(
+    subsystems: &Subsystems,
+    path: &[PathBlock],
+    machine: &[usize],
+    hooked: &PathLength,
+) -> Vec<SubsystemShare> {
+    let mut shares: Vec<SubsystemShare> = vec![];
+    let mut add = |subsystem: String, ir: usize, machine: usize| match shares
+        .iter_mut()
+        .find(|s| s.subsystem == subsystem)
+    {
+        Some(share) => {
+            share.ir += ir;
+            share.machine += machine;
+        }
+        None => shares.push(SubsystemShare {
+            subsystem,
+            ir,
+            machine,
+        }),
+    };
+    for (block, &machine) in path.iter().zip(machine) {
+        add(
+            subsystems.of_crate(&crate_name(&block.func)),
+            block.ir_len,
+            machine,
+        );
+    }
+    if hooked.ir > 0 || hooked.machine > 0 {
+        add("hooked calls".to_owned(), hooked.ir, hooked.machine);
+    }
+    shares.sort_by(|a, b| {
+        b.machine
+            .cmp(&a.machine)
+            .then(b.ir.cmp(&a.ir))
+            .then(a.subsystem.cmp(&b.subsystem))
+    });
+    shares
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    match whole {
+        0 => 0.0,
+        _ => part as f64 / whole as f64 * 100.0,
+    }
+}
+
+/// The share of each subsystem in the instructions of a path.
+pub fn render(shares: &[SubsystemShare]) -> String {
+    let ir = shares.iter().map(|s| s.ir).sum();
+    let machine = shares.iter().map(|s| s.machine).sum();
+    let mut out = "Subsystems (share of IR / machine instructions):\n".to_owned();
+    for share in shares {
+        out += &format!(
+            "  {}: {:.1}% / {:.1}%\n",
+            share.subsystem,
+            percent(share.ir, ir),
+            percent(share.machine, machine)
+        );
+    }
+    out
+}
@@ -0,0 +1,99 @@
+use haybale::Project;
+use std::collections::HashMap;
+
+use crate::call_graph::CallGraph;
+use crate::config_file::HookConfig;
+use crate::difficulty::FunctionMetrics;
+
+/// Total analysis time from which a run is slow enough to suggest
+/// overrides, in seconds.
+pub const SLOW_RUN_S: f64 = 600.0;
+/// Smallest share of the analysis time worth an override.
+const MIN_SHARE: f64 = 0.05;
+const MAX_SUGGESTIONS: usize = 10;
+
+/// A function whose replacement by a trusted cost would likely save much of
+/// the analysis time.
+pub struct Suggestion<'p> {
+    pub function: &'p str,
+    /// Number of analyzed functions reaching it
+    pub analyses: usize,
+    /// Estimated analysis time spent in it, in seconds
+    pub time_s: f64,
+    /// Estimated share of the run's analysis time spent in it
+    pub share: f64,
+}
+
+/// The functions worth replacing with a trusted cost or a hook, given the
+/// analysis time of each function analyzed. haybale does not report where
+/// its time goes, so the time of each analysis is attributed to the
+/// functions it reaches in proportion to their basic blocks. Functions
+/// already hooked are left out.
+pub fn suggest<'p>(
+    project: &'p Project,
+    times: &HashMap<String, f64>,
+    hooks: &HashMap<String, HookConfig>,
+) -> Vec<Suggestion<'p>> {
+    let graph = CallGraph::new(project);
+    let blocks = |name: &str| match project.get_func_by_name(name) {
+        Some((f, _m)) => FunctionMetrics::of(f).blocks,
+        None => 0,
+    };
+    let mut block_counts: HashMap<&'p str, usize> = HashMap::new();
+    let mut attributed: HashMap<&'p str, (usize, f64)> = HashMap::new();
+    for (analyzed, &time_s) in times {
+        let root = match project.get_func_by_name(analyzed) {
+            Some((f, _m)) => f.name.as_str(),
+            None => continue,
+        };
+        let reached = graph.reachable_from(&[root]);
+        for &f in &reached {
+            block_counts.entry(f).or_insert_with(|| blocks(f));
+        }
+        let total: usize = reached.iter().map(|f| block_counts[f]).sum();
+        if total == 0 {
+            continue;
+        }
+        // the analyzed function itself is already a candidate
+        for &f in &reached {
+            let entry = attributed.entry(f).or_insert((0, 0.0));
+            entry.0 += 1;
+            entry.1 += time_s * block_counts[f] as f64 / total as f64;
+        }
+    }
+    let run_s: f64 = times.values().sum();
+    let mut suggestions: Vec<Suggestion> = attributed
+        .into_iter()
+        .filter(|(f, _)| !hooks.contains_key(*f))
+        .map(|(function, (analyses, time_s))| Suggestion {
+            function,
+            analyses,
+            time_s,
+            share: time_s / run_s,
+        })
+        .filter(|s| s.share >= MIN_SHARE)
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.time_s
+            .partial_cmp(&a.time_s)
+            .unwrap()
+            .then(a.function.cmp(b.function))
+    });
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+/// The suggestions as text, one per line.
+pub fn render(suggestions: &[Suggestion]) -> String {
+    let mut out = String::new();
+    for s in suggestions {
+        out += &format!(
+            "{} was reached by {} of the functions analyzed and took an estimated {:.0}% of the analysis time ({:.0}s); consider a [[symbol_costs]] entry or a hook\n",
+            rustc_demangle::demangle(s.function),
+            s.analyses,
+            100.0 * s.share,
+            s.time_s
+        );
+    }
+    out
+}
@@ -0,0 +1,32 @@
+use haybale::Project;
+use serde::{Deserialize, Serialize};
+
+/// Generation of the interface between the kernel and the capsules handling
+/// system calls, which decides what capsule syscall handlers are named.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SyscallAbi {
+    /// Tock 1.x: capsules implement `Driver`, with `command`, `subscribe` and
+    /// `allow`, returning a `ReturnCode`
+    Legacy,
+    /// Tock 2.x: capsules implement `SyscallDriver`, with `command` returning a
+    /// `CommandReturn` and `allow_readwrite`/`allow_readonly`; upcalls are
+    /// subscribed by the kernel itself, without calling the capsule
+    SyscallDriver,
+    /// Neither was recognized (e.g. a crate that is not a Tock board)
+    Unknown,
+}
+
+/// The syscall ABI of the kernel a project was built from, recognized by the
+/// syscall driver trait that capsule methods are implemented for.
+pub fn detect(project: &Project) -> SyscallAbi {
+    let mut abi = SyscallAbi::Unknown;
+    for (f, _m) in project.all_functions() {
+        if f.name.contains("SyscallDriver") {
+            return SyscallAbi::SyscallDriver;
+        }
+        if f.name.contains("driver..Driver") || f.name.contains("ReturnCode") {
+            abi = SyscallAbi::Legacy;
+        }
+    }
+    abi
+}
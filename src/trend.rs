@@ -0,0 +1,213 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::compare::by_demangled_name;
+use crate::report::parse_summary;
+
+/// Number of largest jumps highlighted.
+const HIGHLIGHTED_JUMPS: usize = 10;
+
+/// A run found among accumulated results.
+pub struct Run {
+    pub board: String,
+    pub commit: String,
+    pub started_at: u64,
+    /// Longest path of each function, by demangled name without the crate hash
+    pub results: BTreeMap<String, usize>,
+}
+
+/// The part of a run's manifest locating it in time. The rest, notably the
+/// settings, is not parsed, so manifests of other tool versions are read too.
+#[derive(Deserialize)]
+struct ManifestHeader {
+    board: String,
+    #[serde(default)]
+    tock_commit: String,
+    #[serde(default)]
+    started_at: u64,
+}
+
+/// A change in a function's longest path from one run of a board to the next.
+struct Jump<'a> {
+    board: &'a str,
+    function: &'a str,
+    from_commit: &'a str,
+    commit: &'a str,
+    old: usize,
+    new: usize,
+}
+
+impl Jump<'_> {
+    fn relative(&self) -> f64 {
+        (self.new as f64 - self.old as f64) / self.old.max(1) as f64
+    }
+}
+
+/// Every run under `dir`, found by its `manifest.json` with a `summary.txt`
+/// next to it, as laid out by runs over time in their own results paths or by
+/// `--upload` to a store synced locally.
+pub fn load_runs(dir: &Path) -> Result<Vec<Run>, String> {
+    let mut runs = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            std::fs::read_dir(&dir).map_err(|e| format!("failed to read {:?}: {}", dir, e))?;
+        for entry in entries.filter_map(|e| e.ok()) {
+            // not following the `latest` links to runs found already
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                pending.push(entry.path());
+            }
+        }
+        let (manifest, summary) = match (
+            std::fs::read_to_string(dir.join("manifest.json")),
+            std::fs::read_to_string(dir.join("summary.txt")),
+        ) {
+            (Ok(manifest), Ok(summary)) => (manifest, summary),
+            _ => continue,
+        };
+        let manifest: ManifestHeader = serde_json::from_str(&manifest)
+            .map_err(|e| format!("failed to parse the manifest in {:?}: {}", dir, e))?;
+        runs.push(Run {
+            board: manifest.board,
+            commit: manifest.tock_commit,
+            started_at: manifest.started_at,
+            results: by_demangled_name(&parse_summary(&summary)),
+        });
+    }
+    Ok(runs)
+}
+
+/// Abbreviated commit hash, as a column heading.
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(10)]
+}
+
+/// For each board, a tab-separated table of every function's longest path
+/// (in LLVM IR instructions) at each commit, oldest first, followed by the
+/// largest jumps from one commit to the next across all boards. Of several
+/// runs of a board at the same commit, the latest counts.
+pub fn render(mut runs: Vec<Run>) -> String {
+    runs.sort_by(|a, b| {
+        (&a.board, a.started_at, &a.commit).cmp(&(&b.board, b.started_at, &b.commit))
+    });
+    let mut boards: BTreeMap<&str, Vec<&Run>> = BTreeMap::new();
+    for run in &runs {
+        let board_runs = boards.entry(run.board.as_str()).or_default();
+        board_runs.retain(|earlier| earlier.commit != run.commit);
+        board_runs.push(run);
+    }
+
+    let mut out = String::new();
+    let mut jumps = vec![];
+    for (board, board_runs) in &boards {
+        out += &format!("# {}\nfunction", board);
+        for run in board_runs {
+            out += &format!("\t{}", short(&run.commit));
+        }
+        out += "\n";
+        let mut functions: Vec<&String> =
+            board_runs.iter().flat_map(|r| r.results.keys()).collect();
+        functions.sort_unstable();
+        functions.dedup();
+        for function in functions {
+            out += function;
+            let mut previous: Option<(&Run, usize)> = None;
+            for run in board_runs {
+                let len = run.results.get(function).copied();
+                match len {
+                    Some(len) => out += &format!("\t{}", len),
+                    None => out += "\t-",
+                }
+                if let (Some((earlier, old)), Some(new)) = (previous, len) {
+                    if old != new {
+                        jumps.push(Jump {
+                            board,
+                            function,
+                            from_commit: &earlier.commit,
+                            commit: &run.commit,
+                            old,
+                            new,
+                        });
+                    }
+                }
+                if let Some(len) = len {
+                    previous = Some((run, len));
+                }
+            }
+            out += "\n";
+        }
+        out += "\n";
+    }
+
+    jumps.sort_by(|a, b| {
+        b.relative()
+            .abs()
+            .partial_cmp(&a.relative().abs())
+            .unwrap()
+            .then(a.function.cmp(b.function))
+    });
+    out += "# largest jumps\n";
+    for jump in jumps.iter().take(HIGHLIGHTED_JUMPS) {
+        out += &format!(
+            "{} on {}: {} -> {} ({:+.1}%) from {} to {}\n",
+            jump.function,
+            jump.board,
+            jump.old,
+            jump.new,
+            jump.relative() * 100.0,
+            short(jump.from_commit),
+            short(jump.commit)
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(board: &str, commit: &str, started_at: u64, results: &[(&str, usize)]) -> Run {
+        Run {
+            board: board.to_owned(),
+            commit: commit.to_owned(),
+            started_at,
+            results: results
+                .iter()
+                .map(|&(function, len)| (function.to_owned(), len))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn render_tables_and_jumps() {
+        let (a, b, c) = ("aaaaaaaaaa11", "bbbbbbbbbb22", "cccccccccc33");
+        let runs = vec![
+            run("imix", c, 4, &[("foo", 20), ("bar", 6), ("baz", 3)]),
+            run("hail", b, 2, &[("foo", 7)]),
+            // rerun at the same commit, replacing the earlier run
+            run("imix", b, 3, &[("foo", 20)]),
+            run("imix", a, 1, &[("foo", 10), ("bar", 5)]),
+            run("imix", b, 2, &[("foo", 12), ("bar", 5)]),
+            run("hail", a, 1, &[("foo", 7)]),
+        ];
+        assert_eq!(
+            render(runs),
+            "\
+# hail
+function\taaaaaaaaaa\tbbbbbbbbbb
+foo\t7\t7
+
+# imix
+function\taaaaaaaaaa\tbbbbbbbbbb\tcccccccccc
+bar\t5\t-\t6
+baz\t-\t-\t3
+foo\t10\t20\t20
+
+# largest jumps
+foo on imix: 10 -> 20 (+100.0%) from aaaaaaaaaa to bbbbbbbbbb
+bar on imix: 5 -> 6 (+20.0%) from aaaaaaaaaa to cccccccccc
+"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use once_cell::sync::Lazy;
+use regex::RegexSet;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::PathLength;
+
+// matches each step of delivering an upcall to a process, on `{:#}` demangled
+// names, in the order they run: the capsule schedules the upcall (Tock 2.x)
+// or callback (Tock 1.x), which enqueues a task for the process; the kernel
+// loop later dequeues it, sets up the process's stack to call the function,
+// and switches to the process.
+const STEPS: &[(&str, &str)] = &[
+    (
+        "schedule",
+        r"^kernel::(upcall::Upcall|callback::Callback)::schedule$",
+    ),
+    (
+        "dequeue",
+        r" as kernel::process::(Process|ProcessType)>::dequeue_task$",
+    ),
+    (
+        "set up the call",
+        r" as kernel::process::(Process|ProcessType)>::set_process_function$",
+    ),
+    (
+        "switch to the process",
+        r" as kernel::process::(Process|ProcessType)>::switch_to$",
+    ),
+];
+
+static STEP_PATTERNS: Lazy<RegexSet> =
+    Lazy::new(|| RegexSet::new(STEPS.iter().map(|(_, pattern)| pattern)).unwrap());
+
+/// The index of the step of upcall delivery function `name` (mangled) is.
+pub fn step_of(name: &str) -> Option<usize> {
+    STEP_PATTERNS
+        .matches(&format!("{:#}", rustc_demangle::demangle(name)))
+        .iter()
+        .next()
+}
+
+/// A bound on delivering an upcall, from the capsule scheduling it until
+/// the process runs its function, not counting the wait for the kernel loop
+/// to get to the process.
+#[derive(Serialize, Debug)]
+pub struct UpcallBound {
+    /// Worst case of each step, unknown if it failed or was not analyzed
+    pub steps: Vec<(String, Option<PathLength>)>,
+    /// The steps summed, if all are known
+    pub total: Option<PathLength>,
+}
+
+/// Sum the worst case of each step of upcall delivery in `results`.
+pub fn bound(results: &HashMap<String, Result<PathLength, String>>) -> UpcallBound {
+    let mut steps = vec![];
+    let mut total = Some(PathLength {
+        ir: 0,
+        machine: 0,
+        cycles: 0,
+    });
+    for (i, (step, _)) in STEPS.iter().enumerate() {
+        let worst = results
+            .iter()
+            .filter(|(name, _)| step_of(name) == Some(i))
+            .map(|(_, result)| result.as_ref().ok().copied())
+            .reduce(|a, b| match (a, b) {
+                (Some(a), Some(b)) => Some(PathLength {
+                    ir: a.ir.max(b.ir),
+                    machine: a.machine.max(b.machine),
+                    cycles: a.cycles.max(b.cycles),
+                }),
+                _ => None,
+            })
+            .flatten();
+        total = match (total, worst) {
+            (Some(t), Some(w)) => Some(PathLength {
+                ir: t.ir + w.ir,
+                machine: t.machine + w.machine,
+                cycles: t.cycles + w.cycles,
+            }),
+            _ => None,
+        };
+        steps.push((step.to_string(), worst));
+    }
+    UpcallBound { steps, total }
+}
+
+fn describe(length: &Option<PathLength>) -> String {
+    match length {
+        Some(l) => format!(
+            "{} IR instructions, {} machine instructions, {} cycles",
+            l.ir, l.machine, l.cycles
+        ),
+        None => "unknown".to_owned(),
+    }
+}
+
+/// Render the upcall delivery bound as text.
+pub fn render(bound: &UpcallBound) -> String {
+    let mut out = String::new();
+    for (step, length) in &bound.steps {
+        out += &format!("Worst {}: {}\n", step, describe(length));
+    }
+    out += &format!("Upcall delivery: {}\n", describe(&bound.total));
+    out
+}
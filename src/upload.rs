@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+/// Files of the board's results that are not uploaded.
+const SKIPPED: &[&str] = &["checkpoint.json.partial", "build.log", "run.lock"];
+
+/// The files of a board's results directory, and of its subdirectories, by
+/// name relative to it, with their sizes.
+fn result_files(board_results: &str) -> Result<Vec<(String, PathBuf, u64)>, String> {
+    let mut files = vec![];
+    let mut dirs = vec![(String::new(), PathBuf::from(board_results))];
+    while let Some((prefix, dir)) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+            let name = entry.file_name().into_string().map_err(|name| {
+                format!(
+                    "cannot upload {:?} in {}: not a UTF-8 name",
+                    name,
+                    dir.display()
+                )
+            })?;
+            if SKIPPED.contains(&name.as_str()) {
+                continue;
+            }
+            let path = entry.path();
+            let metadata = std::fs::metadata(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            if metadata.is_dir() {
+                dirs.push((format!("{}{}/", prefix, name), path));
+            } else {
+                files.push((format!("{}{}", prefix, name), path, metadata.len()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The write end of a `Pipe`: each write is sent to the reader as a chunk.
+struct PipeWriter(SyncSender<std::io::Result<Vec<u8>>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.send(Ok(buf.to_vec())).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "upload was abandoned")
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The read end of a `Pipe`, failing with the writer's error if it had one.
+struct Pipe {
+    chunks: Receiver<std::io::Result<Vec<u8>>>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.chunks.recv() {
+                Ok(chunk) => self.current = Cursor::new(chunk?),
+                // the archive was finished
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+/// A tar archive of `files`, written by a `tar::Builder` on its own thread a
+/// few chunks ahead of the reader, so that the results are never all in
+/// memory. A file which cannot be read, or changes size while it is, fails
+/// the read.
+fn tar_stream(files: Vec<(String, PathBuf, u64)>) -> Pipe {
+    let (sender, chunks) = sync_channel(16);
+    let errors = sender.clone();
+    thread::spawn(move || {
+        let mut builder = tar::Builder::new(PipeWriter(sender));
+        let written = files
+            .into_iter()
+            .try_for_each(|(name, path, size)| {
+                let file = File::open(&path).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("failed to read {}: {}", path.display(), e),
+                    )
+                })?;
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(size);
+                header.set_mode(0o644);
+                let contents = ExactSize {
+                    inner: file.take(size),
+                    left: size,
+                    path,
+                };
+                builder.append_data(&mut header, &name, contents)
+            })
+            .and_then(|()| builder.finish());
+        if let Err(e) = written {
+            // the reader may be gone already, in which case nobody is left to tell
+            let _ = errors.send(Err(e));
+        }
+    });
+    Pipe {
+        chunks,
+        current: Cursor::new(vec![]),
+    }
+}
+
+/// A file's contents, failing if it ends before the size in its header.
+struct ExactSize {
+    inner: std::io::Take<File>,
+    left: u64,
+    path: PathBuf,
+}
+
+impl Read for ExactSize {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && self.left > 0 && !buf.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("{} shrank while being uploaded", self.path.display()),
+            ));
+        }
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Run a storage tool's copy command, e.g. `aws s3 cp`.
+fn copy_with(program: &str, args: &[&str]) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{} {} failed", program, args.join(" "))),
+    }
+}
+
+/// Upload the results of a run of `board` at `commit` of Tock to `destination`,
+/// so that every board's runs end up in one place. Object stores (`s3://` and
+/// `gs://` URLs, through the `aws` and `gsutil` tools and their credentials)
+/// get a copy of the results directory under `<destination>/<board>/<commit>/`.
+/// The run's lock, build log and partial checkpoint are left out everywhere.
+/// Other URLs get the results directory POSTed to them as a tar archive, with
+/// the board and commit as query parameters, streamed a file at a time.
+pub fn upload(
+    destination: &str,
+    board: &str,
+    commit: &str,
+    board_results: &str,
+) -> Result<(), String> {
+    let target = format!(
+        "{}/{}/{}/",
+        destination.trim_end_matches('/'),
+        board,
+        commit
+    );
+    if destination.starts_with("s3://") {
+        let mut args = vec!["s3", "cp", "--recursive", board_results, target.as_str()];
+        // `aws` patterns match paths relative to the copied directory
+        let nested: Vec<String> = SKIPPED.iter().map(|name| format!("*/{}", name)).collect();
+        for pattern in SKIPPED
+            .iter()
+            .copied()
+            .chain(nested.iter().map(String::as_str))
+        {
+            args.extend(&["--exclude", pattern]);
+        }
+        copy_with("aws", &args)
+    } else if destination.starts_with("gs://") {
+        // `gsutil cp` cannot exclude files, `gsutil rsync` takes a regex of them
+        let skipped = SKIPPED
+            .iter()
+            .map(|name| regex::escape(name))
+            .collect::<Vec<_>>()
+            .join("|");
+        let exclude = format!("(^|.*/)({})$", skipped);
+        copy_with(
+            "gsutil",
+            &["-m", "rsync", "-r", "-x", &exclude, board_results, &target],
+        )
+    } else if destination.starts_with("http://") || destination.starts_with("https://") {
+        let archive = tar_stream(result_files(board_results)?);
+        ureq::post(destination)
+            .query("board", board)
+            .query("commit", commit)
+            .set("Content-Type", "application/x-tar")
+            .send(archive)
+            .map(|_| ())
+            .map_err(|e| format!("failed to upload results to {}: {}", destination, e))
+    } else {
+        Err(format!(
+            "unsupported upload destination {}, expected an s3://, gs:// or http(s):// URL",
+            destination
+        ))
+    }
+}
@@ -0,0 +1,291 @@
+use haybale::Project;
+use llvm_ir::{Instruction, Terminator};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::call_graph::called_function_name;
+use crate::PathLength;
+
+/// A capsule multiplexing a peripheral between clients, whose dispatch
+/// functions iterate over its registered clients.
+struct Virtualizer {
+    /// The mux type, e.g. `capsules::virtual_alarm::MuxAlarm`
+    mux: &'static str,
+    /// The type of its clients' devices, e.g. `virtual_alarm::VirtualMuxAlarm`
+    device: &'static str,
+    /// Matches the registration of a client with a mux, on `{:#}` demangled
+    /// names, capturing the mux's type arguments
+    registration: Regex,
+    /// Matches the mux's dispatch over its clients, capturing its type arguments
+    dispatch: Regex,
+    /// The callback the mux's dispatch calls on each client, e.g.
+    /// `kernel::hil::time::AlarmClient>::alarm`, ending `{:#}` demangled
+    /// names of its implementations
+    client_callback: &'static str,
+}
+
+fn virtualizer(
+    mux: &'static str,
+    device: &'static str,
+    registration: &str,
+    dispatch: &[&str],
+    client_callback: &'static str,
+) -> Virtualizer {
+    let module = r"capsules(?:_core)?::";
+    Virtualizer {
+        mux,
+        device,
+        registration: Regex::new(&format!("^{}{}$", module, registration)).unwrap(),
+        dispatch: Regex::new(&format!("^<?{}(?:{})$", module, dispatch.join("|"))).unwrap(),
+        client_callback,
+    }
+}
+
+// Clients are registered once each during the board's setup: virtual alarms
+// and SPI devices with `setup`, I2C devices when given their client
+static VIRTUALIZERS: Lazy<Vec<Virtualizer>> = Lazy::new(|| {
+    vec![
+        virtualizer(
+            ALARM_MUX,
+            "virtual_alarm::VirtualMuxAlarm",
+            r"virtual_alarm::VirtualMuxAlarm(?:<(.*)>)?::setup",
+            &[r"virtual_alarm::MuxAlarm(?:<(.*)>)? as kernel::hil::time::AlarmClient>::alarm"],
+            " as kernel::hil::time::AlarmClient>::alarm",
+        ),
+        virtualizer(
+            "capsules::virtual_spi::MuxSpiMaster",
+            "virtual_spi::VirtualSpiMasterDevice",
+            r"virtual_spi::VirtualSpiMasterDevice(?:<(.*)>)?::setup",
+            &[
+                r"virtual_spi::MuxSpiMaster(?:<(.*)>)?::do_next_op",
+                r"virtual_spi::MuxSpiMaster(?:<(.*)>)? as kernel::hil::spi::SpiMasterClient>::read_write_done",
+            ],
+            " as kernel::hil::spi::SpiMasterClient>::read_write_done",
+        ),
+        virtualizer(
+            "capsules::virtual_i2c::MuxI2C",
+            "virtual_i2c::I2CDevice",
+            r"virtual_i2c::(?:I2CDevice|SMBusDevice)(?:<(.*)>)?::set_client",
+            &[
+                r"virtual_i2c::MuxI2C(?:<(.*)>)?::do_next_op",
+                r"virtual_i2c::MuxI2C(?:<(.*)>)? as kernel::hil::i2c::I2CHwMasterClient>::command_complete",
+            ],
+            " as kernel::hil::i2c::I2CClient>::command_complete",
+        ),
+    ]
+});
+
+/// The type arguments captured by any group of `captures`, empty if none.
+fn type_arguments(captures: regex::Captures) -> String {
+    captures
+        .iter()
+        .skip(1)
+        .flatten()
+        .next()
+        .map_or(String::new(), |m| m.as_str().to_owned())
+}
+
+/// A mux of the board, identified by its type: muxes of the same type (e.g.
+/// two alarm muxes on the same timer) are counted together.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Mux {
+    pub mux: &'static str,
+    /// Type arguments of the mux, e.g. the peripheral it multiplexes
+    pub arguments: String,
+}
+
+impl std::fmt::Display for Mux {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.arguments.as_str() {
+            "" => write!(f, "{}", self.mux),
+            arguments => write!(f, "{}<{}>", self.mux, arguments),
+        }
+    }
+}
+
+/// The alarm mux, whose clients can also be counted in the board's sources.
+pub const ALARM_MUX: &str = "capsules::virtual_alarm::MuxAlarm";
+
+/// The mux function `name` (mangled) dispatches for, if it is a dispatch function.
+pub fn dispatch_of(name: &str) -> Option<Mux> {
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    VIRTUALIZERS.iter().find_map(|v| {
+        v.dispatch.captures(&demangled).map(|captures| Mux {
+            mux: v.mux,
+            arguments: type_arguments(captures),
+        })
+    })
+}
+
+/// The dispatch functions of the project, by mangled name, with their mux.
+pub fn dispatch_functions(project: &Project) -> Vec<(&str, Mux)> {
+    project
+        .all_functions()
+        .filter_map(|(f, _m)| dispatch_of(&f.name).map(|mux| (f.name.as_str(), mux)))
+        .collect()
+}
+
+/// Number of clients registered with each mux of the board, counting the
+/// call sites of the registration functions in the bitcode. Registrations
+/// inlined into the board's setup are not found.
+pub fn client_counts(project: &Project) -> HashMap<Mux, usize> {
+    let registrations: HashMap<&str, Mux> = project
+        .all_functions()
+        .filter_map(|(f, _m)| {
+            let demangled = format!("{:#}", rustc_demangle::demangle(&f.name));
+            VIRTUALIZERS.iter().find_map(|v| {
+                v.registration.captures(&demangled).map(|captures| {
+                    let mux = Mux {
+                        mux: v.mux,
+                        arguments: type_arguments(captures),
+                    };
+                    (f.name.as_str(), mux)
+                })
+            })
+        })
+        .collect();
+    let mut counts = HashMap::new();
+    for (f, _m) in project.all_functions() {
+        for bb in &f.basic_blocks {
+            let calls = bb
+                .instrs
+                .iter()
+                .filter_map(|instr| match instr {
+                    Instruction::Call(call) => Some(&call.function),
+                    _ => None,
+                })
+                .chain(match &bb.term {
+                    Terminator::Invoke(invoke) => Some(&invoke.function),
+                    _ => None,
+                });
+            for callee in calls.filter_map(called_function_name) {
+                if let Some(mux) = registrations.get(callee) {
+                    *counts.entry(mux.clone()).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// The device type through which clients of `mux` register, as it appears in
+/// the type arguments of a mux nested on it.
+fn device_of(mux: &Mux) -> String {
+    let device = VIRTUALIZERS
+        .iter()
+        .find(|v| v.mux == mux.mux)
+        .map_or("", |v| v.device);
+    format!("{}<{}>", device, mux.arguments)
+}
+
+/// Loop bound of the dispatch functions of `mux`: one iteration per client,
+/// times the bound of any mux nested on one of its clients (e.g. an alarm
+/// mux built on a virtual alarm), whose dispatch runs within its own. haybale
+/// bounds the iterations of an inner loop across all runs of the outer one.
+pub fn dispatch_bound(mux: &Mux, counts: &HashMap<Mux, usize>) -> Option<usize> {
+    let clients = *counts.get(mux)?;
+    let device = device_of(mux);
+    let nested = counts
+        .keys()
+        .filter(|other| *other != mux && other.arguments.contains(&device))
+        .filter_map(|other| dispatch_bound(other, counts))
+        .max()
+        .unwrap_or(1);
+    Some(clients.max(1) * nested)
+}
+
+/// The results of the callbacks the dispatch of `mux` calls on its clients,
+/// implemented by the capsules using it rather than by the virtualizer's own
+/// devices, by `{:#}` demangled name.
+fn client_callbacks<'r>(
+    mux: &Mux,
+    results: &'r HashMap<String, Result<PathLength, String>>,
+) -> Vec<(String, &'r Result<PathLength, String>)> {
+    let virtualizer = match VIRTUALIZERS.iter().find(|v| v.mux == mux.mux) {
+        Some(virtualizer) => virtualizer,
+        None => return vec![],
+    };
+    // e.g. `virtual_alarm::` for the alarm mux and its virtual alarms
+    let own_module = virtualizer.device.split("::").next().unwrap_or_default();
+    let mut callbacks: Vec<(String, &Result<PathLength, String>)> = results
+        .iter()
+        .map(|(name, result)| (format!("{:#}", rustc_demangle::demangle(name)), result))
+        .filter(|(name, _)| {
+            name.ends_with(virtualizer.client_callback)
+                && !name.contains(&format!("{}::", own_module))
+        })
+        .collect();
+    callbacks.sort_by(|a, b| a.0.cmp(&b.0));
+    callbacks
+}
+
+/// The worst case of the dispatch of each mux analyzed, in total and per
+/// iteration over a client, and the worst case of each client's callback
+/// analyzed in the run, as text.
+pub fn render(
+    counts: &HashMap<Mux, usize>,
+    results: &HashMap<String, Result<PathLength, String>>,
+) -> String {
+    let mut dispatches: Vec<(Mux, String, &Result<PathLength, String>)> = results
+        .iter()
+        .filter_map(|(name, result)| {
+            dispatch_of(name).map(|mux| (mux, rustc_demangle::demangle(name).to_string(), result))
+        })
+        .collect();
+    dispatches.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    let mut out = String::new();
+    let mut current = None;
+    for (mux, name, result) in dispatches {
+        let bound = dispatch_bound(&mux, counts);
+        if current.as_ref() != Some(&mux) {
+            out += &match (counts.get(&mux), bound) {
+                (Some(clients), Some(bound)) => format!(
+                    "{}: {} clients, {} iterations with nested muxes\n",
+                    mux, clients, bound
+                ),
+                _ => format!("{}: clients unknown\n", mux),
+            };
+            current = Some(mux);
+        }
+        out += &match (result, bound) {
+            (Ok(length), Some(bound)) => format!(
+                "  {}: {} cycles in total, {} per client\n",
+                name,
+                length.cycles,
+                (length.cycles as f64 / bound as f64).ceil()
+            ),
+            (Ok(length), None) => format!("  {}: {} cycles in total\n", name, length.cycles),
+            (Err(e), _) => format!("  {}: failed: {}\n", name, e),
+        };
+    }
+    let mut muxes: Vec<&Mux> = counts.keys().collect();
+    muxes.sort();
+    for mux in muxes {
+        let callbacks = client_callbacks(mux, results);
+        if callbacks.is_empty() {
+            continue;
+        }
+        out += &format!("{}: client callbacks\n", mux);
+        for (name, result) in &callbacks {
+            out += &match result {
+                Ok(length) => format!("  {}: {} cycles\n", name, length.cycles),
+                Err(e) => format!("  {}: failed: {}\n", name, e),
+            };
+        }
+        let worst = callbacks
+            .iter()
+            .filter_map(|(_, r)| r.as_ref().ok())
+            .map(|length| length.cycles)
+            .max();
+        if let (Some(worst), Some(bound)) = (worst, dispatch_bound(mux, counts)) {
+            out += &format!(
+                "  worst client: {} cycles, {} cycles for {} iterations\n",
+                worst,
+                worst * bound as u64,
+                bound
+            );
+        }
+    }
+    out
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::PathLength;
+
+/// How often the source tree is checked for modifications.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most recent modification time of any Rust source file under `dir`,
+/// skipping build output directories.
+pub fn latest_source_mtime(dir: &Path) -> Option<SystemTime> {
+    let mut latest = None;
+    for entry in std::fs::read_dir(dir).ok()?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let mtime = if path.is_dir() {
+            if matches!(
+                path.file_name().and_then(|n| n.to_str()),
+                Some("target" | ".git")
+            ) {
+                continue;
+            }
+            latest_source_mtime(&path)
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("rs") {
+            entry.metadata().and_then(|m| m.modified()).ok()
+        } else {
+            None
+        };
+        latest = latest.max(mtime);
+    }
+    latest
+}
+
+/// Block until some Rust source under `dir` is modified after `since`,
+/// returning the new latest modification time.
+pub fn wait_for_change(dir: &Path, since: Option<SystemTime>) -> Option<SystemTime> {
+    println!("Watching {} for changes...", dir.display());
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let latest = latest_source_mtime(dir);
+        if latest > since {
+            return latest;
+        }
+    }
+}
+
+/// Report the result of a re-analysis, relative to the previous successful one.
+pub fn print_delta(func_name: &str, result: &Result<PathLength, String>, previous: Option<usize>) {
+    let demangled = rustc_demangle::demangle(func_name);
+    match (result, previous) {
+        (Ok(length), Some(prev)) => println!(
+            "{}: {} instructions ({:+} from {})",
+            demangled,
+            length.ir,
+            length.ir as i64 - prev as i64,
+            prev
+        ),
+        (Ok(length), None) => println!("{}: {} instructions", demangled, length.ir),
+        (Err(e), _) => println!("{}: {}", demangled, e),
+    }
+}
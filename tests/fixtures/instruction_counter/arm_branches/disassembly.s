@@ -0,0 +1,26 @@
+	.text
+	.syntax unified
+	.eabi_attribute	67, "2.09"
+	.section	.text._ZN4test3foo17h0123456789abcdefE,"ax",%progbits
+	.globl	_ZN4test3foo17h0123456789abcdefE
+	.p2align	1
+	.type	_ZN4test3foo17h0123456789abcdefE,%function
+	.code	16
+	.thumb_func
+_ZN4test3foo17h0123456789abcdefE:
+	.fnstart
+@ %bb.0:                                @ %start
+	push	{r7, lr}
+	mov	r7, sp
+	cmp	r0, #0
+	beq	.LBB0_2
+@ %bb.1:                                @ %bb1
+	adds	r0, #1
+	pop	{r7, pc}
+.LBB0_2:                                @ %bb2
+	movs	r0, #0
+	pop	{r7, pc}
+.Lfunc_end0:
+	.size	_ZN4test3foo17h0123456789abcdefE, .Lfunc_end0-_ZN4test3foo17h0123456789abcdefE
+	.cantunwind
+	.fnend
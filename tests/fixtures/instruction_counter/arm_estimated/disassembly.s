@@ -0,0 +1,21 @@
+	.text
+	.syntax unified
+	.section	.text._ZN4test4quux17h0123456789abcdefE,"ax",%progbits
+	.globl	_ZN4test4quux17h0123456789abcdefE
+	.p2align	1
+	.type	_ZN4test4quux17h0123456789abcdefE,%function
+	.code	16
+	.thumb_func
+_ZN4test4quux17h0123456789abcdefE:
+	.fnstart
+@ %bb.0:                                @ %start
+	movs	r0, #1
+	bx	lr
+.Lfunc_end0:
+	.size	_ZN4test4quux17h0123456789abcdefE, .Lfunc_end0-_ZN4test4quux17h0123456789abcdefE
+	.cantunwind
+	.fnend
+
+	.globl	_ZN4test5alias17h0123456789abcdefE
+	.type	_ZN4test5alias17h0123456789abcdefE,%function
+.set _ZN4test5alias17h0123456789abcdefE, _ZN4test4quux17h0123456789abcdefE
@@ -0,0 +1,279 @@
+	.text
+	.syntax unified
+	.eabi_attribute	67, "2.09"	@ Tag_conformance
+	.cpu	cortex-m4
+	.eabi_attribute	6, 13	@ Tag_CPU_arch
+	.eabi_attribute	7, 77	@ Tag_CPU_arch_profile
+	.eabi_attribute	8, 0	@ Tag_ARM_ISA_use
+	.eabi_attribute	9, 2	@ Tag_THUMB_ISA_use
+	.fpu	fpv4-sp-d16
+	.eabi_attribute	27, 1	@ Tag_ABI_HardFP_use
+	.eabi_attribute	36, 1	@ Tag_FP_HP_extension
+	.eabi_attribute	34, 1	@ Tag_CPU_unaligned_access
+	.eabi_attribute	17, 1	@ Tag_ABI_PCS_GOT_use
+	.eabi_attribute	20, 1	@ Tag_ABI_FP_denormal
+	.eabi_attribute	21, 1	@ Tag_ABI_FP_exceptions
+	.eabi_attribute	23, 3	@ Tag_ABI_FP_number_model
+	.eabi_attribute	24, 1	@ Tag_ABI_align_needed
+	.eabi_attribute	25, 1	@ Tag_ABI_align_preserved
+	.eabi_attribute	28, 1	@ Tag_ABI_VFP_args
+	.eabi_attribute	38, 1	@ Tag_ABI_FP_16bit_format
+	.eabi_attribute	14, 0	@ Tag_ABI_PCS_R9_use
+	.file	"source.ll"
+	.globl	_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE @ -- Begin function _ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE
+	.p2align	2
+	.type	_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE,%function
+	.code	16                              @ @_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE
+	.thumb_func
+_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE:
+.Lfunc_begin0:
+	.file	1 "/tock" "capsules/src/gpio.rs"
+	.loc	1 165 0                         @ capsules/src/gpio.rs:165:0
+	.fnstart
+	.cfi_sections .debug_frame
+	.cfi_startproc
+@ %bb.0:                                @ %start
+	.loc	1 166 12 prologue_end           @ capsules/src/gpio.rs:166:12
+	cmp	r1, #31
+	.loc	1 170 9                         @ capsules/src/gpio.rs:170:9
+	it	ls
+	cmpls	r0, #6
+	bls	.LBB0_2
+@ %bb.1:
+	.loc	1 0 9 is_stmt 0                 @ capsules/src/gpio.rs:0:9
+	mvn	r0, #1
+	.loc	1 206 6 is_stmt 1               @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_2:                                @ %bb1
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movs	r2, #1
+	lsls	r2, r1
+.LCPI0_0:
+	tbb	[pc, r0]
+@ %bb.3:
+.LJTI0_0:
+	.byte	(.LBB0_4-(.LCPI0_0+4))/2
+	.byte	(.LBB0_5-(.LCPI0_0+4))/2
+	.byte	(.LBB0_6-(.LCPI0_0+4))/2
+	.byte	(.LBB0_7-(.LCPI0_0+4))/2
+	.byte	(.LBB0_8-(.LCPI0_0+4))/2
+	.byte	(.LBB0_9-(.LCPI0_0+4))/2
+	.byte	(.LBB0_10-(.LCPI0_0+4))/2
+	.p2align	1
+	.p2align	2
+.LBB0_4:                                @ %bb2
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 172 17 is_stmt 1              @ capsules/src/gpio.rs:172:17
+	str	r2, [r0, #20]
+	movs	r0, #0
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_5:                                @ %bb3
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 177 17 is_stmt 1              @ capsules/src/gpio.rs:177:17
+	str	r2, [r0, #4]
+	movs	r0, #0
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_6:                                @ %bb4
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 182 17 is_stmt 1              @ capsules/src/gpio.rs:182:17
+	str	r2, [r0, #8]
+	movs	r0, #0
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_7:                                @ %bb5
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 187 17 is_stmt 1              @ capsules/src/gpio.rs:187:17
+	ldr	r1, [r0]
+	eors	r1, r2
+	str	r1, [r0]
+	movs	r0, #0
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_8:                                @ %bb6
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 192 17 is_stmt 1              @ capsules/src/gpio.rs:192:17
+	ldr	r0, [r0, #12]
+	lsrs	r0, r1
+	and	r0, r0, #1
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_9:                                @ %bb7
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 196 17 is_stmt 1              @ capsules/src/gpio.rs:196:17
+	str	r2, [r0, #24]
+	movs	r0, #0
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+	.p2align	2
+.LBB0_10:                               @ %bb8
+	.loc	1 0 6 is_stmt 0                 @ capsules/src/gpio.rs:0:6
+	movw	r0, #1284
+	movt	r0, #20480
+	.loc	1 201 17 is_stmt 1              @ capsules/src/gpio.rs:201:17
+	add.w	r0, r0, r1, lsl #2
+	movs	r1, #12
+	str.w	r1, [r0, #508]
+	movs	r0, #0
+	.loc	1 206 6                         @ capsules/src/gpio.rs:206:6
+	bx	lr
+.Ltmp0:
+.Lfunc_end0:
+	.size	_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE, .Lfunc_end0-_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE
+	.cfi_endproc
+	.cantunwind
+	.fnend
+                                        @ -- End function
+	.globl	_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE @ -- Begin function _ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE
+	.p2align	2
+	.type	_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE,%function
+	.code	16                              @ @_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE
+	.thumb_func
+_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE:
+.Lfunc_begin1:
+	.fnstart
+	.cfi_startproc
+@ %bb.0:                                @ %start
+	.save	{r4, r5, r6, lr}
+	push	{r4, r5, r6, lr}
+	.cfi_def_cfa_offset 16
+	.cfi_offset lr, -4
+	.cfi_offset r6, -8
+	.cfi_offset r5, -12
+	.cfi_offset r4, -16
+	ldr	r6, .LCPI1_0
+	mov	r5, r0
+	ldr.w	r0, [r6, #276]
+	cbz	r0, .LBB1_2
+@ %bb.1:
+	mov.w	r0, #-1
+	pop	{r4, r5, r6, pc}
+.LBB1_2:                                @ %bb1
+	mov	r4, r2
+	cmp	r2, #64
+	add.w	r0, r5, #8
+	it	hi
+	movhi	r4, #64
+	mov	r2, r4
+	bl	memcpy
+	ldr	r1, .LCPI1_1
+	str	r0, [r1]
+	movs	r0, #1
+	str	r4, [r1, #4]
+	str	r0, [r6]
+	movs	r0, #0
+	str	r4, [r5, #4]
+	pop	{r4, r5, r6, pc}
+	.p2align	2
+@ %bb.3:
+.LCPI1_0:
+	.long	1073750024                      @ 0x40002008
+.LCPI1_1:
+	.long	1073751364                      @ 0x40002544
+.Lfunc_end1:
+	.size	_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE, .Lfunc_end1-_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE
+	.cfi_endproc
+	.cantunwind
+	.fnend
+                                        @ -- End function
+	.globl	memcpy                          @ -- Begin function memcpy
+	.p2align	1
+	.type	memcpy,%function
+	.code	16                              @ @memcpy
+	.thumb_func
+memcpy:
+.Lfunc_begin2:
+	.fnstart
+	.cfi_startproc
+@ %bb.0:                                @ %start
+	.save	{r4, lr}
+	push	{r4, lr}
+	.cfi_def_cfa_offset 8
+	.cfi_offset lr, -4
+	.cfi_offset r4, -8
+	movs	r3, #0
+.LBB2_1:                                @ %bb1
+                                        @ =>This Inner Loop Header: Depth=1
+	cmp	r2, r3
+	it	eq
+	popeq	{r4, pc}
+.LBB2_2:                                @ %bb2
+                                        @   in Loop: Header=BB2_1 Depth=1
+	ldrb	r4, [r1, r3]
+	strb	r4, [r0, r3]
+	adds	r3, #1
+	b	.LBB2_1
+.Lfunc_end2:
+	.size	memcpy, .Lfunc_end2-memcpy
+	.cfi_endproc
+	.cantunwind
+	.fnend
+                                        @ -- End function
+	.section	.debug_abbrev,"",%progbits
+	.byte	1                               @ Abbreviation Code
+	.byte	17                              @ DW_TAG_compile_unit
+	.byte	0                               @ DW_CHILDREN_no
+	.byte	37                              @ DW_AT_producer
+	.byte	14                              @ DW_FORM_strp
+	.byte	19                              @ DW_AT_language
+	.byte	5                               @ DW_FORM_data2
+	.byte	3                               @ DW_AT_name
+	.byte	14                              @ DW_FORM_strp
+	.byte	16                              @ DW_AT_stmt_list
+	.byte	23                              @ DW_FORM_sec_offset
+	.byte	27                              @ DW_AT_comp_dir
+	.byte	14                              @ DW_FORM_strp
+	.byte	17                              @ DW_AT_low_pc
+	.byte	1                               @ DW_FORM_addr
+	.byte	18                              @ DW_AT_high_pc
+	.byte	6                               @ DW_FORM_data4
+	.byte	0                               @ EOM(1)
+	.byte	0                               @ EOM(2)
+	.byte	0                               @ EOM(3)
+	.section	.debug_info,"",%progbits
+.Lcu_begin0:
+	.long	.Ldebug_info_end0-.Ldebug_info_start0 @ Length of Unit
+.Ldebug_info_start0:
+	.short	4                               @ DWARF version number
+	.long	.debug_abbrev                   @ Offset Into Abbrev. Section
+	.byte	4                               @ Address Size (in bytes)
+	.byte	1                               @ Abbrev [1] 0xb:0x1b DW_TAG_compile_unit
+	.long	.Linfo_string0                  @ DW_AT_producer
+	.short	28                              @ DW_AT_language
+	.long	.Linfo_string1                  @ DW_AT_name
+	.long	.Lline_table_start0             @ DW_AT_stmt_list
+	.long	.Linfo_string2                  @ DW_AT_comp_dir
+	.long	.Lfunc_begin0                   @ DW_AT_low_pc
+	.long	.Lfunc_end0-.Lfunc_begin0       @ DW_AT_high_pc
+.Ldebug_info_end0:
+	.section	.debug_str,"MS",%progbits,1
+.Linfo_string0:
+	.asciz	"clang LLVM (rustc version 1.58.0-nightly)" @ string offset=0
+.Linfo_string1:
+	.asciz	"capsules/src/gpio.rs"          @ string offset=42
+.Linfo_string2:
+	.asciz	"/tock"                         @ string offset=63
+	.globl	_ZN5nrf524uart5Uarte8transmit17h7b9d1f3a5c7e9b0dE
+	.type	_ZN5nrf524uart5Uarte8transmit17h7b9d1f3a5c7e9b0dE,%function
+.set _ZN5nrf524uart5Uarte8transmit17h7b9d1f3a5c7e9b0dE, _ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE
+	.section	".note.GNU-stack","",%progbits
+	.section	.debug_line,"",%progbits
+.Lline_table_start0:
@@ -0,0 +1,153 @@
+; LLVM IR modeled on the nrf52 board's GPIO capsule command and UARTE
+; transmit path (written for these fixtures, not taken from a build), from
+; which disassembly.s was generated with:
+;
+;   llc -O3 -mtriple=thumbv7em-none-eabihf -mcpu=cortex-m4 source.ll -o disassembly.s
+;
+; with LLVM 14's llc. The command's match over command numbers becomes a
+; `tbb` jump table, the UARTE driver (optimized for size, as boards build
+; the kernel) loads its register addresses from a literal pool, and line
+; tables put `.Ltmp` labels and `.loc` directives between the instructions.
+
+target datalayout = "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64"
+target triple = "thumbv7em-none-unknown-eabihf"
+
+%"uart::Uarte" = type { i8*, i32, [64 x i8] }
+
+; capsules::gpio::GPIO::command: 0 sets a pin as output, 1 sets it, 2 clears
+; it, 3 toggles it, 4 reads it, 5 sets it as input, 6 configures its pull
+define i32 @_ZN8capsules4gpio4GPIO7command17h5d0e8b3c1a2f4e6dE(i32 %cmd, i32 %pin) unnamed_addr #0 !dbg !5 {
+start:
+  %valid = icmp ult i32 %pin, 32, !dbg !8
+  br i1 %valid, label %bb1, label %bb10, !dbg !8
+
+bb1:
+  %mask = shl i32 1, %pin, !dbg !9
+  switch i32 %cmd, label %bb10 [
+    i32 0, label %bb2
+    i32 1, label %bb3
+    i32 2, label %bb4
+    i32 3, label %bb5
+    i32 4, label %bb6
+    i32 5, label %bb7
+    i32 6, label %bb8
+  ], !dbg !10
+
+bb2:
+  store volatile i32 %mask, i32* inttoptr (i32 1342178584 to i32*), align 4, !dbg !11
+  br label %bb9, !dbg !11
+
+bb3:
+  store volatile i32 %mask, i32* inttoptr (i32 1342178568 to i32*), align 4, !dbg !12
+  br label %bb9, !dbg !12
+
+bb4:
+  store volatile i32 %mask, i32* inttoptr (i32 1342178572 to i32*), align 4, !dbg !13
+  br label %bb9, !dbg !13
+
+bb5:
+  %out = load volatile i32, i32* inttoptr (i32 1342178564 to i32*), align 4, !dbg !14
+  %toggled = xor i32 %out, %mask, !dbg !14
+  store volatile i32 %toggled, i32* inttoptr (i32 1342178564 to i32*), align 4, !dbg !14
+  br label %bb9, !dbg !14
+
+bb6:
+  %in = load volatile i32, i32* inttoptr (i32 1342178576 to i32*), align 4, !dbg !15
+  %bit = lshr i32 %in, %pin, !dbg !15
+  %level = and i32 %bit, 1, !dbg !15
+  ret i32 %level, !dbg !16
+
+bb7:
+  store volatile i32 %mask, i32* inttoptr (i32 1342178588 to i32*), align 4, !dbg !17
+  br label %bb9, !dbg !17
+
+bb8:
+  %cnf = getelementptr i32, i32* inttoptr (i32 1342179072 to i32*), i32 %pin, !dbg !18
+  store volatile i32 12, i32* %cnf, align 4, !dbg !18
+  br label %bb9, !dbg !18
+
+bb9:
+  ret i32 0, !dbg !16
+
+bb10:
+  ret i32 -2, !dbg !16
+}
+
+; nrf52::uart::Uarte::transmit_buffer: copies the buffer into the DMA
+; buffer, then starts the transmission
+define i32 @_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE(%"uart::Uarte"* %self, i8* %buf, i32 %len) unnamed_addr #1 {
+start:
+  %busy = load volatile i32, i32* inttoptr (i32 1073750300 to i32*), align 4
+  %idle = icmp eq i32 %busy, 0
+  br i1 %idle, label %bb1, label %bb3
+
+bb1:
+  %too_long = icmp ugt i32 %len, 64
+  %n = select i1 %too_long, i32 64, i32 %len
+  %dma = getelementptr inbounds %"uart::Uarte", %"uart::Uarte"* %self, i32 0, i32 2, i32 0
+  %copied = call i8* @memcpy(i8* %dma, i8* %buf, i32 %n)
+  %ptr = ptrtoint i8* %dma to i32
+  store volatile i32 %ptr, i32* inttoptr (i32 1073751364 to i32*), align 4
+  store volatile i32 %n, i32* inttoptr (i32 1073751368 to i32*), align 4
+  store volatile i32 1, i32* inttoptr (i32 1073750024 to i32*), align 4
+  %len_field = getelementptr inbounds %"uart::Uarte", %"uart::Uarte"* %self, i32 0, i32 1
+  store i32 %n, i32* %len_field, align 4
+  br label %bb2
+
+bb2:
+  ret i32 0
+
+bb3:
+  ret i32 -1
+}
+
+; the transmit of the kernel's UART trait, identical to transmit_buffer, and
+; merged with it
+@_ZN5nrf524uart5Uarte8transmit17h7b9d1f3a5c7e9b0dE = unnamed_addr alias i32 (%"uart::Uarte"*, i8*, i32), i32 (%"uart::Uarte"*, i8*, i32)* @_ZN5nrf524uart5Uarte15transmit_buffer17h0f4a6c2e8b1d3f5aE
+
+; compiler_builtins' memcpy, as a byte loop
+define i8* @memcpy(i8* returned %dest, i8* %src, i32 %n) #1 {
+start:
+  br label %bb1
+
+bb1:
+  %i = phi i32 [ 0, %start ], [ %next, %bb2 ]
+  %done = icmp eq i32 %i, %n
+  br i1 %done, label %bb3, label %bb2
+
+bb2:
+  %from = getelementptr inbounds i8, i8* %src, i32 %i
+  %byte = load i8, i8* %from, align 1
+  %to = getelementptr inbounds i8, i8* %dest, i32 %i
+  store i8 %byte, i8* %to, align 1
+  %next = add nuw i32 %i, 1
+  br label %bb1
+
+bb3:
+  ret i8* %dest
+}
+
+attributes #0 = { nounwind "target-cpu"="cortex-m4" }
+attributes #1 = { nounwind minsize optsize "target-cpu"="cortex-m4" }
+
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!3, !4}
+
+!0 = distinct !DICompileUnit(language: DW_LANG_Rust, file: !1, producer: "clang LLVM (rustc version 1.58.0-nightly)", isOptimized: true, runtimeVersion: 0, emissionKind: LineTablesOnly)
+!1 = !DIFile(filename: "capsules/src/gpio.rs", directory: "/tock")
+!3 = !{i32 7, !"Dwarf Version", i32 4}
+!4 = !{i32 2, !"Debug Info Version", i32 3}
+!5 = distinct !DISubprogram(name: "command", scope: !1, file: !1, line: 165, type: !6, scopeLine: 165, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!6 = !DISubroutineType(types: !7)
+!7 = !{}
+!8 = !DILocation(line: 166, column: 12, scope: !5)
+!9 = !DILocation(line: 169, column: 24, scope: !5)
+!10 = !DILocation(line: 170, column: 9, scope: !5)
+!11 = !DILocation(line: 172, column: 17, scope: !5)
+!12 = !DILocation(line: 177, column: 17, scope: !5)
+!13 = !DILocation(line: 182, column: 17, scope: !5)
+!14 = !DILocation(line: 187, column: 17, scope: !5)
+!15 = !DILocation(line: 192, column: 17, scope: !5)
+!16 = !DILocation(line: 206, column: 6, scope: !5)
+!17 = !DILocation(line: 196, column: 17, scope: !5)
+!18 = !DILocation(line: 201, column: 17, scope: !5)
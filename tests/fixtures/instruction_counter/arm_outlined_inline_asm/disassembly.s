@@ -0,0 +1,36 @@
+	.text
+	.syntax unified
+	.section	.text._ZN4test3baz17h0123456789abcdefE,"ax",%progbits
+	.globl	_ZN4test3baz17h0123456789abcdefE
+	.p2align	1
+	.type	_ZN4test3baz17h0123456789abcdefE,%function
+	.code	16
+	.thumb_func
+_ZN4test3baz17h0123456789abcdefE:
+	.fnstart
+@ %bb.0:                                @ %start
+	push	{r4, lr}
+	bl	OUTLINED_FUNCTION_0
+	@APP
+	cpsid	i
+.Ltmp0:
+	wfi
+	@NO_APP
+	pop	{r4, pc}
+.Lfunc_end2:
+	.size	_ZN4test3baz17h0123456789abcdefE, .Lfunc_end2-_ZN4test3baz17h0123456789abcdefE
+	.cantunwind
+	.fnend
+	.p2align	1
+	.type	OUTLINED_FUNCTION_0,%function
+	.code	16
+	.thumb_func
+OUTLINED_FUNCTION_0:
+	.fnstart
+	movs	r0, #0
+	mov	r1, r4
+	bx	lr
+.Lfunc_end3:
+	.size	OUTLINED_FUNCTION_0, .Lfunc_end3-OUTLINED_FUNCTION_0
+	.cantunwind
+	.fnend
@@ -0,0 +1,29 @@
+	.text
+	.syntax unified
+	.section	.text._ZN4test3bar17h0123456789abcdefE,"ax",%progbits
+	.globl	_ZN4test3bar17h0123456789abcdefE
+	.p2align	2
+	.type	_ZN4test3bar17h0123456789abcdefE,%function
+	.code	16
+	.thumb_func
+_ZN4test3bar17h0123456789abcdefE:
+	.fnstart
+@ %bb.0:                                @ %start
+	ldr	r1, .LCPI1_0
+	cmp	r0, r1
+	it	eq
+	moveq	r0, #1
+	beq	.LBB1_2
+@ %bb.1:                                @ %bb1
+	movs	r0, #2
+	bx	lr
+.LBB1_2:                                @ %bb2
+	bx	lr
+	.p2align	2
+@ %bb.3:
+.LCPI1_0:
+	.long	305419896
+.Lfunc_end1:
+	.size	_ZN4test3bar17h0123456789abcdefE, .Lfunc_end1-_ZN4test3bar17h0123456789abcdefE
+	.cantunwind
+	.fnend
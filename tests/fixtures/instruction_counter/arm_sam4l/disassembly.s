@@ -0,0 +1,127 @@
+	.text
+	.syntax unified
+	.eabi_attribute	67, "2.09"	@ Tag_conformance
+	.cpu	cortex-m3
+	.eabi_attribute	6, 10	@ Tag_CPU_arch
+	.eabi_attribute	7, 77	@ Tag_CPU_arch_profile
+	.eabi_attribute	8, 0	@ Tag_ARM_ISA_use
+	.eabi_attribute	9, 2	@ Tag_THUMB_ISA_use
+	.eabi_attribute	34, 1	@ Tag_CPU_unaligned_access
+	.eabi_attribute	17, 1	@ Tag_ABI_PCS_GOT_use
+	.eabi_attribute	20, 1	@ Tag_ABI_FP_denormal
+	.eabi_attribute	21, 1	@ Tag_ABI_FP_exceptions
+	.eabi_attribute	23, 3	@ Tag_ABI_FP_number_model
+	.eabi_attribute	24, 1	@ Tag_ABI_align_needed
+	.eabi_attribute	25, 1	@ Tag_ABI_align_preserved
+	.eabi_attribute	38, 1	@ Tag_ABI_FP_16bit_format
+	.eabi_attribute	14, 0	@ Tag_ABI_PCS_R9_use
+	.file	"source.ll"
+	.globl	_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE @ -- Begin function _ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE
+	.p2align	2
+	.type	_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE,%function
+	.code	16                              @ @_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE
+	.thumb_func
+_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE:
+	.fnstart
+@ %bb.0:                                @ %start
+	.save	{r7, lr}
+	push	{r7, lr}
+	cmp	r1, #3
+	bhi	.LBB0_5
+@ %bb.1:                                @ %start
+	mov	r3, r0
+.LCPI0_0:
+	tbb	[pc, r1]
+@ %bb.2:
+.LJTI0_0:
+	.byte	(.LBB0_4-(.LCPI0_0+4))/2
+	.byte	(.LBB0_3-(.LCPI0_0+4))/2
+	.byte	(.LBB0_6-(.LCPI0_0+4))/2
+	.byte	(.LBB0_7-(.LCPI0_0+4))/2
+	.p2align	1
+	.p2align	2
+.LBB0_3:                                @ %bb1
+	mov	r0, r3
+	str	r2, [r3]
+	bl	_ZN8capsules7console7Console9send_next17h2c4e6a8b0d1f3e5aE
+	cmp	r0, #0
+	it	ne
+	popne	{r7, pc}
+.LBB0_4:                                @ %bb5
+	movs	r0, #0
+	pop	{r7, pc}
+	.p2align	2
+.LBB0_5:
+	mvn	r0, #3
+	pop	{r7, pc}
+	.p2align	2
+.LBB0_6:                                @ %bb3
+	ldr	r0, [r3, #4]
+	cmp	r2, #64
+	it	hs
+	movhs	r2, #64
+	cmp	r0, #0
+	it	ne
+	movne	r2, r0
+	str	r2, [r3, #4]
+	it	ne
+	mvnne	r0, #2
+	pop	{r7, pc}
+	.p2align	2
+.LBB0_7:                                @ %bb4
+	ldr	r1, [r3, #8]
+	movs	r0, #0
+	str	r0, [r3, #4]
+	adds	r0, r1, #1
+	str	r0, [r3, #8]
+	movs	r0, #0
+	pop	{r7, pc}
+.Lfunc_end0:
+	.size	_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE, .Lfunc_end0-_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE
+	.cantunwind
+	.fnend
+                                        @ -- End function
+	.globl	_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE @ -- Begin function _ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE
+	.p2align	2
+	.type	_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE,%function
+	.code	16                              @ @_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE
+	.thumb_func
+_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE:
+	.fnstart
+@ %bb.0:                                @ %start
+	.save	{r4, lr}
+	push	{r4, lr}
+	ldr	r1, .LCPI1_0
+	ldr	r2, [r1]
+	lsls	r3, r2, #31
+	beq	.LBB1_2
+@ %bb.1:                                @ %bb1
+	ldr	r3, [r1, #4]
+	ldr	r3, [r0, #4]
+	subs	r3, #1
+	str	r3, [r0, #4]
+.LBB1_2:                                @ %bb2
+	lsls	r2, r2, #30
+	bpl	.LBB1_5
+@ %bb.3:                                @ %bb3
+	ldr	r2, [r0]
+	cbz	r2, .LBB1_5
+@ %bb.4:                                @ %bb5
+	ldr	r3, [r0, #12]
+	ldrb	r4, [r3], #1
+	str	r4, [r1, #8]
+	subs	r1, r2, #1
+	str	r3, [r0, #12]
+	str	r1, [r0]
+.LBB1_5:                                @ %bb4
+	pop	{r4, pc}
+	.p2align	2
+@ %bb.6:
+.LCPI1_0:
+	.long	1073889300                      @ 0x40024014
+.Lfunc_end1:
+	.size	_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE, .Lfunc_end1-_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE
+	.cantunwind
+	.fnend
+                                        @ -- End function
+	.section	".note.GNU-stack","",%progbits
@@ -0,0 +1,112 @@
+; LLVM IR modeled on the sam4l boards' console capsule command and USART
+; interrupt handler (written for these fixtures, not taken from a build),
+; from which disassembly.s was generated with:
+;
+;   llc -O3 -mtriple=thumbv7m-none-eabi -mcpu=cortex-m3 source.ll -o disassembly.s
+;
+; with LLVM 14's llc. The command's match over command numbers becomes a
+; `tbb` jump table, and the USART driver (optimized for size, as boards
+; build the kernel) loads the USART's address from a literal pool.
+
+target datalayout = "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64"
+target triple = "thumbv7m-none-unknown-eabi"
+
+%"console::Console" = type { i32, i32, i32, i8* }
+
+declare i32 @_ZN8capsules7console7Console9send_next17h2c4e6a8b0d1f3e5aE(%"console::Console"*) unnamed_addr
+
+; capsules::console::Console::command: 0 checks the driver exists, 1 starts
+; printing, 2 starts reading, 3 aborts a read
+define i32 @_ZN8capsules7console7Console7command17h9e1c3a5b7d0f2e4cE(%"console::Console"* %self, i32 %cmd, i32 %len) unnamed_addr #0 {
+start:
+  switch i32 %cmd, label %bb6 [
+    i32 0, label %bb5
+    i32 1, label %bb1
+    i32 2, label %bb3
+    i32 3, label %bb4
+  ]
+
+bb1:
+  %tx_len = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 0
+  store i32 %len, i32* %tx_len, align 4
+  %sent = tail call i32 @_ZN8capsules7console7Console9send_next17h2c4e6a8b0d1f3e5aE(%"console::Console"* %self)
+  %failed = icmp ne i32 %sent, 0
+  br i1 %failed, label %bb2, label %bb5
+
+bb2:
+  ret i32 %sent
+
+bb3:
+  %rx_len = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 1
+  %busy = load i32, i32* %rx_len, align 4
+  %reading = icmp ne i32 %busy, 0
+  %clamped = call i32 @llvm.umin.i32(i32 %len, i32 64)
+  %new = select i1 %reading, i32 %busy, i32 %clamped
+  store i32 %new, i32* %rx_len, align 4
+  %result = select i1 %reading, i32 -3, i32 0
+  ret i32 %result
+
+bb4:
+  %rx = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 1
+  store i32 0, i32* %rx, align 4
+  %aborted = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 2
+  %count = load i32, i32* %aborted, align 4
+  %inc = add i32 %count, 1
+  store i32 %inc, i32* %aborted, align 4
+  br label %bb5
+
+bb5:
+  ret i32 0
+
+bb6:
+  ret i32 -4
+}
+
+; sam4l::usart::USART::handle_interrupt: receives a byte if one is ready,
+; then sends the next byte of the buffer
+define void @_ZN5sam4l5usart5USART16handle_interrupt17h4a6c8e0b2d1f3a5cE(%"console::Console"* %self) unnamed_addr #1 {
+start:
+  %csr = load volatile i32, i32* inttoptr (i32 1073889300 to i32*), align 4
+  %rxrdy = and i32 %csr, 1
+  %has_rx = icmp eq i32 %rxrdy, 0
+  br i1 %has_rx, label %bb2, label %bb1
+
+bb1:
+  %rhr = load volatile i32, i32* inttoptr (i32 1073889304 to i32*), align 4
+  %rx_len = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 1
+  %left = load i32, i32* %rx_len, align 4
+  %dec = add i32 %left, -1
+  store i32 %dec, i32* %rx_len, align 4
+  br label %bb2
+
+bb2:
+  %txrdy = and i32 %csr, 2
+  %no_tx = icmp eq i32 %txrdy, 0
+  br i1 %no_tx, label %bb4, label %bb3
+
+bb3:
+  %tx_len = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 0
+  %pending = load i32, i32* %tx_len, align 4
+  %done = icmp eq i32 %pending, 0
+  br i1 %done, label %bb4, label %bb5
+
+bb5:
+  %buf_field = getelementptr inbounds %"console::Console", %"console::Console"* %self, i32 0, i32 3
+  %buf = load i8*, i8** %buf_field, align 4
+  %byte = load i8, i8* %buf, align 1
+  %word = zext i8 %byte to i32
+  store volatile i32 %word, i32* inttoptr (i32 1073889308 to i32*), align 4
+  %next = getelementptr inbounds i8, i8* %buf, i32 1
+  store i8* %next, i8** %buf_field, align 4
+  %left_tx = add i32 %pending, -1
+  store i32 %left_tx, i32* %tx_len, align 4
+  br label %bb4
+
+bb4:
+  ret void
+}
+
+declare i32 @llvm.umin.i32(i32, i32)
+
+attributes #0 = { nounwind "target-cpu"="cortex-m3" }
+attributes #1 = { nounwind minsize optsize "target-cpu"="cortex-m3" }
@@ -0,0 +1,49 @@
+	.text
+	.attribute	4, 16
+	.attribute	5, "rv32i2p0_m2p0_c2p0"
+	.file	"source.ll"
+	.globl	_ZN4test3foo17h0123456789abcdefE # -- Begin function _ZN4test3foo17h0123456789abcdefE
+	.p2align	1
+	.type	_ZN4test3foo17h0123456789abcdefE,@function
+_ZN4test3foo17h0123456789abcdefE:       # @_ZN4test3foo17h0123456789abcdefE
+# %bb.0:                                # %start
+	addi	sp, sp, -16
+	sw	ra, 12(sp)                      # 4-byte Folded Spill
+	sw	s0, 8(sp)                       # 4-byte Folded Spill
+	mv	s0, a0
+	call	_ZN4test4note17h0123456789abcdedE@plt
+	beqz	s0, .LBB0_2
+# %bb.1:                                # %bb1
+	addi	a0, s0, 1
+	lw	ra, 12(sp)                      # 4-byte Folded Reload
+	lw	s0, 8(sp)                       # 4-byte Folded Reload
+	addi	sp, sp, 16
+	ret
+.LBB0_2:                                # %bb2
+	#APP
+	csrci	mstatus, 8
+	#NO_APP
+	li	a0, 0
+	lw	ra, 12(sp)                      # 4-byte Folded Reload
+	lw	s0, 8(sp)                       # 4-byte Folded Reload
+	addi	sp, sp, 16
+	ret
+.Lfunc_end0:
+	.size	_ZN4test3foo17h0123456789abcdefE, .Lfunc_end0-_ZN4test3foo17h0123456789abcdefE
+                                        # -- End function
+	.globl	_ZN4test3bar17h0123456789abcdeeE # -- Begin function _ZN4test3bar17h0123456789abcdeeE
+	.p2align	1
+	.type	_ZN4test3bar17h0123456789abcdeeE,@function
+_ZN4test3bar17h0123456789abcdeeE:       # @_ZN4test3bar17h0123456789abcdeeE
+# %bb.0:                                # %start
+	bgeu	a0, a1, .LBB1_2
+# %bb.1:                                # %bb1
+	sub	a0, a1, a0
+	ret
+.LBB1_2:                                # %bb2
+	sub	a0, a0, a1
+	ret
+.Lfunc_end1:
+	.size	_ZN4test3bar17h0123456789abcdeeE, .Lfunc_end1-_ZN4test3bar17h0123456789abcdeeE
+                                        # -- End function
+	.section	".note.GNU-stack","",@progbits
@@ -0,0 +1,46 @@
+; LLVM IR of two small functions (written for these fixtures, not taken
+; from a build), from which disassembly.s was generated with:
+;
+;   llc -O3 -mtriple=riscv32-unknown-none-elf -mattr=+m,+c source.ll -o disassembly.s
+;
+; with LLVM 14's llc. RISC-V comments start with `#`, also after function
+; labels, and branches compare registers (`bgeu a0, a1`) or a register with
+; zero (`beqz a0`) rather than flags.
+
+target datalayout = "e-m:e-p:32:32-i64:64-n32-S128"
+target triple = "riscv32-unknown-none-elf"
+
+declare void @_ZN4test4note17h0123456789abcdedE(i32) unnamed_addr
+
+; masks machine interrupts when given 0, through inline assembly
+define i32 @_ZN4test3foo17h0123456789abcdefE(i32 %a) unnamed_addr #0 {
+start:
+  call void @_ZN4test4note17h0123456789abcdedE(i32 %a)
+  %zero = icmp eq i32 %a, 0
+  br i1 %zero, label %bb2, label %bb1
+
+bb1:
+  %inc = add i32 %a, 1
+  ret i32 %inc
+
+bb2:
+  call void asm sideeffect "csrci mstatus, 8", ""()
+  ret i32 0
+}
+
+; the distance between two values
+define i32 @_ZN4test3bar17h0123456789abcdeeE(i32 %a, i32 %b) unnamed_addr #0 {
+start:
+  %lt = icmp ult i32 %a, %b
+  br i1 %lt, label %bb1, label %bb2
+
+bb1:
+  %s = sub i32 %b, %a
+  ret i32 %s
+
+bb2:
+  %d = sub i32 %a, %b
+  ret i32 %d
+}
+
+attributes #0 = { nounwind }